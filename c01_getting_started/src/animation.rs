@@ -0,0 +1,477 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Quaternion, Vector2, Vector3, Vector4};
+use vertex_format::VertexFormat;
+
+use super::mesh::{Vertex, SKINNING_ATTRIBUTE_LOCATION};
+use super::scene::Transform;
+use super::shader::Shader;
+
+/// Maximum number of joints the skinning shaders are written for (`uniform mat4 joints[MAX_JOINTS]`).
+pub const MAX_JOINTS: usize = 64;
+
+/// Maximum number of joints influencing a single vertex.
+pub const MAX_JOINT_INFLUENCES: usize = 4;
+
+/// A vertex of a skinned mesh (`Mesh<SkinnedVertex>`): the attributes of `Vertex` followed by
+/// the joints influencing it. The joint indices are passed to the shader as integers, which
+/// needs OpenGL ES 3.0, so static meshes keep the smaller `Vertex`.
+// Attribute locations: position 0, normal 1, texCoords 2, tangent 3, bitangent 4, joints 6, weights 7;
+// location 5 stays free for the vertex colors.
+#[repr(C)]
+#[derive(Clone, Copy, VertexFormat)]
+pub struct SkinnedVertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub tex_coords: Vector2<f32>,
+    pub tangent: Vector3<f32>,
+    pub bitangent: Vector3<f32>,
+    // indices of the joints influencing the vertex
+    #[vertex(location = 6)]
+    pub joints: [u32; MAX_JOINT_INFLUENCES],
+    // weight of each joint, summing up to 1
+    pub weights: Vector4<f32>,
+}
+
+const _: () = assert!(SkinnedVertex::ATTRIBUTES[5].location == SKINNING_ATTRIBUTE_LOCATION);
+
+impl SkinnedVertex {
+    pub fn new(vertex: &Vertex, joints: [u32; MAX_JOINT_INFLUENCES], weights: Vector4<f32>) -> Self {
+        SkinnedVertex {
+            position: vertex.position,
+            normal: vertex.normal,
+            tex_coords: vertex.tex_coords,
+            tangent: vertex.tangent,
+            bitangent: vertex.bitangent,
+            joints,
+            weights,
+        }
+    }
+}
+
+impl Default for SkinnedVertex {
+    fn default() -> Self {
+        // fully bound to the first joint
+        SkinnedVertex::new(&Vertex::default(), [0; MAX_JOINT_INFLUENCES], Vector4::unit_x())
+    }
+}
+
+pub struct Joint {
+    pub name: String,
+    /// index of the parent joint; parents always come before their children
+    pub parent: Option<usize>,
+    /// transforms from model space to the joint's space in the bind pose
+    pub inverse_bind: Matrix4<f32>,
+    /// local transform used when no animation drives the joint
+    pub rest: Transform,
+}
+
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Self {
+        assert!(joints.len() <= MAX_JOINTS, "too many joints for the skinning shader");
+        for (idx, joint) in joints.iter().enumerate() {
+            if let Some(parent) = joint.parent {
+                assert!(parent < idx, "joint {:?} comes before its parent", joint.name);
+            }
+        }
+        Skeleton { joints }
+    }
+
+    /// local transforms of the rest pose
+    pub fn rest_pose(&self) -> Vec<Transform> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// model-space joint matrices of a pose (local joint transforms)
+    pub fn global_matrices(&self, pose: &[Transform]) -> Vec<Matrix4<f32>> {
+        let mut globals = vec![Matrix4::identity(); self.joints.len()];
+        self.write_global_matrices(pose, &mut globals);
+        globals
+    }
+
+    // parents come before their children, so their global matrix is already in `out`
+    fn write_global_matrices(&self, pose: &[Transform], out: &mut [Matrix4<f32>]) {
+        for (idx, (joint, local)) in self.joints.iter().zip(pose).enumerate() {
+            out[idx] = match joint.parent {
+                Some(parent) => out[parent] * local.matrix(),
+                None => local.matrix(),
+            };
+        }
+    }
+
+    /// matrices that move a vertex from the bind pose into the given pose, one per joint
+    pub fn skinning_matrices(&self, pose: &[Transform]) -> Vec<Matrix4<f32>> {
+        let mut matrices = vec![Matrix4::identity(); self.joints.len()];
+        self.write_skinning_matrices(pose, &mut matrices);
+        matrices
+    }
+
+    /// `skinning_matrices` into a slice with one matrix per joint, without allocating
+    pub fn write_skinning_matrices(&self, pose: &[Transform], out: &mut [Matrix4<f32>]) {
+        self.write_global_matrices(pose, out);
+        for (matrix, joint) in out.iter_mut().zip(&self.joints) {
+            *matrix = *matrix * joint.inverse_bind;
+        }
+    }
+
+    /// uploads the skinning matrices to the `joints` uniform array
+    pub fn set_uniforms(&self, shader: &Shader, pose: &[Transform]) {
+        shader.set_mat4_array("joints", &self.skinning_matrices(pose));
+    }
+}
+
+/// How values between two keyframes are computed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// cubic Hermite spline; every keyframe stores an in-tangent, the value and an
+    /// out-tangent, in that order (the glTF layout)
+    CubicSpline,
+}
+
+/// Values that can be stored in an animation track.
+pub trait Animatable: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+    /// `a * self + b * other`, used to evaluate cubic splines
+    fn weighted_sum(self, a: f32, other: Self, b: f32) -> Self;
+    fn normalized(self) -> Self {
+        self
+    }
+}
+
+impl Animatable for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    fn weighted_sum(self, a: f32, other: Self, b: f32) -> Self {
+        self * a + other * b
+    }
+}
+
+impl Animatable for Vector3<f32> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        VectorSpace::lerp(self, other, t)
+    }
+
+    fn weighted_sum(self, a: f32, other: Self, b: f32) -> Self {
+        self * a + other * b
+    }
+}
+
+impl Animatable for Quaternion<f32> {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        // take the shortest path
+        let other = if self.dot(other) < 0.0 { -other } else { other };
+        self.slerp(other, t)
+    }
+
+    fn weighted_sum(self, a: f32, other: Self, b: f32) -> Self {
+        self * a + other * b
+    }
+
+    fn normalized(self) -> Self {
+        self.normalize()
+    }
+}
+
+/// Keyframes of a single animated value.
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    /// keyframe times in seconds, ascending
+    pub times: Vec<f32>,
+    /// one value per keyframe, or three for cubic splines (see `Interpolation::CubicSpline`)
+    pub values: Vec<T>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Self {
+        let per_key = if interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        assert_eq!(times.len() * per_key, values.len(), "keyframe count mismatch");
+        assert!(!times.is_empty(), "a track needs at least one keyframe");
+        Track {
+            times,
+            values,
+            interpolation,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        *self.times.last().unwrap()
+    }
+
+    fn value(&self, key: usize) -> T {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[key * 3 + 1],
+            _ => self.values[key],
+        }
+    }
+
+    /// value at `time`, clamped to the first and last keyframe
+    pub fn sample(&self, time: f32) -> T {
+        let last = self.times.len() - 1;
+        if time <= self.times[0] {
+            return self.value(0);
+        }
+        if time >= self.times[last] {
+            return self.value(last);
+        }
+        // index of the keyframe starting the segment containing `time`
+        let key = self.times.partition_point(|&t| t <= time) - 1;
+        let (t0, t1) = (self.times[key], self.times[key + 1]);
+        let dt = t1 - t0;
+        let t = (time - t0) / dt;
+
+        match self.interpolation {
+            Interpolation::Step => self.value(key),
+            Interpolation::Linear => self.value(key).lerp(self.value(key + 1), t),
+            Interpolation::CubicSpline => {
+                let p0 = self.values[key * 3 + 1];
+                let m0 = self.values[key * 3 + 2];
+                let m1 = self.values[(key + 1) * 3];
+                let p1 = self.values[(key + 1) * 3 + 1];
+                let t2 = t * t;
+                let t3 = t2 * t;
+                let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+                let h10 = (t3 - 2.0 * t2 + t) * dt;
+                let h01 = -2.0 * t3 + 3.0 * t2;
+                let h11 = (t3 - t2) * dt;
+                p0.weighted_sum(h00, m0, h10)
+                    .weighted_sum(1.0, p1, h01)
+                    .weighted_sum(1.0, m1, h11)
+                    .normalized()
+            }
+        }
+    }
+}
+
+/// Animation of a single joint. Missing tracks leave that part of the joint's transform
+/// at the pose it is sampled on top of.
+#[derive(Clone, Debug, Default)]
+pub struct Channel {
+    pub joint: usize,
+    pub translation: Option<Track<Vector3<f32>>>,
+    pub rotation: Option<Track<Quaternion<f32>>>,
+    pub scale: Option<Track<Vector3<f32>>>,
+}
+
+/// Animation of the weight of one morph target.
+#[derive(Clone, Debug)]
+pub struct MorphChannel {
+    pub target: usize,
+    pub track: Track<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    pub morph_channels: Vec<MorphChannel>,
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, channels: Vec<Channel>) -> Self {
+        let duration = channels
+            .iter()
+            .flat_map(|channel| {
+                let translation = channel.translation.as_ref().map(Track::duration);
+                let rotation = channel.rotation.as_ref().map(Track::duration);
+                let scale = channel.scale.as_ref().map(Track::duration);
+                translation.into_iter().chain(rotation).chain(scale)
+            })
+            .fold(0.0, f32::max);
+        AnimationClip {
+            name: name.into(),
+            channels,
+            morph_channels: Vec::new(),
+            duration,
+        }
+    }
+
+    /// adds tracks driving morph target weights, extending the duration if needed
+    pub fn with_morph_channels(mut self, morph_channels: Vec<MorphChannel>) -> Self {
+        let duration = morph_channels.iter().map(|channel| channel.track.duration());
+        self.duration = duration.fold(self.duration, f32::max);
+        self.morph_channels = morph_channels;
+        self
+    }
+
+    /// number of morph target weights the clip animates
+    pub fn morph_target_count(&self) -> usize {
+        self.morph_channels
+            .iter()
+            .map(|channel| channel.target + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn wrap(&self, time: f32) -> f32 {
+        if self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            0.0
+        }
+    }
+
+    /// writes the animated joint transforms at `time` (wrapped to the clip duration) into `pose`
+    pub fn sample(&self, time: f32, pose: &mut [Transform]) {
+        let time = self.wrap(time);
+        for channel in &self.channels {
+            let transform = &mut pose[channel.joint];
+            if let Some(track) = &channel.translation {
+                transform.translation = track.sample(time);
+            }
+            if let Some(track) = &channel.rotation {
+                transform.rotation = track.sample(time);
+            }
+            if let Some(track) = &channel.scale {
+                transform.scale = track.sample(time);
+            }
+        }
+    }
+
+    /// writes the animated morph target weights at `time` into `weights`; weights without
+    /// a track keep their value
+    pub fn sample_morph_weights(&self, time: f32, weights: &mut [f32]) {
+        let time = self.wrap(time);
+        for channel in &self.morph_channels {
+            if let Some(weight) = weights.get_mut(channel.target) {
+                *weight = channel.track.sample(time);
+            }
+        }
+    }
+}
+
+/// Interpolates every joint transform of two poses; `weight` 0 gives `from`, 1 gives `to`.
+pub fn blend_poses(from: &[Transform], to: &[Transform], weight: f32, out: &mut [Transform]) {
+    for ((a, b), out) in from.iter().zip(to).zip(out.iter_mut()) {
+        *out = Transform {
+            translation: VectorSpace::lerp(a.translation, b.translation, weight),
+            rotation: Animatable::lerp(a.rotation, b.rotation, weight),
+            scale: VectorSpace::lerp(a.scale, b.scale, weight),
+        };
+    }
+}
+
+/// Plays animation clips on a skeleton, cross-fading when switching between clips. All
+/// buffers are allocated up front, `update` doesn't allocate.
+pub struct Animator {
+    pub clips: Vec<AnimationClip>,
+    current: usize,
+    time: f32,
+    // clip being faded out, its playback time, the fade duration and the time faded so far
+    previous: Option<(usize, f32, f32, f32)>,
+    pose: Vec<Transform>,
+    // pose of the clip being faded out, and the blend of both poses
+    scratch: Vec<Transform>,
+    blended: Vec<Transform>,
+    skinning: Vec<Matrix4<f32>>,
+    morph_weights: Vec<f32>,
+    morph_scratch: Vec<f32>,
+}
+
+impl Animator {
+    pub fn new(skeleton: &Skeleton, clips: Vec<AnimationClip>) -> Self {
+        assert!(!clips.is_empty(), "an animator needs at least one clip");
+        let pose = skeleton.rest_pose();
+        let morph_target_count = clips.iter().map(AnimationClip::morph_target_count).max().unwrap_or(0);
+        Animator {
+            clips,
+            current: 0,
+            time: 0.0,
+            previous: None,
+            scratch: pose.clone(),
+            blended: pose.clone(),
+            skinning: skeleton.skinning_matrices(&pose),
+            pose,
+            morph_weights: vec![0.0; morph_target_count],
+            morph_scratch: vec![0.0; morph_target_count],
+        }
+    }
+
+    pub fn current_clip(&self) -> usize {
+        self.current
+    }
+
+    /// switches to another clip, blending from the current one over `fade` seconds
+    pub fn play(&mut self, clip: usize, fade: f32) {
+        if clip == self.current {
+            return;
+        }
+        self.previous = if fade > 0.0 {
+            Some((self.current, self.time, fade, 0.0))
+        } else {
+            None
+        };
+        self.current = clip;
+        self.time = 0.0;
+    }
+
+    /// advances playback and recomputes the pose
+    pub fn update(&mut self, skeleton: &Skeleton, delta_time: f32) {
+        self.time += delta_time;
+
+        reset_to_rest(skeleton, &mut self.pose);
+        self.clips[self.current].sample(self.time, &mut self.pose);
+        self.morph_weights.iter_mut().for_each(|weight| *weight = 0.0);
+        self.clips[self.current].sample_morph_weights(self.time, &mut self.morph_weights);
+
+        if let Some((clip, time, fade, elapsed)) = self.previous {
+            let (time, elapsed) = (time + delta_time, elapsed + delta_time);
+            if elapsed >= fade {
+                self.previous = None;
+            } else {
+                reset_to_rest(skeleton, &mut self.scratch);
+                self.clips[clip].sample(time, &mut self.scratch);
+                blend_poses(&self.scratch, &self.pose, elapsed / fade, &mut self.blended);
+                std::mem::swap(&mut self.pose, &mut self.blended);
+
+                self.morph_scratch.iter_mut().for_each(|weight| *weight = 0.0);
+                self.clips[clip].sample_morph_weights(time, &mut self.morph_scratch);
+                for (weight, previous) in self.morph_weights.iter_mut().zip(&self.morph_scratch) {
+                    *weight = previous.lerp(*weight, elapsed / fade);
+                }
+                self.previous = Some((clip, time, fade, elapsed));
+            }
+        }
+
+        skeleton.write_skinning_matrices(&self.pose, &mut self.skinning);
+    }
+
+    /// local joint transforms computed by the last `update`
+    pub fn pose(&self) -> &[Transform] {
+        &self.pose
+    }
+
+    /// skinning matrices of the pose computed by the last `update`, see `Skeleton::skinning_matrices`
+    pub fn skinning_matrices(&self) -> &[Matrix4<f32>] {
+        &self.skinning
+    }
+
+    /// uploads the skinning matrices of the last `update` to the `joints` uniform array
+    pub fn set_uniforms(&self, shader: &Shader) {
+        shader.set_mat4_array("joints", &self.skinning);
+    }
+
+    /// morph target weights computed by the last `update`, for `Mesh::draw_with_weights`
+    pub fn morph_weights(&self) -> &[f32] {
+        &self.morph_weights
+    }
+}
+
+// overwrites `pose` with the rest transforms of the skeleton's joints
+fn reset_to_rest(skeleton: &Skeleton, pose: &mut [Transform]) {
+    for (transform, joint) in pose.iter_mut().zip(&skeleton.joints) {
+        *transform = joint.rest;
+    }
+}
@@ -1,3 +1,4 @@
+use c01_getting_started::{run_event_loop, GlBuffer, GlProgram, GlVertexArray};
 use dx::{
     gles::{core30::gl, enums::*},
     Instance,
@@ -51,7 +52,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (shader_program, vao) = {
+    let (shader_program, vao, vbo, ebo) = {
         // build and compile our shader program
 
         // vertex shader
@@ -90,17 +91,17 @@ pub fn main() {
         }
 
         // link shaders
-        let shader_program = gl::create_program();
-        gl::attach_shader(shader_program, vertex_shader);
-        gl::attach_shader(shader_program, fragment_shader);
-        gl::link_program(shader_program);
+        let shader_program = GlProgram::new();
+        gl::attach_shader(shader_program.id(), vertex_shader);
+        gl::attach_shader(shader_program.id(), fragment_shader);
+        gl::link_program(shader_program.id());
         // check for linking errors
-        let success = gl::get_programiv(shader_program, GL_LINK_STATUS);
+        let success = gl::get_programiv(shader_program.id(), GL_LINK_STATUS);
 
         if success == 0 {
-            let len = gl::get_programiv(shader_program, GL_INFO_LOG_LENGTH);
+            let len = gl::get_programiv(shader_program.id(), GL_INFO_LOG_LENGTH);
 
-            return match gl::get_program_info_log(shader_program, len) {
+            return match gl::get_program_info_log(shader_program.id(), len) {
                 Some(message) => {
                     println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", message);
                 }
@@ -126,16 +127,16 @@ pub fn main() {
             1, 2, 3, // second Triangle
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
-        let ebo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        let ebo = GlBuffer::new();
         // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo.id());
         gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &indices, GL_STATIC_DRAW);
 
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, 3 * mem::size_of::<f32>() as i32, 0);
@@ -154,11 +155,11 @@ pub fn main() {
         // uncomment this call to draw in wireframe polygons.
         // gl::PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
 
-        (shader_program, vao)
+        (shader_program, vao, vbo, ebo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (vbo, ebo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -182,9 +183,9 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // draw our first triangle
-            gl::use_program(shader_program);
+            gl::use_program(shader_program.id());
             // seeing as we only have a single vao there's no need to bind it every time, but we'll do so to keep things a bit more organized
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             // gl::draw_arrays(GL_TRIANGLES, 0, 3);
             gl::draw_elements_offset(GL_TRIANGLES, 6, GL_UNSIGNED_INT, 0);
             // gl::bind_vertex_array(0); // no need to unbind it every time
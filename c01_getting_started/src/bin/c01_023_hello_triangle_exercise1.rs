@@ -1,3 +1,4 @@
+use c01_getting_started::{run_event_loop, GlBuffer, GlProgram, GlVertexArray};
 use dx::{
     gles::{core30::gl, enums::*},
     Instance,
@@ -47,7 +48,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (shader_program, vao) = {
+    let (shader_program, vao, vbo) = {
         // build and compile our shader program
 
         // vertex shader
@@ -86,17 +87,17 @@ pub fn main() {
         }
 
         // link shaders
-        let shader_program = gl::create_program();
-        gl::attach_shader(shader_program, vertex_shader);
-        gl::attach_shader(shader_program, fragment_shader);
-        gl::link_program(shader_program);
+        let shader_program = GlProgram::new();
+        gl::attach_shader(shader_program.id(), vertex_shader);
+        gl::attach_shader(shader_program.id(), fragment_shader);
+        gl::link_program(shader_program.id());
         // check for linking errors
-        let success = gl::get_programiv(shader_program, GL_LINK_STATUS);
+        let success = gl::get_programiv(shader_program.id(), GL_LINK_STATUS);
 
         if success == 0 {
-            let len = gl::get_programiv(shader_program, GL_INFO_LOG_LENGTH);
+            let len = gl::get_programiv(shader_program.id(), GL_INFO_LOG_LENGTH);
 
-            return match gl::get_program_info_log(shader_program, len) {
+            return match gl::get_program_info_log(shader_program.id(), len) {
                 Some(message) => {
                     println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", message);
                 }
@@ -121,12 +122,12 @@ pub fn main() {
             0.45, 0.5, 0.0, // top
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
         // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, 3 * mem::size_of::<f32>() as i32, 0);
@@ -142,11 +143,11 @@ pub fn main() {
         // uncomment this call to draw in wireframe polygons.
         // gl::PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
 
-        (shader_program, vao)
+        (shader_program, vao, vbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -171,8 +172,8 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // draw our first triangle
-            gl::use_program(shader_program);
-            gl::bind_vertex_array(vao); // seeing as we only have a single vao there's no need to bind it every time, but we'll do so to keep things a bit more organized
+            gl::use_program(shader_program.id());
+            gl::bind_vertex_array(vao.id()); // seeing as we only have a single vao there's no need to bind it every time, but we'll do so to keep things a bit more organized
             gl::draw_arrays(GL_TRIANGLES, 0, 6); // set the count to 6 since we're drawing 6 vertices now (2 triangles); not 3!
                                                  // gl::bind_vertex_array(0); // no need to unbind it every time
 
@@ -1,3 +1,4 @@
+use c01_getting_started::{run_event_loop, GlBuffer, GlProgram, GlVertexArray};
 use dx::{
     gles::{core30::gl, enums::*},
     Instance,
@@ -47,7 +48,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (shader_program, vaos) = {
+    let (shader_program, vaos, vbos) = {
         // build and compile our shader program
 
         // vertex shader
@@ -85,17 +86,17 @@ pub fn main() {
             };
         }
         // link shaders
-        let shader_program = gl::create_program();
-        gl::attach_shader(shader_program, vertex_shader);
-        gl::attach_shader(shader_program, fragment_shader);
-        gl::link_program(shader_program);
+        let shader_program = GlProgram::new();
+        gl::attach_shader(shader_program.id(), vertex_shader);
+        gl::attach_shader(shader_program.id(), fragment_shader);
+        gl::link_program(shader_program.id());
         // check for linking errors
-        let success = gl::get_programiv(shader_program, GL_LINK_STATUS);
+        let success = gl::get_programiv(shader_program.id(), GL_LINK_STATUS);
 
         if success == 0 {
-            let len = gl::get_programiv(shader_program, GL_INFO_LOG_LENGTH);
+            let len = gl::get_programiv(shader_program.id(), GL_INFO_LOG_LENGTH);
 
-            return match gl::get_program_info_log(shader_program, len) {
+            return match gl::get_program_info_log(shader_program.id(), len) {
                 Some(message) => {
                     println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", message);
                 }
@@ -121,16 +122,16 @@ pub fn main() {
 
         let vaos = {
             let v = gl::gen_vertex_arrays(2); // we can also generate multiple vaos or buffers at the same time
-            [v[0], v[1]]
+            [GlVertexArray::from_raw(v[0]), GlVertexArray::from_raw(v[1])]
         };
         let vbos = {
             let v = gl::gen_buffers(2);
-            [v[0], v[1]]
+            [GlBuffer::from_raw(v[0]), GlBuffer::from_raw(v[1])]
         };
         // first triangle setup
 
-        gl::bind_vertex_array(vaos[0]);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[0]);
+        gl::bind_vertex_array(vaos[0].id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[0].id());
         // Vertex attributes stay the same
         gl::buffer_data(GL_ARRAY_BUFFER, &first_triangle, GL_STATIC_DRAW);
 
@@ -139,8 +140,8 @@ pub fn main() {
         // gl::bind_vertex_array(0); // no need to unbind at all as we directly bind a different vao the next few lines
         // second triangle setup
 
-        gl::bind_vertex_array(vaos[1]);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[1]);
+        gl::bind_vertex_array(vaos[1].id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[1].id());
         gl::buffer_data(GL_ARRAY_BUFFER, &second_triangle, GL_STATIC_DRAW);
 
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, 0, 0); // because the vertex data is tightly packed we can also specify 0 as the vertex attribute's stride to let OpenGL figure it out
@@ -150,11 +151,11 @@ pub fn main() {
         // uncomment this call to draw in wireframe polygons.
         // gl::PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
 
-        (shader_program, vaos)
+        (shader_program, vaos, vbos)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbos, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -178,12 +179,12 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // draw our first triangle
-            gl::use_program(shader_program);
+            gl::use_program(shader_program.id());
             // draw first triangle using the data from the first vao
-            gl::bind_vertex_array(vaos[0]);
+            gl::bind_vertex_array(vaos[0].id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3);
             // then we draw the second triangle using the data from the second vao
-            gl::bind_vertex_array(vaos[1]);
+            gl::bind_vertex_array(vaos[1].id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -197,7 +198,5 @@ pub fn main() {
         _ => {}
     });
 
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(2, vaos.as_mut_ptr());
-    //     gl::delete_buffers(2, vbos.as_mut_ptr());
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
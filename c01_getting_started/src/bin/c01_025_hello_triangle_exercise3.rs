@@ -1,3 +1,4 @@
+use c01_getting_started::{run_event_loop, GlBuffer, GlProgram, GlVertexArray};
 use dx::{
     gles::{core30::gl, enums::*},
     Instance,
@@ -55,15 +56,15 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (shader_program_orange, shader_program_yellow, vaos) = {
+    let (shader_program_orange, shader_program_yellow, vaos, vbos) = {
         // build and compile our shader program
 
         // // we skipped compile log checks this time for readability (if you do encounter issues, add the compile-checks! see previous code samples)
         let vertex_shader = gl::create_shader(GL_VERTEX_SHADER);
         let fragment_shader_orange = gl::create_shader(GL_FRAGMENT_SHADER); // the first fragment shader that outputs the color orange
         let fragment_shader_yellow = gl::create_shader(GL_FRAGMENT_SHADER); // the second fragment shader that outputs the color yellow
-        let shader_program_orange = gl::create_program();
-        let shader_program_yellow = gl::create_program(); // the second shader program
+        let shader_program_orange = GlProgram::new();
+        let shader_program_yellow = GlProgram::new(); // the second shader program
 
         gl::shader_source(vertex_shader, VERTEX_SHADER_SOURCE.as_bytes());
         gl::compile_shader(vertex_shader);
@@ -74,14 +75,14 @@ pub fn main() {
         gl::shader_source(fragment_shader_yellow, FRAGMENT_SHADER2_SOURCE.as_bytes());
         gl::compile_shader(fragment_shader_yellow);
         // link the first program object
-        gl::attach_shader(shader_program_orange, vertex_shader);
-        gl::attach_shader(shader_program_orange, fragment_shader_orange);
-        gl::link_program(shader_program_orange);
+        gl::attach_shader(shader_program_orange.id(), vertex_shader);
+        gl::attach_shader(shader_program_orange.id(), fragment_shader_orange);
+        gl::link_program(shader_program_orange.id());
         // then link the second program object using a different fragment shader (but same vertex shader)
         // this is perfectly allowed since the inputs and outputs of both the vertex and fragment shaders are equally matched.
-        gl::attach_shader(shader_program_yellow, vertex_shader);
-        gl::attach_shader(shader_program_yellow, fragment_shader_yellow);
-        gl::link_program(shader_program_yellow);
+        gl::attach_shader(shader_program_yellow.id(), vertex_shader);
+        gl::attach_shader(shader_program_yellow.id(), fragment_shader_yellow);
+        gl::link_program(shader_program_yellow.id());
 
         // set up vertex data (and buffer(s)) and configure vertex attributes
 
@@ -98,17 +99,17 @@ pub fn main() {
 
         let vaos = {
             let v = gl::gen_vertex_arrays(2); // we can also generate multiple vaos or buffers at the same time
-            [v[0], v[1]]
+            [GlVertexArray::from_raw(v[0]), GlVertexArray::from_raw(v[1])]
         };
 
         let vbos = {
             let v = gl::gen_buffers(2);
-            [v[0], v[1]]
+            [GlBuffer::from_raw(v[0]), GlBuffer::from_raw(v[1])]
         };
         // first triangle setup
 
-        gl::bind_vertex_array(vaos[0]);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[0]);
+        gl::bind_vertex_array(vaos[0].id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[0].id());
         // Vertex attributes stay the same
         gl::buffer_data(GL_ARRAY_BUFFER, &first_triangle, GL_STATIC_DRAW);
 
@@ -117,8 +118,8 @@ pub fn main() {
         // gl::bind_vertex_array(0); // no need to unbind at all as we directly bind a different vao the next few lines
         // second triangle setup
 
-        gl::bind_vertex_array(vaos[1]);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[1]);
+        gl::bind_vertex_array(vaos[1].id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbos[1].id());
         gl::buffer_data(GL_ARRAY_BUFFER, &second_triangle, GL_STATIC_DRAW);
 
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, 0, 0); // because the vertex data is tightly packed we can also specify 0 as the vertex attribute's stride to let OpenGL figure it out
@@ -128,11 +129,11 @@ pub fn main() {
         // uncomment this call to draw in wireframe polygons.
         // gl::PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
 
-        (shader_program_orange, shader_program_yellow, vaos)
+        (shader_program_orange, shader_program_yellow, vaos, vbos)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbos, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -156,14 +157,14 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // now when we draw the triangle we first use the vertex and orange fragment shader from the first program
-            gl::use_program(shader_program_orange);
+            gl::use_program(shader_program_orange.id());
             // draw the first triangle using the data from our first vao
-            gl::bind_vertex_array(vaos[0]);
+            gl::bind_vertex_array(vaos[0].id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3); // this call should output an orange triangle
                                                  // then we draw the second triangle using the data from the second vao
                                                  // when we draw the second triangle we want to use a different shader program so we switch to the shader program with our yellow fragment shader.
-            gl::use_program(shader_program_yellow);
-            gl::bind_vertex_array(vaos[1]);
+            gl::use_program(shader_program_yellow.id());
+            gl::bind_vertex_array(vaos[1].id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -177,7 +178,5 @@ pub fn main() {
         _ => {}
     });
 
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(2, vaos.as_mut_ptr());
-    //     gl::delete_buffers(2, vbos.as_mut_ptr());
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
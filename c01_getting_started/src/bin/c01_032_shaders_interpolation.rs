@@ -1,3 +1,4 @@
+use c01_getting_started::{run_event_loop, GlBuffer, GlProgram, GlVertexArray};
 use dx::{
     gles::{core30::gl, enums::*},
     Instance,
@@ -51,7 +52,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let vao = {
+    let (vao, shader_program, vbo) = {
         // build and compile our shader program
 
         // vertex shader
@@ -90,16 +91,16 @@ pub fn main() {
         }
 
         // link shaders
-        let shader_program = gl::create_program();
-        gl::attach_shader(shader_program, vertex_shader);
-        gl::attach_shader(shader_program, fragment_shader);
-        gl::link_program(shader_program);
+        let shader_program = GlProgram::new();
+        gl::attach_shader(shader_program.id(), vertex_shader);
+        gl::attach_shader(shader_program.id(), fragment_shader);
+        gl::link_program(shader_program.id());
         // check for linking errors
-        let success = gl::get_programiv(shader_program, GL_LINK_STATUS);
+        let success = gl::get_programiv(shader_program.id(), GL_LINK_STATUS);
         if success == 0 {
-            let len = gl::get_programiv(shader_program, GL_INFO_LOG_LENGTH);
+            let len = gl::get_programiv(shader_program.id(), GL_INFO_LOG_LENGTH);
 
-            match gl::get_program_info_log(shader_program, len) {
+            match gl::get_program_info_log(shader_program.id(), len) {
                 Some(message) => {
                     println!("ERROR::PROGRAM_LINKING_ERROR:\n{}\n", message);
                 }
@@ -122,12 +123,12 @@ pub fn main() {
             0.0, 0.5, 0.0, 0.0, 0.0, 1.0, // top
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
         // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
         let stride = 6 * mem::size_of::<f32>() as i32;
@@ -142,13 +143,13 @@ pub fn main() {
         // vaos requires a call to gl::bind_vertex_array anyways so we generally don't unbind vaos (nor vbos) when it's not directly necessary.
         // gl::bind_vertex_array(0);
 
-        gl::use_program(shader_program);
+        gl::use_program(shader_program.id());
 
-        vao
+        (vao, shader_program, vbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (shader_program, vbo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -172,7 +173,7 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // render the triangle
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -1,4 +1,4 @@
-use c01_getting_started::Shader;
+use c01_getting_started::{run_event_loop, GlBuffer, GlVertexArray, Shader};
 use dx::{
     assets,
     gles::{core30::gl, enums::*},
@@ -33,7 +33,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (our_shader, vao) = {
+    let (our_shader, vao, vbo) = {
         // you can name your shader files however you like)
         let our_shader = Shader::new(assets!("shaders/3.3.shader.vs"), assets!("shaders/3.3.shader.fs"));
 
@@ -47,12 +47,12 @@ pub fn main() {
             0.0, 0.5, 0.0, 0.0, 0.0, 1.0, // top
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
         // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
         let stride = 6 * mem::size_of::<f32>() as i32;
@@ -67,11 +67,11 @@ pub fn main() {
         // vaos requires a call to gl::bind_vertex_array anyways so we generally don't unbind vaos (nor vbos) when it's not directly necessary.
         // gl::bind_vertex_array(0);
 
-        (our_shader, vao)
+        (our_shader, vao, vbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -96,7 +96,7 @@ pub fn main() {
 
             // render the triangle
             our_shader.use_program();
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 3);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
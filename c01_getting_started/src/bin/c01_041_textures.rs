@@ -1,4 +1,4 @@
-use c01_getting_started::Shader;
+use c01_getting_started::{run_event_loop, GlBuffer, GlTexture, GlVertexArray, Shader};
 use dx::{
     assets,
     gles::{core30::gl, enums::*},
@@ -34,7 +34,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (our_shader, vao, texture) = {
+    let (our_shader, vao, texture, vbo, ebo) = {
         // build and compile our shader program
 
         let our_shader = Shader::new(assets!("shaders/4.1.texture.vs"), assets!("shaders/4.1.texture.fs"));
@@ -54,16 +54,16 @@ pub fn main() {
             1, 2, 3, // second Triangle
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
-        let ebo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        let ebo = GlBuffer::new();
 
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo.id());
         gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &indices, GL_STATIC_DRAW);
 
         let stride = 8 * mem::size_of::<f32>() as i32;
@@ -79,8 +79,8 @@ pub fn main() {
 
         // load and create a texture
 
-        let texture = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture); // all upcoming GL_TEXTURE_2D operations now have effect on this texture object
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture.id()); // all upcoming GL_TEXTURE_2D operations now have effect on this texture object
                                                   // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -103,11 +103,11 @@ pub fn main() {
         );
         gl::generate_mipmap(GL_TEXTURE_2D);
 
-        (our_shader, vao, texture)
+        (our_shader, vao, texture, vbo, ebo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (vbo, ebo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -131,11 +131,11 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT);
 
             // bind Texture
-            gl::bind_texture(GL_TEXTURE_2D, texture);
+            gl::bind_texture(GL_TEXTURE_2D, texture.id());
 
             // render container
             our_shader.use_program();
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_elements_offset(GL_TRIANGLES, 6, GL_UNSIGNED_INT, 0);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -148,8 +148,5 @@ pub fn main() {
         }
         _ => {}
     });
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &vao);
-    //     gl::delete_buffers(1, &vbo);
-    //     gl::delete_buffers(1, &EBO);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -1,4 +1,4 @@
-use c01_getting_started::Shader;
+use c01_getting_started::{run_event_loop, GlBuffer, GlTexture, GlVertexArray, Shader};
 use cgmath::prelude::*;
 use cgmath::{vec3, Matrix4, Rad};
 use dx::{
@@ -38,7 +38,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (our_shader, vao, texture1, texture2) = {
+    let (our_shader, vao, texture1, texture2, vbo, ebo) = {
         // build and compile our shader program
         let our_shader = Shader::new(assets!("shaders/5.1.transform.vs"), assets!("shaders/5.1.transform.fs"));
 
@@ -57,16 +57,16 @@ pub fn main() {
             1, 2, 3, // second Triangle
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
-        let ebo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        let ebo = GlBuffer::new();
 
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo.id());
         gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &indices, GL_STATIC_DRAW);
 
         let stride = 5 * mem::size_of::<f32>() as i32;
@@ -81,8 +81,8 @@ pub fn main() {
 
         // texture 1
 
-        let texture1 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture1);
+        let texture1 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture1.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -107,8 +107,8 @@ pub fn main() {
 
         // texture 2
 
-        let texture2 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture2);
+        let texture2 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture2.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -138,11 +138,11 @@ pub fn main() {
         our_shader.set_int("texture1", 0);
         our_shader.set_int("texture2", 1);
 
-        (our_shader, vao, texture1, texture2)
+        (our_shader, vao, texture1, texture2, vbo, ebo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (vbo, ebo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -167,9 +167,9 @@ pub fn main() {
 
             // bind textures on corresponding texture units
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, texture1);
+            gl::bind_texture(GL_TEXTURE_2D, texture1.id());
             gl::active_texture(GL_TEXTURE1);
-            gl::bind_texture(GL_TEXTURE_2D, texture2);
+            gl::bind_texture(GL_TEXTURE_2D, texture2.id());
 
             // create transformations
             let mut transform: Matrix4<f32> = Matrix4::identity();
@@ -178,11 +178,11 @@ pub fn main() {
 
             // get matrix's uniform location and set matrix
             our_shader.use_program();
-            let transform_loc = gl::get_uniform_location(our_shader.id, "transform");
+            let transform_loc = gl::get_uniform_location(our_shader.id(), "transform");
             gl::uniform_matrix4fv(transform_loc, false, AsRef::<[f32; 16]>::as_ref(&transform));
 
             // render container
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_elements_offset(GL_TRIANGLES, 6, GL_UNSIGNED_INT, 0);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -196,8 +196,5 @@ pub fn main() {
         _ => {}
     });
 
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &vao);
-    //     gl::delete_buffers(1, &vbo);
-    //     gl::delete_buffers(1, &EBO);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -1,4 +1,4 @@
-use c01_getting_started::Shader;
+use c01_getting_started::{run_event_loop, GlBuffer, GlTexture, GlVertexArray, Shader};
 use cgmath::{perspective, vec3, Deg, Matrix4};
 use dx::{
     assets,
@@ -35,7 +35,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (our_shader, vao, texture1, texture2) = {
+    let (our_shader, vao, texture1, texture2, vbo, ebo) = {
         // build and compile our shader program
         let our_shader =
             Shader::new(assets!("shaders/6.1.coordinate_systems.vs"), assets!("shaders/6.1.coordinate_systems.fs"));
@@ -55,16 +55,16 @@ pub fn main() {
             1, 2, 3, // second Triangle
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
-        let ebo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        let ebo = GlBuffer::new();
 
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo.id());
         gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &indices, GL_STATIC_DRAW);
 
         let stride = 5 * mem::size_of::<f32>() as i32;
@@ -79,8 +79,8 @@ pub fn main() {
 
         // texture 1
 
-        let texture1 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture1);
+        let texture1 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture1.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -105,8 +105,8 @@ pub fn main() {
 
         // texture 2
 
-        let texture2 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture2);
+        let texture2 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture2.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -136,11 +136,11 @@ pub fn main() {
         our_shader.set_int("texture1", 0);
         our_shader.set_int("texture2", 1);
 
-        (our_shader, vao, texture1, texture2)
+        (our_shader, vao, texture1, texture2, vbo, ebo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (vbo, ebo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -165,9 +165,9 @@ pub fn main() {
 
             // bind textures on corresponding texture units
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, texture1);
+            gl::bind_texture(GL_TEXTURE_2D, texture1.id());
             gl::active_texture(GL_TEXTURE1);
-            gl::bind_texture(GL_TEXTURE_2D, texture2);
+            gl::bind_texture(GL_TEXTURE_2D, texture2.id());
 
             // activate shader
             our_shader.use_program();
@@ -177,8 +177,8 @@ pub fn main() {
             let view: Matrix4<f32> = Matrix4::from_translation(vec3(0., 0., -3.));
             let projection: Matrix4<f32> = perspective(Deg(45.0), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
             // retrieve the matrix uniform locations
-            let model_loc = gl::get_uniform_location(our_shader.id, "model");
-            let view_loc = gl::get_uniform_location(our_shader.id, "view");
+            let model_loc = gl::get_uniform_location(our_shader.id(), "model");
+            let view_loc = gl::get_uniform_location(our_shader.id(), "view");
             // pass them to the shaders (3 different ways)
             gl::uniform_matrix4fv(model_loc, false, AsRef::<[f32; 16]>::as_ref(&model));
             gl::uniform_matrix4fv(view_loc, false, AsRef::<[f32; 16]>::as_ref(&view));
@@ -186,7 +186,7 @@ pub fn main() {
             our_shader.set_mat4("projection", &projection);
 
             // render container
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_elements_offset(GL_TRIANGLES, 6, GL_UNSIGNED_INT, 0);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -200,8 +200,5 @@ pub fn main() {
         _ => {}
     });
 
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &vao);
-    //     gl::delete_buffers(1, &vbo);
-    //     gl::delete_buffers(1, &EBO);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
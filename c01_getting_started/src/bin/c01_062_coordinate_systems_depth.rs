@@ -1,4 +1,4 @@
-use c01_getting_started::Shader;
+use c01_getting_started::{run_event_loop, GlBuffer, GlTexture, GlVertexArray, Shader};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Rad};
 use dx::{
@@ -38,7 +38,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (our_shader, vao, texture1, texture2) = {
+    let (our_shader, vao, texture1, texture2, vbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -61,12 +61,12 @@ pub fn main() {
             1.0, 0.5, 0.5, 0.5, 1.0, 0.0, 0.5, 0.5, 0.5, 1.0, 0.0, -0.5, 0.5, 0.5, 0.0, 0.0, -0.5, 0.5, -0.5, 0.0, 1.0,
         ];
 
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
 
-        gl::bind_vertex_array(vao);
+        gl::bind_vertex_array(vao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
         let stride = 5 * mem::size_of::<f32>() as i32;
@@ -81,8 +81,8 @@ pub fn main() {
 
         // texture 1
 
-        let texture1 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture1);
+        let texture1 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture1.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -107,8 +107,8 @@ pub fn main() {
 
         // texture 2
 
-        let texture2 = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture2);
+        let texture2 = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture2.id());
         // set the texture wrapping parameters
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32); // set texture wrapping to GL_REPEAT (default wrapping method)
         gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
@@ -138,11 +138,11 @@ pub fn main() {
         our_shader.set_int("texture1", 0);
         our_shader.set_int("texture2", 1);
 
-        (our_shader, vao, texture1, texture2)
+        (our_shader, vao, texture1, texture2, vbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -167,9 +167,9 @@ pub fn main() {
 
             // bind textures on corresponding texture units
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, texture1);
+            gl::bind_texture(GL_TEXTURE_2D, texture1.id());
             gl::active_texture(GL_TEXTURE1);
-            gl::bind_texture(GL_TEXTURE_2D, texture2);
+            gl::bind_texture(GL_TEXTURE_2D, texture2.id());
 
             // activate shader
             our_shader.use_program();
@@ -181,8 +181,8 @@ pub fn main() {
             let view: Matrix4<f32> = Matrix4::from_translation(vec3(0., 0., -3.));
             let projection: Matrix4<f32> = perspective(Deg(45.0), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
             // retrieve the matrix uniform locations
-            let model_loc = gl::get_uniform_location(our_shader.id, "model");
-            let view_loc = gl::get_uniform_location(our_shader.id, "view");
+            let model_loc = gl::get_uniform_location(our_shader.id(), "model");
+            let view_loc = gl::get_uniform_location(our_shader.id(), "view");
             // pass them to the shaders (3 different ways)
             gl::uniform_matrix4fv(model_loc, false, AsRef::<[f32; 16]>::as_ref(&model));
             gl::uniform_matrix4fv(view_loc, false, AsRef::<[f32; 16]>::as_ref(&view));
@@ -190,7 +190,7 @@ pub fn main() {
             our_shader.set_mat4("projection", &projection);
 
             // render container
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -204,7 +204,5 @@ pub fn main() {
         _ => {}
     });
 
-    // // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &vao);
-    //     gl::delete_buffers(1, &vbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -0,0 +1,784 @@
+use cgmath::prelude::*;
+use cgmath::{vec2, vec3, vec4, Matrix3, Matrix4, Quaternion};
+use serde_json::Value;
+use std::{
+    convert::{TryFrom, TryInto},
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str,
+};
+
+use super::mesh::Vertex;
+use super::model::RawMesh;
+
+/// Errors of `load_gltf`.
+#[derive(Debug)]
+pub enum GltfError {
+    Io(io::Error),
+    /// the JSON part doesn't parse
+    Json(String),
+    /// the file parses but doesn't describe a valid glTF 2.0 asset
+    Invalid(String),
+    /// valid glTF using a feature the loader doesn't handle
+    Unsupported(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io(err) => write!(f, "{}", err),
+            GltfError::Json(reason) => write!(f, "invalid JSON: {}", reason),
+            GltfError::Invalid(reason) => write!(f, "invalid glTF: {}", reason),
+            GltfError::Unsupported(reason) => write!(f, "unsupported glTF: {}", reason),
+        }
+    }
+}
+
+impl Error for GltfError {}
+
+/// The meshes and materials of a glTF file.
+pub struct GltfScene {
+    pub meshes: Vec<RawMesh>,
+    /// glTF materials mapped to the OBJ material model the rest of the loader uses; texture
+    /// paths are relative to the directory of the file
+    pub materials: Vec<tobj::Material>,
+    /// external files the buffers were read from
+    pub buffers: Vec<PathBuf>,
+}
+
+/// Reads a glTF 2.0 file, `.gltf` (JSON with external or base64 embedded buffers) or
+/// `.glb` (binary container).
+///
+/// Every triangle primitive of the meshes in the default scene becomes a `RawMesh`, with the
+/// node transforms applied to its vertices: positions, normals, the first texture
+/// coordinates and the first vertex colors are read; tangents are generated from the texture
+/// coordinates when the mesh is processed, as for the other formats. The metallic-roughness base color, normal, occlusion and emissive textures become
+/// the diffuse, normal, ambient and `map_Ke` textures of the material. Textures have to be
+/// image files next to the model, since they are loaded through the `TextureCache`; images
+/// embedded in buffers or data URIs are skipped with a warning, as are skins, morph targets,
+/// animations and primitives that aren't triangle lists.
+pub fn load_gltf(path: &Path) -> Result<GltfScene, GltfError> {
+    let bytes = fs::read(path).map_err(GltfError::Io)?;
+    let (json, binary) = if bytes.starts_with(b"glTF") {
+        split_glb(&bytes)?
+    } else {
+        (&bytes[..], None)
+    };
+    let root: Value = serde_json::from_slice(json).map_err(|err| GltfError::Json(err.to_string()))?;
+
+    let version = root
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Value::as_str);
+    if !version.is_some_and(|version| version.starts_with("2.")) {
+        return Err(GltfError::Unsupported(format!(
+            "version {:?}, only 2.x is read",
+            version
+        )));
+    }
+    if let Some(extension) = root.array("extensionsRequired").first() {
+        return Err(GltfError::Unsupported(format!(
+            "required extension {:?}",
+            extension.as_str()
+        )));
+    }
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut buffers = Vec::new();
+    let mut buffer_files = Vec::new();
+    for (idx, buffer) in root.array("buffers").iter().enumerate() {
+        let data = match buffer.get("uri").and_then(Value::as_str) {
+            Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+            Some(uri) => {
+                let file = directory.join(decode_uri(uri));
+                let data = fs::read(&file).map_err(GltfError::Io)?;
+                buffer_files.push(file);
+                data
+            }
+            // the binary chunk of a .glb file
+            None if idx == 0 => binary
+                .ok_or_else(|| GltfError::Invalid("buffer 0 has no URI and there is no binary chunk".into()))?
+                .to_vec(),
+            None => return Err(GltfError::Invalid(format!("buffer {} has no URI", idx))),
+        };
+        let length = buffer.usize("byteLength")?;
+        if data.len() < length {
+            return Err(GltfError::Invalid(format!(
+                "buffer {} has {} bytes instead of {}",
+                idx,
+                data.len(),
+                length
+            )));
+        }
+        buffers.push(data);
+    }
+    let file = GltfFile { root: &root, buffers };
+
+    let materials = root
+        .array("materials")
+        .iter()
+        .enumerate()
+        .map(|(idx, material)| file.material(idx, material))
+        .collect();
+
+    // nodes of the default scene, or of every scene if there is no default one
+    let scenes = root.array("scenes");
+    let scene_nodes: Vec<&Value> = match root.get("scene").and_then(as_usize) {
+        Some(scene) => file.index(scenes, scene, "scene")?.array("nodes").iter().collect(),
+        None => scenes.iter().flat_map(|scene| scene.array("nodes")).collect(),
+    };
+    let mut meshes = Vec::new();
+    let mut stack = Vec::new();
+    for node in scene_nodes {
+        stack.push((
+            as_usize(node).ok_or_else(|| invalid("node index"))?,
+            Matrix4::identity(),
+            0,
+        ));
+    }
+    let nodes = root.array("nodes");
+    while let Some((idx, parent, depth)) = stack.pop() {
+        // a cycle would otherwise never end
+        if depth > nodes.len() {
+            return Err(GltfError::Invalid("node hierarchy has a cycle".into()));
+        }
+        let node = file.index(nodes, idx, "node")?;
+        let transform = parent * node_transform(node)?;
+        if let Some(mesh) = node.get("mesh").and_then(as_usize) {
+            let mesh = file.index(root.array("meshes"), mesh, "mesh")?;
+            for primitive in mesh.array("primitives") {
+                if let Some(raw) = file.primitive(primitive, &transform)? {
+                    meshes.push(raw);
+                }
+            }
+        }
+        for child in node.array("children") {
+            let child = as_usize(child).ok_or_else(|| invalid("child index"))?;
+            stack.push((child, transform, depth + 1));
+        }
+    }
+    for ignored in ["skins", "animations"] {
+        if !root.array(ignored).is_empty() {
+            println!("WARNING::GLTF: {} are not supported and ignored", ignored);
+        }
+    }
+
+    Ok(GltfScene {
+        meshes,
+        materials,
+        buffers: buffer_files,
+    })
+}
+
+// JSON and binary chunks of a .glb file
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+    let u32_at = |pos: usize| -> Result<u32, GltfError> {
+        let word = bytes.get(pos..pos + 4).ok_or_else(|| invalid("truncated GLB"))?;
+        Ok(u32::from_le_bytes(word.try_into().unwrap()))
+    };
+    if u32_at(4)? != 2 {
+        return Err(GltfError::Unsupported(format!("GLB version {}", u32_at(4)?)));
+    }
+    let length = (u32_at(8)? as usize).min(bytes.len());
+    let (mut json, mut binary) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= length {
+        let chunk_length = u32_at(pos)? as usize;
+        let chunk_type = u32_at(pos + 4)?;
+        let data = bytes
+            .get(pos + 8..pos + 8 + chunk_length)
+            .ok_or_else(|| invalid("truncated GLB chunk"))?;
+        match &chunk_type.to_le_bytes() {
+            b"JSON" => json = json.or(Some(data)),
+            b"BIN\0" => binary = binary.or(Some(data)),
+            // unknown chunks must be ignored
+            _ => {}
+        }
+        pos += 8 + chunk_length;
+    }
+    Ok((json.ok_or_else(|| invalid("GLB without a JSON chunk"))?, binary))
+}
+
+fn invalid(what: &str) -> GltfError {
+    GltfError::Invalid(format!("invalid {}", what))
+}
+
+// local transform of a node: a matrix, or translation, rotation and scale
+fn node_transform(node: &Value) -> Result<Matrix4<f32>, GltfError> {
+    if let Some(matrix) = node.get("matrix") {
+        let m = matrix.floats::<16>().ok_or_else(|| invalid("node matrix"))?;
+        // column-major, like cgmath
+        return Ok(Matrix4::new(
+            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8], m[9], m[10], m[11], m[12], m[13], m[14], m[15],
+        ));
+    }
+    let mut transform = Matrix4::identity();
+    if let Some(translation) = node.get("translation") {
+        let [x, y, z] = translation.floats().ok_or_else(|| invalid("node translation"))?;
+        transform = transform * Matrix4::from_translation(vec3(x, y, z));
+    }
+    if let Some(rotation) = node.get("rotation") {
+        let [x, y, z, w] = rotation.floats().ok_or_else(|| invalid("node rotation"))?;
+        transform = transform * Matrix4::from(Quaternion::new(w, x, y, z).normalize());
+    }
+    if let Some(scale) = node.get("scale") {
+        let [x, y, z] = scale.floats().ok_or_else(|| invalid("node scale"))?;
+        transform = transform * Matrix4::from_nonuniform_scale(x, y, z);
+    }
+    Ok(transform)
+}
+
+// glTF buffers are referenced by URIs, which may escape characters like spaces
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let escaped = bytes
+            .get(pos + 1..pos + 3)
+            .filter(|_| bytes[pos] == b'%')
+            .and_then(|hex| u8::from_str_radix(str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                pos += 3;
+            }
+            None => {
+                decoded.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, GltfError> {
+    let data = uri
+        .split_once(";base64,")
+        .map(|(_, data)| data)
+        .ok_or_else(|| GltfError::Unsupported("data URI that isn't base64".into()))?;
+    let value = |byte: u8| match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut decoded = Vec::with_capacity(data.len() / 4 * 3);
+    let (mut bits, mut count) = (0u32, 0);
+    for byte in data.bytes().take_while(|&byte| byte != b'=') {
+        let value = value(byte).ok_or_else(|| invalid("base64 data"))?;
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+struct GltfFile<'a> {
+    root: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+// how an accessor stores its elements
+struct Accessor<'a> {
+    bytes: &'a [u8],
+    count: usize,
+    components: usize,
+    component_type: u32,
+    normalized: bool,
+    stride: usize,
+}
+
+impl Accessor<'_> {
+    fn component_size(&self) -> usize {
+        match self.component_type {
+            GLTF_BYTE | GLTF_UNSIGNED_BYTE => 1,
+            GLTF_SHORT | GLTF_UNSIGNED_SHORT => 2,
+            _ => 4,
+        }
+    }
+
+    // component of an element as a float, normalized integers mapped to 0..1 or -1..1
+    fn float(&self, element: usize, component: usize) -> f32 {
+        let pos = element * self.stride + component * self.component_size();
+        let bytes = &self.bytes[pos..];
+        let (value, max) = match self.component_type {
+            GLTF_BYTE => (bytes[0] as i8 as f32, i8::MAX as f32),
+            GLTF_UNSIGNED_BYTE => (bytes[0] as f32, u8::MAX as f32),
+            GLTF_SHORT => (i16::from_le_bytes([bytes[0], bytes[1]]) as f32, i16::MAX as f32),
+            GLTF_UNSIGNED_SHORT => (u16::from_le_bytes([bytes[0], bytes[1]]) as f32, u16::MAX as f32),
+            GLTF_UNSIGNED_INT => (
+                u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f32,
+                u32::MAX as f32,
+            ),
+            _ => (f32::from_le_bytes(bytes[..4].try_into().unwrap()), 1.0),
+        };
+        if self.normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    }
+
+    fn index(&self, element: usize) -> u32 {
+        let bytes = &self.bytes[element * self.stride..];
+        match self.component_type {
+            GLTF_UNSIGNED_BYTE => bytes[0] as u32,
+            GLTF_UNSIGNED_SHORT => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            _ => u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+        }
+    }
+}
+
+const GLTF_BYTE: u32 = 5120;
+const GLTF_UNSIGNED_BYTE: u32 = 5121;
+const GLTF_SHORT: u32 = 5122;
+const GLTF_UNSIGNED_SHORT: u32 = 5123;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_TRIANGLES: usize = 4;
+
+impl<'a> GltfFile<'a> {
+    fn index<'j, T>(&self, array: &'j [T], idx: usize, what: &str) -> Result<&'j T, GltfError> {
+        array
+            .get(idx)
+            .ok_or_else(|| GltfError::Invalid(format!("{} {} out of range", what, idx)))
+    }
+
+    fn accessor(&self, idx: usize) -> Result<Accessor<'_>, GltfError> {
+        let accessor = self.index(self.root.array("accessors"), idx, "accessor")?;
+        if accessor.get("sparse").is_some() {
+            return Err(GltfError::Unsupported(format!("sparse accessor {}", idx)));
+        }
+        let components = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => return Err(GltfError::Unsupported(format!("accessor type {:?}", other))),
+        };
+        let component_type = accessor.usize("componentType")? as u32;
+        if !matches!(
+            component_type,
+            GLTF_BYTE | GLTF_UNSIGNED_BYTE | GLTF_SHORT | GLTF_UNSIGNED_SHORT | GLTF_UNSIGNED_INT | GLTF_FLOAT
+        ) {
+            return Err(GltfError::Invalid(format!("component type {}", component_type)));
+        }
+        let mut result = Accessor {
+            bytes: &[],
+            count: accessor.usize("count")?,
+            components,
+            component_type,
+            normalized: accessor.get("normalized").and_then(Value::as_bool).unwrap_or(false),
+            stride: 0,
+        };
+        let element_size = components * result.component_size();
+
+        let view = match accessor.get("bufferView").and_then(as_usize) {
+            Some(view) => self.index(self.root.array("bufferViews"), view, "buffer view")?,
+            // an accessor without a buffer view is all zeros
+            None => {
+                result.bytes = &ZEROS[..element_size];
+                return Ok(result);
+            }
+        };
+        let buffer = self.index(&self.buffers, view.usize("buffer")?, "buffer")?;
+        let start = view.get("byteOffset").and_then(as_usize).unwrap_or(0);
+        let length = view.usize("byteLength")?;
+        let view_bytes = buffer
+            .get(start..start.saturating_add(length))
+            .ok_or_else(|| GltfError::Invalid(format!("buffer view of accessor {} out of range", idx)))?;
+        result.stride = view.get("byteStride").and_then(as_usize).unwrap_or(element_size);
+        let offset = accessor.get("byteOffset").and_then(as_usize).unwrap_or(0);
+        let needed = match result.count {
+            0 => 0,
+            count => (count - 1) * result.stride + element_size,
+        };
+        result.bytes = view_bytes
+            .get(offset..)
+            .filter(|bytes| bytes.len() >= needed)
+            .ok_or_else(|| GltfError::Invalid(format!("accessor {} reads past its buffer view", idx)))?;
+        Ok(result)
+    }
+
+    // the mesh of a primitive with the node transform applied, `None` if it isn't made of triangles
+    fn primitive(&self, primitive: &Value, transform: &Matrix4<f32>) -> Result<Option<RawMesh>, GltfError> {
+        let mode = primitive.get("mode").and_then(as_usize).unwrap_or(GLTF_TRIANGLES);
+        if mode != GLTF_TRIANGLES {
+            println!(
+                "WARNING::GLTF: skipping a primitive with mode {}, only triangle lists are read",
+                mode
+            );
+            return Ok(None);
+        }
+        if !primitive.array("targets").is_empty() {
+            println!("WARNING::GLTF: morph targets are not supported and ignored");
+        }
+        let attributes = primitive.get("attributes");
+        let attribute = |name: &str| match attributes.and_then(|attributes| attributes.get(name)) {
+            Some(accessor) => {
+                let accessor = as_usize(accessor).ok_or_else(|| invalid("attribute accessor"))?;
+                self.accessor(accessor).map(Some)
+            }
+            None => Ok(None),
+        };
+        let positions =
+            attribute("POSITION")?.ok_or_else(|| GltfError::Invalid("primitive without POSITION".into()))?;
+        let count = positions.count;
+        let normals = with_count(attribute("NORMAL")?, count, "NORMAL")?;
+        let tex_coords = with_count(attribute("TEXCOORD_0")?, count, "TEXCOORD_0")?;
+        let colors = with_count(attribute("COLOR_0")?, count, "COLOR_0")?;
+
+        let normal_matrix = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate())
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or_else(Matrix3::identity);
+        let vertices = (0..count)
+            .map(|idx| {
+                let position = vec3(
+                    positions.float(idx, 0),
+                    positions.float(idx, 1),
+                    positions.float(idx, 2),
+                );
+                let mut vertex = Vertex {
+                    position: (transform * position.extend(1.0)).truncate(),
+                    ..Vertex::default()
+                };
+                if let Some(normals) = &normals {
+                    let normal = vec3(normals.float(idx, 0), normals.float(idx, 1), normals.float(idx, 2));
+                    vertex.normal = (normal_matrix * normal).normalize();
+                }
+                if let Some(tex_coords) = &tex_coords {
+                    // glTF puts the origin of texture coordinates in the top left corner
+                    vertex.tex_coords = vec2(tex_coords.float(idx, 0), 1.0 - tex_coords.float(idx, 1));
+                }
+                vertex
+            })
+            .collect();
+        let colors = colors.map(|colors| {
+            (0..count)
+                .map(|idx| {
+                    let alpha = if colors.components == 4 {
+                        colors.float(idx, 3)
+                    } else {
+                        1.0
+                    };
+                    vec4(colors.float(idx, 0), colors.float(idx, 1), colors.float(idx, 2), alpha)
+                })
+                .collect()
+        });
+
+        let mut indices: Vec<u32> = match primitive.get("indices").and_then(as_usize) {
+            Some(accessor) => {
+                let accessor = self.accessor(accessor)?;
+                (0..accessor.count).map(|idx| accessor.index(idx)).collect()
+            }
+            None => (0..count as u32).collect(),
+        };
+        indices.truncate(indices.len() / 3 * 3);
+        if let Some(&idx) = indices.iter().find(|&&idx| idx as usize >= count) {
+            return Err(GltfError::Invalid(format!("index {} of {} vertices", idx, count)));
+        }
+        // a mirroring transform turns the triangles inside out
+        if transform.determinant() < 0.0 {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        Ok(Some(RawMesh {
+            vertices,
+            indices,
+            has_normals: normals.is_some(),
+            has_tex_coords: tex_coords.is_some(),
+            colors,
+            material: primitive.get("material").and_then(as_usize),
+        }))
+    }
+
+    fn material(&self, idx: usize, material: &Value) -> tobj::Material {
+        let pbr = material.get("pbrMetallicRoughness");
+        let base_color = pbr
+            .and_then(|pbr| pbr.get("baseColorFactor"))
+            .and_then(|factor| factor.floats::<4>())
+            .unwrap_or([1.0; 4]);
+        let texture = |info: Option<&Value>| -> String {
+            let texture = match info.and_then(|info| info.get("index")).and_then(as_usize) {
+                Some(texture) => texture,
+                None => return String::new(),
+            };
+            let image = self
+                .root
+                .array("textures")
+                .get(texture)
+                .and_then(|texture| texture.get("source"))
+                .and_then(as_usize)
+                .and_then(|image| self.root.array("images").get(image));
+            match image.and_then(|image| image.get("uri")).and_then(Value::as_str) {
+                Some(uri) if !uri.starts_with("data:") => decode_uri(uri),
+                _ => {
+                    println!(
+                        "WARNING::GLTF: material {} uses texture {}, which isn't an image file; only textures in files are loaded",
+                        idx, texture
+                    );
+                    String::new()
+                }
+            }
+        };
+
+        let mut result = tobj::Material {
+            name: material
+                .get("name")
+                .and_then(Value::as_str)
+                .map_or_else(|| format!("material {}", idx), str::to_string),
+            diffuse: [base_color[0], base_color[1], base_color[2]],
+            // `load_material` blends materials with a dissolve below 1, which glTF only does
+            // with the BLEND alpha mode
+            dissolve: match material.get("alphaMode").and_then(Value::as_str) {
+                Some("BLEND") => base_color[3].min(0.999),
+                _ => 1.0,
+            },
+            diffuse_texture: texture(pbr.and_then(|pbr| pbr.get("baseColorTexture"))),
+            normal_texture: texture(material.get("normalTexture")),
+            ambient_texture: texture(material.get("occlusionTexture")),
+            ..tobj::Material::default()
+        };
+        let emissive_texture = texture(material.get("emissiveTexture"));
+        if !emissive_texture.is_empty() {
+            result.unknown_param.insert("map_Ke".to_string(), emissive_texture);
+        }
+        result
+    }
+}
+
+// an attribute with one element per position
+fn with_count<'a>(accessor: Option<Accessor<'a>>, count: usize, name: &str) -> Result<Option<Accessor<'a>>, GltfError> {
+    match accessor {
+        Some(accessor) if accessor.count != count => Err(GltfError::Invalid(format!(
+            "{} has {} elements for {} positions",
+            name, accessor.count, count
+        ))),
+        accessor => Ok(accessor),
+    }
+}
+
+// backs accessors without a buffer view
+static ZEROS: [u8; 16] = [0; 16];
+
+// lookups in the glTF JSON
+trait JsonExt {
+    // the array member `key`, empty if there is none
+    fn array(&self, key: &str) -> &[Value];
+    // the member `key` as an index or count
+    fn usize(&self, key: &str) -> Result<usize, GltfError>;
+    // an array of exactly N numbers
+    fn floats<const N: usize>(&self) -> Option<[f32; N]>;
+}
+
+impl JsonExt for Value {
+    fn array(&self, key: &str) -> &[Value] {
+        self.get(key).and_then(Value::as_array).map_or(&[], Vec::as_slice)
+    }
+
+    fn usize(&self, key: &str) -> Result<usize, GltfError> {
+        self.get(key)
+            .and_then(as_usize)
+            .ok_or_else(|| GltfError::Invalid(format!("missing or invalid {:?}", key)))
+    }
+
+    fn floats<const N: usize>(&self) -> Option<[f32; N]> {
+        let values = self.as_array().filter(|values| values.len() == N)?;
+        let mut floats = [0.0; N];
+        for (float, value) in floats.iter_mut().zip(values) {
+            *float = value.as_f64()? as f32;
+        }
+        Some(floats)
+    }
+}
+
+// an index or count
+fn as_usize(value: &Value) -> Option<usize> {
+    value.as_u64().and_then(|value| usize::try_from(value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec2, Vector3};
+
+    // a quad in the XY plane: positions, normals, texture coordinates and u16 indices
+    fn quad_buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for _ in 0..4 {
+            for value in [0.0f32, 0.0, 1.0] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for value in [0.0f32, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for idx in [0u16, 1, 2, 0, 2, 3] {
+            bytes.extend_from_slice(&idx.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn quad_json(buffer: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0", "generator": "test \"quoted\" é"}},
+                "scene": 0,
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": [
+                    {{"translation": [0, 0, 2], "children": [1]}},
+                    {{"mesh": 0, "scale": [-1, 1, 1]}}
+                ],
+                "meshes": [{{"primitives": [{{
+                    "attributes": {{"POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2}},
+                    "indices": 3,
+                    "material": 0
+                }}]}}],
+                "materials": [{{
+                    "name": "painted",
+                    "pbrMetallicRoughness": {{"baseColorFactor": [1, 0.5, 0.25, 1], "baseColorTexture": {{"index": 0}}}}
+                }}],
+                "textures": [{{"source": 0}}],
+                "images": [{{"uri": "base%20color.png"}}],
+                "buffers": [{{{}"byteLength": 140}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 128}},
+                    {{"buffer": 0, "byteOffset": 128, "byteLength": 12}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3"}},
+                    {{"bufferView": 0, "byteOffset": 48, "componentType": 5126, "count": 4, "type": "VEC3"}},
+                    {{"bufferView": 0, "byteOffset": 96, "componentType": 5126, "count": 4, "type": "VEC2"}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 6, "type": "SCALAR"}}
+                ]
+            }}"#,
+            buffer
+        )
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (idx, &byte)| bits | (byte as u32) << (16 - 8 * idx));
+            for idx in 0..4 {
+                if idx <= chunk.len() {
+                    encoded.push(DIGITS[(bits >> (18 - 6 * idx) & 63) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    fn load(name: &str, bytes: &[u8]) -> GltfScene {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let scene = load_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        scene.unwrap()
+    }
+
+    fn check_quad(scene: &GltfScene) {
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0];
+        assert!(mesh.has_normals);
+        assert_eq!(mesh.material, Some(0));
+        // mirrored by the scale, moved by the parent translation
+        let positions: Vec<Vector3<f32>> = mesh.vertices.iter().map(|vertex| vertex.position).collect();
+        assert_eq!(positions[1], vec3(-1.0, 0.0, 2.0));
+        assert_eq!(positions[2], vec3(-1.0, 1.0, 2.0));
+        // the mirror flips the winding, the normal stays facing +Z
+        assert_eq!(mesh.indices, [0, 2, 1, 0, 3, 2]);
+        assert_eq!(mesh.vertices[0].normal, Vector3::unit_z());
+        // v flipped to the OpenGL convention
+        assert_eq!(mesh.vertices[0].tex_coords, vec2(0.0, 0.0));
+        assert_eq!(mesh.vertices[2].tex_coords, vec2(1.0, 1.0));
+
+        let material = &scene.materials[0];
+        assert_eq!(material.name, "painted");
+        assert_eq!(material.diffuse, [1.0, 0.5, 0.25]);
+        assert_eq!(material.dissolve, 1.0);
+        assert_eq!(material.diffuse_texture, "base color.png");
+    }
+
+    #[test]
+    fn json_lookups() {
+        let json: Value = serde_json::from_str(r#"{"a": [1, -2.5e1, 3], "count": 4, "c": {}}"#).unwrap();
+        assert_eq!(json.get("a").and_then(|a| a.floats::<3>()), Some([1.0, -25.0, 3.0]));
+        assert_eq!(json.get("a").and_then(|a| a.floats::<2>()), None);
+        assert_eq!(json.array("a").len(), 3);
+        assert!(json.array("c").is_empty() && json.array("missing").is_empty());
+        assert_eq!(json.usize("count").unwrap(), 4);
+        assert!(matches!(json.usize("a"), Err(GltfError::Invalid(_))));
+        assert_eq!(
+            json.array("a").iter().map(as_usize).collect::<Vec<_>>(),
+            [Some(1), None, Some(3)]
+        );
+    }
+
+    #[test]
+    fn embedded_buffer() {
+        let uri = format!(
+            "\"uri\": \"data:application/octet-stream;base64,{}\", ",
+            base64(&quad_buffer())
+        );
+        let scene = load("quad.gltf", quad_json(&uri).as_bytes());
+        check_quad(&scene);
+        assert!(scene.buffers.is_empty());
+    }
+
+    #[test]
+    fn glb() {
+        let mut json = quad_json("").into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut binary = quad_buffer();
+        binary.resize(binary.len().next_multiple_of(4), 0);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"glTF");
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&((12 + 8 + json.len() + 8 + binary.len()) as u32).to_le_bytes());
+        for (chunk_type, data) in [(b"JSON", &json), (b"BIN\0", &binary)] {
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(chunk_type);
+            bytes.extend_from_slice(data);
+        }
+        check_quad(&load("quad.glb", &bytes));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let uri = format!(
+            "\"uri\": \"data:application/octet-stream;base64,{}\", ",
+            base64(&quad_buffer()[..100])
+        );
+        assert!(load_gltf(Path::new("missing.gltf")).is_err());
+        let path = std::env::temp_dir().join(format!("{}_short.gltf", std::process::id()));
+        fs::write(&path, quad_json(&uri)).unwrap();
+        let result = load_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(GltfError::Invalid(_))));
+
+        fs::write(&path, "{\"asset\": [1,]}").unwrap();
+        let result = load_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(GltfError::Json(_))));
+    }
+}
@@ -0,0 +1,559 @@
+use image::codecs::hdr::HdrDecoder;
+use std::{
+    convert::{TryFrom, TryInto},
+    fs,
+    path::Path,
+};
+use vertex_format::Half;
+
+use super::texture::TextureError;
+
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const RADIANCE_MAGIC: &[u8] = b"#?";
+// a 16k x 4k panorama
+const MAX_EXR_PIXELS: i64 = 1 << 26;
+
+/// A floating point image in linear color, read from a Radiance `.hdr` or OpenEXR file.
+/// Create float textures from it with `Texture2DBuilder::from_hdr_image`.
+#[derive(Clone, Debug)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    /// 3 for RGB, 4 for RGBA
+    pub channels: u32,
+    /// row by row from the top
+    pub pixels: Vec<f32>,
+}
+
+impl HdrImage {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<HdrImage, TextureError> {
+        let bytes = fs::read(path).map_err(TextureError::Io)?;
+        HdrImage::parse(&bytes)
+    }
+
+    /// decodes a Radiance or OpenEXR file, told apart by their magic number
+    pub fn parse(bytes: &[u8]) -> Result<HdrImage, TextureError> {
+        if bytes.starts_with(&EXR_MAGIC) {
+            parse_exr(bytes)
+        } else if bytes.starts_with(RADIANCE_MAGIC) {
+            parse_radiance(bytes)
+        } else {
+            Err(TextureError::Format("not a Radiance HDR or OpenEXR file".into()))
+        }
+    }
+
+    /// flips the image so the first row is the bottom, as GL expects
+    pub fn flip_vertically(&mut self) {
+        let row = (self.width * self.channels) as usize;
+        let rows: Vec<&[f32]> = self.pixels.chunks(row).rev().collect();
+        self.pixels = rows.concat();
+    }
+
+    /// the next mipmap level: half the size, averaging 2x2 pixels
+    pub fn downsample(&self) -> HdrImage {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let channels = self.channels as usize;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * channels);
+        for y in 0..height {
+            for x in 0..width {
+                // clamped for odd and 1 texel wide sizes
+                let xs = [(x * 2).min(self.width - 1), (x * 2 + 1).min(self.width - 1)];
+                let ys = [(y * 2).min(self.height - 1), (y * 2 + 1).min(self.height - 1)];
+                for channel in 0..channels {
+                    let mut sum = 0.0;
+                    for &sy in &ys {
+                        for &sx in &xs {
+                            sum += self.pixels[(sy * self.width + sx) as usize * channels + channel];
+                        }
+                    }
+                    pixels.push(sum / 4.0);
+                }
+            }
+        }
+        HdrImage {
+            width,
+            height,
+            channels: self.channels,
+            pixels,
+        }
+    }
+
+    /// the pixels as half floats with `channels` channels, dropping or adding an opaque alpha
+    pub fn to_half(&self, channels: u32) -> Vec<Half> {
+        let mut halves = Vec::with_capacity(self.pixels.len() / self.channels as usize * channels as usize);
+        for pixel in self.pixels.chunks(self.channels as usize) {
+            halves.extend(pixel.iter().take(channels as usize).map(|&value| Half::from_f32(value)));
+            if channels > self.channels {
+                halves.push(Half::from_f32(1.0));
+            }
+        }
+        halves
+    }
+
+    /// the pixels in the RGBE format of Radiance files: 8-bit red, green and blue sharing
+    /// the exponent in alpha. Alpha of the image is lost and negative values become 0.
+    pub fn to_rgbe(&self) -> Vec<u8> {
+        let mut texels = Vec::with_capacity(self.pixels.len() / self.channels as usize * 4);
+        for pixel in self.pixels.chunks(self.channels as usize) {
+            texels.extend_from_slice(&rgbe([pixel[0], pixel[1], pixel[2]]));
+        }
+        texels
+    }
+}
+
+// the shared exponent is that of the largest component, as in Radiance's `float2rgbe`
+fn rgbe(color: [f32; 3]) -> [u8; 4] {
+    let max = color[0].max(color[1]).max(color[2]);
+    if max.is_nan() || max <= 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let exponent = (max.log2().floor() as i32 + 1).clamp(-128, 127);
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |value: f32| (value * scale).clamp(0.0, 255.0) as u8;
+    [
+        channel(color[0]),
+        channel(color[1]),
+        channel(color[2]),
+        (exponent + 128) as u8,
+    ]
+}
+
+fn parse_radiance(bytes: &[u8]) -> Result<HdrImage, TextureError> {
+    let decoder = HdrDecoder::new(bytes).map_err(TextureError::Image)?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr().map_err(TextureError::Image)?;
+    Ok(HdrImage {
+        width: metadata.width,
+        height: metadata.height,
+        channels: 3,
+        pixels: pixels.iter().flat_map(|pixel| pixel.0.iter().copied()).collect(),
+    })
+}
+
+fn invalid(reason: &str) -> TextureError {
+    TextureError::Exr(reason.into())
+}
+
+// bounds checked little endian reads
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], TextureError> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(|| invalid("truncated file"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, TextureError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, TextureError> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn u64(&mut self) -> Result<usize, TextureError> {
+        let value = u64::from_le_bytes(self.bytes(8)?.try_into().unwrap());
+        usize::try_from(value).map_err(|_| invalid("offset out of range"))
+    }
+
+    // null terminated string
+    fn string(&mut self) -> Result<&'a str, TextureError> {
+        let rest = self.bytes.get(self.offset..).unwrap_or_default();
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid("truncated file"))?;
+        let string = std::str::from_utf8(&rest[..len]).map_err(|_| invalid("attribute name"))?;
+        self.offset += len + 1;
+        Ok(string)
+    }
+}
+
+struct ExrChannel {
+    name: String,
+    /// 0 UINT, 1 HALF, 2 FLOAT
+    pixel_type: u32,
+}
+
+impl ExrChannel {
+    fn size(&self) -> usize {
+        if self.pixel_type == 1 {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn value(&self, bytes: &[u8]) -> f32 {
+        match self.pixel_type {
+            0 => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            1 => Half(u16::from_le_bytes(bytes.try_into().unwrap())).to_f32(),
+            _ => f32::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+// A single part scanline OpenEXR file without compression or with RLE, ZIPS or ZIP
+// compression, which is what most tools write. R, G, B and A are read, or Y for
+// greyscale files; other channels are skipped.
+fn parse_exr(bytes: &[u8]) -> Result<HdrImage, TextureError> {
+    let mut reader = Reader { bytes, offset: 4 };
+    let version = reader.u32()?;
+    if version & 0xff != 2 {
+        return Err(invalid("unknown version"));
+    }
+    // tiled, deep and multi-part flags
+    if version & 0x1a00 != 0 {
+        return Err(TextureError::Format(
+            "only scanline OpenEXR files with a single part are supported".into(),
+        ));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = reader.string()?;
+        let size = reader.u32()? as usize;
+        let mut value = Reader {
+            bytes: reader.bytes(size)?,
+            offset: 0,
+        };
+        match name {
+            "channels" => loop {
+                let name = value.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let pixel_type = value.u32()?;
+                // pLinear and reserved bytes
+                value.bytes(4)?;
+                if (value.i32()?, value.i32()?) != (1, 1) {
+                    return Err(TextureError::Format("subsampled EXR channels are not supported".into()));
+                }
+                channels.push(ExrChannel {
+                    name: name.to_string(),
+                    pixel_type,
+                });
+            },
+            "compression" => compression = Some(value.bytes(1)?[0]),
+            "dataWindow" => data_window = Some([value.i32()?, value.i32()?, value.i32()?, value.i32()?]),
+            _ => {}
+        }
+    }
+    // the corners are i32, their distance may not be
+    let [x_min, y_min, x_max, y_max] = data_window.ok_or_else(|| invalid("no dataWindow"))?.map(i64::from);
+    if x_max < x_min || y_max < y_min {
+        return Err(invalid("empty dataWindow"));
+    }
+    let (width, height) = (x_max - x_min + 1, y_max - y_min + 1);
+    // the pixels are allocated before any data is read
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_EXR_PIXELS => {}
+        _ => return Err(invalid("dataWindow too large")),
+    }
+    let (width, height) = (width as usize, height as usize);
+
+    let lines_per_chunk = match compression.ok_or_else(|| invalid("no compression"))? {
+        // none, RLE and ZIPS
+        0..=2 => 1,
+        // ZIP
+        3 => 16,
+        _ => {
+            return Err(TextureError::Format(
+                "PIZ, PXR24, B44 and DWA compressed EXR files are not supported".into(),
+            ))
+        }
+    };
+    let compression = compression.unwrap();
+
+    // where each channel goes in the output pixels
+    let rgb = ["R", "G", "B"]
+        .iter()
+        .all(|&name| channels.iter().any(|c| c.name == name));
+    let has_alpha = channels.iter().any(|c| c.name == "A");
+    let out_channels = if has_alpha { 4 } else { 3 };
+    let slots = |channel: &ExrChannel| -> &'static [usize] {
+        match channel.name.as_str() {
+            "R" if rgb => &[0],
+            "G" if rgb => &[1],
+            "B" if rgb => &[2],
+            "Y" if !rgb => &[0, 1, 2],
+            "A" => &[3],
+            _ => &[],
+        }
+    };
+    if !rgb && !channels.iter().any(|c| c.name == "Y") {
+        return Err(TextureError::Format("EXR file without RGB or Y channels".into()));
+    }
+
+    let line_size: usize = channels.iter().map(|channel| channel.size() * width).sum();
+    let chunk_count = height.div_ceil(lines_per_chunk);
+    let offsets = (0..chunk_count).map(|_| reader.u64()).collect::<Result<Vec<_>, _>>()?;
+
+    let mut pixels = vec![0.0; width * height * out_channels];
+    if has_alpha {
+        // opaque unless the alpha channel says otherwise
+        pixels.iter_mut().skip(3).step_by(4).for_each(|alpha| *alpha = 1.0);
+    }
+    for offset in offsets {
+        let mut chunk = Reader { bytes, offset };
+        let y = i64::from(chunk.i32()?);
+        let size = chunk.u32()? as usize;
+        let data = chunk.bytes(size)?;
+        if y < y_min || y > y_max {
+            return Err(invalid("chunk outside of the dataWindow"));
+        }
+        let first_row = (y - y_min) as usize;
+        let lines = lines_per_chunk.min(height - first_row);
+        let expected = lines * line_size;
+
+        // chunks that don't get smaller are stored uncompressed
+        let data = if data.len() == expected || compression == 0 {
+            data.to_vec()
+        } else if compression == 1 {
+            unpredict(rle_decode(data)?)
+        } else {
+            let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(data).map_err(|_| invalid("bad ZIP data"))?;
+            unpredict(inflated)
+        };
+        if data.len() != expected {
+            return Err(invalid("chunk size doesn't match the channels"));
+        }
+
+        // each line holds every channel in turn, in the order of the channel list
+        let mut position = 0;
+        for row in first_row..first_row + lines {
+            for channel in &channels {
+                let size = channel.size();
+                for x in 0..width {
+                    let value = channel.value(&data[position..position + size]);
+                    position += size;
+                    for &slot in slots(channel) {
+                        pixels[(row * width + x) * out_channels + slot] = value;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(HdrImage {
+        width: width as u32,
+        height: height as u32,
+        channels: out_channels as u32,
+        pixels,
+    })
+}
+
+// runs: a negative count is followed by that many literal bytes, a positive count by
+// one byte repeated count + 1 times
+fn rle_decode(data: &[u8]) -> Result<Vec<u8>, TextureError> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let len = -(count as isize) as usize;
+            out.extend_from_slice(data.get(i..i + len).ok_or_else(|| invalid("bad RLE data"))?);
+            i += len;
+        } else {
+            let value = *data.get(i).ok_or_else(|| invalid("bad RLE data"))?;
+            out.resize(out.len() + count as usize + 1, value);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+// undoes the delta predictor and the split into even and odd bytes applied before RLE and
+// ZIP compression
+fn unpredict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    let half = data.len().div_ceil(2);
+    let mut out = Vec::with_capacity(data.len());
+    for i in 0..half {
+        out.push(data[i]);
+        if let Some(&odd) = data.get(half + i) {
+            out.push(odd);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 3x2 image: R as FLOAT, G and B as HALF; the channel list is sorted by name
+    const WIDTH: usize = 3;
+    const HEIGHT: usize = 2;
+
+    fn pixel(x: usize, y: usize) -> [f32; 3] {
+        [x as f32 + 0.5, y as f32 * 2.0, -0.25 * (x + y) as f32]
+    }
+
+    // the channels of a line, one after the other
+    fn line(y: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for x in 0..WIDTH {
+            bytes.extend_from_slice(&Half::from_f32(pixel(x, y)[2]).0.to_le_bytes());
+        }
+        for x in 0..WIDTH {
+            bytes.extend_from_slice(&Half::from_f32(pixel(x, y)[1]).0.to_le_bytes());
+        }
+        for x in 0..WIDTH {
+            bytes.extend_from_slice(&pixel(x, y)[0].to_le_bytes());
+        }
+        bytes
+    }
+
+    fn attribute(bytes: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+        for string in [name, type_name] {
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value);
+    }
+
+    // a scanline file with the dataWindow from (-1, 10), chunks of `lines_per_chunk` lines
+    // passed through `compress`
+    fn exr(compression: u8, lines_per_chunk: usize, compress: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        exr_with_window(compression, [-1, 10, WIDTH as i32 - 2, 10 + HEIGHT as i32 - 1], |y| {
+            let lines: Vec<u8> = (y..HEIGHT.min(y + lines_per_chunk)).flat_map(line).collect();
+            compress(&lines)
+        })
+    }
+
+    fn exr_with_window(compression: u8, window: [i32; 4], chunk: impl Fn(usize) -> Vec<u8>) -> Vec<u8> {
+        let mut bytes = EXR_MAGIC.to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        let mut channels = Vec::new();
+        for (name, pixel_type) in [("B", 1u32), ("G", 1), ("R", 2)] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&pixel_type.to_le_bytes());
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        attribute(&mut bytes, "channels", "chlist", &channels);
+        attribute(&mut bytes, "compression", "compression", &[compression]);
+        let window: Vec<u8> = window.iter().flat_map(|corner| corner.to_le_bytes()).collect();
+        attribute(&mut bytes, "dataWindow", "box2i", &window);
+        bytes.push(0);
+
+        let lines_per_chunk = if compression == 3 { 16 } else { 1 };
+        let chunks: Vec<Vec<u8>> = (0..HEIGHT).step_by(lines_per_chunk).map(chunk).collect();
+        let mut offset = bytes.len() + chunks.len() * 8;
+        for chunk in &chunks {
+            bytes.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset += 8 + chunk.len();
+        }
+        for (idx, chunk) in chunks.iter().enumerate() {
+            bytes.extend_from_slice(&(10 + (idx * lines_per_chunk) as i32).to_le_bytes());
+            bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(chunk);
+        }
+        bytes
+    }
+
+    // the split into even and odd bytes and the delta predictor, undone by `unpredict`
+    fn predict(data: &[u8]) -> Vec<u8> {
+        let split: Vec<u8> = data
+            .iter()
+            .step_by(2)
+            .chain(data.iter().skip(1).step_by(2))
+            .copied()
+            .collect();
+        let mut predicted = split.clone();
+        for i in 1..split.len() {
+            predicted[i] = split[i].wrapping_sub(split[i - 1]).wrapping_add(128);
+        }
+        predicted
+    }
+
+    // runs of equal bytes, single literal bytes otherwise
+    fn rle_encode(data: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let run = data[i..].iter().take(128).take_while(|&&byte| byte == data[i]).count();
+            if run > 1 {
+                encoded.extend_from_slice(&[(run - 1) as u8, data[i]]);
+            } else {
+                encoded.extend_from_slice(&[-1i8 as u8, data[i]]);
+            }
+            i += run;
+        }
+        encoded
+    }
+
+    fn check(image: &HdrImage) {
+        assert_eq!((image.width, image.height, image.channels), (3, 2, 3));
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let start = (y * WIDTH + x) * 3;
+                assert_eq!(image.pixels[start..start + 3], pixel(x, y), "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn uncompressed_exr() {
+        check(&HdrImage::parse(&exr(0, 1, |lines| lines.to_vec())).unwrap());
+    }
+
+    #[test]
+    fn rle_exr() {
+        let data = line(0);
+        assert_eq!(unpredict(rle_decode(&rle_encode(&predict(&data))).unwrap()), data);
+        check(&HdrImage::parse(&exr(1, 1, |lines| rle_encode(&predict(lines)))).unwrap());
+    }
+
+    #[test]
+    fn zip_exr() {
+        let compress = |lines: &[u8]| miniz_oxide::deflate::compress_to_vec_zlib(&predict(lines), 6);
+        check(&HdrImage::parse(&exr(3, 16, compress)).unwrap());
+    }
+
+    #[test]
+    fn oversized_data_window() {
+        for window in [[i32::MIN, i32::MIN, i32::MAX, i32::MAX], [0, 0, 99_999, 99_999]] {
+            let bytes = exr_with_window(0, window, |_| Vec::new());
+            assert!(matches!(HdrImage::parse(&bytes), Err(TextureError::Exr(_))));
+        }
+    }
+
+    #[test]
+    fn rgbe_shares_the_largest_exponent() {
+        assert_eq!(rgbe([1.0, 0.5, 0.25]), [128, 64, 32, 129]);
+        assert_eq!(rgbe([3.0, 0.0, -1.0]), [192, 0, 0, 130]);
+        assert_eq!(rgbe([0.0; 3]), [0; 4]);
+
+        // a Radiance file of those two pixels reads back exactly
+        let mut file = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        file.extend_from_slice(&[128, 64, 32, 129, 192, 0, 0, 130]);
+        let image = HdrImage::parse(&file).unwrap();
+        assert_eq!((image.width, image.height, image.channels), (2, 1, 3));
+        assert_eq!(image.pixels, [1.0, 0.5, 0.25, 3.0, 0.0, 0.0]);
+        assert_eq!(image.to_rgbe(), [128, 64, 32, 129, 192, 0, 0, 130]);
+    }
+}
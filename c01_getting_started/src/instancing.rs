@@ -0,0 +1,172 @@
+use cgmath::{Matrix4, Vector4};
+use dx::gles::{core30::gl, enums::*};
+use std::{
+    mem::size_of,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use vertex_format::VertexFormat;
+
+use super::gl_object::GlBuffer;
+use super::mesh::{set_attribute_pointers, INSTANCE_ATTRIBUTE_LOCATION, MAX_INSTANCE_ATTRIBUTES};
+
+/// Per-instance model matrix at `INSTANCE_ATTRIBUTE_LOCATION`, `layout (location = 8) in mat4 aInstanceMatrix;`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, VertexFormat)]
+#[vertex(instance)]
+pub struct InstanceTransform {
+    #[vertex(location = 8)]
+    pub model: Matrix4<f32>,
+}
+
+/// Per-instance model matrix and color, the color at location 12.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, VertexFormat)]
+#[vertex(instance)]
+pub struct InstanceTransformColor {
+    #[vertex(location = 8)]
+    pub model: Matrix4<f32>,
+    pub color: Vector4<f32>,
+}
+
+const _: () = assert!(InstanceTransform::ATTRIBUTES[0].location == INSTANCE_ATTRIBUTE_LOCATION);
+const _: () = assert!(InstanceTransformColor::ATTRIBUTES[0].location == INSTANCE_ATTRIBUTE_LOCATION);
+
+// source of `InstanceBuffer::uid`
+static NEXT_INSTANCE_BUFFER: AtomicU64 = AtomicU64::new(1);
+
+/// A GL buffer of per-instance data for `Mesh::draw_instanced` and `Model::draw_instanced`.
+///
+/// Any `VertexFormat` with a divisor works, usually derived with `#[vertex(instance)]`;
+/// its attributes must lie in the `MAX_INSTANCE_ATTRIBUTES` locations from
+/// `INSTANCE_ATTRIBUTE_LOCATION` on to leave the mesh's own attributes alone. The instances
+/// are kept on the CPU too, so partial updates survive the buffer growing; the capacity is
+/// kept when the instances shrink.
+pub struct InstanceBuffer<I: VertexFormat> {
+    instances: Vec<I>,
+    vbo: GlBuffer,
+    // instances the GL buffer has room for
+    capacity: usize,
+    usage: u32,
+    uid: u64,
+}
+
+impl<I: VertexFormat> InstanceBuffer<I> {
+    /// an empty buffer for instances updated every frame or so
+    pub fn new() -> Self {
+        InstanceBuffer::with_usage(GL_DYNAMIC_DRAW)
+    }
+
+    /// a buffer for instances set once
+    pub fn from_slice(instances: &[I]) -> Self {
+        let mut buffer = InstanceBuffer::with_usage(GL_STATIC_DRAW);
+        buffer.set(instances);
+        buffer
+    }
+
+    fn with_usage(usage: u32) -> Self {
+        assert!(
+            I::ATTRIBUTES.iter().all(|attribute| attribute.divisor != 0),
+            "instance data needs a divisor, derive its VertexFormat with #[vertex(instance)]"
+        );
+        let locations = INSTANCE_ATTRIBUTE_LOCATION..INSTANCE_ATTRIBUTE_LOCATION + MAX_INSTANCE_ATTRIBUTES;
+        assert!(
+            I::ATTRIBUTES.iter().all(|attribute| {
+                locations.contains(&attribute.location) && attribute.location + attribute.locations <= locations.end
+            }),
+            "instance attributes must lie in the MAX_INSTANCE_ATTRIBUTES locations from INSTANCE_ATTRIBUTE_LOCATION on"
+        );
+        InstanceBuffer {
+            instances: Vec::new(),
+            vbo: GlBuffer::new(),
+            capacity: 0,
+            usage,
+            uid: NEXT_INSTANCE_BUFFER.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn instances(&self) -> &[I] {
+        &self.instances
+    }
+
+    /// number of instances drawn
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// instances the GL buffer holds without reallocating
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// replaces all instances
+    pub fn set(&mut self, instances: &[I]) {
+        self.instances.clear();
+        self.instances.extend_from_slice(instances);
+        self.upload(0);
+    }
+
+    /// overwrites the instances from `first` on, adding the ones past the end
+    pub fn update(&mut self, first: usize, instances: &[I]) {
+        assert!(first <= self.instances.len(), "update starts past the last instance");
+        let overlap = instances.len().min(self.instances.len() - first);
+        self.instances[first..first + overlap].copy_from_slice(&instances[..overlap]);
+        self.instances.extend_from_slice(&instances[overlap..]);
+        self.upload(first);
+    }
+
+    /// adds instances after the last one
+    pub fn extend(&mut self, instances: &[I]) {
+        self.update(self.instances.len(), instances);
+    }
+
+    /// removes all instances, keeping the capacity
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    // uploads the instances from `first` on, or all of them if the buffer has to grow
+    fn upload(&mut self, first: usize) {
+        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
+        let first = if self.instances.len() > self.capacity {
+            // grow geometrically so a slowly growing set doesn't reallocate every frame
+            self.capacity = self.instances.len().max(self.capacity * 2);
+            gl::buffer_data_size(GL_ARRAY_BUFFER, (self.capacity * size_of::<I>()) as isize, self.usage);
+            0
+        } else {
+            first
+        };
+        if first < self.instances.len() {
+            let offset = (first * size_of::<I>()) as isize;
+            gl::buffer_sub_data(GL_ARRAY_BUFFER, offset, &self.instances[first..]);
+        }
+        gl::bind_buffer(GL_ARRAY_BUFFER, 0);
+    }
+
+    /// points the attributes of `I` at this buffer in the currently bound VAO
+    pub(crate) fn bind_attributes(&self) {
+        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
+        set_attribute_pointers::<I>();
+        gl::bind_buffer(GL_ARRAY_BUFFER, 0);
+    }
+
+    /// name of the GL buffer
+    pub fn id(&self) -> u32 {
+        self.vbo.id()
+    }
+
+    /// identifies the buffer for the lifetime of the process; unlike the GL name, it isn't
+    /// handed out again after the buffer is dropped
+    pub(crate) fn uid(&self) -> u64 {
+        self.uid
+    }
+}
+
+impl<I: VertexFormat> Default for InstanceBuffer<I> {
+    fn default() -> Self {
+        InstanceBuffer::new()
+    }
+}
@@ -0,0 +1,283 @@
+use cgmath::{vec3, vec4, Vector3, Vector4};
+use dx::gles::{core30::gl, enums::*};
+
+use super::capabilities::EnableState;
+use super::mesh::Texture;
+use super::shader::Shader;
+
+/// Texture slots of a material. A material's texture is bound to the texture unit of its
+/// slot index, so samplers keep their unit from one draw to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureSlot {
+    BaseColor,
+    Normal,
+    MetallicRoughness,
+    Occlusion,
+    Emissive,
+    Specular,
+    Height,
+}
+
+impl TextureSlot {
+    pub const COUNT: usize = 7;
+
+    pub const ALL: [TextureSlot; TextureSlot::COUNT] = [
+        TextureSlot::BaseColor,
+        TextureSlot::Normal,
+        TextureSlot::MetallicRoughness,
+        TextureSlot::Occlusion,
+        TextureSlot::Emissive,
+        TextureSlot::Specular,
+        TextureSlot::Height,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Scalar material parameters a shader can receive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MaterialUniform {
+    /// `vec4`
+    BaseColorFactor,
+    /// `float`
+    MetallicFactor,
+    /// `float`
+    RoughnessFactor,
+    /// `vec3`
+    EmissiveFactor,
+    /// `vec3`
+    SpecularFactor,
+    /// `float`
+    Shininess,
+    /// `float`
+    NormalScale,
+    /// `float`
+    OcclusionStrength,
+    /// `float`, only set for `AlphaMode::Mask`
+    AlphaCutoff,
+}
+
+impl MaterialUniform {
+    pub const COUNT: usize = 9;
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How the alpha channel of the base color is interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    /// fragments with an alpha below `cutoff` are discarded (by the shader)
+    Mask { cutoff: f32 },
+    /// alpha blended; such meshes should be drawn after the opaque ones
+    Blend,
+}
+
+/// Names of the samplers and uniforms a shader declares for material data. Slots without
+/// a name are not bound. The default layout uses the sampler names of the model loading
+/// chapter (`texture_diffuse1`, `texture_specular1`, `texture_normal1`, `texture_height1`)
+/// and no factor uniforms.
+#[derive(Clone, Debug)]
+pub struct MaterialLayout {
+    samplers: [Option<String>; TextureSlot::COUNT],
+    uniforms: [Option<String>; MaterialUniform::COUNT],
+}
+
+impl Default for MaterialLayout {
+    fn default() -> Self {
+        MaterialLayout::new()
+            .sampler(TextureSlot::BaseColor, "texture_diffuse1")
+            .sampler(TextureSlot::Specular, "texture_specular1")
+            .sampler(TextureSlot::Normal, "texture_normal1")
+            .sampler(TextureSlot::Height, "texture_height1")
+    }
+}
+
+impl MaterialLayout {
+    /// a layout binding nothing
+    pub fn new() -> Self {
+        MaterialLayout {
+            samplers: Default::default(),
+            uniforms: Default::default(),
+        }
+    }
+
+    pub fn sampler(mut self, slot: TextureSlot, name: &str) -> Self {
+        self.samplers[slot.index()] = Some(name.into());
+        self
+    }
+
+    pub fn uniform(mut self, uniform: MaterialUniform, name: &str) -> Self {
+        self.uniforms[uniform.index()] = Some(name.into());
+        self
+    }
+}
+
+/// A `MaterialLayout` resolved to the uniform locations of one shader program; -1 marks
+/// names the layout leaves out or the program doesn't use.
+#[derive(Clone, Debug)]
+pub struct MaterialBindings {
+    samplers: [i32; TextureSlot::COUNT],
+    uniforms: [i32; MaterialUniform::COUNT],
+}
+
+impl MaterialBindings {
+    pub fn resolve(program: u32, layout: &MaterialLayout) -> Self {
+        let location = |name: &Option<String>| match name {
+            Some(name) => gl::get_uniform_location(program, name),
+            None => -1,
+        };
+        let mut bindings = MaterialBindings {
+            samplers: [-1; TextureSlot::COUNT],
+            uniforms: [-1; MaterialUniform::COUNT],
+        };
+        for (resolved, name) in bindings.samplers.iter_mut().zip(&layout.samplers) {
+            *resolved = location(name);
+        }
+        for (resolved, name) in bindings.uniforms.iter_mut().zip(&layout.uniforms) {
+            *resolved = location(name);
+        }
+        bindings
+    }
+}
+
+/// Surface description shared by the meshes drawn with it: typed texture slots, scalar
+/// factors (glTF metallic-roughness, plus the Phong specular and shininess of OBJ
+/// materials), alpha mode and double-sidedness.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    /// indexed by `TextureSlot::index`, see `texture` and `set_texture`
+    pub textures: [Option<Texture>; TextureSlot::COUNT],
+    pub base_color_factor: Vector4<f32>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: Vector3<f32>,
+    pub specular_factor: Vector3<f32>,
+    pub shininess: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_mode: AlphaMode,
+    /// back faces are not culled
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::new(),
+            textures: Default::default(),
+            base_color_factor: vec4(1.0, 1.0, 1.0, 1.0),
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: vec3(0.0, 0.0, 0.0),
+            specular_factor: vec3(1.0, 1.0, 1.0),
+            shininess: 32.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+impl Material {
+    pub fn texture(&self, slot: TextureSlot) -> Option<&Texture> {
+        self.textures[slot.index()].as_ref()
+    }
+
+    pub fn set_texture(&mut self, slot: TextureSlot, texture: Option<Texture>) {
+        self.textures[slot.index()] = texture;
+    }
+
+    /// textures of every slot that has one
+    pub fn textures(&self) -> impl Iterator<Item = (TextureSlot, &Texture)> {
+        TextureSlot::ALL
+            .iter()
+            .zip(&self.textures)
+            .filter_map(|(&slot, texture)| texture.as_ref().map(|texture| (slot, texture)))
+    }
+
+    /// binds the textures and sets the uniforms the shader's material layout declares;
+    /// the shader must be in use.
+    pub fn bind(&self, shader: &Shader) {
+        let bindings = shader.material_bindings();
+        for (slot, texture) in self.textures() {
+            let location = bindings.samplers[slot.index()];
+            if location < 0 {
+                continue;
+            }
+            gl::active_texture(GL_TEXTURE0 + slot.index() as u32);
+            gl::bind_texture(GL_TEXTURE_2D, texture.id);
+            gl::uniform1i(location, slot.index() as i32);
+        }
+        gl::active_texture(GL_TEXTURE0);
+
+        let uniform = |uniform: MaterialUniform| Some(bindings.uniforms[uniform.index()]).filter(|&loc| loc >= 0);
+        if let Some(loc) = uniform(MaterialUniform::BaseColorFactor) {
+            let c = self.base_color_factor;
+            gl::uniform4f(loc, c.x, c.y, c.z, c.w);
+        }
+        if let Some(loc) = uniform(MaterialUniform::MetallicFactor) {
+            gl::uniform1f(loc, self.metallic_factor);
+        }
+        if let Some(loc) = uniform(MaterialUniform::RoughnessFactor) {
+            gl::uniform1f(loc, self.roughness_factor);
+        }
+        if let Some(loc) = uniform(MaterialUniform::EmissiveFactor) {
+            let e = self.emissive_factor;
+            gl::uniform3f(loc, e.x, e.y, e.z);
+        }
+        if let Some(loc) = uniform(MaterialUniform::SpecularFactor) {
+            let s = self.specular_factor;
+            gl::uniform3f(loc, s.x, s.y, s.z);
+        }
+        if let Some(loc) = uniform(MaterialUniform::Shininess) {
+            gl::uniform1f(loc, self.shininess);
+        }
+        if let Some(loc) = uniform(MaterialUniform::NormalScale) {
+            gl::uniform1f(loc, self.normal_scale);
+        }
+        if let Some(loc) = uniform(MaterialUniform::OcclusionStrength) {
+            gl::uniform1f(loc, self.occlusion_strength);
+        }
+        if let (Some(loc), AlphaMode::Mask { cutoff }) = (uniform(MaterialUniform::AlphaCutoff), self.alpha_mode) {
+            gl::uniform1f(loc, cutoff);
+        }
+    }
+
+    /// applies the render state of the material (blending, face culling) and returns what
+    /// has to be undone with `RenderState::restore` after drawing.
+    pub(crate) fn apply_render_state(&self) -> RenderState {
+        let state = RenderState {
+            enabled_blend: self.alpha_mode == AlphaMode::Blend && EnableState::set(GL_BLEND, true),
+            disabled_cull_face: self.double_sided && EnableState::set(GL_CULL_FACE, false),
+        };
+        if state.enabled_blend {
+            gl::blend_func(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+        }
+        state
+    }
+}
+
+/// GL state changed by `Material::apply_render_state`.
+pub(crate) struct RenderState {
+    enabled_blend: bool,
+    disabled_cull_face: bool,
+}
+
+impl RenderState {
+    pub(crate) fn restore(self) {
+        if self.enabled_blend {
+            EnableState::set(GL_BLEND, false);
+        }
+        if self.disabled_cull_face {
+            EnableState::set(GL_CULL_FACE, true);
+        }
+    }
+}
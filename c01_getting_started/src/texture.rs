@@ -0,0 +1,803 @@
+use dx::gles::{core30::gl, enums::*};
+use image::{imageops::FilterType, DynamicImage, DynamicImage::*, GenericImageView};
+use std::{borrow::Cow, error::Error, fmt, io, path::Path};
+use vertex_format::Half;
+
+use super::capabilities::Capabilities;
+use super::gl_object::GlTexture;
+use super::hdr::HdrImage;
+
+/// Errors of `Texture2DBuilder::load` and `from_image`, and of loading KTX, HDR and EXR files.
+#[derive(Debug)]
+pub enum TextureError {
+    /// the file could not be read or decoded
+    Image(image::ImageError),
+    Io(io::Error),
+    /// the image has a pixel layout textures can't be created from
+    Format(String),
+    /// malformed KTX container
+    Ktx(String),
+    /// malformed OpenEXR file
+    Exr(String),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Image(err) => write!(f, "{}", err),
+            TextureError::Io(err) => write!(f, "{}", err),
+            TextureError::Format(reason) => write!(f, "{}", reason),
+            TextureError::Ktx(reason) => write!(f, "invalid KTX file: {}", reason),
+            TextureError::Exr(reason) => write!(f, "invalid EXR file: {}", reason),
+        }
+    }
+}
+
+impl Error for TextureError {}
+
+/// Texture coordinate wrapping outside of [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    /// repeats the edge texels; keeps transparent borders of sprites clean
+    ClampToEdge,
+}
+
+impl Wrap {
+    pub fn gl_enum(self) -> u32 {
+        match self {
+            Wrap::Repeat => GL_REPEAT,
+            Wrap::MirroredRepeat => GL_MIRRORED_REPEAT,
+            Wrap::ClampToEdge => GL_CLAMP_TO_EDGE,
+        }
+    }
+}
+
+/// Texel filtering, for magnification, minification and between mipmap levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// How a texture is sampled; applied when the texture is created and with
+/// `Texture2D::set_sampling`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// filtering between mipmap levels, ignored for textures without mipmaps
+    pub mipmap_filter: Filter,
+    /// maximum anisotropy, 1.0 to disable; clamped to what the context supports
+    pub anisotropy: f32,
+}
+
+impl Default for Sampling {
+    /// repeating, trilinear filtering
+    fn default() -> Self {
+        Sampling {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: Filter::Linear,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+impl Sampling {
+    /// sets the parameters of the texture bound to `target`
+    pub fn apply(&self, target: u32, mipmaps: bool) {
+        let min_filter = match (mipmaps, self.min_filter, self.mipmap_filter) {
+            (false, Filter::Nearest, _) => GL_NEAREST,
+            (false, Filter::Linear, _) => GL_LINEAR,
+            (true, Filter::Nearest, Filter::Nearest) => GL_NEAREST_MIPMAP_NEAREST,
+            (true, Filter::Nearest, Filter::Linear) => GL_NEAREST_MIPMAP_LINEAR,
+            (true, Filter::Linear, Filter::Nearest) => GL_LINEAR_MIPMAP_NEAREST,
+            (true, Filter::Linear, Filter::Linear) => GL_LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match self.mag_filter {
+            Filter::Nearest => GL_NEAREST,
+            Filter::Linear => GL_LINEAR,
+        };
+        gl::tex_parameteri(target, GL_TEXTURE_WRAP_S, self.wrap_s.gl_enum() as i32);
+        gl::tex_parameteri(target, GL_TEXTURE_WRAP_T, self.wrap_t.gl_enum() as i32);
+        gl::tex_parameteri(target, GL_TEXTURE_MIN_FILTER, min_filter as i32);
+        gl::tex_parameteri(target, GL_TEXTURE_MAG_FILTER, mag_filter as i32);
+
+        let max_anisotropy = Capabilities::current().max_anisotropy;
+        if max_anisotropy > 1.0 {
+            let anisotropy = self.anisotropy.clamp(1.0, max_anisotropy);
+            gl::tex_parameterf(target, GL_TEXTURE_MAX_ANISOTROPY_EXT, anisotropy);
+        }
+    }
+}
+
+/// Channels of a decoded image, in the order the file stores them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    Luma,
+    LumaAlpha,
+    Rgb,
+    Rgba,
+    Bgr,
+    Bgra,
+}
+
+impl ChannelLayout {
+    pub fn channels(self) -> u32 {
+        match self {
+            ChannelLayout::Luma => 1,
+            ChannelLayout::LumaAlpha => 2,
+            ChannelLayout::Rgb | ChannelLayout::Bgr => 3,
+            ChannelLayout::Rgba | ChannelLayout::Bgra => 4,
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            ChannelLayout::LumaAlpha | ChannelLayout::Rgba | ChannelLayout::Bgra
+        )
+    }
+}
+
+/// What an image file contained, see `Texture2D::source`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PixelLayout {
+    pub channels: ChannelLayout,
+    /// bits per channel: 8, 16, or 32 for float images
+    pub bits: u32,
+}
+
+impl PixelLayout {
+    pub fn of(image: &DynamicImage) -> Self {
+        let (channels, bits) = match image {
+            ImageLuma8(_) => (ChannelLayout::Luma, 8),
+            ImageLumaA8(_) => (ChannelLayout::LumaAlpha, 8),
+            ImageRgb8(_) => (ChannelLayout::Rgb, 8),
+            ImageRgba8(_) => (ChannelLayout::Rgba, 8),
+            ImageBgr8(_) => (ChannelLayout::Bgr, 8),
+            ImageBgra8(_) => (ChannelLayout::Bgra, 8),
+            ImageLuma16(_) => (ChannelLayout::Luma, 16),
+            ImageLumaA16(_) => (ChannelLayout::LumaAlpha, 16),
+            ImageRgb16(_) => (ChannelLayout::Rgb, 16),
+            ImageRgba16(_) => (ChannelLayout::Rgba, 16),
+        };
+        PixelLayout { channels, bits }
+    }
+}
+
+impl fmt::Display for PixelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-bit {:?}", self.bits, self.channels)
+    }
+}
+
+/// A 2D texture with 8-bit, 16-bit or float channels, created with `Texture2D::builder()`.
+///
+/// ```ignore
+/// let grass = Texture2D::builder().wrap(Wrap::ClampToEdge).srgb(true).load(path)?;
+/// gl::bind_texture(GL_TEXTURE_2D, grass.id());
+/// ```
+#[derive(Debug)]
+pub struct Texture2D {
+    texture: GlTexture,
+    pub width: u32,
+    pub height: u32,
+    /// sized internal format, e.g. `GL_SRGB8_ALPHA8`
+    pub internal_format: u32,
+    /// number of mipmap levels, 1 without mipmaps
+    pub levels: u32,
+    /// allocated with `glTexStorage2D`: the size and format can't change
+    pub immutable: bool,
+    /// high dynamic range colors encoded as RGBE in `GL_RGBA8`, see `HdrFormat::Rgbe`
+    pub rgbe: bool,
+    /// layout of the image the texture was loaded from; `None` for `from_pixels`
+    pub source: Option<PixelLayout>,
+    sampling: Sampling,
+}
+
+impl Texture2D {
+    pub fn builder() -> Texture2DBuilder {
+        Texture2DBuilder::default()
+    }
+
+    /// name of the GL texture
+    pub fn id(&self) -> u32 {
+        self.texture.id()
+    }
+
+    /// binds the texture to texture unit `unit`
+    pub fn bind(&self, unit: u32) {
+        gl::active_texture(GL_TEXTURE0 + unit);
+        gl::bind_texture(GL_TEXTURE_2D, self.texture.id());
+    }
+
+    pub fn sampling(&self) -> &Sampling {
+        &self.sampling
+    }
+
+    /// changes the sampling; textures that can't repeat on this context stay clamped, see
+    /// `NpotFallback`
+    pub fn set_sampling(&mut self, mut sampling: Sampling) {
+        if npot_restricted(self.width, self.height) {
+            sampling.wrap_s = Wrap::ClampToEdge;
+            sampling.wrap_t = Wrap::ClampToEdge;
+        }
+        gl::bind_texture(GL_TEXTURE_2D, self.texture.id());
+        sampling.apply(GL_TEXTURE_2D, self.levels > 1);
+        gl::bind_texture(GL_TEXTURE_2D, 0);
+        self.sampling = sampling;
+    }
+
+    /// estimated GPU memory, including the mipmap chain
+    pub fn size_bytes(&self) -> usize {
+        let base = self.width as usize * self.height as usize * bytes_per_texel(self.internal_format);
+        // a full mipmap chain adds a third
+        if self.levels > 1 {
+            base * 4 / 3
+        } else {
+            base
+        }
+    }
+}
+
+/// Storage of high dynamic range textures, see `Texture2DBuilder::load_hdr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HdrFormat {
+    /// `GL_RGB16F`, or `GL_RGBA16F` for images with alpha
+    Half,
+    /// `GL_R11F_G11F_B10F`: 4 bytes per texel, without alpha, sign and some precision
+    R11G11B10,
+    /// RGBE in `GL_RGBA8`, for contexts without filterable float textures. The shader
+    /// decodes it with `rgb * (255.0 / 256.0) * exp2(a * 255.0 - 128.0)`; linear filtering blends texels
+    /// with different exponents slightly wrong.
+    Rgbe,
+}
+
+/// What `Texture2DBuilder` does with non-power-of-two images that should be mipmapped or
+/// repeated on a context that can't do either: OpenGL ES 2.0 without `OES_texture_npot`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NpotFallback {
+    /// scale the image up to the next power of two on the CPU, keeping mipmaps and wrapping
+    Resize,
+    /// keep the size, but clamp to the edge and skip the mipmaps
+    Clamp,
+}
+
+/// Creation options of a `Texture2D`. The defaults are those of the tutorials' textures:
+/// repeating, trilinear filtering with mipmaps, linear color and the image's own orientation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Texture2DBuilder {
+    pub sampling: Sampling,
+    pub mipmaps: bool,
+    /// color data in the sRGB color space (GL_SRGB8 / GL_SRGB8_ALPHA8); ignored for one
+    /// and two channel images
+    pub srgb: bool,
+    /// flip images vertically so the first row is the bottom of the image, as GL expects
+    pub flip: bool,
+    /// allocate immutable storage where the context supports it
+    pub immutable: bool,
+    /// storage of HDR and EXR images; falls back to `HdrFormat::Rgbe` where float textures
+    /// can't be filtered
+    pub hdr_format: HdrFormat,
+    /// non-power-of-two images on OpenGL ES 2.0; `from_pixels` and HDR images always clamp
+    pub npot: NpotFallback,
+}
+
+impl Default for Texture2DBuilder {
+    fn default() -> Self {
+        Texture2DBuilder {
+            sampling: Sampling::default(),
+            mipmaps: true,
+            srgb: false,
+            flip: false,
+            immutable: false,
+            hdr_format: HdrFormat::Half,
+            npot: NpotFallback::Resize,
+        }
+    }
+}
+
+impl Texture2DBuilder {
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// wrapping along both axes
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.sampling.wrap_s = wrap;
+        self.sampling.wrap_t = wrap;
+        self
+    }
+
+    pub fn wrap_s(mut self, wrap: Wrap) -> Self {
+        self.sampling.wrap_s = wrap;
+        self
+    }
+
+    pub fn wrap_t(mut self, wrap: Wrap) -> Self {
+        self.sampling.wrap_t = wrap;
+        self
+    }
+
+    /// minification, magnification and mipmap filtering
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.sampling.min_filter = filter;
+        self.sampling.mag_filter = filter;
+        self.sampling.mipmap_filter = filter;
+        self
+    }
+
+    pub fn min_filter(mut self, filter: Filter) -> Self {
+        self.sampling.min_filter = filter;
+        self
+    }
+
+    pub fn mag_filter(mut self, filter: Filter) -> Self {
+        self.sampling.mag_filter = filter;
+        self
+    }
+
+    pub fn mipmap_filter(mut self, filter: Filter) -> Self {
+        self.sampling.mipmap_filter = filter;
+        self
+    }
+
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self {
+        self.sampling.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn flip(mut self, flip: bool) -> Self {
+        self.flip = flip;
+        self
+    }
+
+    pub fn immutable(mut self, immutable: bool) -> Self {
+        self.immutable = immutable;
+        self
+    }
+
+    pub fn hdr_format(mut self, hdr_format: HdrFormat) -> Self {
+        self.hdr_format = hdr_format;
+        self
+    }
+
+    pub fn npot(mut self, npot: NpotFallback) -> Self {
+        self.npot = npot;
+        self
+    }
+
+    /// loads an image file
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Texture2D, TextureError> {
+        let image = image::open(path).map_err(TextureError::Image)?;
+        self.from_image(&image)
+    }
+
+    /// creates the texture from any decoded image, see `texture_data` for the formats chosen
+    pub fn from_image(&self, image: &DynamicImage) -> Result<Texture2D, TextureError> {
+        let flipped;
+        let image = if self.flip {
+            flipped = image.flipv();
+            &flipped
+        } else {
+            image
+        };
+        let source = PixelLayout::of(image);
+        let (builder, resize) = self.npot_fallback(image.width(), image.height(), true);
+        let resized;
+        let image = match resize {
+            Some((width, height)) => {
+                resized = image.resize_exact(width, height, FilterType::Triangle);
+                &resized
+            }
+            None => image,
+        };
+        let data = texture_data(image, builder.srgb, &Capabilities::current());
+        Ok(builder.upload(image.width(), image.height(), &data, Some(source)))
+    }
+
+    /// creates the texture from tightly packed rows of 8-bit texels; `format` is one of
+    /// `GL_RED`, `GL_RG`, `GL_RGB` and `GL_RGBA`. The rows are used in the order given.
+    pub fn from_pixels(&self, width: u32, height: u32, format: u32, pixels: &[u8]) -> Texture2D {
+        let internal_format = sized_format(format, self.srgb);
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * bytes_per_texel(internal_format),
+            "pixel data size"
+        );
+        let internal_format = if unsized_formats(&Capabilities::current()) {
+            format
+        } else {
+            internal_format
+        };
+        let data = TextureData {
+            internal_format,
+            format,
+            pixel_type: GL_UNSIGNED_BYTE,
+            pixels: Cow::Borrowed(pixels),
+            swizzle: None,
+        };
+        let (builder, _) = self.npot_fallback(width, height, false);
+        builder.upload(width, height, &data, None)
+    }
+
+    /// loads a Radiance `.hdr` or OpenEXR file into a float texture
+    pub fn load_hdr<P: AsRef<Path>>(&self, path: P) -> Result<Texture2D, TextureError> {
+        let image = HdrImage::open(path)?;
+        Ok(self.from_hdr_image(&image))
+    }
+
+    /// creates a float texture; the half floats, RGBE texels and mipmaps are computed on
+    /// the CPU, since float formats can't always render into themselves for
+    /// `glGenerateMipmap`. `srgb` and `immutable` don't apply.
+    pub fn from_hdr_image(&self, image: &HdrImage) -> Texture2D {
+        let (builder, _) = self.npot_fallback(image.width, image.height, false);
+        let capabilities = Capabilities::current();
+        let hdr_format = if capabilities.half_float_textures() {
+            self.hdr_format
+        } else {
+            HdrFormat::Rgbe
+        };
+        let (internal_format, format, pixel_type) = match hdr_format {
+            HdrFormat::Half if image.channels == 4 => (GL_RGBA16F, GL_RGBA, GL_HALF_FLOAT),
+            HdrFormat::Half => (GL_RGB16F, GL_RGB, GL_HALF_FLOAT),
+            HdrFormat::R11G11B10 => (GL_R11F_G11F_B10F, GL_RGB, GL_HALF_FLOAT),
+            HdrFormat::Rgbe if unsized_formats(&capabilities) => (GL_RGBA, GL_RGBA, GL_UNSIGNED_BYTE),
+            HdrFormat::Rgbe => (GL_RGBA8, GL_RGBA, GL_UNSIGNED_BYTE),
+        };
+        let mut level_image = if self.flip {
+            let mut flipped = image.clone();
+            flipped.flip_vertically();
+            flipped
+        } else {
+            image.clone()
+        };
+        let levels = if builder.mipmaps {
+            mip_levels(image.width, image.height)
+        } else {
+            1
+        };
+
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture.id());
+        // rows of 3 half floats aren't 4 byte aligned
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 1);
+        for level in 0..levels {
+            let (width, height) = (level_image.width as i32, level_image.height as i32);
+            let level = level as i32;
+            if hdr_format == HdrFormat::Rgbe {
+                let texels = level_image.to_rgbe();
+                gl::tex_image_2d(
+                    GL_TEXTURE_2D,
+                    level,
+                    internal_format as i32,
+                    width,
+                    height,
+                    0,
+                    format,
+                    pixel_type,
+                    &texels,
+                );
+            } else {
+                // GL converts the half floats to R11F_G11F_B10F
+                let channels = if format == GL_RGBA { 4 } else { 3 };
+                let texels = level_image.to_half(channels);
+                gl::tex_image_2d(
+                    GL_TEXTURE_2D,
+                    level,
+                    internal_format as i32,
+                    width,
+                    height,
+                    0,
+                    format,
+                    pixel_type,
+                    &texels,
+                );
+            }
+            if level + 1 < levels as i32 {
+                level_image = level_image.downsample();
+            }
+        }
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 4);
+        builder.sampling.apply(GL_TEXTURE_2D, levels > 1);
+        gl::bind_texture(GL_TEXTURE_2D, 0);
+
+        Texture2D {
+            texture,
+            width: image.width,
+            height: image.height,
+            internal_format,
+            levels,
+            immutable: false,
+            rgbe: hdr_format == HdrFormat::Rgbe,
+            source: Some(PixelLayout {
+                channels: if image.channels == 4 {
+                    ChannelLayout::Rgba
+                } else {
+                    ChannelLayout::Rgb
+                },
+                bits: 32,
+            }),
+            sampling: builder.sampling,
+        }
+    }
+
+    /// where the context can't mipmap or repeat a `width` x `height` texture that should do
+    /// either, returns the builder with clamped sampling and no mipmaps, or keeps it and
+    /// returns the power of two size to resize to if `can_resize` and `npot` allow it
+    fn npot_fallback(&self, width: u32, height: u32, can_resize: bool) -> (Texture2DBuilder, Option<(u32, u32)>) {
+        let repeats = self.sampling.wrap_s != Wrap::ClampToEdge || self.sampling.wrap_t != Wrap::ClampToEdge;
+        if !(self.mipmaps || repeats) || !npot_restricted(width, height) {
+            return (*self, None);
+        }
+        if can_resize && self.npot == NpotFallback::Resize {
+            let size = (width.next_power_of_two(), height.next_power_of_two());
+            println!(
+                "WARNING::TEXTURE: this context can't mipmap or repeat {}x{} textures, resizing to {}x{}",
+                width, height, size.0, size.1
+            );
+            return (*self, Some(size));
+        }
+        println!(
+            "WARNING::TEXTURE: this context can't mipmap or repeat {}x{} textures, clamping to the edge without mipmaps",
+            width, height
+        );
+        (self.wrap(Wrap::ClampToEdge).mipmaps(false), None)
+    }
+
+    fn upload(&self, width: u32, height: u32, data: &TextureData, source: Option<PixelLayout>) -> Texture2D {
+        let texture = GlTexture::new();
+        let levels = if self.mipmaps { mip_levels(width, height) } else { 1 };
+        // the unsized luminance formats can't be allocated with glTexStorage2D
+        let immutable =
+            self.immutable && Capabilities::current().texture_storage() && data.internal_format != data.format;
+
+        gl::bind_texture(GL_TEXTURE_2D, texture.id());
+        // rows are tightly packed, e.g. 3 bytes per texel of odd width images
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 1);
+        if immutable {
+            gl::tex_storage_2d(
+                GL_TEXTURE_2D,
+                levels as i32,
+                data.internal_format,
+                width as i32,
+                height as i32,
+            );
+            gl::tex_sub_image_2d(
+                GL_TEXTURE_2D,
+                0,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                data.format,
+                data.pixel_type,
+                &data.pixels,
+            );
+        } else {
+            gl::tex_image_2d(
+                GL_TEXTURE_2D,
+                0,
+                data.internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                data.format,
+                data.pixel_type,
+                &data.pixels,
+            );
+        }
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 4);
+        data.apply_swizzle(GL_TEXTURE_2D);
+        if self.mipmaps {
+            gl::generate_mipmap(GL_TEXTURE_2D);
+        }
+        self.sampling.apply(GL_TEXTURE_2D, self.mipmaps);
+        gl::bind_texture(GL_TEXTURE_2D, 0);
+
+        Texture2D {
+            texture,
+            width,
+            height,
+            internal_format: data.internal_format,
+            levels,
+            immutable,
+            rgbe: false,
+            source,
+            sampling: self.sampling,
+        }
+    }
+}
+
+/// a `width` x `height` texture can't mipmap or repeat on this context
+fn npot_restricted(width: u32, height: u32) -> bool {
+    !(Capabilities::current().npot_textures() || width.is_power_of_two() && height.is_power_of_two())
+}
+
+/// OpenGL ES 2.0 only accepts unsized internal formats, the same as the pixel format
+fn unsized_formats(capabilities: &Capabilities) -> bool {
+    capabilities.es && !capabilities.gles3_features()
+}
+
+/// number of levels of a full mipmap chain down to 1x1
+pub fn mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// 16-bit normalized formats: desktop GL, or EXT_texture_norm16 on ES
+const GL_R16: u32 = 0x822A;
+const GL_RG16: u32 = 0x822C;
+const GL_RGBA16: u32 = 0x805B;
+
+/// Texels of an image in a format GL can upload, see `texture_data`.
+pub(crate) struct TextureData<'a> {
+    pub internal_format: u32,
+    pub format: u32,
+    pub pixel_type: u32,
+    /// tightly packed rows
+    pub pixels: Cow<'a, [u8]>,
+    /// `GL_TEXTURE_SWIZZLE_R`..`A` that show one and two channel textures as gray and alpha
+    pub swizzle: Option<[u32; 4]>,
+}
+
+impl TextureData<'_> {
+    /// sets the swizzle of the texture bound to `target`
+    pub fn apply_swizzle(&self, target: u32) {
+        if let Some(swizzle) = self.swizzle {
+            let names = [
+                GL_TEXTURE_SWIZZLE_R,
+                GL_TEXTURE_SWIZZLE_G,
+                GL_TEXTURE_SWIZZLE_B,
+                GL_TEXTURE_SWIZZLE_A,
+            ];
+            for (&name, &source) in names.iter().zip(&swizzle) {
+                gl::tex_parameteri(target, name, source as i32);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Precision {
+    Byte,
+    Norm16,
+    Half,
+}
+
+/// converts any decoded image for upload:
+///
+/// - 8-bit images are uploaded as they are; BGR and BGRA are reordered, OpenGL ES has no
+///   BGR formats. OpenGL ES 2.0 gets the unsized formats and no sRGB.
+/// - gray images use `GL_R8` / `GL_RG8` swizzled to gray and alpha, or the luminance formats
+///   where the context can't swizzle.
+/// - 16-bit images keep their precision in `GL_R16`..`GL_RGBA16` or half floats where the
+///   context can filter and render into them, and are rounded to 8 bits otherwise. RGB
+///   gains an alpha channel since three channel formats can't be rendered into for
+///   `glGenerateMipmap`. There are no 16-bit sRGB formats, so sRGB color images get 8 bits.
+pub(crate) fn texture_data<'a>(image: &'a DynamicImage, srgb: bool, capabilities: &Capabilities) -> TextureData<'a> {
+    let layout = PixelLayout::of(image);
+    let gray = matches!(layout.channels, ChannelLayout::Luma | ChannelLayout::LumaAlpha);
+    let swizzle = capabilities.texture_swizzle();
+    let precision = if layout.bits == 8 || (srgb && !gray) || (gray && !swizzle) {
+        Precision::Byte
+    } else if capabilities.norm16_textures() {
+        Precision::Norm16
+    } else if capabilities.half_float_textures() && capabilities.color_buffer_half_float() {
+        Precision::Half
+    } else {
+        Precision::Byte
+    };
+
+    let (format, gray_swizzle) = match layout.channels {
+        ChannelLayout::Luma if !swizzle => (GL_LUMINANCE, None),
+        ChannelLayout::LumaAlpha if !swizzle => (GL_LUMINANCE_ALPHA, None),
+        ChannelLayout::Luma => (GL_RED, Some([GL_RED, GL_RED, GL_RED, GL_ONE])),
+        ChannelLayout::LumaAlpha => (GL_RG, Some([GL_RED, GL_RED, GL_RED, GL_GREEN])),
+        ChannelLayout::Rgb | ChannelLayout::Bgr if precision == Precision::Byte => (GL_RGB, None),
+        _ => (GL_RGBA, None),
+    };
+
+    if precision == Precision::Byte {
+        let pixels = match (image, layout.channels) {
+            (ImageLuma8(_), _) | (ImageLumaA8(_), _) | (ImageRgb8(_), _) | (ImageRgba8(_), _) => {
+                Cow::Borrowed(image.as_bytes())
+            }
+            (_, ChannelLayout::Luma) => Cow::Owned(image.to_luma8().into_raw()),
+            (_, ChannelLayout::LumaAlpha) => Cow::Owned(image.to_luma_alpha8().into_raw()),
+            (_, ChannelLayout::Rgb) | (_, ChannelLayout::Bgr) => Cow::Owned(image.to_rgb8().into_raw()),
+            (_, ChannelLayout::Rgba) | (_, ChannelLayout::Bgra) => Cow::Owned(image.to_rgba8().into_raw()),
+        };
+        let internal_format = match format {
+            GL_LUMINANCE | GL_LUMINANCE_ALPHA => format,
+            _ if unsized_formats(capabilities) => {
+                if srgb {
+                    println!("WARNING::TEXTURE: OpenGL ES 2.0 has no sRGB textures, sampling as linear");
+                }
+                format
+            }
+            _ => sized_format(format, srgb),
+        };
+        return TextureData {
+            internal_format,
+            format,
+            pixel_type: GL_UNSIGNED_BYTE,
+            pixels,
+            swizzle: gray_swizzle,
+        };
+    }
+
+    let words = match layout.channels {
+        ChannelLayout::Luma => image.to_luma16().into_raw(),
+        ChannelLayout::LumaAlpha => image.to_luma_alpha16().into_raw(),
+        _ => image.to_rgba16().into_raw(),
+    };
+    let (internal_format, pixel_type, pixels) = if precision == Precision::Norm16 {
+        let internal_format = match format {
+            GL_RED => GL_R16,
+            GL_RG => GL_RG16,
+            _ => GL_RGBA16,
+        };
+        let pixels = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        (internal_format, GL_UNSIGNED_SHORT, pixels)
+    } else {
+        let internal_format = match format {
+            GL_RED => GL_R16F,
+            GL_RG => GL_RG16F,
+            _ => GL_RGBA16F,
+        };
+        let pixels = words
+            .iter()
+            .flat_map(|&word| Half::from_f32(f32::from(word) / 65535.0).0.to_ne_bytes())
+            .collect();
+        (internal_format, GL_HALF_FLOAT, pixels)
+    };
+    TextureData {
+        internal_format,
+        format,
+        pixel_type,
+        pixels: Cow::Owned(pixels),
+        swizzle: gray_swizzle,
+    }
+}
+
+// sRGB only exists for color textures
+pub(crate) fn sized_format(format: u32, srgb: bool) -> u32 {
+    match format {
+        GL_RED => GL_R8,
+        GL_RG => GL_RG8,
+        GL_RGB if srgb => GL_SRGB8,
+        GL_RGB => GL_RGB8,
+        GL_RGBA if srgb => GL_SRGB8_ALPHA8,
+        GL_RGBA => GL_RGBA8,
+        _ => panic!("unhandled pixel format 0x{:x}", format),
+    }
+}
+
+pub(crate) fn bytes_per_texel(internal_format: u32) -> usize {
+    match internal_format {
+        GL_R8 | GL_LUMINANCE => 1,
+        GL_RG8 | GL_LUMINANCE_ALPHA | GL_R16 | GL_R16F => 2,
+        GL_RGB8 | GL_SRGB8 | GL_RGB => 3,
+        GL_RGB16F => 6,
+        GL_RGBA16 | GL_RGBA16F => 8,
+        _ => 4,
+    }
+}
@@ -0,0 +1,217 @@
+use image::DynamicImage;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+use super::model::ModelError;
+use super::texture::Texture2D;
+
+/// How an image file is turned into a GL texture. Part of the cache key: the same file
+/// loaded with different options gives different textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    /// color data in the sRGB color space (GL_SRGB8 / GL_SRGB8_ALPHA8); ignored for one
+    /// and two channel images
+    pub srgb: bool,
+    /// flip vertically so the first row is the bottom of the image, as GL expects
+    pub flip: bool,
+    pub mipmaps: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions {
+            srgb: false,
+            flip: true,
+            mipmaps: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct TextureKey {
+    path: PathBuf,
+    options: TextureOptions,
+}
+
+/// A texture owned by the cache; the GL texture is deleted when the last `Rc` is dropped.
+pub struct CachedTexture {
+    pub texture: Texture2D,
+    /// canonical path of the image file
+    pub path: PathBuf,
+    pub options: TextureOptions,
+    pub width: u32,
+    pub height: u32,
+    /// estimated GPU memory, including the mipmap chain
+    pub size_bytes: usize,
+}
+
+impl fmt::Debug for CachedTexture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CachedTexture({} {:?} {}x{})",
+            self.texture.id(),
+            self.path,
+            self.width,
+            self.height
+        )
+    }
+}
+
+impl Drop for CachedTexture {
+    fn drop(&mut self) {
+        let key = TextureKey {
+            path: self.path.clone(),
+            options: self.options,
+        };
+        // the cache may already be gone when textures outlive it at thread exit
+        let _ = CACHE.try_with(|cache| {
+            if let Ok(mut cache) = cache.try_borrow_mut() {
+                if let Some(entry) = cache.get(&key) {
+                    if entry.strong_count() == 0 {
+                        cache.remove(&key);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Number of textures alive in the cache and their estimated GPU memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureCacheStats {
+    pub textures: usize,
+    pub size_bytes: usize,
+}
+
+thread_local! {
+    static CACHE: RefCell<HashMap<TextureKey, Weak<CachedTexture>>> = RefCell::new(HashMap::new());
+}
+
+/// Reference-counted texture cache shared by everything loading textures from files, keyed
+/// by canonical path and `TextureOptions`. GL objects belong to the context of the thread
+/// they were created on, so the cache is per thread, which for these examples means per
+/// process.
+pub struct TextureCache;
+
+impl TextureCache {
+    /// returns the cached texture for `path`, loading and uploading the image on first use
+    pub fn load(path: &Path, options: TextureOptions) -> Result<Rc<CachedTexture>, ModelError> {
+        match TextureCache::get(path, options) {
+            Some(texture) => Ok(texture),
+            None => TextureCache::insert(TextureImage::decode(path, options)?),
+        }
+    }
+
+    /// the cached texture for `path`, if it is alive
+    pub fn get(path: &Path, options: TextureOptions) -> Option<Rc<CachedTexture>> {
+        let key = TextureKey {
+            path: canonical(path),
+            options,
+        };
+        CACHE.with(|cache| cache.borrow().get(&key).and_then(Weak::upgrade))
+    }
+
+    /// uploads an image decoded with `TextureImage::decode`, unless the same texture is
+    /// already cached
+    pub fn insert(image: TextureImage) -> Result<Rc<CachedTexture>, ModelError> {
+        let key = TextureKey {
+            path: image.path.clone(),
+            options: image.options,
+        };
+        let cached = CACHE.with(|cache| cache.borrow().get(&key).and_then(Weak::upgrade));
+        if let Some(texture) = cached {
+            return Ok(texture);
+        }
+
+        let texture = Rc::new(image.upload()?);
+        CACHE.with(|cache| cache.borrow_mut().insert(key, Rc::downgrade(&texture)));
+        Ok(texture)
+    }
+
+    /// textures currently alive and their estimated memory usage
+    pub fn stats() -> TextureCacheStats {
+        CACHE.with(|cache| {
+            cache
+                .borrow()
+                .values()
+                .filter_map(Weak::upgrade)
+                .fold(TextureCacheStats::default(), |stats, texture| TextureCacheStats {
+                    textures: stats.textures + 1,
+                    size_bytes: stats.size_bytes + texture.size_bytes,
+                })
+        })
+    }
+
+    /// prints every texture alive in the cache and the total memory usage
+    pub fn report() {
+        CACHE.with(|cache| {
+            for texture in cache.borrow().values().filter_map(Weak::upgrade) {
+                println!(
+                    "{:?}: {}x{}, {} KiB, {} users",
+                    texture.path,
+                    texture.width,
+                    texture.height,
+                    texture.size_bytes / 1024,
+                    // not counting the one upgraded for the report
+                    Rc::strong_count(&texture) - 1
+                );
+            }
+        });
+        let stats = TextureCache::stats();
+        println!("{} textures, {} KiB", stats.textures, stats.size_bytes / 1024);
+    }
+}
+
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.into())
+}
+
+/// An image decoded and converted for upload. Decoding doesn't touch GL, so it can run on
+/// any thread; the upload has to happen on the GL thread, see `TextureCache::insert`.
+pub struct TextureImage {
+    /// canonical path of the image file
+    pub path: PathBuf,
+    pub options: TextureOptions,
+    image: DynamicImage,
+}
+
+impl TextureImage {
+    pub fn decode(path: &Path, options: TextureOptions) -> Result<TextureImage, ModelError> {
+        let path = canonical(path);
+        let img = image::open(&path).map_err(|err| ModelError::Texture {
+            path: path.clone(),
+            reason: err.to_string(),
+        })?;
+        let img = if options.flip { img.flipv() } else { img };
+        Ok(TextureImage {
+            path,
+            options,
+            image: img,
+        })
+    }
+
+    fn upload(self) -> Result<CachedTexture, ModelError> {
+        let texture = Texture2D::builder()
+            .srgb(self.options.srgb)
+            .mipmaps(self.options.mipmaps)
+            .from_image(&self.image)
+            .map_err(|err| ModelError::Texture {
+                path: self.path.clone(),
+                reason: err.to_string(),
+            })?;
+        Ok(CachedTexture {
+            width: texture.width,
+            height: texture.height,
+            size_bytes: texture.size_bytes(),
+            texture,
+            path: self.path,
+            options: self.options,
+        })
+    }
+}
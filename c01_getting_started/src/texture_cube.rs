@@ -0,0 +1,530 @@
+use cgmath::{Matrix4, Vector3};
+use dx::gles::{core30::gl, enums::*};
+use image::{DynamicImage, GenericImageView};
+use std::path::Path;
+
+use super::capabilities::{Capabilities, EnableState};
+use super::geometry::Geometry;
+use super::gl_object::{GlBuffer, GlFramebuffer, GlTexture, GlVertexArray};
+use super::hdr::HdrImage;
+use super::shader::Shader;
+use super::texture::{
+    bytes_per_texel, mip_levels, texture_data, Filter, HdrFormat, Sampling, Texture2D, TextureError, Wrap,
+};
+
+/// A cubemap, created with `TextureCube::builder()`. The faces are in GL order:
+/// +X (right), -X (left), +Y (top), -Y (bottom), +Z (front), -Z (back).
+///
+/// ```ignore
+/// let sky = TextureCube::builder().load_equirect("resources/textures/hdr/newport_loft.hdr")?;
+/// skybox.draw(&sky, &camera.get_view_matrix(), &projection);
+/// ```
+#[derive(Debug)]
+pub struct TextureCube {
+    texture: GlTexture,
+    /// width and height of every face
+    pub size: u32,
+    /// sized internal format, e.g. `GL_RGBA16F`
+    pub internal_format: u32,
+    /// number of mipmap levels, 1 without mipmaps
+    pub levels: u32,
+    /// high dynamic range colors encoded as RGBE in `GL_RGBA8`, see `HdrFormat::Rgbe`
+    pub rgbe: bool,
+    sampling: Sampling,
+}
+
+impl TextureCube {
+    pub fn builder() -> TextureCubeBuilder {
+        TextureCubeBuilder::default()
+    }
+
+    /// name of the GL texture
+    pub fn id(&self) -> u32 {
+        self.texture.id()
+    }
+
+    /// binds the cubemap to texture unit `unit`
+    pub fn bind(&self, unit: u32) {
+        gl::active_texture(GL_TEXTURE0 + unit);
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, self.texture.id());
+    }
+
+    pub fn sampling(&self) -> &Sampling {
+        &self.sampling
+    }
+
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, self.texture.id());
+        apply_sampling(&sampling, self.levels > 1);
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, 0);
+        self.sampling = sampling;
+    }
+
+    /// estimated GPU memory of the six faces, including the mipmap chain
+    pub fn size_bytes(&self) -> usize {
+        let face = self.size as usize * self.size as usize * bytes_per_texel(self.internal_format);
+        // a full mipmap chain adds a third
+        if self.levels > 1 {
+            6 * face * 4 / 3
+        } else {
+            6 * face
+        }
+    }
+}
+
+// cubemaps are sampled with direction vectors; R wraps like S and T
+fn apply_sampling(sampling: &Sampling, mipmaps: bool) {
+    sampling.apply(GL_TEXTURE_CUBE_MAP, mipmaps);
+    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_WRAP_R, sampling.wrap_s.gl_enum() as i32);
+}
+
+/// Creation options of a `TextureCube`. The defaults are those of a skybox: clamped to the
+/// edges, linear filtering without mipmaps and seamless filtering across the faces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureCubeBuilder {
+    pub sampling: Sampling,
+    pub mipmaps: bool,
+    /// color data in the sRGB color space; ignored for HDR panoramas
+    pub srgb: bool,
+    /// filter across the edges of the faces; a global switch on desktop GL, which this
+    /// turns on, and always the case from OpenGL ES 3.0
+    pub seamless: bool,
+    /// storage of HDR and EXR panoramas
+    pub hdr_format: HdrFormat,
+    /// face size of cubemaps converted from a panorama; a quarter of its width by default
+    pub face_size: Option<u32>,
+}
+
+impl Default for TextureCubeBuilder {
+    fn default() -> Self {
+        TextureCubeBuilder {
+            sampling: Sampling {
+                wrap_s: Wrap::ClampToEdge,
+                wrap_t: Wrap::ClampToEdge,
+                ..Sampling::default()
+            },
+            mipmaps: false,
+            srgb: false,
+            seamless: true,
+            hdr_format: HdrFormat::Half,
+            face_size: None,
+        }
+    }
+}
+
+impl TextureCubeBuilder {
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// minification, magnification and mipmap filtering
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.sampling.min_filter = filter;
+        self.sampling.mag_filter = filter;
+        self.sampling.mipmap_filter = filter;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn seamless(mut self, seamless: bool) -> Self {
+        self.seamless = seamless;
+        self
+    }
+
+    pub fn hdr_format(mut self, hdr_format: HdrFormat) -> Self {
+        self.hdr_format = hdr_format;
+        self
+    }
+
+    pub fn face_size(mut self, face_size: u32) -> Self {
+        self.face_size = Some(face_size);
+        self
+    }
+
+    /// loads six face images in GL order, see `TextureCube`
+    pub fn load_faces<P: AsRef<Path>>(&self, paths: &[P]) -> Result<TextureCube, TextureError> {
+        let faces = paths
+            .iter()
+            .map(|path| image::open(path).map_err(TextureError::Image))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.from_faces(&faces)
+    }
+
+    /// creates the cubemap from six square images of the same size and format
+    pub fn from_faces(&self, faces: &[DynamicImage]) -> Result<TextureCube, TextureError> {
+        if faces.len() != 6 {
+            return Err(TextureError::Format(format!(
+                "a cubemap has 6 faces, not {}",
+                faces.len()
+            )));
+        }
+        let size = faces[0].width();
+        let capabilities = Capabilities::current();
+        let data: Vec<_> = faces
+            .iter()
+            .map(|face| texture_data(face, self.srgb, &capabilities))
+            .collect();
+        for (face, face_data) in faces.iter().zip(&data) {
+            if face.dimensions() != (size, size) || face_data.internal_format != data[0].internal_format {
+                return Err(TextureError::Format(
+                    "cubemap faces must be square and of the same size and format".into(),
+                ));
+            }
+        }
+
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, texture.id());
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 1);
+        for (i, face_data) in data.iter().enumerate() {
+            gl::tex_image_2d(
+                GL_TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+                0,
+                face_data.internal_format as i32,
+                size as i32,
+                size as i32,
+                0,
+                face_data.format,
+                face_data.pixel_type,
+                &face_data.pixels,
+            );
+        }
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 4);
+        data[0].apply_swizzle(GL_TEXTURE_CUBE_MAP);
+        Ok(self.finish(texture, size, data[0].internal_format, false))
+    }
+
+    /// loads an image with the faces laid out as a horizontal (4:3) or vertical (3:4) cross
+    pub fn load_cross<P: AsRef<Path>>(&self, path: P) -> Result<TextureCube, TextureError> {
+        let image = image::open(path).map_err(TextureError::Image)?;
+        self.from_cross(&image)
+    }
+
+    /// splits a cross into its faces. The horizontal cross has -X, +Z, +X and -Z in its
+    /// middle row with +Y above and -Y below +Z; the vertical one has -Z under -Y, upside down.
+    pub fn from_cross(&self, image: &DynamicImage) -> Result<TextureCube, TextureError> {
+        let (width, height) = image.dimensions();
+        let (size, back) = if width * 3 == height * 4 {
+            (width / 4, (3, 1))
+        } else if width * 4 == height * 3 {
+            (width / 3, (1, 3))
+        } else {
+            return Err(TextureError::Format(format!(
+                "a cubemap cross is 4:3 or 3:4, not {}x{}",
+                width, height
+            )));
+        };
+        // columns and rows of +X, -X, +Y, -Y, +Z and -Z
+        let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), back];
+        let faces: Vec<DynamicImage> = cells
+            .iter()
+            .map(|&(column, row)| {
+                let face = image.crop_imm(column * size, row * size, size, size);
+                if (column, row) == (1, 3) {
+                    face.rotate180()
+                } else {
+                    face
+                }
+            })
+            .collect();
+        self.from_faces(&faces)
+    }
+
+    /// loads an equirectangular panorama, a Radiance `.hdr` or OpenEXR file or any other
+    /// image, and converts it with `from_equirect`
+    pub fn load_equirect<P: AsRef<Path>>(&self, path: P) -> Result<TextureCube, TextureError> {
+        let path = path.as_ref();
+        let hdr = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                extension.eq_ignore_ascii_case("hdr") || extension.eq_ignore_ascii_case("exr")
+            });
+        // the panorama is sampled once for every texel of the faces
+        let builder = Texture2D::builder()
+            .flip(true)
+            .mipmaps(false)
+            .wrap_t(Wrap::ClampToEdge)
+            .srgb(self.srgb)
+            .hdr_format(self.hdr_format);
+        let panorama = if hdr {
+            builder.from_hdr_image(&HdrImage::open(path)?)
+        } else {
+            builder.load(path)?
+        };
+        Ok(self.from_equirect(&panorama))
+    }
+
+    /// renders an equirectangular panorama, flipped so its first row is the bottom, into
+    /// the faces of a new cubemap. HDR panoramas go into `GL_RGBA16F` where the context can
+    /// render to it, RGBE encoded `GL_RGBA8` otherwise.
+    ///
+    /// Binds framebuffer 0 when done and leaves the viewport at the face size.
+    pub fn from_equirect(&self, panorama: &Texture2D) -> TextureCube {
+        let capabilities = Capabilities::current();
+        let size = self.face_size.unwrap_or(panorama.width / 4).max(1);
+        let hdr = panorama.rgbe || [GL_RGB16F, GL_RGBA16F, GL_R11F_G11F_B10F].contains(&panorama.internal_format);
+        let rgbe = hdr && !capabilities.color_buffer_half_float();
+        let (internal_format, pixel_type) = if rgbe {
+            (GL_RGBA8, GL_UNSIGNED_BYTE)
+        } else if hdr {
+            (GL_RGBA16F, GL_HALF_FLOAT)
+        } else if self.srgb {
+            (GL_SRGB8_ALPHA8, GL_UNSIGNED_BYTE)
+        } else {
+            (GL_RGBA8, GL_UNSIGNED_BYTE)
+        };
+
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, texture.id());
+        for face in 0..6 {
+            gl::empty_tex_image_2d(
+                GL_TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                0,
+                internal_format as i32,
+                size as i32,
+                size as i32,
+                0,
+                GL_RGBA,
+                pixel_type,
+            );
+        }
+        gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, 0);
+
+        let shader = Shader::from_source(FACE_VERTEX_SHADER, EQUIRECT_FRAGMENT_SHADER);
+        let framebuffer = GlFramebuffer::new();
+        // attribute-less draws still need a vertex array
+        let vao = GlVertexArray::new();
+        let disabled: Vec<u32> = [GL_DEPTH_TEST, GL_BLEND, GL_CULL_FACE]
+            .iter()
+            .copied()
+            .filter(|&capability| EnableState::set(capability, false))
+            .collect();
+
+        gl::bind_framebuffer(GL_FRAMEBUFFER, framebuffer.id());
+        gl::viewport(0, 0, size as i32, size as i32);
+        shader.use_program();
+        shader.set_int("equirect", 0);
+        shader.set_bool("decodeRgbe", panorama.rgbe);
+        shader.set_bool("encodeRgbe", rgbe);
+        panorama.bind(0);
+        gl::bind_vertex_array(vao.id());
+        for face in 0..6 {
+            gl::framebuffer_texture_2d(
+                GL_FRAMEBUFFER,
+                GL_COLOR_ATTACHMENT0,
+                GL_TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                texture.id(),
+                0,
+            );
+            shader.set_int("face", face as i32);
+            gl::draw_arrays(GL_TRIANGLES, 0, 3);
+        }
+        gl::bind_vertex_array(0);
+        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+        for &capability in &disabled {
+            EnableState::set(capability, true);
+        }
+
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, texture.id());
+        self.finish(texture, size, internal_format, rgbe)
+    }
+
+    // sets up the cubemap bound to GL_TEXTURE_CUBE_MAP with its level 0 filled in
+    fn finish(&self, texture: GlTexture, size: u32, internal_format: u32, rgbe: bool) -> TextureCube {
+        // averaging RGBE texels with different exponents is meaningless
+        let mipmaps = self.mipmaps && !rgbe;
+        if mipmaps {
+            gl::generate_mipmap(GL_TEXTURE_CUBE_MAP);
+        }
+        apply_sampling(&self.sampling, mipmaps);
+        gl::bind_texture(GL_TEXTURE_CUBE_MAP, 0);
+        if self.seamless {
+            enable_seamless_cube_maps();
+        }
+
+        TextureCube {
+            texture,
+            size,
+            internal_format,
+            levels: if mipmaps { mip_levels(size, size) } else { 1 },
+            rgbe,
+            sampling: self.sampling,
+        }
+    }
+}
+
+// desktop GL only, not part of the ES enums
+const GL_TEXTURE_CUBE_MAP_SEAMLESS: u32 = 0x884F;
+
+/// filters cubemaps across the edges of their faces; only needed on desktop GL, where it
+/// is a global switch of the context
+pub fn enable_seamless_cube_maps() {
+    let capabilities = Capabilities::current();
+    if !capabilities.es && capabilities.seamless_cube_maps() {
+        gl::enable(GL_TEXTURE_CUBE_MAP_SEAMLESS);
+    }
+}
+
+// a triangle covering the viewport; Position goes from -1 to 1 across it
+const FACE_VERTEX_SHADER: &str = r#"#version 330 core
+out vec2 Position;
+
+void main()
+{
+    Position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    gl_Position = vec4(Position, 0.0, 1.0);
+}
+"#;
+
+const EQUIRECT_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 Position;
+
+uniform sampler2D equirect;
+uniform int face;
+uniform bool decodeRgbe;
+uniform bool encodeRgbe;
+
+// direction of the center of each face and of increasing s and t on it, in the order
+// +X, -X, +Y, -Y, +Z, -Z
+const vec3 forward[6] = vec3[](vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0),
+                               vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, -1.0));
+const vec3 right[6] = vec3[](vec3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0),
+                             vec3(1.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0));
+const vec3 up[6] = vec3[](vec3(0.0, -1.0, 0.0), vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, 1.0),
+                          vec3(0.0, 0.0, -1.0), vec3(0.0, -1.0, 0.0), vec3(0.0, -1.0, 0.0));
+const vec2 invAtan = vec2(0.1591, 0.3183);
+
+void main()
+{
+    vec3 v = normalize(forward[face] + Position.x * right[face] + Position.y * up[face]);
+    vec2 uv = vec2(atan(v.z, v.x), asin(v.y)) * invAtan + 0.5;
+    vec4 color = texture(equirect, uv);
+    vec3 rgb = decodeRgbe ? color.rgb * (255.0 / 256.0) * exp2(color.a * 255.0 - 128.0) : color.rgb;
+
+    float m = max(rgb.r, max(rgb.g, rgb.b));
+    if (!encodeRgbe)
+        FragColor = vec4(rgb, 1.0);
+    else if (m < 1e-32)
+        FragColor = vec4(0.0);
+    else
+    {
+        float e = floor(log2(m)) + 1.0;
+        FragColor = vec4(rgb / exp2(e) * (256.0 / 255.0), (e + 128.0) / 255.0);
+    }
+}
+"#;
+
+/// Draws a cubemap around the camera, behind everything drawn before it.
+pub struct Skybox {
+    shader: Shader,
+    vao: GlVertexArray,
+    _vbo: GlBuffer,
+    _ebo: GlBuffer,
+    index_count: i32,
+}
+
+impl Default for Skybox {
+    fn default() -> Self {
+        Skybox::new()
+    }
+}
+
+impl Skybox {
+    pub fn new() -> Skybox {
+        let cube = Geometry::cube(2.0);
+        let positions: Vec<Vector3<f32>> = cube.vertices.iter().map(|vertex| vertex.position).collect();
+
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        let ebo = GlBuffer::new();
+        gl::bind_vertex_array(vao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
+        gl::buffer_data(GL_ARRAY_BUFFER, &positions, GL_STATIC_DRAW);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo.id());
+        gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &cube.indices, GL_STATIC_DRAW);
+        gl::enable_vertex_attrib_array(0);
+        gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, 3 * 4, 0);
+        gl::bind_vertex_array(0);
+
+        let shader = Shader::from_source(SKYBOX_VERTEX_SHADER, SKYBOX_FRAGMENT_SHADER);
+        shader.use_program();
+        shader.set_int("skybox", 0);
+
+        Skybox {
+            shader,
+            vao,
+            _vbo: vbo,
+            _ebo: ebo,
+            index_count: cube.indices.len() as i32,
+        }
+    }
+
+    /// draws `cubemap` with the rotation of `view`; uses texture unit 0 and restores the
+    /// depth function
+    pub fn draw(&self, cubemap: &TextureCube, view: &Matrix4<f32>, projection: &Matrix4<f32>) {
+        // remove translation from the view matrix
+        let mut view = *view;
+        view.w[0] = 0.0;
+        view.w[1] = 0.0;
+        view.w[2] = 0.0;
+
+        // the skybox is at the far plane: pass where the depth buffer is still cleared
+        let depth_func = gl::get_integerv(GL_DEPTH_FUNC) as u32;
+        gl::depth_func(GL_LEQUAL);
+        self.shader.use_program();
+        self.shader.set_mat4("view", &view);
+        self.shader.set_mat4("projection", projection);
+        self.shader.set_bool("decodeRgbe", cubemap.rgbe);
+        cubemap.bind(0);
+        gl::bind_vertex_array(self.vao.id());
+        gl::draw_elements_offset(GL_TRIANGLES, self.index_count, GL_UNSIGNED_INT, 0);
+        gl::bind_vertex_array(0);
+        gl::depth_func(depth_func);
+    }
+}
+
+const SKYBOX_VERTEX_SHADER: &str = r#"#version 330 core
+layout (location = 0) in vec3 aPos;
+
+out vec3 TexCoords;
+
+uniform mat4 projection;
+uniform mat4 view;
+
+void main()
+{
+    TexCoords = aPos;
+    vec4 pos = projection * view * vec4(aPos, 1.0);
+    gl_Position = pos.xyww;
+}
+"#;
+
+const SKYBOX_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec3 TexCoords;
+
+uniform samplerCube skybox;
+uniform bool decodeRgbe;
+
+void main()
+{
+    vec4 color = texture(skybox, TexCoords);
+    if (decodeRgbe)
+        color = vec4(color.rgb * (255.0 / 256.0) * exp2(color.a * 255.0 - 128.0), 1.0);
+    FragColor = color;
+}
+"#;
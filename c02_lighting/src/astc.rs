@@ -0,0 +1,742 @@
+use std::convert::TryInto;
+
+// the color of malformed blocks and of blocks that need the HDR profile
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+// the quantization levels of the color endpoints, the finest first
+const COLOR_LEVELS: [u32; 17] = [256, 192, 160, 128, 96, 80, 64, 48, 40, 32, 24, 20, 16, 12, 10, 8, 6];
+
+/// Decodes an ASTC LDR image of `width` x `height` texels with `block_width` x
+/// `block_height` blocks into tightly packed rows of RGBA8 texels. Blocks using HDR
+/// endpoints or invalid encodings decode to magenta, as on hardware without the HDR
+/// profile. `data` holds the 16-byte blocks row by row, as in a KTX file or for
+/// `glCompressedTexImage2D`.
+pub fn decode_astc(block_width: u32, block_height: u32, srgb: bool, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let (block_width, block_height) = (block_width as usize, block_height as usize);
+    let (width, height) = (width as usize, height as usize);
+    assert!(
+        (4..=12).contains(&block_width) && (4..=12).contains(&block_height),
+        "not an ASTC block footprint"
+    );
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    assert!(data.len() >= blocks_x * blocks_y * 16, "ASTC data too short");
+
+    let footprint = Footprint {
+        width: block_width,
+        height: block_height,
+        srgb,
+    };
+    let mut texels = vec![0u8; width * height * 4];
+    let mut block_texels = vec![[0u8; 4]; block_width * block_height];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let offset = (by * blocks_x + bx) * 16;
+            let block = u128::from_le_bytes(data[offset..offset + 16].try_into().unwrap());
+            if decode_block(block, &footprint, &mut block_texels).is_none() {
+                block_texels.fill(ERROR_COLOR);
+            }
+
+            // blocks on the right and bottom edge may stick out of the image
+            for y in 0..block_height.min(height - by * block_height) {
+                for x in 0..block_width.min(width - bx * block_width) {
+                    let dst = ((by * block_height + y) * width + bx * block_width + x) * 4;
+                    texels[dst..dst + 4].copy_from_slice(&block_texels[y * block_width + x]);
+                }
+            }
+        }
+    }
+    texels
+}
+
+struct Footprint {
+    width: usize,
+    height: usize,
+    srgb: bool,
+}
+
+// `count` bits of the block starting at bit `offset`, bits past the end read as 0
+fn field(block: u128, offset: u32, count: u32) -> u32 {
+    if count == 0 || offset >= 128 {
+        return 0;
+    }
+    ((block >> offset) & ((1 << count) - 1)) as u32
+}
+
+// the weight grid of a block, from the 11-bit block mode
+struct BlockMode {
+    grid_width: usize,
+    grid_height: usize,
+    dual_plane: bool,
+    weight_levels: u32,
+}
+
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let a = (mode >> 5) & 3;
+    let mut high_precision = (mode >> 9) & 1 == 1;
+    let mut dual_plane = (mode >> 10) & 1 == 1;
+    let (grid_width, grid_height, range);
+    if mode & 3 != 0 {
+        let b = (mode >> 7) & 3;
+        let size = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 == 0 => (a + 2, (b & 1) + 6),
+            _ => ((b & 1) + 2, a + 2),
+        };
+        grid_width = size.0;
+        grid_height = size.1;
+        range = ((mode >> 4) & 1) | ((mode & 3) << 1);
+    } else {
+        let size = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                // the precision and dual plane bits hold the height
+                high_precision = false;
+                dual_plane = false;
+                (a + 6, ((mode >> 9) & 3) + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        grid_width = size.0;
+        grid_height = size.1;
+        range = ((mode >> 4) & 1) | (((mode >> 2) & 3) << 1);
+    }
+    if range < 2 {
+        return None;
+    }
+    let weight_levels = if high_precision {
+        [10, 12, 16, 20, 24, 32][range as usize - 2]
+    } else {
+        [2, 3, 4, 5, 6, 8][range as usize - 2]
+    };
+    Some(BlockMode {
+        grid_width: grid_width as usize,
+        grid_height: grid_height as usize,
+        dual_plane,
+        weight_levels,
+    })
+}
+
+fn decode_block(block: u128, footprint: &Footprint, texels: &mut [[u8; 4]]) -> Option<()> {
+    if field(block, 0, 9) == 0x1FC {
+        return decode_void_extent(block, texels);
+    }
+
+    let mode = block_mode(field(block, 0, 11))?;
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let grid_size = mode.grid_width * mode.grid_height;
+    let weight_count = grid_size * planes;
+    if mode.grid_width > footprint.width || mode.grid_height > footprint.height || weight_count > 64 {
+        return None;
+    }
+    let weight_bits = ise_bit_count(weight_count, mode.weight_levels);
+    if !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+    let partitions = field(block, 11, 2) as usize + 1;
+    if partitions == 4 && mode.dual_plane {
+        return None;
+    }
+
+    // the color endpoint modes; with several partitions their high bits sit right below
+    // the weights
+    let mut color_end = 128 - weight_bits;
+    let mut endpoint_modes = [0; 4];
+    let color_start = if partitions == 1 {
+        endpoint_modes[0] = field(block, 13, 4);
+        17
+    } else if field(block, 23, 2) == 0 {
+        endpoint_modes = [field(block, 25, 4); 4];
+        29
+    } else {
+        let extra_bits = 3 * partitions as u32 - 4;
+        color_end -= extra_bits;
+        let modes = field(block, 23, 6) | (field(block, color_end, extra_bits) << 6);
+        let class = (modes & 3) - 1;
+        for (partition, endpoint_mode) in endpoint_modes.iter_mut().enumerate().take(partitions) {
+            let class = class + ((modes >> (2 + partition)) & 1);
+            *endpoint_mode = (class << 2) | ((modes >> (2 + partitions + 2 * partition)) & 3);
+        }
+        29
+    };
+    // the channel using the second weight plane
+    let plane_channel = if mode.dual_plane {
+        color_end -= 2;
+        Some(field(block, color_end, 2) as usize)
+    } else {
+        None
+    };
+
+    let value_counts = endpoint_modes.map(|endpoint_mode| ((endpoint_mode as usize >> 2) + 1) * 2);
+    let value_count: usize = value_counts[..partitions].iter().sum();
+    if value_count > 18 {
+        return None;
+    }
+    let color_bits = color_end.checked_sub(color_start)?;
+    let color_levels = COLOR_LEVELS
+        .iter()
+        .copied()
+        .find(|&levels| ise_bit_count(value_count, levels) <= color_bits)?;
+    let mut values = [0; 18];
+    decode_ise(block, color_start, color_levels, &mut values[..value_count]);
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    let mut offset = 0;
+    for partition in 0..partitions {
+        let count = value_counts[partition];
+        let mut unquantized = [0; 8];
+        for (value, quantized) in unquantized.iter_mut().zip(&values[offset..offset + count]) {
+            *value = unquantize_color(*quantized, color_levels) as i32;
+        }
+        endpoints[partition] = decode_endpoints(endpoint_modes[partition], &unquantized)?;
+        offset += count;
+    }
+
+    // the weights are stored backwards from the top of the block, the planes interleaved
+    let mut grid = [0; 64];
+    decode_ise(block.reverse_bits(), 0, mode.weight_levels, &mut grid[..weight_count]);
+    let mut weights = [[0; 144]; 2];
+    for (plane, plane_weights) in weights.iter_mut().enumerate().take(planes) {
+        let mut plane_grid = [0; 64];
+        for (i, weight) in plane_grid.iter_mut().enumerate().take(grid_size) {
+            *weight = unquantize_weight(grid[i * planes + plane], mode.weight_levels);
+        }
+        infill_weights(&plane_grid, &mode, footprint, plane_weights);
+    }
+
+    let seed = field(block, 13, 10);
+    let small_block = footprint.width * footprint.height < 31;
+    for y in 0..footprint.height {
+        for x in 0..footprint.width {
+            let texel = y * footprint.width + x;
+            let partition = select_partition(seed, x as u32, y as u32, partitions as u32, small_block);
+            let [e0, e1] = endpoints[partition as usize];
+            for channel in 0..4 {
+                let plane = (plane_channel == Some(channel)) as usize;
+                let weight = weights[plane][texel] as i32;
+                // sRGB endpoints are expanded to the middle of their 16-bit range
+                let expand = |e: i32| {
+                    if footprint.srgb && channel < 3 {
+                        (e << 8) | 0x80
+                    } else {
+                        e * 257
+                    }
+                };
+                let value = (expand(e0[channel]) * (64 - weight) + expand(e1[channel]) * weight + 32) >> 6;
+                texels[texel][channel] = (value >> 8) as u8;
+            }
+        }
+    }
+    Some(())
+}
+
+// a block of one color, only LDR void extents decode
+fn decode_void_extent(block: u128, texels: &mut [[u8; 4]]) -> Option<()> {
+    if field(block, 9, 1) == 1 || field(block, 10, 2) != 3 {
+        return None;
+    }
+    let extent = [12, 25, 38, 51].map(|offset| field(block, offset, 13));
+    let unbounded = extent.iter().all(|&coordinate| coordinate == 0x1FFF);
+    if !unbounded && (extent[0] >= extent[1] || extent[2] >= extent[3]) {
+        return None;
+    }
+    let color = [0, 1, 2, 3].map(|channel| (field(block, 64 + 16 * channel, 16) >> 8) as u8);
+    texels.fill(color);
+    Some(())
+}
+
+// the integer sequence encoding packs values of `levels` levels as 1 trit (3 levels) or
+// quint (5 levels) and low bits each; returns 3, 5 or 1 and the number of low bits
+fn ise_mode(levels: u32) -> (u32, u32) {
+    let base = if levels.is_multiple_of(3) {
+        3
+    } else if levels.is_multiple_of(5) {
+        5
+    } else {
+        1
+    };
+    (base, (levels / base).trailing_zeros())
+}
+
+fn ise_bit_count(count: usize, levels: u32) -> u32 {
+    let (base, bits) = ise_mode(levels);
+    let count = count as u32;
+    count * bits
+        + match base {
+            3 => (8 * count).div_ceil(5),
+            5 => (7 * count).div_ceil(3),
+            _ => 0,
+        }
+}
+
+// reads `values.len()` values from bit `start`; the last group of trits or quints is cut
+// short, its missing bits read as 0
+fn decode_ise(block: u128, start: u32, levels: u32, values: &mut [u32]) {
+    let (base, bits) = ise_mode(levels);
+    let end = start + ise_bit_count(values.len(), levels);
+    let mut offset = start;
+    let mut read = |count: u32| {
+        let value = field(block, offset, count.min(end.saturating_sub(offset)));
+        offset += count;
+        value
+    };
+    // the bits of the packed trits or quints that follow each value of a group
+    let packed_bits: &[u32] = match base {
+        3 => &[2, 2, 1, 2, 1],
+        5 => &[3, 2, 2],
+        _ => &[0],
+    };
+    for group in values.chunks_mut(packed_bits.len()) {
+        let mut low = [0; 5];
+        let mut packed = 0;
+        let mut shift = 0;
+        for (i, &count) in packed_bits.iter().enumerate() {
+            low[i] = read(bits);
+            packed |= read(count) << shift;
+            shift += count;
+        }
+        let high = match base {
+            3 => decode_trits(packed),
+            5 => {
+                let [q0, q1, q2] = decode_quints(packed);
+                [q0, q1, q2, 0, 0]
+            }
+            _ => [0; 5],
+        };
+        for (i, value) in group.iter_mut().enumerate() {
+            *value = (high[i] << bits) | low[i];
+        }
+    }
+}
+
+// five trits from 8 bits
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let bit = |value: u32, i: u32| (value >> i) & 1;
+    let (c, t3, t4);
+    if (packed >> 2) & 7 == 7 {
+        c = (((packed >> 5) & 7) << 2) | (packed & 3);
+        t3 = 2;
+        t4 = 2;
+    } else {
+        c = packed & 0x1F;
+        if (packed >> 5) & 3 == 3 {
+            t3 = bit(packed, 7);
+            t4 = 2;
+        } else {
+            t3 = (packed >> 5) & 3;
+            t4 = bit(packed, 7);
+        }
+    }
+    let (t0, t1, t2);
+    if c & 3 == 3 {
+        t0 = (bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1);
+        t1 = bit(c, 4);
+        t2 = 2;
+    } else if (c >> 2) & 3 == 3 {
+        t0 = c & 3;
+        t1 = 2;
+        t2 = 2;
+    } else {
+        t0 = (bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1);
+        t1 = (c >> 2) & 3;
+        t2 = bit(c, 4);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+// three quints from 7 bits
+fn decode_quints(packed: u32) -> [u32; 3] {
+    let bit = |i: u32| (packed >> i) & 1;
+    if (packed >> 1) & 3 == 3 && (packed >> 5) & 3 == 0 {
+        let q2 = (bit(0) << 2) | ((bit(4) & !bit(0) & 1) << 1) | (bit(3) & !bit(0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if (packed >> 1) & 3 == 3 {
+        (4, (((packed >> 3) & 3) << 3) | ((!(packed >> 5) & 3) << 1) | bit(0))
+    } else {
+        ((packed >> 5) & 3, packed & 0x1F)
+    };
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+// repeats the `bits` bits of `value` to fill `to` bits
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    if bits == 0 {
+        return 0;
+    }
+    let mut result = 0;
+    let mut filled = 0;
+    while filled < to {
+        result = (result << bits) | value;
+        filled += bits;
+    }
+    result >> (filled - to)
+}
+
+// color value to 0..=255; the lowest bit of trit and quint values mirrors the range
+fn unquantize_color(value: u32, levels: u32) -> u32 {
+    let (base, bits) = ise_mode(levels);
+    if base == 1 {
+        return replicate(value, bits, 8);
+    }
+    let low = value & ((1 << bits) - 1);
+    let x = low >> 1;
+    let (b, c) = match (base, bits) {
+        (3, 1) => (0, 204),
+        (3, 2) => ((x << 8) | (x << 4) | (x << 2) | (x << 1), 93),
+        (3, 3) => ((x << 7) | (x << 2) | x, 44),
+        (3, 4) => ((x << 6) | x, 22),
+        (3, 5) => ((x << 5) | (x >> 2), 11),
+        (3, _) => ((x << 4) | (x >> 4), 5),
+        (_, 1) => (0, 113),
+        (_, 2) => ((x << 8) | (x << 3) | (x << 2), 54),
+        (_, 3) => ((x << 7) | (x << 1) | (x >> 1), 26),
+        (_, 4) => ((x << 6) | (x >> 1), 13),
+        _ => ((x << 5) | (x >> 3), 6),
+    };
+    let a = if low & 1 == 1 { 0x1FF } else { 0 };
+    let t = ((value >> bits) * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+// weight value to 0..=64
+fn unquantize_weight(value: u32, levels: u32) -> u32 {
+    let (base, bits) = ise_mode(levels);
+    let weight = match (base, bits) {
+        (1, _) => replicate(value, bits, 6),
+        // 3 and 5 levels
+        (_, 0) => return value * 64 / (levels - 1),
+        _ => {
+            let low = value & ((1 << bits) - 1);
+            let x = low >> 1;
+            let (b, c) = match (base, bits) {
+                (3, 1) => (0, 50),
+                (3, 2) => ((x << 6) | (x << 2) | x, 23),
+                (3, _) => ((x << 5) | x, 11),
+                (_, 1) => (0, 28),
+                _ => ((x << 6) | (x << 1), 13),
+            };
+            let a = if low & 1 == 1 { 0x7F } else { 0 };
+            let t = ((value >> bits) * c + b) ^ a;
+            (a & 0x20) | (t >> 2)
+        }
+    };
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+// moves the top bit of `base` into `offset`, which becomes a signed 6-bit value
+fn bit_transfer_signed(offset: i32, base: i32) -> (i32, i32) {
+    let base = (base >> 1) | (offset & 0x80);
+    let offset = (offset >> 1) & 0x3F;
+    let offset = if offset & 0x20 != 0 { offset - 0x40 } else { offset };
+    (offset, base)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    let [r, g, b, a] = color;
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+// the two RGBA endpoints of a partition, None for the HDR modes
+fn decode_endpoints(mode: u32, v: &[i32; 8]) -> Option<[[i32; 4]; 2]> {
+    let endpoints = match mode {
+        // luminance, direct and base + offset
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        // luminance and alpha, direct and base + offset
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (l1, l0) = bit_transfer_signed(v[1], v[0]);
+            let (a1, a0) = bit_transfer_signed(v[3], v[2]);
+            [[l0, l0, l0, a0], [l0 + l1, l0 + l1, l0 + l1, a0 + a1]]
+        }
+        // RGB base and scale, without and with two alphas
+        6 => [
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, 255],
+            [v[0], v[1], v[2], 255],
+        ],
+        10 => [
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, v[4]],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        // RGB(A) direct, swapped and blue contracted if the second endpoint is darker
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                ]
+            }
+        }
+        // RGB(A) base + offset, likewise for a negative offset
+        9 | 13 => {
+            let (r1, r0) = bit_transfer_signed(v[1], v[0]);
+            let (g1, g0) = bit_transfer_signed(v[3], v[2]);
+            let (b1, b0) = bit_transfer_signed(v[5], v[4]);
+            let (a1, a0) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 255)
+            };
+            let base = [r0, g0, b0, a0];
+            let sum = [r0 + r1, g0 + g1, b0 + b1, a0 + a1];
+            if r1 + g1 + b1 >= 0 {
+                [base, sum]
+            } else {
+                [blue_contract(sum), blue_contract(base)]
+            }
+        }
+        _ => return None,
+    };
+    Some(endpoints.map(|endpoint| endpoint.map(|channel| channel.clamp(0, 255))))
+}
+
+// bilinear interpolation of the weight grid at the texels of the block
+fn infill_weights(grid: &[u32; 64], mode: &BlockMode, footprint: &Footprint, weights: &mut [u32; 144]) {
+    let scale = |size: usize| (1024 + size / 2) / (size - 1);
+    let (scale_s, scale_t) = (scale(footprint.width), scale(footprint.height));
+    let at = |i: usize| grid.get(i).copied().unwrap_or(0);
+    for t in 0..footprint.height {
+        for s in 0..footprint.width {
+            let gs = (scale_s * s * (mode.grid_width - 1) + 32) >> 6;
+            let gt = (scale_t * t * (mode.grid_height - 1) + 32) >> 6;
+            let (fs, ft) = ((gs & 0xF) as u32, (gt & 0xF) as u32);
+            let v0 = (gs >> 4) + (gt >> 4) * mode.grid_width;
+            let w11 = (fs * ft + 8) >> 4;
+            let w10 = ft - w11;
+            let w01 = fs - w11;
+            let w00 = 16 + w11 - fs - ft;
+            weights[t * footprint.width + s] = (at(v0) * w00
+                + at(v0 + 1) * w01
+                + at(v0 + mode.grid_width) * w10
+                + at(v0 + mode.grid_width + 1) * w11
+                + 8)
+                >> 4;
+        }
+    }
+}
+
+// the partition of texel (x, y), a hash of the 10-bit partition seed
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> u32 {
+    if partitions == 1 {
+        return 0;
+    }
+    let (x, y) = if small_block { (x << 1, y << 1) } else { (x, y) };
+    let seed = seed + (partitions - 1) * 1024;
+
+    let mut rnum = seed;
+    rnum ^= rnum >> 15;
+    rnum = rnum.wrapping_sub(rnum << 17);
+    rnum = rnum.wrapping_add(rnum << 7);
+    rnum = rnum.wrapping_add(rnum << 4);
+    rnum ^= rnum >> 5;
+    rnum = rnum.wrapping_add(rnum << 16);
+    rnum ^= rnum >> 7;
+    rnum ^= rnum >> 3;
+    rnum ^= rnum << 6;
+    rnum ^= rnum >> 17;
+
+    // 2D textures have z = 0, which leaves out the seeds scaling it
+    let mut seeds = [0, 4, 8, 12, 16, 20, 24, 28].map(|shift| (rnum >> shift) & 0xF);
+    let (sh1, sh2) = match (seed & 1 == 1, seed & 2 == 2) {
+        (true, small) => (if small { 4 } else { 5 }, if partitions == 3 { 6 } else { 5 }),
+        (false, small) => (if partitions == 3 { 6 } else { 5 }, if small { 4 } else { 5 }),
+    };
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        let shift = if i % 2 == 0 { sh1 } else { sh2 };
+        *seed = (*seed * *seed) >> shift;
+    }
+
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3F;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions < 3 {
+        0
+    } else {
+        (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if partitions < 4 {
+        0
+    } else {
+        (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3F
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+
+    // a void extent block of one 16-bit RGBA color, unbounded
+    fn void_extent(color: [u128; 4]) -> u128 {
+        let mut block = 0xFFFF_FFFF_FFFF_FDFC;
+        for (channel, value) in color.iter().enumerate() {
+            block |= value << (64 + 16 * channel);
+        }
+        block
+    }
+
+    // weights of `bits` bits each, stored backwards from the top of the block
+    fn weights(bits: u32, values: &[u128]) -> u128 {
+        let stream = values
+            .iter()
+            .enumerate()
+            .fold(0, |stream, (i, value)| stream | (value << (i as u32 * bits)));
+        stream.reverse_bits()
+    }
+
+    // endpoint values of 8 bits each, after the mode of a single partition block
+    fn colors(values: &[u128]) -> u128 {
+        values
+            .iter()
+            .enumerate()
+            .fold(0, |block, (i, value)| block | (value << (17 + 8 * i)))
+    }
+
+    fn decode(block: u128) -> Vec<[u8; 4]> {
+        decode_astc(4, 4, false, 4, 4, &block.to_le_bytes())
+            .chunks(4)
+            .map(|texel| texel.try_into().unwrap())
+            .collect()
+    }
+
+    // 4x4 grid of 2-bit weights, luminance endpoints 32 and 224, weight x in column x
+    fn luminance_block(endpoint_mode: u128) -> u128 {
+        let grid: Vec<u128> = (0..16).map(|i| i % 4).collect();
+        66 | (endpoint_mode << 13) | colors(&[0x20, 0xE0]) | weights(2, &grid)
+    }
+
+    #[test]
+    fn void_extent_is_one_color() {
+        let texels = decode(void_extent([0x1234, 0x8000, 0xFFFF, 0]));
+        assert!(texels.iter().all(|&texel| texel == [0x12, 0x80, 0xFF, 0]));
+
+        // HDR void extents need the HDR profile
+        let texels = decode(void_extent([0; 4]) | 1 << 9);
+        assert!(texels.iter().all(|&texel| texel == MAGENTA));
+    }
+
+    #[test]
+    fn luminance_interpolates_the_weights() {
+        let texels = decode(luminance_block(0));
+        for y in 0..4 {
+            let row: Vec<u8> = texels[y * 4..y * 4 + 4].iter().map(|texel| texel[0]).collect();
+            assert_eq!(row, [32, 95, 161, 224]);
+        }
+        assert!(texels.iter().all(|texel| texel[0] == texel[2] && texel[3] == 255));
+    }
+
+    #[test]
+    fn dual_plane_drives_alpha_separately() {
+        // RGBA direct from black and opaque to white and transparent, alpha on the
+        // second plane with all weights 1
+        let grid: Vec<u128> = (0..32).map(|i| if i % 2 == 0 { (i / 2) % 2 } else { 1 }).collect();
+        let block = 1089 | (12 << 13) | colors(&[0, 255, 0, 255, 0, 255, 255, 0]) | (3 << 94) | weights(1, &grid);
+        let texels = decode(block);
+        assert_eq!(texels[0], [0, 0, 0, 0]);
+        assert_eq!(texels[1], [255, 255, 255, 0]);
+        assert_eq!(texels[14], [0, 0, 0, 0]);
+        assert_eq!(texels[15], [255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn weight_grid_is_upsampled() {
+        // a 2x3 grid of 5-bit weights, 0 on the left and 31 on the right
+        let block = 831 | colors(&[0, 255]) | weights(5, &[0, 31, 0, 31, 0, 31]);
+        let texels = decode(block);
+        for y in 0..4 {
+            let row: Vec<u8> = texels[y * 4..y * 4 + 4].iter().map(|texel| texel[0]).collect();
+            assert_eq!(row, [0, 80, 175, 255]);
+        }
+    }
+
+    #[test]
+    fn invalid_blocks_are_magenta() {
+        // reserved block mode
+        assert!(decode(0).iter().all(|&texel| texel == MAGENTA));
+        // HDR endpoints
+        assert!(decode(luminance_block(2)).iter().all(|&texel| texel == MAGENTA));
+    }
+
+    #[test]
+    fn edge_blocks_are_cropped() {
+        // two 6x6 blocks, red and blue, for a 7x5 image
+        let data: Vec<u8> = [void_extent([0xFFFF, 0, 0, 0xFFFF]), void_extent([0, 0, 0xFFFF, 0xFFFF])]
+            .iter()
+            .flat_map(|block| block.to_le_bytes())
+            .collect();
+        let texels = decode_astc(6, 6, false, 7, 5, &data);
+        assert_eq!(texels.len(), 7 * 5 * 4);
+        let at = |x: usize, y: usize| &texels[(y * 7 + x) * 4..(y * 7 + x) * 4 + 4];
+        assert_eq!(at(0, 0), [255, 0, 0, 255]);
+        assert_eq!(at(5, 4), [255, 0, 0, 255]);
+        assert_eq!(at(6, 0), [0, 0, 255, 255]);
+        assert_eq!(at(6, 4), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn packed_trits_and_quints_cover_every_combination() {
+        let trits: HashSet<[u32; 5]> = (0..256).map(decode_trits).collect();
+        assert_eq!(trits.len(), 243);
+        assert!(trits.iter().flatten().all(|&trit| trit < 3));
+        let quints: HashSet<[u32; 3]> = (0..128).map(decode_quints).collect();
+        assert_eq!(quints.len(), 125);
+        assert!(quints.iter().flatten().all(|&quint| quint < 5));
+    }
+
+    #[test]
+    fn unquantization() {
+        // the lowest bit of a trit value picks the upper or lower half of the range
+        let colors: Vec<u32> = (0..6).map(|value| unquantize_color(value, 6)).collect();
+        assert_eq!(colors, [0, 255, 51, 204, 102, 153]);
+        let weights: Vec<u32> = (0..3).map(|value| unquantize_weight(value, 3)).collect();
+        assert_eq!(weights, [0, 32, 64]);
+        let weights: Vec<u32> = (0..4).map(|value| unquantize_weight(value, 4)).collect();
+        assert_eq!(weights, [0, 21, 43, 64]);
+        assert_eq!(unquantize_color(0xAB, 256), 0xAB);
+    }
+
+    #[test]
+    fn partitions_stay_in_range() {
+        for partitions in 1..=4 {
+            let mut used = HashSet::new();
+            for seed in 0..1024 {
+                for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+                    used.insert(select_partition(seed, x, y, partitions, true));
+                }
+            }
+            assert_eq!(used, (0..partitions).collect());
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use c02_lighting::{load_texture, process_events, process_input, Camera, Shader};
+use c02_lighting::{
+    load_texture, process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector3};
 use dx::{
@@ -52,7 +54,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (lighting_shader, cubevao, diffuse_map, specular_map, cube_positions) = {
+    let (lighting_shader, cubevao, diffuse_map, specular_map, cube_positions, vbo, lightvao) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -97,13 +99,13 @@ pub fn main() {
         ];
         // first, configure the cube's vao (and vbo)
 
-        let cubevao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
+        let cubevao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
 
-        gl::bind_vertex_array(cubevao);
+        gl::bind_vertex_array(cubevao.id());
         let stride = 8 * mem::size_of::<f32>() as i32;
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
         gl::enable_vertex_attrib_array(0);
@@ -113,10 +115,10 @@ pub fn main() {
         gl::enable_vertex_attrib_array(2);
 
         // second, configure the light's vao (vbo stays the same; the vertices are the same for the light object which is also a 3D cube)
-        let lightvao = gl::gen_vertex_array();
-        gl::bind_vertex_array(lightvao);
+        let lightvao = GlVertexArray::new();
+        gl::bind_vertex_array(lightvao.id());
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         // note that we update the lamp's position attribute's stride to reflect the updated buffer data
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
         gl::enable_vertex_attrib_array(0);
@@ -132,11 +134,11 @@ pub fn main() {
         lighting_shader.set_int("material.diffuse", 0);
         lighting_shader.set_int("material.specular", 1);
 
-        (lighting_shader, cubevao, diffuse_map, specular_map, cube_positions)
+        (lighting_shader, cubevao, diffuse_map, specular_map, cube_positions, vbo, lightvao)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (vbo, lightvao), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -203,13 +205,13 @@ pub fn main() {
 
             // bind diffuse map
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, diffuse_map);
+            gl::bind_texture(GL_TEXTURE_2D, diffuse_map.id());
             // bind specular map
             gl::active_texture(GL_TEXTURE1);
-            gl::bind_texture(GL_TEXTURE_2D, specular_map);
+            gl::bind_texture(GL_TEXTURE_2D, specular_map.id());
 
             // render containers
-            gl::bind_vertex_array(cubevao);
+            gl::bind_vertex_array(cubevao.id());
             for (i, position) in cube_positions.iter().enumerate() {
                 // calculate the model matrix for each object and pass it to shader before drawing
                 let mut model: Matrix4<f32> = Matrix4::from_translation(*position);
@@ -243,8 +245,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &cubevao);
-    // gl::delete_vertex_arrays(1, &lightvao);
-    // gl::delete_buffers(1, &vbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
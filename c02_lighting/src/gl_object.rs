@@ -0,0 +1,278 @@
+use dx::gles::core30::gl;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt,
+    panic::Location,
+    rc::Rc,
+};
+
+/// The kinds of GL objects with an owned wrapper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GlObjectKind {
+    Buffer,
+    VertexArray,
+    Texture,
+    Framebuffer,
+    Renderbuffer,
+    Program,
+}
+
+/// A GL object that has not been dropped yet, see `GlContext::live_objects`.
+#[derive(Clone, Copy, Debug)]
+pub struct LiveObject {
+    pub kind: GlObjectKind,
+    pub id: u32,
+    /// where the wrapper was created
+    pub created_at: &'static Location<'static>,
+}
+
+impl fmt::Display for LiveObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {} created at {}", self.kind, self.id, self.created_at)
+    }
+}
+
+struct ContextState {
+    live: RefCell<BTreeMap<(GlObjectKind, u32), &'static Location<'static>>>,
+    torn_down: Cell<bool>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<GlContext>> = const { RefCell::new(None) };
+}
+
+/// Handle to the GL context current on this thread, shared by every object created in it.
+///
+/// The handle is reference counted without atomics, so neither it nor the objects holding
+/// it can be sent to another thread: they are always dropped where the context is current.
+/// Call `teardown` before the context goes away to get a report of what is still alive, or
+/// keep a `teardown_guard` where the main loop never returns.
+#[derive(Clone)]
+pub struct GlContext {
+    state: Rc<ContextState>,
+}
+
+impl GlContext {
+    /// the context current on this thread; created on first use, and again after `teardown`.
+    pub fn current() -> GlContext {
+        CURRENT.with(|current| {
+            current
+                .borrow_mut()
+                .get_or_insert_with(|| GlContext {
+                    state: Rc::new(ContextState {
+                        live: RefCell::new(BTreeMap::new()),
+                        torn_down: Cell::new(false),
+                    }),
+                })
+                .clone()
+        })
+    }
+
+    /// objects created in this context and not dropped yet, ordered by kind and name
+    pub fn live_objects(&self) -> Vec<LiveObject> {
+        self.state
+            .live
+            .borrow()
+            .iter()
+            .map(|(&(kind, id), &created_at)| LiveObject { kind, id, created_at })
+            .collect()
+    }
+
+    pub fn is_torn_down(&self) -> bool {
+        self.state.torn_down.get()
+    }
+
+    /// marks the context as destroyed, once everything rendered with it has been dropped.
+    ///
+    /// In debug builds the objects still alive are printed as leaks. They go away with the
+    /// context, and dropping them afterwards no longer calls into GL.
+    pub fn teardown(&self) {
+        if self.state.torn_down.replace(true) {
+            return;
+        }
+        // the next `current` on this thread belongs to a new context
+        let _ = CURRENT.try_with(|current| {
+            let mut current = current.borrow_mut();
+            if matches!(&*current, Some(context) if Rc::ptr_eq(&context.state, &self.state)) {
+                *current = None;
+            }
+        });
+
+        if cfg!(debug_assertions) {
+            let leaked = self.live_objects();
+            if !leaked.is_empty() {
+                println!("WARNING::GL_CONTEXT: {} objects still alive at teardown", leaked.len());
+                for object in leaked {
+                    println!("    {}", object);
+                }
+            }
+        }
+    }
+
+    /// tears the context down when the guard is dropped; owners of GL objects keep it after
+    /// them so that it goes last, e.g. as the last field of the app struct.
+    pub fn teardown_guard(&self) -> TeardownGuard {
+        TeardownGuard { context: self.clone() }
+    }
+
+    fn track(self, kind: GlObjectKind, id: u32, created_at: &'static Location<'static>) -> GlContext {
+        self.state.live.borrow_mut().insert((kind, id), created_at);
+        self
+    }
+
+    // forgets the object; true if it still has to be deleted
+    fn release(&self, kind: GlObjectKind, id: u32) -> bool {
+        self.state.live.borrow_mut().remove(&(kind, id));
+        !self.state.torn_down.get()
+    }
+}
+
+impl fmt::Debug for GlContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GlContext({} live objects{})",
+            self.state.live.borrow().len(),
+            if self.is_torn_down() { ", torn down" } else { "" }
+        )
+    }
+}
+
+/// Calls `GlContext::teardown` when dropped, see `GlContext::teardown_guard`.
+#[must_use]
+pub struct TeardownGuard {
+    context: GlContext,
+}
+
+impl Drop for TeardownGuard {
+    fn drop(&mut self) {
+        self.context.teardown();
+    }
+}
+
+impl fmt::Debug for TeardownGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TeardownGuard({:?})", self.context)
+    }
+}
+
+macro_rules! gl_object {
+    ($(#[$doc:meta])* $name:ident, $kind:ident, $gen:expr, $delete:expr) => {
+        $(#[$doc])*
+        pub struct $name {
+            id: u32,
+            context: GlContext,
+        }
+
+        impl $name {
+            #[track_caller]
+            pub fn new() -> Self {
+                $name::from_raw($gen)
+            }
+
+            /// takes ownership of an object created with raw GL calls
+            #[track_caller]
+            pub fn from_raw(id: u32) -> Self {
+                $name {
+                    id,
+                    context: GlContext::current().track(GlObjectKind::$kind, id, Location::caller()),
+                }
+            }
+
+            /// name of the GL object
+            pub fn id(&self) -> u32 {
+                self.id
+            }
+
+            pub fn context(&self) -> &GlContext {
+                &self.context
+            }
+        }
+
+        impl Default for $name {
+            #[track_caller]
+            fn default() -> Self {
+                $name::new()
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.id)
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                if self.context.release(GlObjectKind::$kind, self.id) {
+                    $delete(self.id);
+                }
+            }
+        }
+    };
+}
+
+gl_object!(
+    /// An owned buffer object, deleted on drop.
+    GlBuffer,
+    Buffer,
+    gl::gen_buffer(),
+    |id| gl::delete_buffers(&[id])
+);
+
+gl_object!(
+    /// An owned vertex array object, deleted on drop.
+    GlVertexArray,
+    VertexArray,
+    gl::gen_vertex_array(),
+    |id| gl::delete_vertex_arrays(&[id])
+);
+
+gl_object!(
+    /// An owned texture object of any target, deleted on drop.
+    GlTexture,
+    Texture,
+    gl::gen_texture(),
+    |id| gl::delete_textures(&[id])
+);
+
+gl_object!(
+    /// An owned framebuffer object, deleted on drop.
+    GlFramebuffer,
+    Framebuffer,
+    gl::gen_framebuffer(),
+    |id| gl::delete_framebuffers(&[id])
+);
+
+gl_object!(
+    /// An owned renderbuffer object, deleted on drop.
+    GlRenderbuffer,
+    Renderbuffer,
+    gl::gen_renderbuffer(),
+    |id| gl::delete_renderbuffers(&[id])
+);
+
+gl_object!(
+    /// An owned shader program, deleted on drop.
+    GlProgram,
+    Program,
+    gl::create_program(),
+    gl::delete_program
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::GlContext;
+
+    #[test]
+    fn teardown_guard_tears_the_context_down_when_dropped() {
+        let context = GlContext::current();
+        let guard = context.teardown_guard();
+        assert!(!context.is_torn_down());
+        drop(guard);
+        assert!(context.is_torn_down());
+        // the thread gets a fresh context afterwards
+        assert!(!GlContext::current().is_torn_down());
+    }
+}
@@ -0,0 +1,170 @@
+use cgmath::prelude::*;
+use cgmath::{Deg, Matrix4, Point3, Rad};
+use vertex_format::VertexFormat;
+
+use super::bounds::BoundingSphere;
+use super::simplify::simplify;
+
+/// How `Mesh::generate_lods` builds the LOD chain.
+#[derive(Clone, Copy, Debug)]
+pub struct LodOptions {
+    /// maximum number of levels besides the full resolution mesh
+    pub levels: usize,
+    /// index count of every level relative to the previous one
+    pub reduction: f32,
+    /// largest simplification error allowed, relative to the radius of the mesh's bounding sphere
+    pub max_error: f32,
+}
+
+impl Default for LodOptions {
+    fn default() -> Self {
+        LodOptions {
+            levels: 4,
+            reduction: 0.5,
+            max_error: 0.05,
+        }
+    }
+}
+
+/// A simplified version of a mesh, sharing the mesh's vertices.
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    /// simplification error in model units
+    pub error: f32,
+    // offset of the indices in the mesh's element buffer, in indices
+    pub(crate) offset: usize,
+}
+
+impl Lod {
+    pub fn new(indices: Vec<u32>, error: f32) -> Self {
+        Lod {
+            indices,
+            error,
+            offset: 0,
+        }
+    }
+}
+
+/// Simplifies a mesh repeatedly into a chain of LODs, coarsest last. The chain ends early
+/// when a level can't get meaningfully smaller within the error bound.
+pub fn build_lods<V: VertexFormat>(vertices: &[V], indices: &[u32], options: &LodOptions) -> Vec<Lod> {
+    let radius = BoundingSphere::from_points(vertices.iter().map(|vertex| Point3::from_vec(vertex.position()))).radius;
+    let max_error = options.max_error * radius.max(0.0);
+
+    let mut lods: Vec<Lod> = Vec::new();
+    for _ in 0..options.levels {
+        let previous = lods.last().map_or(indices, |lod| &lod.indices);
+        let target = (previous.len() as f32 * options.reduction) as usize;
+        let simplified = simplify(vertices, previous, target, max_error);
+        // not worth another level
+        if simplified.indices.len() as f32 > previous.len() as f32 * 0.9 || simplified.indices.is_empty() {
+            break;
+        }
+        // errors accumulate along the chain
+        let error = lods.last().map_or(0.0, |lod| lod.error) + simplified.error;
+        lods.push(Lod::new(simplified.indices, error));
+    }
+    lods
+}
+
+/// What LOD selection needs to know about the camera.
+#[derive(Clone, Copy, Debug)]
+pub struct LodCamera {
+    pub position: Point3<f32>,
+    /// pixels covered by one world unit at distance 1
+    pub projection_scale: f32,
+}
+
+impl LodCamera {
+    pub fn new(position: Point3<f32>, fov_y: Deg<f32>, viewport_height: f32) -> Self {
+        let fov_y: Rad<f32> = fov_y.into();
+        LodCamera {
+            position,
+            projection_scale: viewport_height / (2.0 * (fov_y.0 / 2.0).tan()),
+        }
+    }
+
+    /// size in pixels of a length `error` seen at `distance`
+    pub fn screen_size(&self, error: f32, distance: f32) -> f32 {
+        error * self.projection_scale / distance.max(1e-4)
+    }
+}
+
+/// Picks the coarsest LOD whose error covers at most `threshold` pixels on screen.
+/// With hysteresis, an object keeps its current LOD until the error is a fraction off the
+/// threshold in either direction, so objects near the switching distance don't pop back
+/// and forth.
+#[derive(Clone, Copy, Debug)]
+pub struct LodSelector {
+    /// largest screen-space error in pixels
+    pub threshold: f32,
+    /// relative margin around the threshold, 0 to disable
+    pub hysteresis: f32,
+}
+
+impl Default for LodSelector {
+    fn default() -> Self {
+        LodSelector {
+            threshold: 1.0,
+            hysteresis: 0.2,
+        }
+    }
+}
+
+impl LodSelector {
+    /// `errors` are the errors of every level in world units, starting with 0 for the full
+    /// resolution level; `previous` is the level picked for the object last frame.
+    pub fn select(&self, errors: &[f32], distance: f32, camera: &LodCamera, previous: Option<usize>) -> usize {
+        self.select_scaled(errors, 1.0, distance, camera, previous)
+    }
+
+    // `select` with every error multiplied by `scale`
+    fn select_scaled(
+        &self,
+        errors: &[f32],
+        scale: f32,
+        distance: f32,
+        camera: &LodCamera,
+        previous: Option<usize>,
+    ) -> usize {
+        let mut lod = 0;
+        for (level, &error) in errors.iter().enumerate().skip(1) {
+            let threshold = match previous {
+                // staying at (or refining to) a level already good enough last frame
+                Some(previous) if level <= previous => self.threshold * (1.0 + self.hysteresis),
+                // coarsening
+                Some(_) => self.threshold * (1.0 - self.hysteresis),
+                None => self.threshold,
+            };
+            if camera.screen_size(error * scale, distance) <= threshold {
+                lod = level;
+            }
+        }
+        lod
+    }
+
+    /// `select` for an object with the given model-space bounds placed by `world`: the
+    /// distance is measured to the nearest point of the bounding sphere and the errors are
+    /// scaled like the sphere.
+    pub fn select_for(
+        &self,
+        errors: &[f32],
+        bounds: &BoundingSphere,
+        world: &Matrix4<f32>,
+        camera: &LodCamera,
+        previous: Option<usize>,
+    ) -> usize {
+        if bounds.is_empty() {
+            return 0;
+        }
+        let world_bounds = bounds.transform(world);
+        let scale = if bounds.radius > 0.0 {
+            world_bounds.radius / bounds.radius
+        } else {
+            1.0
+        };
+        let distance = camera.position.distance(world_bounds.center) - world_bounds.radius;
+        self.select_scaled(errors, scale, distance, camera, previous)
+    }
+}
@@ -0,0 +1,522 @@
+use dx::gles::{core30::gl, enums::*};
+use std::{error::Error, fmt};
+
+use super::gl_object::{GlFramebuffer, GlRenderbuffer, GlTexture};
+use super::texture::Filter;
+
+/// Why a `RenderTarget` could not be created, mostly the status of
+/// `glCheckFramebufferStatus`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenderTargetError {
+    /// an attachment can't be rendered into, e.g. a float format without
+    /// `EXT_color_buffer_float`
+    IncompleteAttachment,
+    /// nothing is attached
+    MissingAttachment,
+    /// the attachments differ in size (OpenGL ES 2.0)
+    IncompleteDimensions,
+    /// the attachments differ in their number of samples
+    IncompleteMultisample,
+    /// the driver doesn't support this combination of formats
+    Unsupported,
+    /// any other status of `glCheckFramebufferStatus`
+    Status(u32),
+    /// a format or sample count this context doesn't have
+    Format(String),
+}
+
+impl RenderTargetError {
+    fn from_status(status: u32) -> Option<Self> {
+        match status {
+            GL_FRAMEBUFFER_COMPLETE => None,
+            GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Some(RenderTargetError::IncompleteAttachment),
+            GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Some(RenderTargetError::MissingAttachment),
+            GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS => Some(RenderTargetError::IncompleteDimensions),
+            GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Some(RenderTargetError::IncompleteMultisample),
+            GL_FRAMEBUFFER_UNSUPPORTED => Some(RenderTargetError::Unsupported),
+            status => Some(RenderTargetError::Status(status)),
+        }
+    }
+}
+
+impl fmt::Display for RenderTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderTargetError::IncompleteAttachment => write!(f, "framebuffer attachment can't be rendered into"),
+            RenderTargetError::MissingAttachment => write!(f, "framebuffer has no attachments"),
+            RenderTargetError::IncompleteDimensions => write!(f, "framebuffer attachments differ in size"),
+            RenderTargetError::IncompleteMultisample => {
+                write!(f, "framebuffer attachments differ in their number of samples")
+            }
+            RenderTargetError::Unsupported => write!(f, "framebuffer format combination is not supported"),
+            RenderTargetError::Status(status) => write!(f, "framebuffer is not complete: 0x{:x}", status),
+            RenderTargetError::Format(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for RenderTargetError {}
+
+// OpenGL ES 2.0 only, not part of the ES 3.0 enums
+const GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS: u32 = 0x8CD9;
+
+/// How an attachment is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AttachmentKind {
+    /// a texture that can be sampled after rendering
+    Texture,
+    /// a renderbuffer, for attachments that are only rendered into or resolved
+    Renderbuffer,
+}
+
+/// Declaration of one attachment of a `RenderTarget`. Depth, depth-stencil and stencil
+/// formats go to their attachment point, see `RenderTargetBuilder::depth`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attachment {
+    /// sized internal format, e.g. `GL_RGBA16F` or `GL_DEPTH24_STENCIL8`
+    pub internal_format: u32,
+    pub kind: AttachmentKind,
+    /// minification and magnification filter of texture attachments
+    pub filter: Filter,
+}
+
+impl Attachment {
+    pub fn texture(internal_format: u32) -> Self {
+        Attachment {
+            internal_format,
+            kind: AttachmentKind::Texture,
+            filter: Filter::Linear,
+        }
+    }
+
+    pub fn renderbuffer(internal_format: u32) -> Self {
+        Attachment {
+            internal_format,
+            kind: AttachmentKind::Renderbuffer,
+            filter: Filter::Linear,
+        }
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// Attachments of a `RenderTarget`, created with `RenderTarget::builder()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderTargetBuilder {
+    pub colors: Vec<Attachment>,
+    /// depth or depth-stencil attachment
+    pub depth: Option<Attachment>,
+    /// separate stencil attachment; a depth-stencil format in `depth` doesn't need one
+    pub stencil: Option<Attachment>,
+    /// samples per pixel; above 1 every attachment is a renderbuffer, since OpenGL ES 3.0
+    /// has no multisampled textures. Resolve into a single sampled target to read it.
+    pub samples: u32,
+}
+
+impl RenderTargetBuilder {
+    /// adds color attachment `GL_COLOR_ATTACHMENT0 + n` for the nth call
+    pub fn color(mut self, attachment: Attachment) -> Self {
+        self.colors.push(attachment);
+        self
+    }
+
+    pub fn depth(mut self, attachment: Attachment) -> Self {
+        self.depth = Some(attachment);
+        self
+    }
+
+    pub fn stencil(mut self, attachment: Attachment) -> Self {
+        self.stencil = Some(attachment);
+        self
+    }
+
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// allocates the attachments and checks the framebuffer is complete
+    pub fn build(&self, width: u32, height: u32) -> Result<RenderTarget, RenderTargetError> {
+        let max_color_attachments = gl::get_integerv(GL_MAX_COLOR_ATTACHMENTS) as usize;
+        if self.colors.len() > max_color_attachments {
+            return Err(RenderTargetError::Format(format!(
+                "{} color attachments, this context has {}",
+                self.colors.len(),
+                max_color_attachments
+            )));
+        }
+        if self.samples > 1 {
+            let max_samples = gl::get_integerv(GL_MAX_SAMPLES) as u32;
+            if self.samples > max_samples {
+                return Err(RenderTargetError::Format(format!(
+                    "{} samples, this context has at most {}",
+                    self.samples, max_samples
+                )));
+            }
+        }
+        for color in &self.colors {
+            if attachment_point(color.internal_format) != GL_COLOR_ATTACHMENT0 {
+                return Err(RenderTargetError::Format(format!(
+                    "0x{:x} is not a color format",
+                    color.internal_format
+                )));
+            }
+        }
+        for (attachment, name) in [(self.depth, "depth"), (self.stencil, "stencil")] {
+            if let Some(attachment) = attachment {
+                if attachment_point(attachment.internal_format) == GL_COLOR_ATTACHMENT0 {
+                    return Err(RenderTargetError::Format(format!(
+                        "0x{:x} is not a {} format",
+                        attachment.internal_format, name
+                    )));
+                }
+            }
+        }
+
+        let mut target = RenderTarget {
+            framebuffer: GlFramebuffer::new(),
+            builder: self.clone(),
+            width: 0,
+            height: 0,
+            colors: Vec::new(),
+            depth: None,
+            stencil: None,
+            complete: false,
+        };
+        target.allocate(width, height)?;
+        Ok(target)
+    }
+}
+
+#[derive(Debug)]
+enum Storage {
+    Texture(GlTexture),
+    Renderbuffer(GlRenderbuffer),
+}
+
+impl Storage {
+    fn allocate(attachment: &Attachment, samples: u32, width: u32, height: u32) -> Result<Self, RenderTargetError> {
+        let (width, height) = (width as i32, height as i32);
+        if attachment.kind == AttachmentKind::Renderbuffer || samples > 1 {
+            let renderbuffer = GlRenderbuffer::new();
+            gl::bind_renderbuffer(GL_RENDERBUFFER, renderbuffer.id());
+            if samples > 1 {
+                gl::renderbuffer_storage_multisample(
+                    GL_RENDERBUFFER,
+                    samples as i32,
+                    attachment.internal_format,
+                    width,
+                    height,
+                );
+            } else {
+                gl::renderbuffer_storage(GL_RENDERBUFFER, attachment.internal_format, width, height);
+            }
+            gl::bind_renderbuffer(GL_RENDERBUFFER, 0);
+            return Ok(Storage::Renderbuffer(renderbuffer));
+        }
+
+        let (format, pixel_type) = pixel_format(attachment.internal_format).ok_or_else(|| {
+            RenderTargetError::Format(format!(
+                "0x{:x} can't be used for texture attachments",
+                attachment.internal_format
+            ))
+        })?;
+        let filter = match attachment.filter {
+            Filter::Nearest => GL_NEAREST,
+            Filter::Linear => GL_LINEAR,
+        };
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture.id());
+        gl::empty_tex_image_2d(
+            GL_TEXTURE_2D,
+            0,
+            attachment.internal_format as i32,
+            width,
+            height,
+            0,
+            format,
+            pixel_type,
+        );
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, filter as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, filter as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
+        gl::bind_texture(GL_TEXTURE_2D, 0);
+        Ok(Storage::Texture(texture))
+    }
+
+    /// attaches to the bound `GL_FRAMEBUFFER`
+    fn attach(&self, attachment_point: u32) {
+        match self {
+            Storage::Texture(texture) => {
+                gl::framebuffer_texture_2d(GL_FRAMEBUFFER, attachment_point, GL_TEXTURE_2D, texture.id(), 0)
+            }
+            Storage::Renderbuffer(renderbuffer) => {
+                gl::framebuffer_renderbuffer(GL_FRAMEBUFFER, attachment_point, GL_RENDERBUFFER, renderbuffer.id())
+            }
+        }
+    }
+
+    fn texture(&self) -> Option<u32> {
+        match self {
+            Storage::Texture(texture) => Some(texture.id()),
+            Storage::Renderbuffer(_) => None,
+        }
+    }
+}
+
+/// A framebuffer object with owned attachments, reallocated when it is resized.
+///
+/// ```ignore
+/// let mut scene = RenderTarget::builder()
+///     .color(Attachment::texture(GL_RGBA16F))
+///     .depth(Attachment::renderbuffer(GL_DEPTH24_STENCIL8))
+///     .build(width, height)?;
+/// // on WindowEvent::Resized
+/// scene.resize(new_width, new_height)?;
+/// // every frame
+/// scene.bind();
+/// draw_scene();
+/// RenderTarget::bind_default(width, height);
+/// scene.bind_color_texture(0, 0);
+/// ```
+#[derive(Debug)]
+pub struct RenderTarget {
+    framebuffer: GlFramebuffer,
+    builder: RenderTargetBuilder,
+    pub width: u32,
+    pub height: u32,
+    colors: Vec<Storage>,
+    depth: Option<Storage>,
+    stencil: Option<Storage>,
+    // the last allocation gave a complete framebuffer; `resize` retries until it does
+    complete: bool,
+}
+
+impl RenderTarget {
+    pub fn builder() -> RenderTargetBuilder {
+        RenderTargetBuilder::default()
+    }
+
+    /// name of the GL framebuffer
+    pub fn id(&self) -> u32 {
+        self.framebuffer.id()
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.builder.samples.max(1)
+    }
+
+    /// renders into the target, with the viewport covering all of it
+    pub fn bind(&self) {
+        gl::bind_framebuffer(GL_FRAMEBUFFER, self.framebuffer.id());
+        gl::viewport(0, 0, self.width as i32, self.height as i32);
+    }
+
+    /// renders into the window again, with the viewport covering `width` x `height`
+    pub fn bind_default(width: u32, height: u32) {
+        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+        gl::viewport(0, 0, width as i32, height as i32);
+    }
+
+    /// texture name of color attachment `index`; `None` for renderbuffers
+    pub fn color_texture(&self, index: usize) -> Option<u32> {
+        self.colors.get(index).and_then(Storage::texture)
+    }
+
+    /// texture name of the depth attachment; `None` for renderbuffers
+    pub fn depth_texture(&self) -> Option<u32> {
+        self.depth.as_ref().and_then(Storage::texture)
+    }
+
+    /// binds color attachment `index` to texture unit `unit`
+    pub fn bind_color_texture(&self, index: usize, unit: u32) {
+        let texture = self
+            .color_texture(index)
+            .unwrap_or_else(|| panic!("color attachment {} is not a texture", index));
+        gl::active_texture(GL_TEXTURE0 + unit);
+        gl::bind_texture(GL_TEXTURE_2D, texture);
+    }
+
+    /// reallocates the attachments at the new size; does nothing if the size didn't change
+    /// and the target is complete, so it can be called every frame with the window size and
+    /// keeps reporting a failed reallocation. The zero size of minimized windows becomes 1x1,
+    /// empty attachments are never complete.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), RenderTargetError> {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.complete && (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+        self.allocate(width, height)
+    }
+
+    /// copies every color attachment into the one of the same index of `target` and depth
+    /// and stencil where both have them, resolving multisampled targets. Both must have
+    /// the same size.
+    pub fn resolve(&self, target: &RenderTarget) {
+        let mut mask = 0;
+        if self.depth.is_some() && target.depth.is_some() {
+            mask |= GL_DEPTH_BUFFER_BIT;
+        }
+        if self.stencil.is_some() && target.stencil.is_some() {
+            mask |= GL_STENCIL_BUFFER_BIT;
+        }
+        gl::bind_framebuffer(GL_READ_FRAMEBUFFER, self.framebuffer.id());
+        gl::bind_framebuffer(GL_DRAW_FRAMEBUFFER, target.framebuffer.id());
+        let colors = self.colors.len().min(target.colors.len());
+        for index in 0..colors {
+            let attachment = GL_COLOR_ATTACHMENT0 + index as u32;
+            gl::read_buffer(attachment);
+            let mut draw_buffers = vec![GL_NONE; index + 1];
+            draw_buffers[index] = attachment;
+            gl::draw_buffers(&draw_buffers);
+            // depth and stencil only once
+            let mask = if index == 0 {
+                mask | GL_COLOR_BUFFER_BIT
+            } else {
+                GL_COLOR_BUFFER_BIT
+            };
+            self.blit(target.width, target.height, mask, GL_NEAREST);
+        }
+        if colors == 0 && mask != 0 {
+            self.blit(target.width, target.height, mask, GL_NEAREST);
+        }
+        // back to the state allocate() left behind
+        gl::read_buffer(if self.colors.is_empty() {
+            GL_NONE
+        } else {
+            GL_COLOR_ATTACHMENT0
+        });
+        gl::bind_framebuffer(GL_DRAW_FRAMEBUFFER, target.framebuffer.id());
+        target.set_draw_buffers();
+        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+    }
+
+    /// copies color attachment 0 to the window, scaled to `width` x `height`
+    pub fn blit_to_default(&self, width: u32, height: u32, filter: Filter) {
+        let filter = match filter {
+            Filter::Nearest => GL_NEAREST,
+            Filter::Linear => GL_LINEAR,
+        };
+        gl::bind_framebuffer(GL_READ_FRAMEBUFFER, self.framebuffer.id());
+        gl::bind_framebuffer(GL_DRAW_FRAMEBUFFER, 0);
+        self.blit(width, height, GL_COLOR_BUFFER_BIT, filter);
+        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+    }
+
+    fn blit(&self, width: u32, height: u32, mask: u32, filter: u32) {
+        gl::blit_framebuffer(
+            0,
+            0,
+            self.width as i32,
+            self.height as i32,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            mask,
+            filter,
+        );
+    }
+
+    // the size and attachments only change together: if an attachment can't be created,
+    // the target keeps its old ones
+    fn allocate(&mut self, width: u32, height: u32) -> Result<(), RenderTargetError> {
+        let builder = &self.builder;
+        let samples = builder.samples;
+        let colors = builder
+            .colors
+            .iter()
+            .map(|color| Storage::allocate(color, samples, width, height))
+            .collect::<Result<Vec<_>, _>>()?;
+        let depth = match &builder.depth {
+            Some(depth) => Some(Storage::allocate(depth, samples, width, height)?),
+            None => None,
+        };
+        let stencil = match &builder.stencil {
+            Some(stencil) => Some(Storage::allocate(stencil, samples, width, height)?),
+            None => None,
+        };
+
+        gl::bind_framebuffer(GL_FRAMEBUFFER, self.framebuffer.id());
+        for (index, color) in colors.iter().enumerate() {
+            color.attach(GL_COLOR_ATTACHMENT0 + index as u32);
+        }
+        if let (Some(storage), Some(attachment)) = (&depth, &builder.depth) {
+            storage.attach(attachment_point(attachment.internal_format));
+        }
+        if let (Some(storage), Some(attachment)) = (&stencil, &builder.stencil) {
+            storage.attach(attachment_point(attachment.internal_format));
+        }
+        self.set_draw_buffers();
+        if colors.is_empty() {
+            gl::read_buffer(GL_NONE);
+        }
+        let status = gl::check_framebuffer_status(GL_FRAMEBUFFER);
+        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+
+        // the old attachments are deleted here, after nothing refers to them anymore
+        self.colors = colors;
+        self.depth = depth;
+        self.stencil = stencil;
+        self.width = width;
+        self.height = height;
+        let error = RenderTargetError::from_status(status);
+        self.complete = error.is_none();
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// draws into every color attachment, or none for depth only targets; the framebuffer
+    /// must be bound to `GL_DRAW_FRAMEBUFFER`
+    fn set_draw_buffers(&self) {
+        let draw_buffers: Vec<u32> = (0..self.builder.colors.len() as u32)
+            .map(|index| GL_COLOR_ATTACHMENT0 + index)
+            .collect();
+        if draw_buffers.is_empty() {
+            gl::draw_buffers(&[GL_NONE]);
+        } else {
+            gl::draw_buffers(&draw_buffers);
+        }
+    }
+}
+
+/// the attachment point of a sized internal format
+fn attachment_point(internal_format: u32) -> u32 {
+    match internal_format {
+        GL_DEPTH_COMPONENT16 | GL_DEPTH_COMPONENT24 | GL_DEPTH_COMPONENT32F => GL_DEPTH_ATTACHMENT,
+        GL_DEPTH24_STENCIL8 | GL_DEPTH32F_STENCIL8 => GL_DEPTH_STENCIL_ATTACHMENT,
+        GL_STENCIL_INDEX8 => GL_STENCIL_ATTACHMENT,
+        _ => GL_COLOR_ATTACHMENT0,
+    }
+}
+
+/// pixel format and type `glTexImage2D` accepts with a color-renderable or depth internal
+/// format
+fn pixel_format(internal_format: u32) -> Option<(u32, u32)> {
+    Some(match internal_format {
+        GL_R8 => (GL_RED, GL_UNSIGNED_BYTE),
+        GL_RG8 => (GL_RG, GL_UNSIGNED_BYTE),
+        GL_RGB8 | GL_RGB => (GL_RGB, GL_UNSIGNED_BYTE),
+        GL_RGBA8 | GL_SRGB8_ALPHA8 | GL_RGBA => (GL_RGBA, GL_UNSIGNED_BYTE),
+        GL_RGB10_A2 => (GL_RGBA, GL_UNSIGNED_INT_2_10_10_10_REV),
+        GL_R16F => (GL_RED, GL_HALF_FLOAT),
+        GL_RG16F => (GL_RG, GL_HALF_FLOAT),
+        GL_RGB16F | GL_R11F_G11F_B10F => (GL_RGB, GL_HALF_FLOAT),
+        GL_RGBA16F => (GL_RGBA, GL_HALF_FLOAT),
+        GL_R32F => (GL_RED, GL_FLOAT),
+        GL_RG32F => (GL_RG, GL_FLOAT),
+        GL_RGBA32F => (GL_RGBA, GL_FLOAT),
+        GL_DEPTH_COMPONENT16 => (GL_DEPTH_COMPONENT, GL_UNSIGNED_SHORT),
+        GL_DEPTH_COMPONENT24 => (GL_DEPTH_COMPONENT, GL_UNSIGNED_INT),
+        GL_DEPTH_COMPONENT32F => (GL_DEPTH_COMPONENT, GL_FLOAT),
+        GL_DEPTH24_STENCIL8 => (GL_DEPTH_STENCIL, GL_UNSIGNED_INT_24_8),
+        GL_DEPTH32F_STENCIL8 => (GL_DEPTH_STENCIL, GL_FLOAT_32_UNSIGNED_INT_24_8_REV),
+        _ => return None,
+    })
+}
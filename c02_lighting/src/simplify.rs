@@ -0,0 +1,509 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+};
+use vertex_format::VertexFormat;
+
+/// Result of `simplify`.
+#[derive(Clone, Debug)]
+pub struct Simplified {
+    /// triangles of the simplified mesh, indexing the original vertices
+    pub indices: Vec<u32>,
+    /// largest distance in model units of a kept vertex to the original triangles it
+    /// replaces; 0 if nothing was collapsed
+    pub error: f32,
+}
+
+/// Error quadric (Garland & Heckbert): the sum of squared distances to a set of planes,
+/// stored as the upper triangle of a symmetric 4x4 matrix.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// plane `normal . p + d = 0`
+    fn from_plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Quadric([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|value| value * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            *a += b;
+        }
+    }
+
+    fn sum(&self, other: &Quadric) -> Quadric {
+        let mut sum = *self;
+        sum.add(other);
+        sum
+    }
+
+    /// squared distance of `p` to the planes, weighted
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+// candidate collapse of vertex `from` onto vertex `to`, valid while neither has changed
+struct Collapse {
+    // area weighted quadric error: collapses are done cheapest first
+    cost: f64,
+    // largest squared distance of `to` to the original triangles merged into either vertex
+    error: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // reversed: BinaryHeap is a max-heap and the cheapest collapse comes first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+// what collapses are measured with, per vertex
+struct Metric {
+    positions: Vec<Vector3<f64>>,
+    // plane (normal, d) of every triangle
+    planes: Vec<(Vector3<f64>, f64)>,
+    // area weighted plane quadrics of the triangles merged into every vertex
+    quadrics: Vec<Quadric>,
+    // the original triangles merged into every vertex, sorted
+    merged: Vec<Vec<u32>>,
+    // bumped when the vertex changes, invalidating its queued collapses
+    versions: Vec<u32>,
+}
+
+impl Metric {
+    fn collapse(&self, from: u32, to: u32) -> Collapse {
+        let (from_idx, to_idx) = (from as usize, to as usize);
+        let p = self.positions[to_idx];
+        let error = self.merged[from_idx]
+            .iter()
+            .chain(&self.merged[to_idx])
+            .map(|&t| {
+                let (normal, d) = self.planes[t as usize];
+                let distance = normal.dot(p) + d;
+                distance * distance
+            })
+            .fold(0.0, f64::max);
+        Collapse {
+            cost: self.quadrics[from_idx].sum(&self.quadrics[to_idx]).error(p).max(0.0),
+            error,
+            from,
+            to,
+            from_version: self.versions[from_idx],
+            to_version: self.versions[to_idx],
+        }
+    }
+
+    fn merge(&mut self, from: usize, to: usize) {
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        let merged = std::mem::take(&mut self.merged[from]);
+        self.merged[to].extend(merged);
+        self.merged[to].sort_unstable();
+        self.merged[to].dedup();
+        self.versions[to] += 1;
+    }
+}
+
+/// Simplifies a triangle mesh with quadric error metrics until it has at most
+/// `target_index_count` indices, or until no edge collapse is left that keeps every
+/// vertex within `max_error` (in model units) of the original triangles it replaces.
+///
+/// Vertices are collapsed onto one of their neighbours (half-edge collapses), so the
+/// result indexes the original vertex buffer and every LOD can share it. Vertices on an
+/// attribute seam (several vertices at one position) are never removed, and vertices on a
+/// border of an open mesh only where the border runs straight through them, by collapsing
+/// along it. This keeps outlines and texture seams intact at the cost of limiting how far
+/// heavily seamed meshes can be reduced. Collapses that would flip a triangle are rejected.
+pub fn simplify<V: VertexFormat>(
+    vertices: &[V],
+    indices: &[u32],
+    target_index_count: usize,
+    max_error: f32,
+) -> Simplified {
+    let vertex_count = vertices.len();
+    let positions: Vec<Vector3<f64>> = vertices
+        .iter()
+        .map(|vertex| vertex.position().cast().unwrap())
+        .collect();
+
+    // vertices sharing a position are welded for border detection and locked
+    let mut locked = vec![false; vertex_count];
+    let mut canonical: Vec<u32> = (0..vertex_count as u32).collect();
+    let mut first_at: HashMap<[u32; 3], u32> = HashMap::new();
+    for (idx, vertex) in vertices.iter().enumerate() {
+        let p = vertex.position();
+        match first_at.entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]) {
+            Entry::Occupied(first) => {
+                canonical[idx] = *first.get();
+                locked[idx] = true;
+                locked[*first.get() as usize] = true;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(idx as u32);
+            }
+        }
+    }
+
+    let mut triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|tri| [tri[0], tri[1], tri[2]])
+        .filter(|tri| tri[0] != tri[1] && tri[1] != tri[2] && tri[2] != tri[0])
+        .collect();
+
+    let mut metric = Metric {
+        planes: Vec::with_capacity(triangles.len()),
+        quadrics: vec![Quadric::default(); vertex_count],
+        merged: vec![Vec::new(); vertex_count],
+        versions: vec![0; vertex_count],
+        positions,
+    };
+    let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (t, tri) in triangles.iter().enumerate() {
+        let [p0, p1, p2] = tri.map(|idx| metric.positions[idx as usize]);
+        let normal = (p1 - p0).cross(p2 - p0);
+        let double_area = normal.magnitude();
+        let normal = if double_area > 0.0 {
+            normal / double_area
+        } else {
+            normal
+        };
+        metric.planes.push((normal, -normal.dot(p0)));
+        for &idx in tri {
+            // degenerate triangles have no plane to stay close to
+            if double_area > 0.0 {
+                let quadric = Quadric::from_plane(normal, -normal.dot(p0), double_area * 0.5);
+                metric.quadrics[idx as usize].add(&quadric);
+                metric.merged[idx as usize].push(t as u32);
+            }
+            vertex_triangles[idx as usize].push(t);
+        }
+    }
+
+    let mut heap = BinaryHeap::new();
+    for tri in &triangles {
+        for corner in 0..3 {
+            let (a, b) = (tri[corner], tri[(corner + 1) % 3]);
+            if !locked[a as usize] {
+                heap.push(metric.collapse(a, b));
+            }
+            if !locked[b as usize] {
+                heap.push(metric.collapse(b, a));
+            }
+        }
+    }
+
+    let max_squared_error = max_error as f64 * max_error as f64;
+    let target_triangles = target_index_count / 3;
+    let mut removed = vec![false; triangles.len()];
+    let mut live = triangles.len();
+    let mut collapsed = vec![false; vertex_count];
+    let mut error: f64 = 0.0;
+
+    while live > target_triangles {
+        let collapse = match heap.pop() {
+            Some(collapse) => collapse,
+            None => break,
+        };
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if collapsed[from]
+            || collapsed[to]
+            || metric.versions[from] != collapse.from_version
+            || metric.versions[to] != collapse.to_version
+        {
+            continue;
+        }
+        // ordered by cost, not error: a more expensive collapse may still be within the bound
+        if collapse.error > max_squared_error {
+            continue;
+        }
+        let around = &vertex_triangles[from];
+        if !border_allows(
+            &triangles,
+            &removed,
+            around,
+            &canonical,
+            &metric.positions,
+            collapse.from,
+            collapse.to,
+        ) || flips(
+            &triangles,
+            &removed,
+            around,
+            &metric.positions,
+            collapse.from,
+            collapse.to,
+        ) {
+            continue;
+        }
+
+        // triangles sharing the edge disappear, the others move to `to`
+        for t in vertex_triangles[from].clone() {
+            if removed[t] {
+                continue;
+            }
+            if triangles[t].contains(&collapse.to) {
+                removed[t] = true;
+                live -= 1;
+            } else {
+                for idx in triangles[t].iter_mut() {
+                    if *idx == collapse.from {
+                        *idx = collapse.to;
+                    }
+                }
+                vertex_triangles[to].push(t);
+            }
+        }
+        metric.merge(from, to);
+        collapsed[from] = true;
+        error = error.max(collapse.error);
+
+        // new candidates around the merged vertex
+        vertex_triangles[to].retain(|&t| !removed[t]);
+        for &t in &vertex_triangles[to] {
+            for &other in &triangles[t] {
+                if other == collapse.to {
+                    continue;
+                }
+                if !locked[other as usize] {
+                    heap.push(metric.collapse(other, collapse.to));
+                }
+                if !locked[to] {
+                    heap.push(metric.collapse(collapse.to, other));
+                }
+            }
+        }
+    }
+
+    let indices = triangles
+        .iter()
+        .zip(&removed)
+        .filter(|(_, &removed)| !removed)
+        .flat_map(|(tri, _)| tri.iter().cloned())
+        .collect();
+    Simplified {
+        indices,
+        error: error.sqrt() as f32,
+    }
+}
+
+/// false if `from` is on a border (edges used by a single triangle) that doesn't run
+/// straight from `to` through `from`, where collapsing would change the outline
+fn border_allows(
+    triangles: &[[u32; 3]],
+    removed: &[bool],
+    around: &[usize],
+    canonical: &[u32],
+    positions: &[Vector3<f64>],
+    from: u32,
+    to: u32,
+) -> bool {
+    // the other end of every edge at `from` and the number of triangles using it, with
+    // vertices at the same position welded
+    let mut edges: Vec<(u32, u32)> = Vec::new();
+    for &t in around.iter().filter(|&&t| !removed[t]) {
+        for &other in triangles[t].iter().filter(|&&idx| idx != from) {
+            let other = canonical[other as usize];
+            match edges.iter_mut().find(|(idx, _)| *idx == other) {
+                Some((_, uses)) => *uses += 1,
+                None => edges.push((other, 1)),
+            }
+        }
+    }
+    let border: Vec<u32> = edges
+        .iter()
+        .filter(|(_, uses)| *uses == 1)
+        .map(|&(idx, _)| idx)
+        .collect();
+    if border.is_empty() {
+        return true;
+    }
+    let to = canonical[to as usize];
+    if border.len() != 2 || !border.contains(&to) {
+        return false;
+    }
+    let previous = if border[0] == to { border[1] } else { border[0] };
+    let p = positions[from as usize];
+    let (a, b) = (p - positions[previous as usize], positions[to as usize] - p);
+    a.cross(b).magnitude() <= 1e-6 * a.magnitude() * b.magnitude() && a.dot(b) > 0.0
+}
+
+/// true if moving `from` onto `to` turns any of the remaining triangles around `from` over
+fn flips(
+    triangles: &[[u32; 3]],
+    removed: &[bool],
+    around: &[usize],
+    positions: &[Vector3<f64>],
+    from: u32,
+    to: u32,
+) -> bool {
+    around.iter().any(|&t| {
+        let tri = triangles[t];
+        if removed[t] || tri.contains(&to) {
+            return false;
+        }
+        let before = tri.map(|idx| positions[idx as usize]);
+        let after = tri.map(|idx| positions[if idx == from { to } else { idx } as usize]);
+        let normal = |[p0, p1, p2]: [Vector3<f64>; 3]| (p1 - p0).cross(p2 - p0);
+        normal(before).dot(normal(after)) <= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Geometry, Vertex};
+    use std::collections::HashSet;
+
+    // the geometry with vertices at the same position merged, so only borders stay open
+    fn welded(geometry: &Geometry) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut at: HashMap<[u32; 3], u32> = HashMap::new();
+        let remap: Vec<u32> = geometry
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let p = vertex.position;
+                *at.entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                    .or_insert_with(|| {
+                        vertices.push(*vertex);
+                        vertices.len() as u32 - 1
+                    })
+            })
+            .collect();
+        let indices = geometry.indices.iter().map(|&idx| remap[idx as usize]).collect();
+        (vertices, indices)
+    }
+
+    fn used(indices: &[u32]) -> HashSet<u32> {
+        indices.iter().cloned().collect()
+    }
+
+    // largest distance to the unit sphere over the corners, edge midpoints and centroids
+    fn sphere_deviation(vertices: &[Vertex], indices: &[u32]) -> f32 {
+        indices
+            .chunks_exact(3)
+            .flat_map(|tri| {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| vertices[idx as usize].position);
+                vec![a, b, c, (a + b) / 2.0, (b + c) / 2.0, (c + a) / 2.0, (a + b + c) / 3.0]
+            })
+            .map(|p| (1.0 - p.magnitude()).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn flat_grid_collapses_to_two_triangles() {
+        let (vertices, indices) = welded(&Geometry::grid(2.0, 2.0, 8, 8));
+        let simplified = simplify(&vertices, &indices, 0, 1e-4);
+        assert_eq!(simplified.indices.len(), 6);
+        assert!(simplified.error < 1e-6, "error {}", simplified.error);
+        for idx in used(&simplified.indices) {
+            let p = vertices[idx as usize].position;
+            assert!(p.x.abs() == 1.0 && p.z.abs() == 1.0, "{:?} is not a corner", p);
+        }
+    }
+
+    #[test]
+    fn sphere_error_stays_within_bound() {
+        let (vertices, indices) = welded(&Geometry::ico_sphere(1.0, 4));
+        let original = sphere_deviation(&vertices, &indices);
+        for &max_error in &[0.005, 0.02, 0.05] {
+            let simplified = simplify(&vertices, &indices, 0, max_error);
+            assert!(
+                simplified.indices.len() < indices.len(),
+                "nothing collapsed at {}",
+                max_error
+            );
+            assert!(
+                simplified.error <= max_error,
+                "reported {} over {}",
+                simplified.error,
+                max_error
+            );
+            let deviation = sphere_deviation(&vertices, &simplified.indices);
+            assert!(
+                deviation <= max_error + original,
+                "deviation {} over {} + {}",
+                deviation,
+                max_error,
+                original
+            );
+        }
+    }
+
+    #[test]
+    fn seam_vertices_are_kept() {
+        let geometry = Geometry::uv_sphere(1.0, 32, 16);
+        let simplified = simplify(&geometry.vertices, &geometry.indices, 0, 1.0);
+        assert!(simplified.indices.len() < geometry.indices.len());
+        let key = |vertex: &Vertex| {
+            let p = vertex.position;
+            [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+        };
+        let mut count = HashMap::new();
+        for vertex in &geometry.vertices {
+            *count.entry(key(vertex)).or_insert(0) += 1;
+        }
+        let kept: HashSet<[u32; 3]> = used(&simplified.indices)
+            .into_iter()
+            .map(|idx| key(&geometry.vertices[idx as usize]))
+            .collect();
+        for (position, _) in count.iter().filter(|(_, &count)| count > 1) {
+            // the poles are only used by degenerate triangles, which are dropped
+            let y = f32::from_bits(position[1]);
+            if y.abs() < 1.0 - 1e-6 {
+                assert!(kept.contains(position), "seam vertex at {:?} removed", position);
+            }
+        }
+    }
+
+    #[test]
+    fn border_vertices_are_kept() {
+        let (mut vertices, indices) = welded(&Geometry::grid(2.0, 2.0, 12, 12));
+        for vertex in &mut vertices {
+            let p = vertex.position;
+            vertex.position.y = 0.3 * (p.x * p.x + p.z * p.z) + 0.2 * p.x * p.z;
+        }
+        let simplified = simplify(&vertices, &indices, 0, 1.0);
+        assert!(simplified.indices.len() < indices.len());
+        let kept = used(&simplified.indices);
+        for (idx, vertex) in vertices.iter().enumerate() {
+            let p = vertex.position;
+            if p.x.abs() == 1.0 || p.z.abs() == 1.0 {
+                assert!(kept.contains(&(idx as u32)), "border vertex at {:?} removed", p);
+            }
+        }
+    }
+}
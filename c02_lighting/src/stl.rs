@@ -0,0 +1,124 @@
+use cgmath::{vec3, Vector3};
+use std::{collections::HashMap, convert::TryInto, error::Error, fmt, fs, io, path::Path, str};
+
+use super::mesh::Vertex;
+use super::model::RawMesh;
+
+/// Errors of `load_stl`.
+#[derive(Debug)]
+pub enum StlError {
+    Io(io::Error),
+    /// the file is neither valid ASCII nor binary STL
+    Parse(String),
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StlError::Io(err) => write!(f, "{}", err),
+            StlError::Parse(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for StlError {}
+
+/// Reads an ASCII or binary STL file.
+///
+/// STL stores every triangle on its own with a facet normal. Corners at the same position
+/// are welded into one vertex and the facet normals are ignored, so normals are generated
+/// like for any other mesh without them: smooth surfaces come out smooth and the crease
+/// angle of `ModelOptions::normals` keeps the hard edges of CAD parts.
+pub fn load_stl(path: &Path) -> Result<RawMesh, StlError> {
+    let bytes = fs::read(path).map_err(StlError::Io)?;
+    let corners = if is_binary(&bytes) {
+        binary_corners(&bytes)?
+    } else {
+        ascii_corners(&bytes)?
+    };
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::with_capacity(corners.len());
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+    for position in corners {
+        // +0.0 turns -0.0 into 0.0, which would otherwise not weld with it
+        let key = [position.x + 0.0, position.y + 0.0, position.z + 0.0].map(f32::to_bits);
+        let idx = *welded.entry(key).or_insert_with(|| {
+            vertices.push(Vertex {
+                position,
+                ..Vertex::default()
+            });
+            (vertices.len() - 1) as u32
+        });
+        indices.push(idx);
+    }
+
+    Ok(RawMesh {
+        vertices,
+        indices,
+        has_normals: false,
+        has_tex_coords: false,
+        colors: None,
+        material: None,
+    })
+}
+
+// binary files may start with "solid" like ASCII ones, the size is the reliable test
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() >= 84 {
+        let triangles = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as u64;
+        if 84 + triangles * 50 == bytes.len() as u64 {
+            return true;
+        }
+    }
+    !bytes.trim_ascii_start().starts_with(b"solid")
+}
+
+// 80 byte header, triangle count, then per triangle: normal, three corners, attribute bytes
+fn binary_corners(bytes: &[u8]) -> Result<Vec<Vector3<f32>>, StlError> {
+    if bytes.len() < 84 {
+        return Err(StlError::Parse("binary STL shorter than its header".into()));
+    }
+    let triangles = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    if bytes.len() < 84 + triangles * 50 {
+        return Err(StlError::Parse(format!(
+            "binary STL too short for {} triangles",
+            triangles
+        )));
+    }
+    let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let mut corners = Vec::with_capacity(triangles * 3);
+    for triangle in 0..triangles {
+        let start = 84 + triangle * 50 + 12;
+        for corner in 0..3 {
+            let offset = start + corner * 12;
+            corners.push(vec3(float(offset), float(offset + 4), float(offset + 8)));
+        }
+    }
+    Ok(corners)
+}
+
+// solid name / facet normal nx ny nz / outer loop / vertex x y z (x3) / endloop / endfacet / ... / endsolid
+fn ascii_corners(bytes: &[u8]) -> Result<Vec<Vector3<f32>>, StlError> {
+    let text = str::from_utf8(bytes).map_err(|_| StlError::Parse("ASCII STL is not valid UTF-8".into()))?;
+    let mut tokens = text.split_ascii_whitespace();
+    let mut corners = Vec::new();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> Result<f32, StlError> {
+            let token = tokens
+                .next()
+                .ok_or_else(|| StlError::Parse("unexpected end of file".into()))?;
+            token
+                .parse()
+                .map_err(|_| StlError::Parse(format!("invalid number {:?}", token)))
+        };
+        corners.push(vec3(coordinate()?, coordinate()?, coordinate()?));
+    }
+    if !corners.len().is_multiple_of(3) {
+        return Err(StlError::Parse("facet without three vertices".into()));
+    }
+    Ok(corners)
+}
@@ -1,4 +1,4 @@
-use c03_model_loading::{process_events, process_input, Camera, Model, Shader};
+use c03_model_loading::{process_events, process_input, run_event_loop, Camera, Model, ModelData, ModelOptions, Shader};
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
     assets,
@@ -57,11 +57,20 @@ pub fn main() {
         gl::enable(GL_DEPTH_TEST);
 
         // build and compile shaders
-        let our_shader = Shader::new(assets!("shaders/1.model_loading.vs"), assets!("shaders/1.model_loading.fs"));
+        let our_shader = Shader::new(
+            assets!("shaders/1.model_loading.vs"),
+            assets!("shaders/1.model_loading.fs"),
+        );
 
         // load models
 
-        let our_model = Model::new(assets!("objects/nanosuit/nanosuit.obj"));
+        // meshes are reordered for the vertex cache while loading, see how much that saves
+        let options = ModelOptions::default().for_current_context();
+        let data = ModelData::load(assets!("objects/nanosuit/nanosuit.obj"), &options).expect("Failed to load model");
+        for report in &data.optimize_reports {
+            println!("{}", report);
+        }
+        let our_model = Model::from_data(data, &options);
 
         // draw in wireframe
         // gl::PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
@@ -70,7 +79,7 @@ pub fn main() {
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -0,0 +1,77 @@
+// Compiles model files (OBJ, PLY, STL, glTF and GLB) into the binary format `Model::new`
+// loads instead of the source when it is up to date (see `model_cache.rs`).
+//
+// usage: c03_020_compile_model [--lods] [--split-u16] [model files...]
+// without files, compiles the models used by the examples. The options have to match the
+// `ModelOptions` the example loads the model with, or the compiled file is ignored.
+use c03_model_loading::{model_cache_path, write_model_cache, LodOptions, ModelData, ModelOptions};
+use dx::assets;
+use std::{fs, path::PathBuf, process, time::Instant};
+
+pub fn main() {
+    let mut options = ModelOptions::default();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--lods" => options.lods = Some(LodOptions::default()),
+            "--split-u16" => {
+                if let Some(optimize) = &mut options.optimize {
+                    optimize.split_for_u16 = true;
+                }
+            }
+            _ => paths.push(arg.into()),
+        }
+    }
+    if paths.is_empty() {
+        let examples = [
+            assets!("objects/nanosuit/nanosuit.obj"),
+            assets!("objects/planet/planet.obj"),
+        ];
+        paths.extend(examples.iter().map(PathBuf::from));
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let start = Instant::now();
+        let data = match ModelData::parse(&path, &options) {
+            Ok(data) => data,
+            Err(err) => {
+                println!("{:?}: {}", path, err);
+                failed = true;
+                continue;
+            }
+        };
+        let parse_time = start.elapsed();
+
+        let cache_path = model_cache_path(&path);
+        if let Err(err) = write_model_cache(&data, &options, &cache_path) {
+            println!("{:?}: {}", cache_path, err);
+            failed = true;
+            continue;
+        }
+        let size = fs::metadata(&cache_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+
+        // time loading the compiled file the way the examples will
+        let start = Instant::now();
+        let loaded = ModelData::load(&path, &options).map(|data| data.meshes.len());
+        let load_time = start.elapsed();
+
+        println!(
+            "{:?} -> {:?}: {} meshes, {} bytes; parsing took {:?}, loading the compiled file {:?}",
+            path,
+            cache_path,
+            loaded.unwrap_or_default(),
+            size,
+            parse_time,
+            load_time
+        );
+        for report in &data.optimize_reports {
+            println!("    {}", report);
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
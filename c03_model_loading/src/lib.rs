@@ -3,6 +3,9 @@
 mod common;
 pub use common::*;
 
+mod gl_object;
+pub use gl_object::*;
+
 mod shader;
 pub use shader::*;
 
@@ -11,6 +14,87 @@ pub use camera::*;
 
 mod mesh;
 pub use mesh::*;
+pub use vertex_format::{AttributeType, ComponentType, Half, PackedNormal, VertexAttribute, VertexFormat};
+
+mod material;
+pub use material::*;
+
+mod texture_cache;
+pub use texture_cache::*;
+
+mod asset_loader;
+pub use asset_loader::*;
 
 mod model;
 pub use model::*;
+
+mod normals;
+pub use normals::*;
+
+mod geometry;
+pub use geometry::*;
+
+mod bounds;
+pub use bounds::*;
+
+mod scene;
+pub use scene::*;
+
+mod animation;
+pub use animation::*;
+
+mod capabilities;
+pub use capabilities::*;
+
+mod morph;
+pub use morph::*;
+
+mod simplify;
+pub use simplify::*;
+
+mod lod;
+pub use lod::*;
+
+mod optimize;
+pub use optimize::*;
+
+mod model_cache;
+pub use model_cache::*;
+
+mod ply;
+pub use ply::*;
+
+mod stl;
+pub use stl::*;
+
+mod gltf;
+pub use gltf::*;
+
+mod instancing;
+pub use instancing::*;
+
+mod texture;
+pub use texture::*;
+
+mod etc2;
+pub use etc2::*;
+mod astc;
+pub use astc::*;
+
+mod ktx;
+pub use ktx::*;
+
+mod hdr;
+pub use hdr::*;
+
+mod texture_cube;
+pub use texture_cube::*;
+
+mod atlas;
+pub use atlas::*;
+
+mod render_target;
+pub use render_target::*;
+
+mod post_process;
+pub use post_process::*;
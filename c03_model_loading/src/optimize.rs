@@ -0,0 +1,517 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use std::{cmp::Ordering, fmt};
+use vertex_format::VertexFormat;
+
+/// Most vertices a mesh drawn with 16-bit indices can have.
+pub const MAX_U16_VERTICES: usize = 65536;
+
+/// How `optimize_mesh` reorders a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizeOptions {
+    /// entries of the simulated post-transform vertex cache (FIFO) the ACMR is measured with
+    pub cache_size: usize,
+    /// how much worse than the cache-optimized order the overdraw pass may make the ACMR,
+    /// 1.05 allows 5%; below 1 the pass is skipped
+    pub overdraw_threshold: f32,
+    /// split meshes with more than `MAX_U16_VERTICES` vertices so every part can be drawn with
+    /// 16-bit indices, for OpenGL ES 2.0 without `OES_element_index_uint`
+    pub split_for_u16: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            cache_size: 16,
+            overdraw_threshold: 1.05,
+            split_for_u16: false,
+        }
+    }
+}
+
+/// Cache efficiency of a mesh before and after `optimize_mesh`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OptimizeReport {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// average cache miss ratio: vertex shader runs per triangle, between 0.5 and 3
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+    /// average transformed vertex ratio: vertex shader runs per vertex, 1 is ideal
+    pub atvr_before: f32,
+    pub atvr_after: f32,
+}
+
+impl fmt::Display for OptimizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} vertices, {} triangles: ACMR {:.3} -> {:.3}, ATVR {:.3} -> {:.3}",
+            self.vertices, self.triangles, self.acmr_before, self.acmr_after, self.atvr_before, self.atvr_after
+        )
+    }
+}
+
+/// number of vertex shader runs for `indices` with a FIFO post-transform cache of `cache_size` entries
+pub fn cache_misses(indices: &[u32], vertex_count: usize, cache_size: usize) -> usize {
+    // a vertex is in the cache while fewer than `cache_size` vertices were added after it
+    let mut added_at = vec![0usize; vertex_count];
+    let mut time = cache_size + 1;
+    let mut misses = 0;
+    for &idx in indices {
+        if time - added_at[idx as usize] > cache_size {
+            added_at[idx as usize] = time;
+            time += 1;
+            misses += 1;
+        }
+    }
+    misses
+}
+
+/// average cache miss ratio, see `OptimizeReport`
+pub fn acmr(indices: &[u32], vertex_count: usize, cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    cache_misses(indices, vertex_count, cache_size) as f32 / triangles as f32
+}
+
+/// average transformed vertex ratio, see `OptimizeReport`
+pub fn atvr(indices: &[u32], vertex_count: usize, cache_size: usize) -> f32 {
+    if vertex_count == 0 {
+        return 0.0;
+    }
+    cache_misses(indices, vertex_count, cache_size) as f32 / vertex_count as f32
+}
+
+// size of the LRU cache the scores are computed for, larger than real caches on purpose
+const SCORE_CACHE_SIZE: usize = 32;
+
+/// Forsyth's vertex score: recently used vertices and vertices with few triangles left score high
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // the last triangle's vertices score the same, so the next triangle isn't biased towards one edge
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (SCORE_CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    // finishing off vertices avoids leaving lone triangles to pick up later
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Reorders triangles for the post-transform vertex cache (Tom Forsyth, "Linear-Speed Vertex
+/// Cache Optimisation"). Works well for any cache size, so it doesn't need to know the GPU's.
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // triangles around every vertex, as one flat list
+    let mut remaining = vec![0u32; vertex_count];
+    for &idx in &indices[..triangle_count * 3] {
+        remaining[idx as usize] += 1;
+    }
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] = offsets[vertex] + remaining[vertex] as usize;
+    }
+    let mut vertex_triangles = vec![0usize; triangle_count * 3];
+    let mut filled = offsets.clone();
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for &idx in tri {
+            vertex_triangles[filled[idx as usize]] = t;
+            filled[idx as usize] += 1;
+        }
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut scores: Vec<f32> = remaining.iter().map(|&count| vertex_score(None, count)).collect();
+    let triangle_score =
+        |scores: &[f32], t: usize| -> f32 { indices[t * 3..t * 3 + 3].iter().map(|&i| scores[i as usize]).sum() };
+
+    let mut added = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(SCORE_CACHE_SIZE + 3);
+    let mut result: Vec<u32> = Vec::with_capacity(triangle_count * 3);
+    // the first triangle is the best overall, after that only triangles around cached vertices are scored
+    let mut best = (0..triangle_count).max_by(|&a, &b| {
+        triangle_score(&scores, a)
+            .partial_cmp(&triangle_score(&scores, b))
+            .unwrap_or(Ordering::Equal)
+    });
+    // next triangle to try when none around the cache is left
+    let mut next_unadded = 0;
+
+    while let Some(t) = best {
+        added[t] = true;
+        let tri = [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]];
+        result.extend_from_slice(&tri);
+        for &idx in &tri {
+            remaining[idx as usize] -= 1;
+        }
+
+        // the triangle's vertices move to the front of the cache
+        let mut new_cache: Vec<u32> = Vec::with_capacity(cache.len() + 3);
+        for &idx in &tri {
+            if !new_cache.contains(&idx) {
+                new_cache.push(idx);
+            }
+        }
+        new_cache.extend(cache.iter().filter(|idx| !tri.contains(idx)));
+        for (position, &idx) in new_cache.iter().enumerate() {
+            cache_position[idx as usize] = if position < SCORE_CACHE_SIZE {
+                Some(position)
+            } else {
+                None
+            };
+            scores[idx as usize] = vertex_score(cache_position[idx as usize], remaining[idx as usize]);
+        }
+        new_cache.truncate(SCORE_CACHE_SIZE);
+        cache = new_cache;
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for &idx in &cache {
+            let idx = idx as usize;
+            for &candidate in &vertex_triangles[offsets[idx]..offsets[idx + 1]] {
+                if added[candidate] {
+                    continue;
+                }
+                let score = triangle_score(&scores, candidate);
+                if score > best_score {
+                    best_score = score;
+                    best = Some(candidate);
+                }
+            }
+        }
+        if best.is_none() {
+            while next_unadded < triangle_count && added[next_unadded] {
+                next_unadded += 1;
+            }
+            if next_unadded < triangle_count {
+                best = Some(next_unadded);
+            }
+        }
+    }
+
+    indices[..triangle_count * 3].copy_from_slice(&result);
+}
+
+/// Reorders the triangles of a cache-optimized mesh so that, seen from most directions, outer
+/// surfaces tend to be drawn before the surfaces they hide, which lets the depth test reject
+/// more fragments (after Sander et al., "Fast Triangle Reordering for Vertex Locality and
+/// Reduced Overdraw"). The mesh is cut into clusters where the cache order already starts over,
+/// and the clusters are sorted by how far they face outwards from the mesh's center. The new
+/// order is kept only if its ACMR is within `threshold` of the current one.
+pub fn optimize_overdraw<V: VertexFormat>(indices: &mut [u32], vertices: &[V], cache_size: usize, threshold: f32) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count < 2 || threshold < 1.0 {
+        return;
+    }
+
+    // a new cluster starts at every triangle whose vertices all miss the cache
+    let mut clusters = vec![0];
+    let mut added_at = vec![0usize; vertices.len()];
+    let mut time = cache_size + 1;
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        let mut misses = 0;
+        for &idx in tri {
+            if time - added_at[idx as usize] > cache_size {
+                added_at[idx as usize] = time;
+                time += 1;
+                misses += 1;
+            }
+        }
+        if misses == 3 && t > 0 {
+            clusters.push(t);
+        }
+    }
+    if clusters.len() < 2 {
+        return;
+    }
+    clusters.push(triangle_count);
+
+    let position = |idx: u32| vertices[idx as usize].position();
+    let centroid = |tri: &[u32]| (position(tri[0]) + position(tri[1]) + position(tri[2])) / 3.0;
+    let mesh_center = indices
+        .chunks_exact(3)
+        .fold(Vector3::zero(), |sum, tri| sum + centroid(tri))
+        / triangle_count as f32;
+
+    // (first triangle, end, outwardness) of every cluster
+    let mut sorted: Vec<(usize, usize, f32)> = clusters
+        .windows(2)
+        .map(|range| {
+            let tris = &indices[range[0] * 3..range[1] * 3];
+            let mut center = Vector3::zero();
+            let mut normal = Vector3::zero();
+            let mut area = 0.0;
+            for tri in tris.chunks_exact(3) {
+                let [p0, p1, p2] = [position(tri[0]), position(tri[1]), position(tri[2])];
+                let weighted_normal = (p1 - p0).cross(p2 - p0);
+                let double_area = weighted_normal.magnitude();
+                center += centroid(tri) * double_area;
+                normal += weighted_normal;
+                area += double_area;
+            }
+            let outwardness = if area > 0.0 && normal.magnitude2() > 0.0 {
+                (center / area - mesh_center).dot(normal.normalize())
+            } else {
+                0.0
+            };
+            (range[0], range[1], outwardness)
+        })
+        .collect();
+    sorted.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+
+    let reordered: Vec<u32> = sorted
+        .iter()
+        .flat_map(|&(start, end, _)| indices[start * 3..end * 3].iter().cloned())
+        .collect();
+    let before = acmr(indices, vertices.len(), cache_size);
+    if acmr(&reordered, vertices.len(), cache_size) <= before * threshold {
+        indices[..triangle_count * 3].copy_from_slice(&reordered);
+    }
+}
+
+/// Reorders the vertices in the order the triangles first use them, so vertex fetches walk the
+/// vertex buffer mostly forwards, and drops vertices no triangle uses. Returns the new index of
+/// every old vertex, `u32::MAX` for dropped ones, to remap data kept outside the vertices
+/// (morph targets, for instance).
+pub fn optimize_vertex_fetch<V: Copy>(vertices: &mut Vec<V>, indices: &mut [u32]) -> Vec<u32> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+    for idx in indices.iter_mut() {
+        let new_idx = &mut remap[*idx as usize];
+        if *new_idx == u32::MAX {
+            *new_idx = reordered.len() as u32;
+            reordered.push(vertices[*idx as usize]);
+        }
+        *idx = *new_idx;
+    }
+    *vertices = reordered;
+    remap
+}
+
+/// Runs the vertex cache, overdraw and vertex fetch passes on a mesh.
+pub fn optimize_mesh<V: VertexFormat>(
+    vertices: &mut Vec<V>,
+    indices: &mut [u32],
+    options: &OptimizeOptions,
+) -> OptimizeReport {
+    let acmr_before = acmr(indices, vertices.len(), options.cache_size);
+    let atvr_before = atvr(indices, vertices.len(), options.cache_size);
+
+    optimize_vertex_cache(indices, vertices.len());
+    optimize_overdraw(indices, vertices, options.cache_size, options.overdraw_threshold);
+    optimize_vertex_fetch(vertices, indices);
+
+    OptimizeReport {
+        vertices: vertices.len(),
+        triangles: indices.len() / 3,
+        acmr_before,
+        acmr_after: acmr(indices, vertices.len(), options.cache_size),
+        atvr_before,
+        atvr_after: atvr(indices, vertices.len(), options.cache_size),
+    }
+}
+
+/// Splits a mesh into parts of at most `MAX_U16_VERTICES` vertices, keeping the triangle
+/// order; a mesh small enough already is returned as is. Vertices used by triangles of
+/// several parts are duplicated.
+pub fn split_for_u16<V: Copy>(vertices: &[V], indices: &[u32]) -> Vec<(Vec<V>, Vec<u32>)> {
+    if vertices.len() <= MAX_U16_VERTICES {
+        return vec![(vertices.to_vec(), indices.to_vec())];
+    }
+
+    let mut parts = Vec::new();
+    // index of every vertex in the current part, valid if `part_of` is the current part
+    let mut remap = vec![0u32; vertices.len()];
+    let mut part_of = vec![usize::MAX; vertices.len()];
+    let mut part_vertices: Vec<V> = Vec::new();
+    let mut part_indices: Vec<u32> = Vec::new();
+    for tri in indices.chunks_exact(3) {
+        let part = parts.len();
+        let mut new_vertices = 0;
+        for (corner, &idx) in tri.iter().enumerate() {
+            if part_of[idx as usize] != part && !tri[..corner].contains(&idx) {
+                new_vertices += 1;
+            }
+        }
+        if part_vertices.len() + new_vertices > MAX_U16_VERTICES {
+            parts.push((std::mem::take(&mut part_vertices), std::mem::take(&mut part_indices)));
+        }
+        let part = parts.len();
+        for &idx in tri {
+            if part_of[idx as usize] != part {
+                part_of[idx as usize] = part;
+                remap[idx as usize] = part_vertices.len() as u32;
+                part_vertices.push(vertices[idx as usize]);
+            }
+            part_indices.push(remap[idx as usize]);
+        }
+    }
+    if !part_indices.is_empty() {
+        parts.push((part_vertices, part_indices));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Geometry, Vertex};
+
+    /// the triangles as positions, each rotated to start at its smallest position so the
+    /// winding is kept, in draw order
+    fn triangles(vertices: &[Vertex], indices: &[u32]) -> Vec<[[u32; 3]; 3]> {
+        indices
+            .chunks_exact(3)
+            .map(|tri| {
+                let mut triangle = [[0; 3]; 3];
+                for (position, &idx) in triangle.iter_mut().zip(tri) {
+                    let p = vertices[idx as usize].position;
+                    *position = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+                }
+                let first = (0..3).min_by_key(|&corner| triangle[corner]).unwrap();
+                triangle.rotate_left(first);
+                triangle
+            })
+            .collect()
+    }
+
+    fn sorted(mut triangles: Vec<[[u32; 3]; 3]>) -> Vec<[[u32; 3]; 3]> {
+        triangles.sort_unstable();
+        triangles
+    }
+
+    /// shuffles the triangles with a fixed seed, the worst case for the vertex cache
+    fn shuffle(indices: &mut [u32]) {
+        let mut state = 0x2545_f491_u32;
+        let count = indices.len() / 3;
+        for i in (1..count).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let j = state as usize % (i + 1);
+            for corner in 0..3 {
+                indices.swap(i * 3 + corner, j * 3 + corner);
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_cache_lowers_acmr() {
+        let Geometry { vertices, mut indices } = Geometry::grid(1.0, 1.0, 48, 48);
+        shuffle(&mut indices);
+        let before = triangles(&vertices, &indices);
+        let acmr_before = acmr(&indices, vertices.len(), 16);
+
+        optimize_vertex_cache(&mut indices, vertices.len());
+        let acmr_after = acmr(&indices, vertices.len(), 16);
+        assert!(
+            acmr_after < 0.8 && acmr_after < acmr_before / 2.0,
+            "{} -> {}",
+            acmr_before,
+            acmr_after
+        );
+        assert_eq!(sorted(triangles(&vertices, &indices)), sorted(before));
+    }
+
+    #[test]
+    fn overdraw_stays_within_threshold() {
+        let Geometry { vertices, mut indices } = Geometry::ico_sphere(1.0, 3);
+        shuffle(&mut indices);
+        optimize_vertex_cache(&mut indices, vertices.len());
+        let cache_optimized = indices.clone();
+        let acmr_before = acmr(&indices, vertices.len(), 16);
+
+        optimize_overdraw(&mut indices, &vertices, 16, 1.05);
+        assert!(acmr(&indices, vertices.len(), 16) <= acmr_before * 1.05);
+        assert_eq!(
+            sorted(triangles(&vertices, &indices)),
+            sorted(triangles(&vertices, &cache_optimized))
+        );
+
+        // no slack at all keeps any order that would be worse
+        let mut strict = cache_optimized.clone();
+        optimize_overdraw(&mut strict, &vertices, 16, 1.0);
+        assert!(acmr(&strict, vertices.len(), 16) <= acmr_before);
+        let mut skipped = cache_optimized.clone();
+        optimize_overdraw(&mut skipped, &vertices, 16, 0.5);
+        assert_eq!(skipped, cache_optimized);
+    }
+
+    #[test]
+    fn vertex_fetch_keeps_the_triangles() {
+        let Geometry {
+            mut vertices,
+            mut indices,
+        } = Geometry::uv_sphere(1.0, 16, 8);
+        shuffle(&mut indices);
+        // a vertex no triangle uses
+        vertices.push(vertices[0]);
+        let unused = vertices.len() - 1;
+        let before = triangles(&vertices, &indices);
+        let old_indices = indices.clone();
+
+        let remap = optimize_vertex_fetch(&mut vertices, &mut indices);
+        assert_eq!(triangles(&vertices, &indices), before);
+        let used: std::collections::HashSet<u32> = old_indices.iter().copied().collect();
+        assert_eq!(vertices.len(), used.len());
+        assert_eq!(remap[unused], u32::MAX);
+        for (&old, &new) in old_indices.iter().zip(&indices) {
+            assert_eq!(remap[old as usize], new);
+        }
+        // vertices are stored in the order the triangles first use them
+        let mut next = 0;
+        for &idx in &indices {
+            assert!(idx <= next);
+            if idx == next {
+                next += 1;
+            }
+        }
+        assert_eq!(next as usize, vertices.len());
+    }
+
+    #[test]
+    fn optimize_mesh_report() {
+        let Geometry {
+            mut vertices,
+            mut indices,
+        } = Geometry::grid(1.0, 1.0, 24, 24);
+        shuffle(&mut indices);
+        let before = sorted(triangles(&vertices, &indices));
+        let report = optimize_mesh(&mut vertices, &mut indices, &OptimizeOptions::default());
+        assert_eq!((report.vertices, report.triangles), (25 * 25, 24 * 24 * 2));
+        assert!(report.acmr_after < report.acmr_before);
+        assert!(report.atvr_after < report.atvr_before);
+        assert_eq!(report.acmr_after, acmr(&indices, vertices.len(), 16));
+        assert_eq!(sorted(triangles(&vertices, &indices)), before);
+    }
+
+    #[test]
+    fn split_for_u16_parts() {
+        let Geometry { vertices, indices } = Geometry::grid(1.0, 1.0, 300, 300);
+        assert!(vertices.len() > MAX_U16_VERTICES);
+        let parts = split_for_u16(&vertices, &indices);
+        assert!(parts.len() >= 2);
+        let mut joined = Vec::new();
+        for (part_vertices, part_indices) in &parts {
+            assert!(part_vertices.len() <= MAX_U16_VERTICES);
+            assert!(part_indices.iter().all(|&idx| (idx as usize) < part_vertices.len()));
+            joined.extend(triangles(part_vertices, part_indices));
+        }
+        // the same triangles in the same order
+        assert_eq!(joined, triangles(&vertices, &indices));
+
+        let Geometry { vertices, indices } = Geometry::cube(1.0);
+        let parts = split_for_u16(&vertices, &indices);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].1, indices);
+    }
+}
@@ -1,9 +1,14 @@
 use cgmath::{Matrix4, Vector3};
 use dx::gles::{core30::gl, enums::*};
-use std::{fs::File, io::Read, path::PathBuf, str};
+use std::{cell::RefCell, fs::File, io::Read, path::PathBuf, rc::Rc, str};
+
+use super::gl_object::GlProgram;
+use super::material::{MaterialBindings, MaterialLayout};
 
 pub struct Shader {
-    pub id: u32,
+    program: GlProgram,
+    // uniform locations of the material layout, resolved on first use
+    material_bindings: RefCell<Option<Rc<MaterialBindings>>>,
 }
 
 /// NOTE: mixture of `shader_s.h` and `shader_m.h` (the latter just contains
@@ -14,8 +19,6 @@ impl Shader {
     where
         T: Into<PathBuf>,
     {
-        let mut shader = Shader { id: 0 };
-
         // 1. retrieve the vertex/fragment source code from filesystem
         let vertex_path: PathBuf = vertex_path.into();
         let mut v_shader_file =
@@ -35,6 +38,16 @@ impl Shader {
             .read_to_string(&mut fragment_code)
             .expect("Failed to read fragment shader");
 
+        Shader::from_source(&vertex_code, &fragment_code)
+    }
+
+    /// compiles a shader from source code, e.g. one embedded in the library
+    pub fn from_source(vertex_code: &str, fragment_code: &str) -> Self {
+        let shader = Shader {
+            program: GlProgram::new(),
+            material_bindings: RefCell::new(None),
+        };
+
         // 2. compile shaders
 
         // vertex shader
@@ -48,7 +61,7 @@ impl Shader {
         gl::compile_shader(fragment);
         shader.check_compile_errors(fragment, "FRAGMENT");
         // shader Program
-        let id = gl::create_program();
+        let id = shader.id();
         gl::attach_shader(id, vertex);
         gl::attach_shader(id, fragment);
         gl::link_program(id);
@@ -56,43 +69,77 @@ impl Shader {
         // delete the shaders as they're linked into our program now and no longer necessary
         gl::delete_shader(vertex);
         gl::delete_shader(fragment);
-        shader.id = id;
 
         shader
     }
 
+    /// name of the GL program
+    pub fn id(&self) -> u32 {
+        self.program.id()
+    }
+
     /// activate the shader
 
     pub fn use_program(&self) {
-        gl::use_program(self.id)
+        gl::use_program(self.id())
     }
 
     /// utility uniform functions
 
     pub fn set_bool(&self, name: &str, value: bool) {
-        gl::uniform1i(gl::get_uniform_location(self.id, name), value as i32);
+        gl::uniform1i(gl::get_uniform_location(self.id(), name), value as i32);
     }
 
     pub fn set_int(&self, name: &str, value: i32) {
-        gl::uniform1i(gl::get_uniform_location(self.id, name), value);
+        gl::uniform1i(gl::get_uniform_location(self.id(), name), value);
     }
 
     pub fn set_float(&self, name: &str, value: f32) {
-        gl::uniform1f(gl::get_uniform_location(self.id, name), value);
+        gl::uniform1f(gl::get_uniform_location(self.id(), name), value);
+    }
+
+    pub fn set_vec2(&self, name: &str, x: f32, y: f32) {
+        gl::uniform2f(gl::get_uniform_location(self.id(), name), x, y);
     }
 
     pub fn set_vector3(&self, name: &str, value: &Vector3<f32>) {
         let value: &[f32; 3] = value.as_ref();
-        gl::uniform3fv(gl::get_uniform_location(self.id, name), value);
+        gl::uniform3fv(gl::get_uniform_location(self.id(), name), value);
     }
 
     pub fn set_vec3(&self, name: &str, x: f32, y: f32, z: f32) {
-        gl::uniform3f(gl::get_uniform_location(self.id, name), x, y, z);
+        gl::uniform3f(gl::get_uniform_location(self.id(), name), x, y, z);
     }
 
     pub fn set_mat4(&self, name: &str, mat: &Matrix4<f32>) {
         let value: &[f32; 16] = mat.as_ref();
-        gl::uniform_matrix4fv(gl::get_uniform_location(self.id, name), false, value);
+        gl::uniform_matrix4fv(gl::get_uniform_location(self.id(), name), false, value);
+    }
+
+    /// sets a `float` uniform array, e.g. the morph target weights
+    pub fn set_float_array(&self, name: &str, values: &[f32]) {
+        gl::uniform1fv(gl::get_uniform_location(self.id(), name), values);
+    }
+
+    /// sets a `mat4` uniform array, e.g. the joint matrices used for skinning
+    pub fn set_mat4_array(&self, name: &str, mats: &[Matrix4<f32>]) {
+        // SAFETY: `Matrix4<f32>` is repr(C), four columns of four f32 without padding
+        let values = unsafe { std::slice::from_raw_parts(mats.as_ptr() as *const f32, mats.len() * 16) };
+        gl::uniform_matrix4fv(gl::get_uniform_location(self.id(), name), false, values);
+    }
+
+    /// declares the samplers and uniforms the shader uses for material data, see
+    /// `MaterialLayout`; shaders without a declared layout use the default one.
+    pub fn set_material_layout(&self, layout: &MaterialLayout) {
+        let bindings = MaterialBindings::resolve(self.id(), layout);
+        self.material_bindings.replace(Some(Rc::new(bindings)));
+    }
+
+    pub fn material_bindings(&self) -> Rc<MaterialBindings> {
+        let mut bindings = self.material_bindings.borrow_mut();
+        bindings
+            .get_or_insert_with(|| Rc::new(MaterialBindings::resolve(self.id(), &MaterialLayout::default())))
+            .clone()
     }
 
     /// utility function for checking shader compilation/linking errors.
@@ -135,7 +182,10 @@ impl Shader {
     where
         T: Into<PathBuf>,
     {
-        let mut shader = Shader { id: 0 };
+        let shader = Shader {
+            program: GlProgram::new(),
+            material_bindings: RefCell::new(None),
+        };
 
         // 1. retrieve the vertex/fragment source code from filesystem
         let vertex_path: PathBuf = vertex_path.into();
@@ -183,7 +233,7 @@ impl Shader {
         shader.check_compile_errors(geometry, "GEOMETRY");
 
         // shader Program
-        let id = gl::create_program();
+        let id = shader.id();
         gl::attach_shader(id, vertex);
         gl::attach_shader(id, fragment);
         gl::attach_shader(id, geometry);
@@ -193,7 +243,6 @@ impl Shader {
         gl::delete_shader(vertex);
         gl::delete_shader(fragment);
         gl::delete_shader(geometry);
-        shader.id = id;
 
         shader
     }
@@ -1,4 +1,6 @@
-use c04_advanced_opengl::{load_texture, process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{
+    load_texture, process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
@@ -52,7 +54,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, cubevao, planevao, cube_texture, floor_texture) = {
+    let (shader, cubevao, planevao, cube_texture, floor_texture, cubevbo, planevbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -83,10 +85,10 @@ pub fn main() {
         ];
 
         // cube vao
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
-        gl::bind_vertex_array(cubevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
+        gl::bind_vertex_array(cubevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &cube_vertices, GL_STATIC_DRAW);
         let stride = 5 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -96,10 +98,10 @@ pub fn main() {
         gl::bind_vertex_array(0);
         // plane vao
 
-        let planevao = gl::gen_vertex_array();
-        let planevbo = gl::gen_buffer();
-        gl::bind_vertex_array(planevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo);
+        let planevao = GlVertexArray::new();
+        let planevbo = GlBuffer::new();
+        gl::bind_vertex_array(planevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &plane_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
@@ -117,11 +119,11 @@ pub fn main() {
         shader.use_program();
         shader.set_int("texture1", 0);
 
-        (shader, cubevao, planevao, cube_texture, floor_texture)
+        (shader, cubevao, planevao, cube_texture, floor_texture, cubevbo, planevbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (cubevbo, planevbo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -163,9 +165,9 @@ pub fn main() {
             shader.set_mat4("view", &view);
             shader.set_mat4("projection", &projection);
             // cubes
-            gl::bind_vertex_array(cubevao);
+            gl::bind_vertex_array(cubevao.id());
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, cube_texture);
+            gl::bind_texture(GL_TEXTURE_2D, cube_texture.id());
             model = Matrix4::from_translation(vec3(-1.0, 0.0, -1.0));
             shader.set_mat4("model", &model);
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
@@ -173,8 +175,8 @@ pub fn main() {
             shader.set_mat4("model", &model);
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
             // floor
-            gl::bind_vertex_array(planevao);
-            gl::bind_texture(GL_TEXTURE_2D, floor_texture);
+            gl::bind_vertex_array(planevao.id());
+            gl::bind_texture(GL_TEXTURE_2D, floor_texture.id());
             shader.set_mat4("model", &Matrix4::identity());
             gl::draw_arrays(GL_TRIANGLES, 0, 6);
             gl::bind_vertex_array(0);
@@ -190,9 +192,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &cubevao);
-    //     gl::delete_vertex_arrays(1, &planevao);
-    //     gl::delete_buffers(1, &cubevbo);
-    //     gl::delete_buffers(1, &planevbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -1,4 +1,7 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{
+    load_texture, process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader, Texture2D,
+    Wrap,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
@@ -7,8 +10,6 @@ use dx::{
     utils::*,
     Instance,
 };
-use image::{DynamicImage::*, GenericImageView};
-use std::path::PathBuf;
 use std::{mem, time::SystemTime};
 use winit::{
     event::*,
@@ -54,7 +55,19 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, cubevao, planevao, transparentvao, cube_texture, floor_texture, transparent_texture, vegetation) = {
+    let (
+        shader,
+        cubevao,
+        planevao,
+        transparentvao,
+        cube_texture,
+        floor_texture,
+        transparent_texture,
+        vegetation,
+        cubevbo,
+        planevbo,
+        transparentvbo,
+    ) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -88,10 +101,10 @@ pub fn main() {
         ];
 
         // cube vao
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
-        gl::bind_vertex_array(cubevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
+        gl::bind_vertex_array(cubevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &cube_vertices, GL_STATIC_DRAW);
         let stride = 5 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -101,10 +114,10 @@ pub fn main() {
         gl::bind_vertex_array(0);
         // plane vao
 
-        let planevao = gl::gen_vertex_array();
-        let planevbo = gl::gen_buffer();
-        gl::bind_vertex_array(planevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo);
+        let planevao = GlVertexArray::new();
+        let planevbo = GlBuffer::new();
+        gl::bind_vertex_array(planevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &plane_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
@@ -112,10 +125,10 @@ pub fn main() {
         gl::vertex_attrib_pointer_offset(1, 2, GL_FLOAT, false, stride, 3 * mem::size_of::<f32>() as u32);
 
         // transparent vao
-        let transparentvao = gl::gen_vertex_array();
-        let transparentvbo = gl::gen_buffer();
-        gl::bind_vertex_array(transparentvao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, transparentvbo);
+        let transparentvao = GlVertexArray::new();
+        let transparentvbo = GlBuffer::new();
+        gl::bind_vertex_array(transparentvao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, transparentvbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &transparent_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
@@ -127,7 +140,12 @@ pub fn main() {
 
         let cube_texture = load_texture(assets!("textures/marble.jpg"));
         let floor_texture = load_texture(assets!("textures/metal.png"));
-        let transparent_texture = load_texture(assets!("textures/grass.png"));
+        // clamp to the edge to prevent semi-transparent borders: due to interpolation the
+        // border texels would take texels from the next repeat
+        let transparent_texture = Texture2D::builder()
+            .wrap(Wrap::ClampToEdge)
+            .load(assets!("textures/grass.png"))
+            .expect("Texture failed to load");
 
         // transparent vegetation locations
 
@@ -153,11 +171,14 @@ pub fn main() {
             floor_texture,
             transparent_texture,
             vegetation,
+            cubevbo,
+            planevbo,
+            transparentvbo,
         )
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (cubevbo, planevbo, transparentvbo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -200,9 +221,9 @@ pub fn main() {
             shader.set_mat4("projection", &projection);
             shader.set_mat4("view", &view);
             // cubes
-            gl::bind_vertex_array(cubevao);
+            gl::bind_vertex_array(cubevao.id());
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, cube_texture);
+            gl::bind_texture(GL_TEXTURE_2D, cube_texture.id());
             model = Matrix4::from_translation(vec3(-1.0, 0.0, -1.0));
             shader.set_mat4("model", &model);
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
@@ -210,14 +231,14 @@ pub fn main() {
             shader.set_mat4("model", &model);
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
             // floor
-            gl::bind_vertex_array(planevao);
-            gl::bind_texture(GL_TEXTURE_2D, floor_texture);
+            gl::bind_vertex_array(planevao.id());
+            gl::bind_texture(GL_TEXTURE_2D, floor_texture.id());
             shader.set_mat4("model", &Matrix4::identity());
             gl::draw_arrays(GL_TRIANGLES, 0, 6);
             gl::bind_vertex_array(0);
             // vegetation
-            gl::bind_vertex_array(transparentvao);
-            gl::bind_texture(GL_TEXTURE_2D, transparent_texture);
+            gl::bind_vertex_array(transparentvao.id());
+            gl::bind_texture(GL_TEXTURE_2D, transparent_texture.id());
             for v in &vegetation {
                 let model = Matrix4::from_translation(*v);
                 shader.set_mat4("model", &model);
@@ -235,59 +256,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &cubevao);
-    // gl::delete_vertex_arrays(1, &planevao);
-    // gl::delete_vertex_arrays(1, &transparentvao);
-    // gl::delete_buffers(1, &cubevbo);
-    // gl::delete_buffers(1, &planevbo);
-    // gl::delete_buffers(1, &transparentvbo);
-}
-
-/// utility function for loading a 2D texture from file
-/// NOTE: not the version from common.rs, slightly adapted for this tutorial
-
-pub fn load_texture(path: PathBuf) -> u32 {
-    let texture_id = gl::gen_texture();
-    let path = path.as_path();
-
-    let img = image::open(&path).expect("Texture failed to load");
-    let format = match img {
-        ImageLuma8(_) => GL_RED,
-        ImageLumaA8(_) => GL_RG,
-        ImageRgb8(_) => GL_RGB,
-        ImageRgba8(_) => GL_RGBA,
-        _ => panic!("unhandled image format"),
-    };
-
-    let data = img.as_bytes();
-
-    gl::bind_texture(GL_TEXTURE_2D, texture_id);
-    gl::tex_image_2d(
-        GL_TEXTURE_2D,
-        0,
-        format as i32,
-        img.width() as i32,
-        img.height() as i32,
-        0,
-        format,
-        GL_UNSIGNED_BYTE,
-        &data,
-    );
-    gl::generate_mipmap(GL_TEXTURE_2D);
-
-    gl::tex_parameteri(
-        GL_TEXTURE_2D,
-        GL_TEXTURE_WRAP_S,
-        if format == GL_RGBA { GL_CLAMP_TO_EDGE } else { GL_REPEAT } as i32,
-    ); // for this tutorial: use GL_CLAMP_TO_EDGE to prevent semi-transparent borders. Due to interpolation it takes texels from next repeat );
-    gl::tex_parameteri(
-        GL_TEXTURE_2D,
-        GL_TEXTURE_WRAP_T,
-        if format == GL_RGBA { GL_CLAMP_TO_EDGE } else { GL_REPEAT } as i32,
-    );
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR_MIPMAP_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-
-    texture_id
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
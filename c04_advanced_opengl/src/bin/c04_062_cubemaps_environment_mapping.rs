@@ -1,4 +1,6 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{
+    process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader, Skybox, TextureCube,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, Deg, Matrix4, Point3};
 use dx::{
@@ -7,8 +9,6 @@ use dx::{
     utils::*,
     Instance,
 };
-use image::GenericImageView;
-use std::path::PathBuf;
 use std::{mem, time::SystemTime};
 use winit::{
     event::*,
@@ -54,7 +54,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, skybox_shader, cubevao, skyboxvao, cubemap_texture) = {
+    let (shader, skybox, cubevao, cubemap_texture, cubevbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -62,7 +62,6 @@ pub fn main() {
 
         // build and compile our shader program
         let shader = Shader::new(assets!("shaders/6.2.cubemaps.vs"), assets!("shaders/6.2.cubemaps.fs"));
-        let skybox_shader = Shader::new(assets!("shaders/6.2.skybox.vs"), assets!("shaders/6.2.skybox.fs"));
 
         // set up vertex data (and buffer(s)) and configure vertex attributes
 
@@ -80,21 +79,11 @@ pub fn main() {
             -1.0, 0.0, -0.5, 0.5, -0.5, 0.0, 1.0, 0.0, 0.5, 0.5, -0.5, 0.0, 1.0, 0.0, 0.5, 0.5, 0.5, 0.0, 1.0, 0.0,
             0.5, 0.5, 0.5, 0.0, 1.0, 0.0, -0.5, 0.5, 0.5, 0.0, 1.0, 0.0, -0.5, 0.5, -0.5, 0.0, 1.0, 0.0,
         ];
-        let skybox_vertices: [f32; 108] = [
-            // positions
-            -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0,
-            -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0,
-            -1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, -1.0,
-            1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0,
-            1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0,
-            -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
-        ];
-
         // cube vao
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
-        gl::bind_vertex_array(cubevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
+        gl::bind_vertex_array(cubevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &cube_vertices, GL_STATIC_DRAW);
         let stride = 6 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -102,16 +91,6 @@ pub fn main() {
         gl::enable_vertex_attrib_array(1);
         gl::vertex_attrib_pointer_offset(1, 3, GL_FLOAT, false, stride, 3 * mem::size_of::<f32>() as u32);
 
-        // skybox vao
-        let skyboxvao = gl::gen_vertex_array();
-        let skyboxvbo = gl::gen_buffer();
-        gl::bind_vertex_array(skyboxvao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, skyboxvbo);
-        gl::buffer_data(GL_ARRAY_BUFFER, &skybox_vertices, GL_STATIC_DRAW);
-        gl::enable_vertex_attrib_array(0);
-        let stride = 3 * mem::size_of::<f32>() as i32;
-        gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
-
         // load textures
 
         let faces = [
@@ -122,21 +101,21 @@ pub fn main() {
             assets!("textures/skybox/back.jpg"),
             assets!("textures/skybox/front.jpg"),
         ];
-        let cubemap_texture = load_cubemap(&faces);
+        let cubemap_texture = TextureCube::builder()
+            .load_faces(&faces)
+            .expect("Cubemap texture failed to load");
+        let skybox = Skybox::new();
 
         // shader configuration
 
         shader.use_program();
         shader.set_int("skybox", 0);
 
-        skybox_shader.use_program();
-        skybox_shader.set_int("skybox", 0);
-
-        (shader, skybox_shader, cubevao, skyboxvao, cubemap_texture)
+        (shader, skybox, cubevao, cubemap_texture, cubevbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, cubevbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -172,7 +151,7 @@ pub fn main() {
 
             shader.use_program();
             let model: Matrix4<f32> = Matrix4::identity();
-            let mut view = camera.get_view_matrix();
+            let view = camera.get_view_matrix();
             let projection: Matrix4<f32> =
                 perspective(Deg(camera.zoom), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
             shader.set_mat4("model", &model);
@@ -180,29 +159,13 @@ pub fn main() {
             shader.set_mat4("projection", &projection);
             shader.set_vector3("camera_pos", &camera.position.to_vec());
             // cubes
-            gl::bind_vertex_array(cubevao);
-            gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, cubemap_texture);
+            gl::bind_vertex_array(cubevao.id());
+            cubemap_texture.bind(0);
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
             gl::bind_vertex_array(0);
 
             // draw skybox as last
-            gl::depth_func(GL_LEQUAL); // change depth function so depth test passes when values are equal to depth buffer's content
-            skybox_shader.use_program();
-            // remove translation from the view matrix
-            view = camera.get_view_matrix();
-            view.w[0] = 0.0;
-            view.w[1] = 0.0;
-            view.w[2] = 0.0;
-            skybox_shader.set_mat4("view", &view);
-            skybox_shader.set_mat4("projection", &projection);
-            // skybox cube
-            gl::bind_vertex_array(skyboxvao);
-            gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_CUBE_MAP, cubemap_texture);
-            gl::draw_arrays(GL_TRIANGLES, 0, 36);
-            gl::bind_vertex_array(0);
-            gl::depth_func(GL_LESS); // set depth function back to default
+            skybox.draw(&cubemap_texture, &view, &projection);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
             swapchain.present(&surface);
@@ -215,50 +178,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &cubevao);
-    // gl::delete_vertex_arrays(1, &skyboxvao);
-    // gl::delete_buffers(1, &cubevbo);
-    // gl::delete_buffers(1, &skyboxvbo);
-}
-
-/// loads a cubemap texture from 6 individual texture faces
-/// order:
-/// +X (right)
-/// -X (left)
-/// +Y (top)
-/// -Y (bottom)
-/// +Z (front)
-/// -Z (back)
-
-fn load_cubemap(faces: &[PathBuf]) -> u32 {
-    let texture_id = gl::gen_texture();
-    gl::bind_texture(GL_TEXTURE_CUBE_MAP, texture_id);
-
-    for (i, face) in faces.iter().enumerate() {
-        let path = face.as_path();
-
-        let img = image::open(&path).expect("Cubemap texture failed to load");
-
-        let data = img.as_bytes();
-        gl::tex_image_2d(
-            GL_TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
-            0,
-            GL_RGB as i32,
-            img.width() as i32,
-            img.height() as i32,
-            0,
-            GL_RGB,
-            GL_UNSIGNED_BYTE,
-            &data,
-        );
-    }
-
-    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
-    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
-    gl::tex_parameteri(GL_TEXTURE_CUBE_MAP, GL_TEXTURE_WRAP_R, GL_CLAMP_TO_EDGE as i32);
-
-    texture_id
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
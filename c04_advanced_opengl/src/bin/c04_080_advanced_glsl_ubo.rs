@@ -1,4 +1,4 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader};
 // use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
@@ -52,7 +52,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader_red, shader_green, shader_blue, shader_yellow, _cubevbo, cubevao, ubo_matrices) = {
+    let (shader_red, shader_green, shader_blue, shader_yellow, cubevbo, cubevao, ubo_matrices) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -76,10 +76,10 @@ pub fn main() {
         ];
 
         // cube vao
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
-        gl::bind_vertex_array(cubevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
+        gl::bind_vertex_array(cubevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &cube_yertices, GL_STATIC_DRAW);
         let stride = 3 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -88,26 +88,32 @@ pub fn main() {
         // configure a uniform buffer object
 
         // first. We get the relevant block indices
-        let uniform_block_index_red = gl::get_uniform_block_index(shader_red.id, "Matrices");
-        let uniform_block_index_green = gl::get_uniform_block_index(shader_green.id, "Matrices");
-        let uniform_block_index_blue = gl::get_uniform_block_index(shader_blue.id, "Matrices");
-        let uniform_block_index_yellow = gl::get_uniform_block_index(shader_yellow.id, "Matrices");
+        let uniform_block_index_red = gl::get_uniform_block_index(shader_red.id(), "Matrices");
+        let uniform_block_index_green = gl::get_uniform_block_index(shader_green.id(), "Matrices");
+        let uniform_block_index_blue = gl::get_uniform_block_index(shader_blue.id(), "Matrices");
+        let uniform_block_index_yellow = gl::get_uniform_block_index(shader_yellow.id(), "Matrices");
         // then we link each shader's uniform block to this uniform binding point
-        gl::uniform_block_binding(shader_red.id, uniform_block_index_red, 0);
-        gl::uniform_block_binding(shader_green.id, uniform_block_index_green, 0);
-        gl::uniform_block_binding(shader_blue.id, uniform_block_index_blue, 0);
-        gl::uniform_block_binding(shader_yellow.id, uniform_block_index_yellow, 0);
+        gl::uniform_block_binding(shader_red.id(), uniform_block_index_red, 0);
+        gl::uniform_block_binding(shader_green.id(), uniform_block_index_green, 0);
+        gl::uniform_block_binding(shader_blue.id(), uniform_block_index_blue, 0);
+        gl::uniform_block_binding(shader_yellow.id(), uniform_block_index_yellow, 0);
 
         // Now actually create the buffer
-        let ubo_matrices = gl::gen_buffer();
-        gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices);
+        let ubo_matrices = GlBuffer::new();
+        gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices.id());
         gl::buffer_data_size(GL_UNIFORM_BUFFER, 0, GL_STATIC_DRAW);
         // define the range of the buffer that links to a uniform binding point
-        gl::bind_buffer_range(GL_UNIFORM_BUFFER, 0, ubo_matrices, 0, 2 * 2 * mem::size_of::<Matrix4<f32>>() as isize);
+        gl::bind_buffer_range(
+            GL_UNIFORM_BUFFER,
+            0,
+            ubo_matrices.id(),
+            0,
+            2 * 2 * mem::size_of::<Matrix4<f32>>() as isize,
+        );
 
         // store the projection matrix (we only do this once now) (note: we're not using zoom anymore by changing the FoV)
         let projection: Matrix4<f32> = perspective(Deg(45.0), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
-        gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices);
+        gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices.id());
         gl::buffer_sub_data(GL_UNIFORM_BUFFER, 0, &[projection]);
         gl::bind_buffer(GL_UNIFORM_BUFFER, 0);
 
@@ -115,7 +121,7 @@ pub fn main() {
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, cubevbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -154,14 +160,14 @@ pub fn main() {
 
             // set the view and projection matrix in the uniform block - we only have to do this once per loop iteration.
             let view = camera.get_view_matrix();
-            gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices);
+            gl::bind_buffer(GL_UNIFORM_BUFFER, ubo_matrices.id());
             let size = mem::size_of::<Matrix4<f32>>() as isize;
             gl::buffer_sub_data(GL_UNIFORM_BUFFER, size, &[view]);
             gl::bind_buffer(GL_UNIFORM_BUFFER, 0);
 
             // draw 4 cubes
             // RED
-            gl::bind_vertex_array(cubevao);
+            gl::bind_vertex_array(cubevao.id());
             shader_red.use_program();
             let mut model = Matrix4::from_translation(vec3(-0.75, 0.75, 0.0)); // move top-left
             shader_red.set_mat4("model", &model);
@@ -194,7 +200,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &cubevao);
-    // gl::delete_buffers(1, &cubevbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
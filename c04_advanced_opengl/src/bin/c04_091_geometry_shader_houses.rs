@@ -1,4 +1,4 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader};
 use cgmath::Point3;
 use dx::{
     assets,
@@ -51,7 +51,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, vao) = {
+    let (shader, vao, vbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -73,10 +73,10 @@ pub fn main() {
         ];
 
         // cube vao
-        let vao = gl::gen_vertex_array();
-        let vbo = gl::gen_buffer();
-        gl::bind_vertex_array(vao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        let vao = GlVertexArray::new();
+        let vbo = GlBuffer::new();
+        gl::bind_vertex_array(vao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &points, GL_STATIC_DRAW);
         let stride = 5 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -85,11 +85,11 @@ pub fn main() {
         gl::vertex_attrib_pointer_offset(1, 3, GL_FLOAT, false, stride, 2 * mem::size_of::<f32>() as u32);
         gl::bind_vertex_array(0);
 
-        (shader, vao)
+        (shader, vao, vbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, vbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -124,7 +124,7 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
 
             shader.use_program();
-            gl::bind_vertex_array(vao);
+            gl::bind_vertex_array(vao.id());
             gl::draw_arrays(GL_POINTS, 0, 4);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -138,7 +138,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    //     gl::delete_vertex_arrays(1, &vao);
-    //     gl::delete_buffers(1, &vbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -1,4 +1,4 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Model, Shader};
+use c04_advanced_opengl::{process_events, process_input, run_event_loop, Camera, Model, Shader};
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
     assets,
@@ -64,13 +64,13 @@ pub fn main() {
         );
 
         // load models
-        let nano_suit = Model::new(assets!("objects/nanosuit/nanosuit.obj"));
+        let nano_suit = Model::new(assets!("objects/nanosuit/nanosuit.obj")).expect("Failed to load model");
 
         (shader, nano_suit)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
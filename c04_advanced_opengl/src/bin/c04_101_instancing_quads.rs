@@ -1,4 +1,4 @@
-use c04_advanced_opengl::Shader;
+use c04_advanced_opengl::{run_event_loop, GlBuffer, GlVertexArray, Shader};
 use cgmath::Vector2;
 use dx::{
     assets,
@@ -35,7 +35,7 @@ pub fn main() {
     };
     let swapchain = device.create_swap_chain(&surface, &desc);
 
-    let (shader, quadvao) = {
+    let (shader, quadvao, instancevbo, quadvbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -56,8 +56,8 @@ pub fn main() {
             }
         }
 
-        let instancevbo = gl::gen_buffer();
-        gl::bind_buffer(GL_ARRAY_BUFFER, instancevbo);
+        let instancevbo = GlBuffer::new();
+        gl::bind_buffer(GL_ARRAY_BUFFER, instancevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &translations, GL_STATIC_DRAW);
         gl::bind_buffer(GL_ARRAY_BUFFER, 0);
 
@@ -69,10 +69,10 @@ pub fn main() {
             0.0, 0.05, -0.05, 0.0, 1.0, 0.0, 0.05, 0.05, 0.0, 1.0, 1.0,
         ];
 
-        let quadvao = gl::gen_vertex_array();
-        let quadvbo = gl::gen_buffer();
-        gl::bind_vertex_array(quadvao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo);
+        let quadvao = GlVertexArray::new();
+        let quadvbo = GlBuffer::new();
+        gl::bind_vertex_array(quadvao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &quad_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         let stride = 5 * mem::size_of::<f32>() as i32;
@@ -81,16 +81,16 @@ pub fn main() {
         gl::vertex_attrib_pointer_offset(1, 3, GL_FLOAT, false, stride, 2 * mem::size_of::<f32>() as u32);
         // also set instance data
         gl::enable_vertex_attrib_array(2);
-        gl::bind_buffer(GL_ARRAY_BUFFER, instancevbo); // this attribute comes from a different vertex buffer
+        gl::bind_buffer(GL_ARRAY_BUFFER, instancevbo.id()); // this attribute comes from a different vertex buffer
         gl::vertex_attrib_pointer_offset(2, 2, GL_FLOAT, false, (2 * mem::size_of::<f32>()) as i32, 0);
         gl::bind_buffer(GL_ARRAY_BUFFER, 0);
         gl::vertex_attrib_divisor(2, 1); // tell OpenGL this is an instanced vertex attribute.
 
-        (shader, quadvao)
+        (shader, quadvao, instancevbo, quadvbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (instancevbo, quadvbo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -115,7 +115,7 @@ pub fn main() {
 
             // draw 100 instanced quads
             shader.use_program();
-            gl::bind_vertex_array(quadvao);
+            gl::bind_vertex_array(quadvao.id());
             gl::draw_arrays_instanced(GL_TRIANGLES, 0, 6, 100); // 100 triangles of 6 vertices each
             gl::bind_vertex_array(0);
 
@@ -130,7 +130,5 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &quadvao);
-    // gl::delete_buffers(1, &quadvbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
@@ -1,6 +1,9 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Model, Shader};
+use c04_advanced_opengl::{
+    process_events, process_input, run_event_loop, Camera, Component, LodCamera, LodOptions, LodSelector, Model,
+    ModelOptions, Scene, Shader, Transform,
+};
 use cgmath::prelude::*;
-use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
+use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Quaternion};
 use dx::{
     assets,
     gles::{core30::gl, enums::*},
@@ -8,7 +11,7 @@ use dx::{
     Instance,
 };
 use rand::Rng;
-use std::time::SystemTime;
+use std::{rc::Rc, time::SystemTime};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -53,23 +56,45 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, rock, planet, model_matrices) = {
+    let (shader, mut scene) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
 
         // build and compile shaders
-        let shader = Shader::new(assets!("shaders/10.2.instancing.vs"), assets!("shaders/10.2.instancing.fs"));
+        let shader = Shader::new(
+            assets!("shaders/10.2.instancing.vs"),
+            assets!("shaders/10.2.instancing.fs"),
+        );
 
         // load models
 
-        let rock = Model::new(assets!("objects/rock/rock.obj"));
-        let planet = Model::new(assets!("objects/planet/planet.obj"));
+        // rocks are small and numerous: simplified versions are drawn for the distant ones
+        let rock_options = ModelOptions {
+            lods: Some(LodOptions::default()),
+            ..ModelOptions::default()
+        };
+        let rock =
+            Rc::new(Model::with_options(assets!("objects/rock/rock.obj"), rock_options).expect("Failed to load model"));
+        let planet = Rc::new(Model::new(assets!("objects/planet/planet.obj")).expect("Failed to load model"));
+
+        // build the scene: the planet and an asteroid belt node holding all the rocks
+        let mut scene = Scene::new();
+        let planet_node = scene.add_node("planet", None);
+        scene.set_transform(
+            planet_node,
+            Transform {
+                translation: vec3(0.0, -3.0, 0.0),
+                scale: vec3(4.0, 4.0, 4.0),
+                ..Transform::default()
+            },
+        );
+        scene.add_component(planet_node, Component::Model(planet));
+        let belt_node = scene.add_node("asteroid belt", None);
 
-        // generate a large list of semi-random model transformation matrices
+        // generate a large list of semi-random rock transformations
 
         let amount = 1000;
-        let mut model_matrices: Vec<Matrix4<f32>> = Vec::with_capacity(amount);
         let mut rng = rand::thread_rng();
         let radius = 50.0;
         let offset: f32 = 2.5;
@@ -81,25 +106,33 @@ pub fn main() {
             let y = displacement * 0.4; // keep height of asteroid field smaller compared to width of x and z
             displacement = (rng.gen::<i32>() % (2.0 * offset * 100.0) as i32) as f32 / 100.0 - offset;
             let z = angle.cos() * radius + displacement;
-            let mut model = Matrix4::<f32>::from_translation(vec3(x, y, z));
 
             // 2. scale: Scale between 0.05 and 0.25
             let scale = (rng.gen::<i32>() % 20) as f32 / 100.0 + 0.05;
-            model = model * Matrix4::from_scale(scale);
 
             // 3. rotation: add random rotation around a (semi)randomly picked rotation axis vector
             let rot_angle = (rng.gen::<i32>() % 360) as f32;
-            model = model * Matrix4::from_axis_angle(vec3(0.4, 0.6, 0.8).normalize(), Deg(rot_angle));
 
-            // 4. now add to list of matrices
-            model_matrices.push(model);
+            // 4. now add the rock to the belt
+            let rock_node = scene.add_node("rock", Some(belt_node));
+            scene.set_transform(
+                rock_node,
+                Transform {
+                    translation: vec3(x, y, z),
+                    rotation: Quaternion::from_axis_angle(vec3(0.4, 0.6, 0.8).normalize(), Deg(rot_angle)),
+                    scale: vec3(scale, scale, scale),
+                },
+            );
+            scene.add_component(rock_node, Component::Model(rock.clone()));
         }
 
-        (shader, rock, planet, model_matrices)
+        (shader, scene)
     };
 
+    let lod_selector = LodSelector::default();
+
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -141,17 +174,9 @@ pub fn main() {
             shader.set_mat4("projection", &projection);
             shader.set_mat4("view", &view);
 
-            // draw planet
-            let mut model = Matrix4::<f32>::from_translation(vec3(0.0, -3.0, 0.0));
-            model = model * Matrix4::from_scale(4.0);
-            shader.set_mat4("model", &model);
-            planet.draw(&shader);
-
-            // draw meteorites
-            for model in &model_matrices {
-                shader.set_mat4("model", model);
-                rock.draw(&shader);
-            }
+            // draw planet and meteorites, at the level of detail their distance needs
+            let lod_camera = LodCamera::new(camera.position, Deg(camera.zoom), SCR_HEIGHT as f32);
+            scene.draw_with_lod(&shader, &lod_camera, &lod_selector);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
             swapchain.present(&surface);
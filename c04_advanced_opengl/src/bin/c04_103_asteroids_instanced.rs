@@ -1,6 +1,9 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Model, Shader};
+use c04_advanced_opengl::{
+    process_events, process_input, run_event_loop, AssetLoader, Camera, InstanceBuffer, InstanceTransform, ModelOptions,
+    Shader,
+};
 use cgmath::prelude::*;
-use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector4};
+use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
     assets,
     gles::{core30::gl, enums::*},
@@ -8,7 +11,7 @@ use dx::{
     Instance,
 };
 use rand::Rng;
-use std::{mem, time::SystemTime};
+use std::time::{Duration, SystemTime};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -19,6 +22,9 @@ use winit::{
 const SCR_WIDTH: u32 = 1280;
 const SCR_HEIGHT: u32 = 720;
 
+// time per frame spent creating GL objects for the models loaded in the background
+const UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+
 pub fn main() {
     let start_time = SystemTime::now();
     let event_loop = EventLoop::new();
@@ -54,24 +60,29 @@ pub fn main() {
     // window.set_cursor_mode(CursorMode::Disabled);
 
     #[allow(unused_variables)]
-    let (asteroid_shader, planet_shader, rock, planet, amount) = {
+    let (asteroid_shader, planet_shader, mut loader, rock, planet, placeholder, instances) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
 
         // build and compile shaders
-        let asteroid_shader = Shader::new(assets!("shaders/10.3.asteroids.vs"), assets!("shaders/10.3.asteroids.fs"));
+        let asteroid_shader = Shader::new(
+            assets!("shaders/10.3.asteroids.vs"),
+            assets!("shaders/10.3.asteroids.fs"),
+        );
         let planet_shader = Shader::new(assets!("shaders/10.3.planet.vs"), assets!("shaders/10.3.planet.fs"));
 
-        // load models
+        // load models in the background; a placeholder cube is drawn until they are ready
 
-        let rock = Model::new(assets!("objects/rock/rock.obj"));
-        let planet = Model::new(assets!("objects/planet/planet.obj"));
+        let mut loader = AssetLoader::new(2);
+        let rock = loader.load_model(assets!("objects/rock/rock.obj"), ModelOptions::default());
+        let planet = loader.load_model(assets!("objects/planet/planet.obj"), ModelOptions::default());
+        let placeholder = loader.placeholder_model();
 
         // generate a large list of semi-random model transformation matrices
 
         let amount = 100_000;
-        let mut model_matrices: Vec<Matrix4<f32>> = Vec::with_capacity(amount);
+        let mut model_matrices: Vec<InstanceTransform> = Vec::with_capacity(amount);
         let mut rng = rand::thread_rng();
         let radius = 150.0;
         let offset: f32 = 25.0;
@@ -94,46 +105,26 @@ pub fn main() {
             model = model * Matrix4::from_axis_angle(vec3(0.4, 0.6, 0.8).normalize(), Deg(rot_angle));
 
             // 4. now add to list of matrices
-            model_matrices.push(model);
+            model_matrices.push(InstanceTransform { model });
         }
 
-        // configure instanced array
-
-        let buffer = gl::gen_buffer();
-        gl::bind_buffer(GL_ARRAY_BUFFER, buffer);
-        gl::buffer_data(GL_ARRAY_BUFFER, &model_matrices.as_slice(), GL_STATIC_DRAW);
-
-        // set transformation matrices as an instance vertex attribute (with divisor 1)
-        // note: we're cheating a little by taking the, now publicly declared, vao of the model's mesh(es) and adding new vertexAttribPointers
-        // normally you'd want to do this in a more organized fashion, but for learning purposes this will do.
-        let size_mat4 = mem::size_of::<Matrix4<f32>>() as i32;
-        let size_vec4 = mem::size_of::<Vector4<f32>>() as u32;
-        for mesh in &rock.meshes {
-            let vao = mesh.vao;
-            gl::bind_vertex_array(vao);
-            // set attribute pointers for matrix (4 times vec4)
-            gl::enable_vertex_attrib_array(3);
-            gl::vertex_attrib_pointer_offset(3, 4, GL_FLOAT, false, size_mat4, 0);
-            gl::enable_vertex_attrib_array(4);
-            gl::vertex_attrib_pointer_offset(4, 4, GL_FLOAT, false, size_mat4, size_vec4);
-            gl::enable_vertex_attrib_array(5);
-            gl::vertex_attrib_pointer_offset(5, 4, GL_FLOAT, false, size_mat4, 2 * size_vec4);
-            gl::enable_vertex_attrib_array(6);
-            gl::vertex_attrib_pointer_offset(6, 4, GL_FLOAT, false, size_mat4, 3 * size_vec4);
-
-            gl::vertex_attrib_divisor(3, 1);
-            gl::vertex_attrib_divisor(4, 1);
-            gl::vertex_attrib_divisor(5, 1);
-            gl::vertex_attrib_divisor(6, 1);
-
-            gl::bind_vertex_array(0);
-        }
+        // configure instanced array: the matrices go to the shader's aInstanceMatrix, one per instance
 
-        (asteroid_shader, planet_shader, rock, planet, amount)
+        let instances = InstanceBuffer::from_slice(&model_matrices);
+
+        (
+            asteroid_shader,
+            planet_shader,
+            loader,
+            rock,
+            planet,
+            placeholder,
+            instances,
+        )
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -164,6 +155,11 @@ pub fn main() {
             // redraw here for not active games like a RPG or RTS
             // per-frame time logic
 
+            // create the GL objects of models loaded in the meantime
+            loader.update(UPLOAD_BUDGET);
+            let rock = rock.get_or(&placeholder);
+            let planet = planet.get_or(&placeholder);
+
             gl::clear_color(0.1, 0.1, 0.1, 1.0);
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
 
@@ -185,21 +181,7 @@ pub fn main() {
 
             // draw meteorites
             asteroid_shader.use_program();
-            asteroid_shader.set_int("texture_diffuse1", 0);
-            gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, rock.textures_loaded[0].id); // note: we also made the textures_loaded vector public (instead of private) from the model class.
-
-            for mesh in &rock.meshes {
-                gl::bind_vertex_array(mesh.vao);
-                gl::draw_elements_instanced_offset(
-                    GL_TRIANGLES,
-                    mesh.indices.len() as i32,
-                    GL_UNSIGNED_INT,
-                    0,
-                    amount as i32,
-                );
-                gl::bind_vertex_array(0);
-            }
+            rock.draw_instanced(&asteroid_shader, &instances);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
             swapchain.present(&surface);
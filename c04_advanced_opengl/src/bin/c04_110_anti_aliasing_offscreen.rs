@@ -1,4 +1,6 @@
-use c04_advanced_opengl::{process_events, process_input, Camera, Shader};
+use c04_advanced_opengl::{
+    process_events, process_input, run_event_loop, Attachment, Camera, GlBuffer, GlVertexArray, RenderTarget, Shader,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, Deg, Matrix4, Point3};
 use dx::{
@@ -56,7 +58,7 @@ pub fn main() {
 
     // query framebuffer size as it might be quite different from the requested size on Retina displays
     // let (scr_width, scr_height) = window.get_framebuffer_size();
-    let (scr_width, scr_height) = (SCR_WIDTH as i32, SCR_HEIGHT as i32);
+    let (mut scr_width, mut scr_height) = (SCR_WIDTH, SCR_HEIGHT);
 
     // window.make_current();
     // window.set_framebuffer_size_polling(true);
@@ -66,7 +68,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, screen_shader, cubevao, quadvao, framebuffer, intermediate_fbo, screen_texture) = {
+    let (shader, screen_shader, cubevao, quadvao, mut framebuffer, mut intermediate_fbo, cubevbo, quadvbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -95,20 +97,20 @@ pub fn main() {
         ];
 
         // setup cube vao
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
-        gl::bind_vertex_array(cubevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
+        gl::bind_vertex_array(cubevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &cube_vertices, GL_STATIC_DRAW);
         let stride = 3 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
 
         // setup screen vao
-        let quadvao = gl::gen_vertex_array();
-        let quadvbo = gl::gen_buffer();
-        gl::bind_vertex_array(quadvbo);
-        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo);
+        let quadvao = GlVertexArray::new();
+        let quadvbo = GlBuffer::new();
+        gl::bind_vertex_array(quadvbo.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &quad_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         let stride = 4 * mem::size_of::<f32>() as i32;
@@ -116,60 +118,30 @@ pub fn main() {
         gl::enable_vertex_attrib_array(1);
         gl::vertex_attrib_pointer_offset(1, 2, GL_FLOAT, false, stride, 2 * mem::size_of::<f32>() as u32);
 
-        // configure MSAA framebuffer
-
-        let framebuffer = gl::gen_framebuffer();
-        gl::bind_framebuffer(GL_FRAMEBUFFER, framebuffer);
-        // create a multisampled color attachment texture
-        let texture_color_buffer_multisampled = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D_MULTISAMPLE, texture_color_buffer_multisampled);
-
-        // core 3.1
-        // gl::TexImage2dMultisample(GL_TEXTURE_2D_MULTISAMPLE, 4, GL_RGB, scr_width, scr_height, GL_TRUE); //DV
-        gl::bind_texture(GL_TEXTURE_2D_MULTISAMPLE, 0);
-        gl::framebuffer_texture_2d(
-            GL_FRAMEBUFFER,
-            GL_COLOR_ATTACHMENT0,
-            GL_TEXTURE_2D_MULTISAMPLE,
-            texture_color_buffer_multisampled,
-            0,
-        );
-        // create a (also multisampled) renderbuffer object for depth and stencil attachments
-        let rbo = gl::gen_renderbuffer();
-        gl::bind_renderbuffer(GL_RENDERBUFFER, rbo);
-        gl::renderbuffer_storage_multisample(GL_RENDERBUFFER, 4, GL_DEPTH24_STENCIL8, scr_width, scr_height);
-        gl::bind_renderbuffer(GL_RENDERBUFFER, 0);
-
-        if gl::check_framebuffer_status(GL_FRAMEBUFFER) != GL_FRAMEBUFFER_COMPLETE {
-            println!("ERROR::FRAMEBUFFER:: Framebuffer is not complete!");
-        }
-        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
-
-        // configure second post-processing framebuffer
-        let intermediate_fbo = gl::gen_framebuffer();
-        gl::bind_framebuffer(GL_FRAMEBUFFER, intermediate_fbo);
-
-        // create a color attachment texture
-        let screen_texture = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, screen_texture);
-        gl::empty_tex_image_2d(GL_TEXTURE_2D, 0, GL_RGB as i32, scr_width, scr_height, 0, GL_RGB, GL_UNSIGNED_BYTE);
-        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-        gl::framebuffer_texture_2d(GL_FRAMEBUFFER, GL_COLOR_ATTACHMENT0, GL_TEXTURE_2D, screen_texture, 0); // we only need a color buffer
-
-        if gl::check_framebuffer_status(GL_FRAMEBUFFER) != GL_FRAMEBUFFER_COMPLETE {
-            println!("ERROR::FRAMEBUFFER:: Intermediate framebuffer is not complete!");
-        }
-        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+        // configure MSAA framebuffer: a multisampled color attachment and a (also
+        // multisampled) depth and stencil attachment. OpenGL ES 3.0 has no multisampled
+        // textures, so both are renderbuffers.
+        let framebuffer = RenderTarget::builder()
+            .color(Attachment::renderbuffer(GL_RGB8))
+            .depth(Attachment::renderbuffer(GL_DEPTH24_STENCIL8))
+            .samples(4)
+            .build(scr_width, scr_height)
+            .expect("Framebuffer is not complete");
+
+        // configure second post-processing framebuffer; we only need a color buffer
+        let intermediate_fbo = RenderTarget::builder()
+            .color(Attachment::texture(GL_RGB8))
+            .build(scr_width, scr_height)
+            .expect("Intermediate framebuffer is not complete");
 
         screen_shader.use_program();
         screen_shader.set_int("screenTexture", 0);
 
-        (shader, screen_shader, cubevao, quadvao, framebuffer, intermediate_fbo, screen_texture)
+        (shader, screen_shader, cubevao, quadvao, framebuffer, intermediate_fbo, cubevbo, quadvbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (cubevbo, quadvbo), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -190,6 +162,15 @@ pub fn main() {
                 // make sure the viewport matches the new window dimensions; note that width and
                 // height will be significantly larger than specified on retina displays.
                 gl::viewport(0, 0, physical_size.width as i32, physical_size.height as i32);
+                scr_width = physical_size.width;
+                scr_height = physical_size.height;
+                // both offscreen framebuffers follow the window
+                framebuffer
+                    .resize(scr_width, scr_height)
+                    .expect("Framebuffer is not complete");
+                intermediate_fbo
+                    .resize(scr_width, scr_height)
+                    .expect("Intermediate framebuffer is not complete");
             }
             _ => {
                 // events
@@ -206,48 +187,35 @@ pub fn main() {
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
 
             // 1. draw scene as normal in multisampled buffers
-            gl::bind_framebuffer(GL_FRAMEBUFFER, framebuffer);
+            framebuffer.bind();
+            gl::enable(GL_DEPTH_TEST);
             gl::clear_color(0.1, 0.1, 0.1, 1.0);
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
 
             // set transformation matrices
             shader.use_program();
             let projection: Matrix4<f32> =
-                perspective(Deg(camera.zoom), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
+                perspective(Deg(camera.zoom), scr_width as f32 / scr_height as f32, 0.1, 100.0);
             shader.set_mat4("projection", &projection);
             shader.set_mat4("view", &camera.get_view_matrix());
             shader.set_mat4("model", &Matrix4::identity());
 
-            gl::bind_vertex_array(cubevao);
+            gl::bind_vertex_array(cubevao.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
 
             // 2. now blit multisampled buffer(s) to normal colorbuffer of intermediate FBO. Image is stored in screenTexture
-            gl::bind_framebuffer(GL_READ_FRAMEBUFFER, framebuffer);
-            gl::bind_framebuffer(GL_DRAW_FRAMEBUFFER, intermediate_fbo);
-            gl::blit_framebuffer(
-                0,
-                0,
-                scr_width,
-                scr_height,
-                0,
-                0,
-                scr_width,
-                scr_height,
-                GL_COLOR_BUFFER_BIT,
-                GL_NEAREST,
-            );
+            framebuffer.resolve(&intermediate_fbo);
 
             // 3. now render quad with scene's visuals as its texture image
-            gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+            RenderTarget::bind_default(scr_width, scr_height);
             gl::clear_color(1.0, 1.0, 1.0, 1.0);
             gl::clear(GL_COLOR_BUFFER_BIT);
             gl::disable(GL_DEPTH_TEST);
 
             // draw Screen quad
             screen_shader.use_program();
-            gl::bind_vertex_array(quadvao);
-            gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, screen_texture); // use the now resolved color attachment as the quad's texture
+            gl::bind_vertex_array(quadvao.id());
+            intermediate_fbo.bind_color_texture(0, 0); // use the now resolved color attachment as the quad's texture
             gl::draw_arrays(GL_TRIANGLES, 0, 6);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
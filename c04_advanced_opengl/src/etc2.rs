@@ -0,0 +1,557 @@
+use std::convert::TryInto;
+
+/// The ETC2 and EAC block formats of OpenGL ES 3.0, see `decode_etc2`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Etc2Format {
+    /// `GL_COMPRESSED_RGB8_ETC2`, which also decodes ETC1
+    Rgb,
+    /// `GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2`
+    RgbA1,
+    /// `GL_COMPRESSED_RGBA8_ETC2_EAC`
+    Rgba,
+    /// `GL_COMPRESSED_R11_EAC`
+    R11,
+    /// `GL_COMPRESSED_SIGNED_R11_EAC`
+    SignedR11,
+    /// `GL_COMPRESSED_RG11_EAC`
+    Rg11,
+    /// `GL_COMPRESSED_SIGNED_RG11_EAC`
+    SignedRg11,
+}
+
+impl Etc2Format {
+    /// bytes per 4x4 block
+    pub fn block_size(self) -> usize {
+        match self {
+            Etc2Format::Rgb | Etc2Format::RgbA1 | Etc2Format::R11 | Etc2Format::SignedR11 => 8,
+            Etc2Format::Rgba | Etc2Format::Rg11 | Etc2Format::SignedRg11 => 16,
+        }
+    }
+
+    /// channels of the decoded texels
+    pub fn channels(self) -> usize {
+        match self {
+            Etc2Format::R11 | Etc2Format::SignedR11 => 1,
+            Etc2Format::Rg11 | Etc2Format::SignedRg11 => 2,
+            Etc2Format::Rgb => 3,
+            Etc2Format::RgbA1 | Etc2Format::Rgba => 4,
+        }
+    }
+
+    /// the decoded texels are `i8` (stored as their bytes) rather than `u8`
+    pub fn signed(self) -> bool {
+        matches!(self, Etc2Format::SignedR11 | Etc2Format::SignedRg11)
+    }
+}
+
+/// Decodes an ETC2/EAC image of `width` x `height` texels into tightly packed rows of
+/// 8-bit texels with `format.channels()` channels. The 11-bit EAC channels lose their
+/// low bits. `data` holds the 4x4 blocks row by row, as in a KTX file or for
+/// `glCompressedTexImage2D`.
+pub fn decode_etc2(format: Etc2Format, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let block_size = format.block_size();
+    assert!(data.len() >= blocks_x * blocks_y * block_size, "ETC2 data too short");
+
+    let channels = format.channels();
+    let mut texels = vec![0u8; width * height * channels];
+    let mut block_texels = [[0u8; 4]; 16];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let offset = (by * blocks_x + bx) * block_size;
+            let block = &data[offset..offset + block_size];
+            decode_block(format, block, &mut block_texels);
+
+            // blocks on the right and bottom edge may stick out of the image
+            for y in 0..4.min(height - by * 4) {
+                for x in 0..4.min(width - bx * 4) {
+                    let dst = ((by * 4 + y) * width + bx * 4 + x) * channels;
+                    texels[dst..dst + channels].copy_from_slice(&block_texels[y * 4 + x][..channels]);
+                }
+            }
+        }
+    }
+    texels
+}
+
+// texels of one block, row by row
+fn decode_block(format: Etc2Format, block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let word = |offset: usize| u64::from_be_bytes(block[offset..offset + 8].try_into().unwrap());
+    match format {
+        Etc2Format::Rgb => decode_color(word(0), false, texels),
+        Etc2Format::RgbA1 => decode_color(word(0), true, texels),
+        Etc2Format::Rgba => {
+            decode_color(word(8), false, texels);
+            let alpha = decode_eac(word(0));
+            for (texel, alpha) in texels.iter_mut().zip(alpha.iter()) {
+                texel[3] = *alpha as u8;
+            }
+        }
+        Etc2Format::R11 | Etc2Format::SignedR11 | Etc2Format::Rg11 | Etc2Format::SignedRg11 => {
+            let signed = format.signed();
+            for channel in 0..format.channels() {
+                let values = decode_eac11(word(channel * 8), signed);
+                for (texel, value) in texels.iter_mut().zip(values.iter()) {
+                    texel[channel] = *value;
+                }
+            }
+        }
+    }
+}
+
+// the intensity modifiers of the individual and differential modes
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+// the distances of the T and H modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+fn bits(word: u64, high: u32, low: u32) -> i32 {
+    ((word >> low) & ((1 << (high - low + 1)) - 1)) as i32
+}
+
+fn extend4(value: i32) -> i32 {
+    value * 17
+}
+
+fn extend5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend6(value: i32) -> i32 {
+    (value << 2) | (value >> 4)
+}
+
+fn extend7(value: i32) -> i32 {
+    (value << 1) | (value >> 6)
+}
+
+fn clamp_color(color: [i32; 3]) -> [u8; 4] {
+    [
+        color[0].clamp(0, 255) as u8,
+        color[1].clamp(0, 255) as u8,
+        color[2].clamp(0, 255) as u8,
+        255,
+    ]
+}
+
+// 2-bit index of the texel at (x, y); the indices are stored column by column, the
+// high bits of all texels before the low bits
+fn texel_index(word: u64, x: usize, y: usize) -> usize {
+    let bit = x * 4 + y;
+    ((((word >> (bit + 16)) & 1) << 1) | ((word >> bit) & 1)) as usize
+}
+
+// ETC2 RGB block with punch-through alpha if `punchthrough`: there the differential bit
+// is the opaque bit and there is no individual mode
+fn decode_color(word: u64, punchthrough: bool, texels: &mut [[u8; 4]; 16]) {
+    let diff_bit = (word >> 33) & 1 == 1;
+    let differential = punchthrough || diff_bit;
+    let transparent = punchthrough && !diff_bit;
+
+    if !differential {
+        let base = [
+            [bits(word, 63, 60), bits(word, 55, 52), bits(word, 47, 44)].map(extend4),
+            [bits(word, 59, 56), bits(word, 51, 48), bits(word, 43, 40)].map(extend4),
+        ];
+        decode_subblocks(word, base, false, texels);
+        return;
+    }
+
+    let r = bits(word, 63, 59);
+    let g = bits(word, 55, 51);
+    let b = bits(word, 47, 43);
+    // 3-bit two's complement deltas
+    let delta = |value: i32| if value >= 4 { value - 8 } else { value };
+    let r2 = r + delta(bits(word, 58, 56));
+    let g2 = g + delta(bits(word, 50, 48));
+    let b2 = b + delta(bits(word, 42, 40));
+
+    if !(0..32).contains(&r2) {
+        decode_t(word, transparent, texels);
+    } else if !(0..32).contains(&g2) {
+        decode_h(word, transparent, texels);
+    } else if !(0..32).contains(&b2) {
+        decode_planar(word, texels);
+    } else {
+        let base = [[r, g, b].map(extend5), [r2, g2, b2].map(extend5)];
+        decode_subblocks(word, base, transparent, texels);
+    }
+}
+
+// individual and differential mode: two 2x4 or 4x2 subblocks with a base color and
+// modifier table each
+fn decode_subblocks(word: u64, base: [[i32; 3]; 2], transparent: bool, texels: &mut [[u8; 4]; 16]) {
+    let flip = (word >> 32) & 1 == 1;
+    let tables = [bits(word, 39, 37) as usize, bits(word, 36, 34) as usize];
+    for y in 0..4 {
+        for x in 0..4 {
+            let subblock = if flip { y / 2 } else { x / 2 };
+            let [small, large] = MODIFIERS[tables[subblock]];
+            let index = texel_index(word, x, y);
+            if transparent && index == 2 {
+                texels[y * 4 + x] = [0; 4];
+                continue;
+            }
+            let modifier = match index {
+                // without the opaque bit the small modifiers are 0
+                0 if transparent => 0,
+                0 => small,
+                1 => large,
+                2 => -small,
+                _ => -large,
+            };
+            texels[y * 4 + x] = clamp_color(base[subblock].map(|c| c + modifier));
+        }
+    }
+}
+
+// paint colors selected directly by the texel indices, index 2 transparent without the
+// opaque bit
+fn paint(word: u64, paint_colors: [[i32; 3]; 4], transparent: bool, texels: &mut [[u8; 4]; 16]) {
+    for y in 0..4 {
+        for x in 0..4 {
+            let index = texel_index(word, x, y);
+            texels[y * 4 + x] = if transparent && index == 2 {
+                [0; 4]
+            } else {
+                clamp_color(paint_colors[index])
+            };
+        }
+    }
+}
+
+fn decode_t(word: u64, transparent: bool, texels: &mut [[u8; 4]; 16]) {
+    let c1 = [
+        (bits(word, 60, 59) << 2) | bits(word, 57, 56),
+        bits(word, 55, 52),
+        bits(word, 51, 48),
+    ]
+    .map(extend4);
+    let c2 = [bits(word, 47, 44), bits(word, 43, 40), bits(word, 39, 36)].map(extend4);
+    let d = DISTANCES[((bits(word, 35, 34) << 1) | bits(word, 32, 32)) as usize];
+    paint(
+        word,
+        [c1, c2.map(|c| c + d), c2, c2.map(|c| c - d)],
+        transparent,
+        texels,
+    );
+}
+
+fn decode_h(word: u64, transparent: bool, texels: &mut [[u8; 4]; 16]) {
+    let c1 = [
+        bits(word, 62, 59),
+        (bits(word, 58, 56) << 1) | bits(word, 52, 52),
+        (bits(word, 51, 51) << 3) | bits(word, 49, 47),
+    ];
+    let c2 = [bits(word, 46, 43), bits(word, 42, 39), bits(word, 38, 35)];
+    // the order of the two colors holds the lowest bit of the distance index
+    let value = |c: [i32; 3]| (c[0] << 8) | (c[1] << 4) | c[2];
+    let index = (bits(word, 34, 34) << 2) | (bits(word, 32, 32) << 1) | (value(c1) >= value(c2)) as i32;
+    let d = DISTANCES[index as usize];
+    let (c1, c2) = (c1.map(extend4), c2.map(extend4));
+    paint(
+        word,
+        [
+            c1.map(|c| c + d),
+            c1.map(|c| c - d),
+            c2.map(|c| c + d),
+            c2.map(|c| c - d),
+        ],
+        transparent,
+        texels,
+    );
+}
+
+// a color gradient over the block, always opaque
+fn decode_planar(word: u64, texels: &mut [[u8; 4]; 16]) {
+    let origin = [
+        extend6(bits(word, 62, 57)),
+        extend7((bits(word, 56, 56) << 6) | bits(word, 54, 49)),
+        extend6((bits(word, 48, 48) << 5) | (bits(word, 44, 43) << 3) | bits(word, 41, 39)),
+    ];
+    let horizontal = [
+        extend6((bits(word, 38, 34) << 1) | bits(word, 32, 32)),
+        extend7(bits(word, 31, 25)),
+        extend6(bits(word, 24, 19)),
+    ];
+    let vertical = [
+        extend6(bits(word, 18, 13)),
+        extend7(bits(word, 12, 6)),
+        extend6(bits(word, 5, 0)),
+    ];
+    for y in 0..4 {
+        for x in 0..4 {
+            let mut color = [0; 3];
+            for c in 0..3 {
+                color[c] =
+                    (x as i32 * (horizontal[c] - origin[c]) + y as i32 * (vertical[c] - origin[c]) + 4 * origin[c] + 2)
+                        >> 2;
+            }
+            texels[y * 4 + x] = clamp_color(color);
+        }
+    }
+}
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// the fields of an EAC block: base codeword, multiplier, and the modifier of every texel,
+// row by row (the 3-bit indices are stored column by column)
+fn eac_fields(word: u64) -> (i32, i32, [i32; 16]) {
+    let table = &EAC_MODIFIERS[bits(word, 51, 48) as usize];
+    let mut modifiers = [0; 16];
+    for y in 0..4 {
+        for x in 0..4 {
+            let bit = 45 - 3 * (x * 4 + y) as u32;
+            modifiers[y * 4 + x] = table[bits(word, bit + 2, bit) as usize];
+        }
+    }
+    (bits(word, 63, 56), bits(word, 55, 52), modifiers)
+}
+
+// 8-bit alpha of the RGBA format
+fn decode_eac(word: u64) -> [i32; 16] {
+    let (base, multiplier, modifiers) = eac_fields(word);
+    modifiers.map(|modifier| (base + modifier * multiplier).clamp(0, 255))
+}
+
+// an 11-bit channel, reduced to 8 bits; signed values are returned as the bytes of an i8
+fn decode_eac11(word: u64, signed: bool) -> [u8; 16] {
+    let (base, multiplier, modifiers) = eac_fields(word);
+    // a multiplier of 0 means 1/8
+    let scale = |modifier: i32| {
+        if multiplier == 0 {
+            modifier
+        } else {
+            modifier * multiplier * 8
+        }
+    };
+    if signed {
+        // -128 is treated as -127
+        let base = (base as u8 as i8).max(-127) as i32;
+        modifiers.map(|modifier| {
+            let value = (base * 8 + scale(modifier)).clamp(-1023, 1023);
+            ((value * 127 + value.signum() * 511) / 1023) as i8 as u8
+        })
+    } else {
+        modifiers.map(|modifier| {
+            let value = (base * 8 + 4 + scale(modifier)).clamp(0, 2047);
+            ((value * 255 + 1023) / 2047) as u8
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decode_etc2, Etc2Format};
+
+    // the 2-bit texel indices of a color block, `index(x, y)` for every texel
+    fn indices(index: impl Fn(usize, usize) -> u64) -> u64 {
+        let mut word = 0;
+        for y in 0..4 {
+            for x in 0..4 {
+                let bit = x * 4 + y;
+                word |= ((index(x, y) >> 1) << (bit + 16)) | ((index(x, y) & 1) << bit);
+            }
+        }
+        word
+    }
+
+    // an EAC block with the 3-bit texel indices `index(x, y)`
+    fn eac(base: u64, multiplier: u64, table: u64, index: impl Fn(usize, usize) -> u64) -> u64 {
+        let mut word = (base << 56) | (multiplier << 52) | (table << 48);
+        for y in 0..4 {
+            for x in 0..4 {
+                word |= index(x, y) << (45 - 3 * (x * 4 + y));
+            }
+        }
+        word
+    }
+
+    fn decode(format: Etc2Format, words: &[u64]) -> Vec<u8> {
+        let data: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        decode_etc2(format, 4, 4, &data)
+    }
+
+    fn texel(texels: &[u8], channels: usize, x: usize, y: usize) -> &[u8] {
+        let start = (y * 4 + x) * channels;
+        &texels[start..start + channels]
+    }
+
+    // the differential block below: 16 + 3, 0 + 0 and 31 - 4 in 5 bits, tables 1 and 2,
+    // split into top and bottom half
+    fn differential(opaque: bool) -> u64 {
+        (16 << 59) | (3 << 56) | (31 << 43) | (4 << 40) | (1 << 37) | (2 << 34) | ((opaque as u64) << 33) | (1 << 32)
+    }
+
+    // a T mode block: red overflows with 30 + 3, colors 11, 0, 15 and 8, 8, 8 in 4 bits,
+    // distance 64
+    fn t_mode(opaque: bool) -> u64 {
+        (0b111 << 61)
+            | (0b10 << 59)
+            | (0b11 << 56)
+            | (15 << 48)
+            | (0x888 << 36)
+            | (0b11 << 34)
+            | ((opaque as u64) << 33)
+            | (1 << 32)
+    }
+
+    #[test]
+    fn individual_mode() {
+        // colors 8, 4, 2 and 0, 0, 0 in 4 bits, tables 0 and 7, left and right half
+        let word = (8 << 60) | (4 << 52) | (2 << 44) | (7 << 34) | indices(|x, y| if (x, y) == (3, 3) { 3 } else { 0 });
+        let texels = decode(Etc2Format::Rgb, &[word]);
+        assert_eq!(texels.len(), 48);
+        assert_eq!(texel(&texels, 3, 0, 0), [138, 70, 36]);
+        assert_eq!(texel(&texels, 3, 1, 3), [138, 70, 36]);
+        assert_eq!(texel(&texels, 3, 2, 1), [47, 47, 47]);
+        assert_eq!(texel(&texels, 3, 3, 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn differential_mode() {
+        let texels = decode(Etc2Format::Rgb, &[differential(true) | indices(|_, _| 1)]);
+        assert_eq!(texel(&texels, 3, 3, 1), [149, 17, 255]);
+        assert_eq!(texel(&texels, 3, 0, 2), [185, 29, 251]);
+    }
+
+    #[test]
+    fn t_mode_paints_four_colors() {
+        let texels = decode(Etc2Format::Rgb, &[t_mode(true) | indices(|x, y| ((x + y) % 4) as u64)]);
+        assert_eq!(texel(&texels, 3, 0, 0), [187, 0, 255]);
+        assert_eq!(texel(&texels, 3, 1, 0), [200, 200, 200]);
+        assert_eq!(texel(&texels, 3, 2, 0), [136, 136, 136]);
+        assert_eq!(texel(&texels, 3, 3, 0), [72, 72, 72]);
+        assert_eq!(texel(&texels, 3, 3, 3), [136, 136, 136]);
+    }
+
+    #[test]
+    fn h_mode_paints_four_colors() {
+        // green underflows with 0 - 3; colors 8, 4, 2 and 2, 4, 6 in 4 bits, the first
+        // one larger, distance 32
+        let word = (8 << 59)
+            | (0b010 << 56)
+            | (1 << 50)
+            | (0b010 << 47)
+            | (0x246 << 35)
+            | (1 << 34)
+            | (1 << 33)
+            | indices(|_, y| y as u64);
+        let texels = decode(Etc2Format::Rgb, &[word]);
+        assert_eq!(texel(&texels, 3, 0, 0), [168, 100, 66]);
+        assert_eq!(texel(&texels, 3, 1, 1), [104, 36, 2]);
+        assert_eq!(texel(&texels, 3, 2, 2), [66, 100, 134]);
+        assert_eq!(texel(&texels, 3, 3, 3), [2, 36, 70]);
+    }
+
+    #[test]
+    fn planar_mode_interpolates() {
+        // blue underflows with 0 - 4; origin 32, 64, 0, horizontal 0, 0, 0 and vertical
+        // 63, 127, 63
+        let word = (32 << 57) | (1 << 56) | (1 << 42) | (1 << 33) | (63 << 13) | (127 << 6) | 63;
+        let texels = decode(Etc2Format::Rgb, &[word]);
+        assert_eq!(texel(&texels, 3, 0, 0), [130, 129, 0]);
+        assert_eq!(texel(&texels, 3, 3, 0), [33, 32, 0]);
+        assert_eq!(texel(&texels, 3, 0, 3), [224, 224, 191]);
+        assert_eq!(texel(&texels, 3, 3, 3), [126, 127, 191]);
+        assert_eq!(texel(&texels, 3, 1, 2), [160, 160, 128]);
+    }
+
+    #[test]
+    fn punchthrough_alpha() {
+        let columns = indices(|x, _| x as u64);
+        let texels = decode(Etc2Format::RgbA1, &[differential(false) | columns]);
+        assert_eq!(texel(&texels, 4, 0, 0), [132, 0, 255, 255]);
+        assert_eq!(texel(&texels, 4, 0, 3), [156, 0, 222, 255]);
+        assert_eq!(texel(&texels, 4, 1, 0), [149, 17, 255, 255]);
+        assert_eq!(texel(&texels, 4, 2, 1), [0, 0, 0, 0]);
+        assert_eq!(texel(&texels, 4, 3, 3), [127, 0, 193, 255]);
+
+        // opaque blocks keep all four modifiers
+        let texels = decode(Etc2Format::RgbA1, &[differential(true) | columns]);
+        assert_eq!(texel(&texels, 4, 0, 0), [137, 5, 255, 255]);
+        assert_eq!(texel(&texels, 4, 2, 1), [127, 0, 250, 255]);
+
+        let texels = decode(
+            Etc2Format::RgbA1,
+            &[t_mode(false) | indices(|x, y| ((x + y) % 4) as u64)],
+        );
+        assert_eq!(texel(&texels, 4, 0, 0), [187, 0, 255, 255]);
+        assert_eq!(texel(&texels, 4, 2, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn eac_alpha() {
+        let alpha = eac(200, 3, 0, |x, y| ((y * 4 + x) % 8) as u64);
+        let color = differential(true) | indices(|_, _| 1);
+        let texels = decode(Etc2Format::Rgba, &[alpha, color]);
+        let alphas: Vec<u8> = (0..8).map(|i| texel(&texels, 4, i % 4, i / 4)[3]).collect();
+        assert_eq!(alphas, [191, 182, 173, 155, 206, 215, 224, 242]);
+        assert_eq!(texel(&texels, 4, 0, 0)[..3], [149, 17, 255]);
+    }
+
+    #[test]
+    fn eac_unsigned() {
+        let texels = decode(Etc2Format::R11, &[eac(128, 2, 0, |x, y| ((y * 4 + x) % 8) as u64)]);
+        assert_eq!(texels[..8], [122, 116, 110, 98, 132, 138, 144, 156]);
+        assert_eq!(texels[8..], texels[..8]);
+
+        // a multiplier of 0 scales the modifiers by 1/8
+        let texels = decode(Etc2Format::R11, &[eac(128, 0, 0, |x, y| ((y * 4 + x) % 8) as u64)]);
+        assert_eq!(texels[..8], [128, 127, 127, 126, 128, 129, 129, 130]);
+    }
+
+    #[test]
+    fn eac_signed() {
+        // base -64, multiplier 1, table 13
+        let red = eac(0xC0, 1, 13, |x, y| ((y * 4 + x) % 8) as u64);
+        let green = eac(0x7F, 15, 0, |_, _| 7);
+        let texels = decode(Etc2Format::SignedRg11, &[red, green]);
+        let reds: Vec<i8> = texels.iter().step_by(2).take(8).map(|&value| value as i8).collect();
+        assert_eq!(reds, [-65, -66, -67, -73, -64, -63, -62, -55]);
+        // clamped to the largest value
+        assert!(texels.iter().skip(1).step_by(2).all(|&value| value as i8 == 127));
+    }
+
+    #[test]
+    fn edge_blocks_are_cropped() {
+        // two solid individual mode blocks, red and gray, for a 5x3 image
+        let red = (15 << 60) | (15 << 56);
+        let data: Vec<u8> = [red, 0u64].iter().flat_map(|word| word.to_be_bytes()).collect();
+        let texels = decode_etc2(Etc2Format::Rgb, 5, 3, &data);
+        assert_eq!(texels.len(), 5 * 3 * 3);
+        let at = |x: usize, y: usize| &texels[(y * 5 + x) * 3..(y * 5 + x) * 3 + 3];
+        assert_eq!(at(0, 0), [255, 2, 2]);
+        assert_eq!(at(3, 2), [255, 2, 2]);
+        assert_eq!(at(4, 0), [2, 2, 2]);
+        assert_eq!(at(4, 2), [2, 2, 2]);
+    }
+}
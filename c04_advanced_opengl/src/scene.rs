@@ -0,0 +1,494 @@
+use cgmath::prelude::*;
+use cgmath::{vec3, Matrix4, Quaternion, Vector3};
+use std::{cell::Cell, rc::Rc};
+
+use super::lod::{LodCamera, LodSelector};
+use super::mesh::Mesh;
+use super::model::Model;
+use super::shader::Shader;
+
+/// Local transform of a scene node: scale first, then rotate, then translate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vector3<f32>) -> Self {
+        Transform {
+            translation,
+            ..Transform::default()
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Light source attached to a scene node. The position comes from the node's world
+/// transform and the direction is rotated along with it.
+#[derive(Clone, Copy, Debug)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot { cut_off: f32, outer_cut_off: f32 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    /// direction in the node's local space, ignored for point lights
+    pub direction: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    // attenuation, ignored for directional lights
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Light {
+            kind: LightKind::Point,
+            direction: vec3(0.0, 0.0, -1.0),
+            ambient: vec3(0.05, 0.05, 0.05),
+            diffuse: vec3(0.8, 0.8, 0.8),
+            specular: vec3(1.0, 1.0, 1.0),
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+impl Light {
+    /// sets the uniforms of a light struct as used in the lighting chapter, e.g.
+    /// `light.set_uniforms(&shader, "pointLights[0]", &world)`.
+    pub fn set_uniforms(&self, shader: &Shader, name: &str, world: &Matrix4<f32>) {
+        let position = world.w.truncate();
+        let direction = (world * self.direction.extend(0.0)).truncate().normalize();
+
+        shader.set_vector3(&format!("{}.ambient", name), &self.ambient);
+        shader.set_vector3(&format!("{}.diffuse", name), &self.diffuse);
+        shader.set_vector3(&format!("{}.specular", name), &self.specular);
+        if let LightKind::Directional = self.kind {
+            shader.set_vector3(&format!("{}.direction", name), &direction);
+            return;
+        }
+        shader.set_vector3(&format!("{}.position", name), &position);
+        shader.set_float(&format!("{}.constant", name), self.constant);
+        shader.set_float(&format!("{}.linear", name), self.linear);
+        shader.set_float(&format!("{}.quadratic", name), self.quadratic);
+        if let LightKind::Spot { cut_off, outer_cut_off } = self.kind {
+            shader.set_vector3(&format!("{}.direction", name), &direction);
+            shader.set_float(&format!("{}.cutOff", name), cut_off.to_radians().cos());
+            shader.set_float(&format!("{}.outerCutOff", name), outer_cut_off.to_radians().cos());
+        }
+    }
+}
+
+/// Something attached to a scene node.
+#[derive(Clone)]
+pub enum Component {
+    Model(Rc<Model>),
+    Mesh(Rc<Mesh>),
+    Light(Light),
+}
+
+/// Handle of a node inside its `Scene`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct Node {
+    pub name: String,
+    pub components: Vec<Component>,
+    local: Transform,
+    world: Matrix4<f32>,
+    dirty: bool,
+    // level picked by the last `Scene::draw_with_lod`, for hysteresis
+    lod: Cell<Option<usize>>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.local
+    }
+
+    /// world matrix as of the last `Scene::update`
+    pub fn world_matrix(&self) -> &Matrix4<f32> {
+        &self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// Scene graph: a hierarchy of nodes whose world matrices are the product of the local
+/// transforms along the path from the root. World matrices are cached and only recomputed
+/// for the subtrees whose transforms changed since the last update.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// adds a node with an identity transform, as a root if `parent` is `None`.
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.into(),
+            components: Vec::new(),
+            local: Transform::default(),
+            world: Matrix4::identity(),
+            dirty: true,
+            lod: Cell::new(None),
+            parent,
+            children: Vec::new(),
+        });
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    /// moves a node (and its subtree) under another parent, or to the root level.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        if let Some(parent) = parent {
+            assert!(
+                !self.is_ancestor(id, parent),
+                "a node cannot be parented to its own subtree"
+            );
+        }
+        match self.nodes[id.0].parent {
+            Some(old) => self.nodes[old.0].children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id),
+        }
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes[id.0].parent = parent;
+        self.nodes[id.0].dirty = true;
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name).map(NodeId)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        let node = &mut self.nodes[id.0];
+        node.local = transform;
+        node.dirty = true;
+    }
+
+    pub fn set_translation(&mut self, id: NodeId, translation: Vector3<f32>) {
+        let transform = Transform {
+            translation,
+            ..self.nodes[id.0].local
+        };
+        self.set_transform(id, transform);
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: Quaternion<f32>) {
+        let transform = Transform {
+            rotation,
+            ..self.nodes[id.0].local
+        };
+        self.set_transform(id, transform);
+    }
+
+    pub fn set_scale(&mut self, id: NodeId, scale: Vector3<f32>) {
+        let transform = Transform {
+            scale,
+            ..self.nodes[id.0].local
+        };
+        self.set_transform(id, transform);
+    }
+
+    pub fn add_component(&mut self, id: NodeId, component: Component) {
+        self.nodes[id.0].components.push(component);
+    }
+
+    /// recomputes the world matrices of every node whose transform, or whose ancestor's
+    /// transform, changed since the last update.
+    pub fn update(&mut self) {
+        let mut stack: Vec<(NodeId, Matrix4<f32>, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Matrix4::identity(), false))
+            .collect();
+        while let Some((id, parent_world, parent_dirty)) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            let dirty = parent_dirty || node.dirty;
+            if dirty {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().rev().map(|&child| (child, world, dirty)));
+        }
+    }
+
+    /// visits every node depth-first, parents before children
+    pub fn traverse<F>(&self, mut visit: F)
+    where
+        F: FnMut(NodeId, &Node),
+    {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().cloned().collect();
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id.0];
+            visit(id, node);
+            stack.extend(node.children.iter().rev());
+        }
+    }
+
+    /// all lights in the scene together with the world matrix of their node
+    pub fn lights(&self) -> Vec<(Light, Matrix4<f32>)> {
+        let mut lights = Vec::new();
+        self.traverse(|_, node| {
+            for component in &node.components {
+                if let Component::Light(light) = component {
+                    lights.push((*light, node.world));
+                }
+            }
+        });
+        lights
+    }
+
+    /// updates the world matrices and draws every model and mesh with the given shader,
+    /// setting its `model` uniform to the world matrix of the node.
+    pub fn draw(&mut self, shader: &Shader) {
+        self.update();
+        self.traverse(|_, node| {
+            let drawable = |component: &Component| !matches!(component, Component::Light(_));
+            if !node.components.iter().any(drawable) {
+                return;
+            }
+            shader.set_mat4("model", &node.world);
+            for component in &node.components {
+                match component {
+                    Component::Model(model) => model.draw(shader),
+                    Component::Mesh(mesh) => mesh.draw(shader),
+                    Component::Light(_) => {}
+                }
+            }
+        });
+    }
+
+    /// like `draw`, but models and meshes are drawn at the LOD `selector` picks for their
+    /// distance to the camera. Nodes remember their level between calls for hysteresis.
+    pub fn draw_with_lod(&mut self, shader: &Shader, camera: &LodCamera, selector: &LodSelector) {
+        self.update();
+        self.traverse(|_, node| {
+            let drawable = |component: &Component| !matches!(component, Component::Light(_));
+            if !node.components.iter().any(drawable) {
+                return;
+            }
+            shader.set_mat4("model", &node.world);
+            let previous = node.lod.get();
+            let mut selected = None;
+            for component in &node.components {
+                let level = match component {
+                    Component::Model(model) => model.select_lod(selector, &node.world, camera, previous),
+                    Component::Mesh(mesh) => {
+                        selector.select_for(mesh.lod_errors(), &mesh.bounding_sphere, &node.world, camera, previous)
+                    }
+                    Component::Light(_) => continue,
+                };
+                match component {
+                    Component::Model(model) => model.draw_lod(shader, level),
+                    Component::Mesh(mesh) => mesh.draw_lod(shader, level),
+                    Component::Light(_) => {}
+                }
+                selected = selected.max(Some(level));
+            }
+            node.lod.set(selected);
+        });
+    }
+
+    fn is_ancestor(&self, ancestor: NodeId, mut id: NodeId) -> bool {
+        loop {
+            if id == ancestor {
+                return true;
+            }
+            match self.nodes[id.0].parent {
+                Some(parent) => id = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Matrix4};
+
+    fn assert_near(a: &Matrix4<f32>, b: &Matrix4<f32>) {
+        let a: &[f32; 16] = a.as_ref();
+        let b: &[f32; 16] = b.as_ref();
+        assert!(a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    /// arm (root) > hand > finger, and a separate table root
+    fn scene() -> (Scene, [NodeId; 4]) {
+        let mut scene = Scene::new();
+        let arm = scene.add_node("arm", None);
+        let hand = scene.add_node("hand", Some(arm));
+        let finger = scene.add_node("finger", Some(hand));
+        let table = scene.add_node("table", None);
+        scene.set_translation(arm, vec3(1.0, 0.0, 0.0));
+        scene.set_transform(
+            hand,
+            Transform {
+                translation: vec3(0.0, 0.0, 2.0),
+                rotation: Quaternion::from_angle_y(Deg(90.0)),
+                scale: vec3(2.0, 2.0, 2.0),
+            },
+        );
+        scene.set_translation(finger, vec3(1.0, 0.0, 0.0));
+        scene.set_translation(table, vec3(0.0, -1.0, 0.0));
+        (scene, [arm, hand, finger, table])
+    }
+
+    fn local(scene: &Scene, id: NodeId) -> Matrix4<f32> {
+        scene.node(id).transform().matrix()
+    }
+
+    #[test]
+    fn hierarchy() {
+        let (mut scene, [arm, hand, finger, table]) = scene();
+        assert_eq!(scene.find("finger"), Some(finger));
+        assert_eq!(scene.find("leg"), None);
+        assert_eq!(scene.node(hand).parent(), Some(arm));
+        assert_eq!(scene.node(arm).children(), [hand]);
+
+        let mut order = Vec::new();
+        scene.traverse(|id, _| order.push(id));
+        assert_eq!(order, [arm, hand, finger, table]);
+
+        scene.set_parent(finger, Some(table));
+        assert!(scene.node(hand).children().is_empty());
+        assert_eq!(scene.node(table).children(), [finger]);
+        assert_eq!(scene.node(finger).parent(), Some(table));
+
+        scene.set_parent(hand, None);
+        assert_eq!(scene.node(hand).parent(), None);
+        assert!(scene.node(arm).children().is_empty());
+        let mut order = Vec::new();
+        scene.traverse(|id, _| order.push(id));
+        assert_eq!(order, [arm, table, finger, hand]);
+    }
+
+    #[test]
+    #[should_panic(expected = "own subtree")]
+    fn parent_to_own_subtree() {
+        let (mut scene, [arm, _, finger, _]) = scene();
+        scene.set_parent(arm, Some(finger));
+    }
+
+    #[test]
+    fn world_matrices() {
+        let (mut scene, [arm, hand, finger, table]) = scene();
+        scene.update();
+        assert_near(scene.node(arm).world_matrix(), &local(&scene, arm));
+        assert_near(
+            scene.node(hand).world_matrix(),
+            &(local(&scene, arm) * local(&scene, hand)),
+        );
+        let finger_world = local(&scene, arm) * local(&scene, hand) * local(&scene, finger);
+        assert_near(scene.node(finger).world_matrix(), &finger_world);
+        // the hand's rotation and scale turn the finger's +X offset into -Z, twice as long
+        assert_near(
+            &Matrix4::from_translation(scene.node(finger).world_matrix().w.truncate()),
+            &Matrix4::from_translation(vec3(1.0, 0.0, 0.0)),
+        );
+        assert_near(scene.node(table).world_matrix(), &local(&scene, table));
+        assert!(scene.nodes.iter().all(|node| !node.dirty));
+    }
+
+    #[test]
+    fn moving_an_ancestor() {
+        let (mut scene, [arm, hand, finger, table]) = scene();
+        scene.update();
+        // stale values show which world matrices the next update recomputes
+        let stale = Matrix4::from_scale(7.0);
+        scene.nodes[table.0].world = stale;
+
+        scene.set_translation(arm, vec3(0.0, 3.0, 0.0));
+        assert!(scene.node(arm).dirty && !scene.node(hand).dirty && !scene.node(finger).dirty);
+        scene.update();
+        let finger_world = local(&scene, arm) * local(&scene, hand) * local(&scene, finger);
+        assert_near(scene.node(finger).world_matrix(), &finger_world);
+        assert_near(
+            &Matrix4::from_translation(scene.node(finger).world_matrix().w.truncate()),
+            &Matrix4::from_translation(vec3(0.0, 3.0, 0.0)),
+        );
+        // the other root didn't change
+        assert_near(scene.node(table).world_matrix(), &stale);
+
+        // nothing changed since the last update
+        scene.nodes[finger.0].world = stale;
+        scene.update();
+        assert_near(scene.node(finger).world_matrix(), &stale);
+    }
+
+    #[test]
+    fn reparenting() {
+        let (mut scene, [arm, hand, finger, table]) = scene();
+        scene.update();
+        scene.set_parent(hand, Some(table));
+        scene.update();
+        assert_near(
+            scene.node(hand).world_matrix(),
+            &(local(&scene, table) * local(&scene, hand)),
+        );
+        let finger_world = local(&scene, table) * local(&scene, hand) * local(&scene, finger);
+        assert_near(scene.node(finger).world_matrix(), &finger_world);
+
+        // back to the root level, only its own transform is left
+        scene.set_parent(finger, None);
+        scene.update();
+        assert_near(scene.node(finger).world_matrix(), &local(&scene, finger));
+        assert_near(scene.node(arm).world_matrix(), &local(&scene, arm));
+    }
+}
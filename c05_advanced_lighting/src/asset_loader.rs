@@ -0,0 +1,468 @@
+use cgmath::{vec2, vec3, Vector3};
+use dx::gles::enums::*;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::material::{Material, TextureSlot};
+use super::mesh::{Mesh, Texture, Vertex};
+use super::model::{FailedTextures, Model, ModelData, ModelError, ModelOptions, ModelUpload};
+use super::texture::{Filter, Texture2D};
+use super::texture_cache::{CachedTexture, TextureCache, TextureImage, TextureOptions};
+
+/// Progress of an asset requested from an `AssetLoader`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Ready,
+    Failed,
+}
+
+enum Slot<T> {
+    Loading,
+    Ready(Rc<T>),
+    Failed(Rc<ModelError>),
+}
+
+/// Handle to an asset being loaded in the background; it becomes ready during one of the
+/// `AssetLoader::update` calls.
+pub struct AssetHandle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+}
+
+impl<T> Clone for AssetHandle<T> {
+    fn clone(&self) -> Self {
+        AssetHandle {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<T> AssetHandle<T> {
+    fn new() -> Self {
+        AssetHandle {
+            slot: Rc::new(RefCell::new(Slot::Loading)),
+        }
+    }
+
+    pub fn state(&self) -> LoadState {
+        match *self.slot.borrow() {
+            Slot::Loading => LoadState::Loading,
+            Slot::Ready(_) => LoadState::Ready,
+            Slot::Failed(_) => LoadState::Failed,
+        }
+    }
+
+    /// the asset, once it is ready
+    pub fn get(&self) -> Option<Rc<T>> {
+        match &*self.slot.borrow() {
+            Slot::Ready(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    /// the asset, or `placeholder` while it is loading or if it failed
+    pub fn get_or(&self, placeholder: &Rc<T>) -> Rc<T> {
+        self.get().unwrap_or_else(|| placeholder.clone())
+    }
+
+    /// why the asset failed to load
+    pub fn error(&self) -> Option<Rc<ModelError>> {
+        match &*self.slot.borrow() {
+            Slot::Failed(err) => Some(err.clone()),
+            _ => None,
+        }
+    }
+
+    fn set(&self, slot: Slot<T>) {
+        *self.slot.borrow_mut() = slot;
+    }
+}
+
+// work for the loader threads
+enum Job {
+    Model(usize, PathBuf, ModelOptions),
+    Texture(usize, PathBuf, TextureOptions),
+}
+
+type DecodedTexture = Result<TextureImage, ModelError>;
+
+// results of the loader threads
+enum Decoded {
+    Model(
+        usize,
+        Result<(ModelData, Vec<DecodedTexture>), ModelError>,
+        ModelOptions,
+    ),
+    Texture(usize, DecodedTexture),
+}
+
+// results waiting to be uploaded on the GL thread
+enum Upload {
+    Model {
+        handle: AssetHandle<Model>,
+        options: ModelOptions,
+        // textures still to upload, then the model's meshes
+        images: Vec<DecodedTexture>,
+        uploaded: Vec<Rc<CachedTexture>>,
+        // the textures of `images` that failed, handed to the `ModelUpload`
+        failed: Option<Box<FailedTextures>>,
+        data: Option<ModelData>,
+        model: Option<Box<ModelUpload>>,
+    },
+    Texture(AssetHandle<CachedTexture>, TextureImage),
+}
+
+/// Loads models and textures in the background. File I/O, OBJ parsing and image decoding
+/// run on worker threads; the GL objects are created on the thread owning the loader, in
+/// `update`, which does at most a given amount of work per call so frames don't stall.
+///
+/// Textures decoded by the workers go through the `TextureCache` on upload, so an image
+/// already cached is decoded again but not uploaded twice. Model textures that fail are
+/// replaced by the placeholder texture.
+pub struct AssetLoader {
+    jobs: Option<Sender<Job>>,
+    results: Receiver<Decoded>,
+    workers: Vec<JoinHandle<()>>,
+    next_id: usize,
+    models: HashMap<usize, AssetHandle<Model>>,
+    textures: HashMap<usize, AssetHandle<CachedTexture>>,
+    uploads: VecDeque<Upload>,
+    placeholder_texture: Option<Rc<CachedTexture>>,
+    placeholder_model: Option<Rc<Model>>,
+}
+
+impl AssetLoader {
+    /// starts `workers` loader threads (at least one)
+    pub fn new(workers: usize) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..workers.max(1))
+            .map(|idx| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("asset loader {}", idx))
+                    .spawn(move || worker(jobs, results))
+                    .expect("Failed to spawn asset loader thread")
+            })
+            .collect();
+
+        AssetLoader {
+            jobs: Some(jobs),
+            results,
+            workers,
+            next_id: 0,
+            models: HashMap::new(),
+            textures: HashMap::new(),
+            uploads: VecDeque::new(),
+            placeholder_texture: None,
+            placeholder_model: None,
+        }
+    }
+
+    pub fn load_model<T>(&mut self, path: T, options: ModelOptions) -> AssetHandle<Model>
+    where
+        T: Into<PathBuf>,
+    {
+        let id = self.next_id();
+        let handle = AssetHandle::new();
+        self.models.insert(id, handle.clone());
+        self.send(Job::Model(id, path.into(), options.for_current_context()));
+        handle
+    }
+
+    pub fn load_texture<T>(&mut self, path: T, options: TextureOptions) -> AssetHandle<CachedTexture>
+    where
+        T: Into<PathBuf>,
+    {
+        let id = self.next_id();
+        let handle = AssetHandle::new();
+        self.textures.insert(id, handle.clone());
+        self.send(Job::Texture(id, path.into(), options));
+        handle
+    }
+
+    /// number of assets not ready or failed yet
+    pub fn pending(&self) -> usize {
+        self.models.len() + self.textures.len() + self.uploads.len()
+    }
+
+    /// collects the work finished by the loader threads and creates GL objects until
+    /// `budget` is used up; at least one texture or mesh is uploaded per call so loading
+    /// always progresses. Call once per frame on the GL thread.
+    pub fn update(&mut self, budget: Duration) {
+        while let Ok(decoded) = self.results.try_recv() {
+            self.receive(decoded);
+        }
+
+        let start = Instant::now();
+        while let Some(upload) = self.uploads.pop_front() {
+            if let Some(rest) = upload.step() {
+                self.uploads.push_front(rest);
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// a grey and white checkerboard to show instead of textures that are not ready
+    pub fn placeholder_texture(&mut self) -> Rc<CachedTexture> {
+        self.placeholder_texture
+            .get_or_insert_with(|| Rc::new(checkerboard()))
+            .clone()
+    }
+
+    /// a unit cube with the placeholder texture to show instead of models that are not ready
+    pub fn placeholder_model(&mut self) -> Rc<Model> {
+        if let Some(model) = &self.placeholder_model {
+            return model.clone();
+        }
+        let cached = self.placeholder_texture();
+        let texture = Texture {
+            id: cached.texture.id(),
+            path: "placeholder".into(),
+            cached: Some(cached),
+        };
+        let model = Rc::new(cube(texture));
+        self.placeholder_model = Some(model.clone());
+        model
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn send(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            jobs.send(job).expect("asset loader threads stopped");
+        }
+    }
+
+    fn receive(&mut self, decoded: Decoded) {
+        match decoded {
+            Decoded::Model(id, result, options) => {
+                let handle = match self.models.remove(&id) {
+                    Some(handle) => handle,
+                    None => return,
+                };
+                match result {
+                    Ok((data, images)) => {
+                        let failed = FailedTextures {
+                            errors: HashMap::new(),
+                            placeholder: self.placeholder_texture(),
+                        };
+                        self.uploads.push_back(Upload::Model {
+                            handle,
+                            options,
+                            images,
+                            uploaded: Vec::new(),
+                            failed: Some(Box::new(failed)),
+                            data: Some(data),
+                            model: None,
+                        })
+                    }
+                    Err(err) => {
+                        println!("{}", err);
+                        handle.set(Slot::Failed(Rc::new(err)));
+                    }
+                }
+            }
+            Decoded::Texture(id, result) => {
+                let handle = match self.textures.remove(&id) {
+                    Some(handle) => handle,
+                    None => return,
+                };
+                match result {
+                    Ok(image) => self.uploads.push_back(Upload::Texture(handle, image)),
+                    Err(err) => {
+                        println!("{}", err);
+                        handle.set(Slot::Failed(Rc::new(err)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // closing the job channel stops the workers once they finish their current job
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker(jobs: Arc<Mutex<Receiver<Job>>>, results: Sender<Decoded>) {
+    loop {
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+        let decoded = match job {
+            Ok(Job::Model(id, path, options)) => {
+                let result = ModelData::load(path, &options).map(|data| {
+                    // textures that fail to decode are reported when the model is uploaded
+                    let images = data
+                        .texture_paths()
+                        .iter()
+                        .map(|path| TextureImage::decode(path, options.textures))
+                        .collect();
+                    (data, images)
+                });
+                Decoded::Model(id, result, options)
+            }
+            Ok(Job::Texture(id, path, options)) => Decoded::Texture(id, TextureImage::decode(&path, options)),
+            // the loader was dropped
+            Err(_) => return,
+        };
+        if results.send(decoded).is_err() {
+            return;
+        }
+    }
+}
+
+impl Upload {
+    /// does one unit of GL work; returns what is left of the upload
+    fn step(self) -> Option<Upload> {
+        match self {
+            Upload::Texture(handle, image) => {
+                match TextureCache::insert(image) {
+                    Ok(texture) => handle.set(Slot::Ready(texture)),
+                    Err(err) => {
+                        println!("{}", err);
+                        handle.set(Slot::Failed(Rc::new(err)));
+                    }
+                }
+                None
+            }
+            Upload::Model {
+                handle,
+                options,
+                mut images,
+                mut uploaded,
+                mut failed,
+                mut data,
+                mut model,
+            } => {
+                if let Some(image) = images.pop() {
+                    // 1. one texture per step; failed ones are reported by the materials
+                    // using them in step 2
+                    match image.and_then(TextureCache::insert) {
+                        Ok(texture) => uploaded.push(texture),
+                        Err(err) => {
+                            if let (Some(failed), ModelError::Texture { path, .. }) = (&mut failed, &err) {
+                                failed.errors.insert(path.clone(), err);
+                            }
+                        }
+                    }
+                } else if let Some(data) = data.take() {
+                    // 2. the materials, using the textures uploaded above
+                    model = Some(Box::new(ModelUpload::new(
+                        data,
+                        &options,
+                        failed.take().map(|failed| *failed),
+                    )));
+                } else if !model.as_mut().expect("model upload started").upload_next_mesh() {
+                    // 3. one mesh per step, until all are uploaded
+                    let model = model.take().unwrap().finish();
+                    handle.set(Slot::Ready(Rc::new(model)));
+                    return None;
+                }
+                Some(Upload::Model {
+                    handle,
+                    options,
+                    images,
+                    uploaded,
+                    failed,
+                    data,
+                    model,
+                })
+            }
+        }
+    }
+}
+
+fn checkerboard() -> CachedTexture {
+    const SIZE: usize = 4;
+    let mut pixels = Vec::with_capacity(SIZE * SIZE * 3);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let value = if (x + y) % 2 == 0 { 255 } else { 128 };
+            pixels.extend_from_slice(&[value, value, value]);
+        }
+    }
+
+    let texture = Texture2D::builder().filter(Filter::Nearest).mipmaps(false).from_pixels(
+        SIZE as u32,
+        SIZE as u32,
+        GL_RGB,
+        &pixels,
+    );
+
+    CachedTexture {
+        texture,
+        path: "placeholder".into(),
+        options: TextureOptions::default(),
+        width: SIZE as u32,
+        height: SIZE as u32,
+        size_bytes: pixels.len(),
+    }
+}
+
+/// a cube from -0.5 to 0.5 with the given base color texture on every face
+fn cube(texture: Texture) -> Model {
+    let faces: [(Vector3<f32>, Vector3<f32>, Vector3<f32>); 6] = [
+        (vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+        (vec3(0.0, 0.0, -1.0), vec3(-1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+        (vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0)),
+        (vec3(-1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0)),
+        (vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0)),
+        (vec3(0.0, -1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0)),
+    ];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces.iter() {
+        let base = vertices.len() as u32;
+        for (s, t) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter() {
+            vertices.push(Vertex {
+                position: (normal + u * (s * 2.0 - 1.0) + v * (t * 2.0 - 1.0)) * 0.5,
+                normal: *normal,
+                tex_coords: vec2(*s, *t),
+                tangent: *u,
+                bitangent: *v,
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let mut material = Material {
+        name: "placeholder".into(),
+        ..Material::default()
+    };
+    material.set_texture(TextureSlot::BaseColor, Some(texture.clone()));
+    let material = Rc::new(material);
+
+    let mut model = Model::default();
+    model.meshes.push(Mesh::new(vertices, indices, material.clone()));
+    model.materials.push(material);
+    model.textures_loaded.push(texture);
+    model.update_bounds();
+    model.update_lod_errors();
+    model
+}
@@ -1,4 +1,6 @@
-use c05_advanced_lighting::{load_texture, process_events, Camera, Camera_Movement::*, Shader};
+use c05_advanced_lighting::{
+    load_texture, process_events, run_event_loop, Camera, Camera_Movement::*, GlBuffer, GlVertexArray, Shader,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3};
 use dx::{
@@ -55,7 +57,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, planevao, floor_texture) = {
+    let (shader, planevao, floor_texture, planevbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -75,10 +77,10 @@ pub fn main() {
         ];
         // plane vao
 
-        let planevao = gl::gen_vertex_array();
-        let planevbo = gl::gen_buffer();
-        gl::bind_vertex_array(planevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo);
+        let planevao = GlVertexArray::new();
+        let planevbo = GlBuffer::new();
+        gl::bind_vertex_array(planevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &plane_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         let stride = 8 * mem::size_of::<f32>() as i32;
@@ -98,7 +100,7 @@ pub fn main() {
         shader.use_program();
         shader.set_int("texture1", 0);
 
-        (shader, planevao, floor_texture)
+        (shader, planevao, floor_texture, planevbo)
     };
 
     // lighting info
@@ -106,7 +108,7 @@ pub fn main() {
     let light_pos = vec3(0.0, 0.0, 0.0);
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, planevbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -156,9 +158,9 @@ pub fn main() {
             shader.set_vector3("lightPos", &light_pos);
             shader.set_int("blinn", blinn as i32);
             // floor
-            gl::bind_vertex_array(planevao);
+            gl::bind_vertex_array(planevao.id());
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, floor_texture);
+            gl::bind_texture(GL_TEXTURE_2D, floor_texture.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 6);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -172,9 +174,7 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &planevao);
-    // gl::delete_buffers(1, &planevbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
 
 // NOTE: not the same version as in common.rs
@@ -1,4 +1,7 @@
-use c05_advanced_lighting::{process_events, Camera, Camera_Movement::*, Shader};
+use c05_advanced_lighting::{
+    load_texture, process_events, run_event_loop, Camera, Camera_Movement::*, GlBuffer, GlVertexArray, Shader,
+    Texture2D,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector3};
 use dx::{
@@ -7,8 +10,6 @@ use dx::{
     utils::*,
     Instance,
 };
-use image::{DynamicImage::*, GenericImageView};
-use std::path::PathBuf;
 use std::{mem, time::SystemTime};
 use winit::{
     event::*,
@@ -57,7 +58,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, planevao, floor_texture, floor_texture_gamma_corrected) = {
+    let (shader, planevao, floor_texture, floor_texture_gamma_corrected, planevbo) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -77,10 +78,10 @@ pub fn main() {
         ];
         // plane vao
 
-        let planevao = gl::gen_vertex_array();
-        let planevbo = gl::gen_buffer();
-        gl::bind_vertex_array(planevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo);
+        let planevao = GlVertexArray::new();
+        let planevbo = GlBuffer::new();
+        gl::bind_vertex_array(planevao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, planevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &plane_vertices, GL_STATIC_DRAW);
         gl::enable_vertex_attrib_array(0);
         let stride = 8 * mem::size_of::<f32>() as i32;
@@ -93,15 +94,19 @@ pub fn main() {
 
         // load textures
 
-        let floor_texture = load_texture(assets!("textures/wood.png"), false);
-        let floor_texture_gamma_corrected = load_texture(assets!("textures/wood.png"), true);
+        // the same image twice: as linear color and as sRGB, which GL converts to linear when sampling
+        let floor_texture = load_texture(assets!("textures/wood.png"));
+        let floor_texture_gamma_corrected = Texture2D::builder()
+            .srgb(true)
+            .load(assets!("textures/wood.png"))
+            .expect("Texture failed to load");
 
         // shader configuration
 
         shader.use_program();
         shader.set_int("floorTexture", 0);
 
-        (shader, planevao, floor_texture, floor_texture_gamma_corrected)
+        (shader, planevao, floor_texture, floor_texture_gamma_corrected, planevbo)
     };
 
     // lighting info
@@ -120,7 +125,7 @@ pub fn main() {
     ];
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, planevbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -179,14 +184,14 @@ pub fn main() {
             shader.set_vector3("viewPos", &camera.position.to_vec());
             shader.set_bool("gamma", gamma_enabled);
             // floor
-            gl::bind_vertex_array(planevao);
+            gl::bind_vertex_array(planevao.id());
             gl::active_texture(GL_TEXTURE0);
             gl::bind_texture(
                 GL_TEXTURE_2D,
                 if gamma_enabled {
-                    floor_texture_gamma_corrected
+                    floor_texture_gamma_corrected.id()
                 } else {
-                    floor_texture
+                    floor_texture.id()
                 },
             );
             gl::draw_arrays(GL_TRIANGLES, 0, 6);
@@ -202,9 +207,7 @@ pub fn main() {
         _ => {}
     });
 
-    // optional: de-allocate all resources once they've outlived their purpose:
-    // gl::delete_vertex_arrays(1, &planevao);
-    // gl::delete_buffers(1, &planevbo);
+    // no clean-up needed: the GL object wrappers delete themselves when dropped
 }
 
 // NOTE: not the same version as in common.rs
@@ -264,42 +267,3 @@ pub fn process_input(
         _ => {}
     }
 }
-
-// NOTE: not the same version as in common.rs
-pub fn load_texture(path: PathBuf, gamma_correction: bool) -> u32 {
-    let texture_id = gl::gen_texture();
-    let path = path.as_path();
-
-    let img = image::open(&path).expect("Texture failed to load");
-    // need two different formats for gamma correction
-    let (internal_format, data_format) = match img {
-        ImageLuma8(_) => (GL_RED, GL_RED),
-        ImageLumaA8(_) => (GL_RG, GL_RG),
-        ImageRgb8(_) => (if gamma_correction { GL_SRGB } else { GL_RGB }, GL_RGB),
-        // ImageRgba8(_) => (if gammaCorrection { GL_SRGB_ALPHA } else { GL_RGB }, GL_RGBA),
-        _ => panic!("unhandled image format"),
-    };
-
-    let data = img.as_bytes();
-
-    gl::bind_texture(GL_TEXTURE_2D, texture_id);
-    gl::tex_image_2d(
-        GL_TEXTURE_2D,
-        0,
-        internal_format as i32,
-        img.width() as i32,
-        img.height() as i32,
-        0,
-        data_format,
-        GL_UNSIGNED_BYTE,
-        &data,
-    );
-    gl::generate_mipmap(GL_TEXTURE_2D);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR_MIPMAP_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-
-    texture_id
-}
@@ -1,4 +1,6 @@
-use c05_advanced_lighting::{load_texture, process_events, process_input, Camera, Shader};
+use c05_advanced_lighting::{
+    load_texture, process_events, process_input, run_event_loop, Camera, GlBuffer, GlVertexArray, Shader,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec2, vec3, Deg, Matrix4, Point3, Vector2, Vector3};
 use dx::{
@@ -78,12 +80,11 @@ pub fn main() {
 
     let light_pos: Vector3<f32> = vec3(0.5, 1.0, 0.3);
 
-    let mut quadvao = 0;
-    let mut quadvbo = 0;
+    let mut quad = None;
 
     // render loop
 
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -138,16 +139,16 @@ pub fn main() {
             shader.set_vector3("viewPos", &camera.position.to_vec());
             shader.set_vector3("lightPos", &light_pos);
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, diffuse_map);
+            gl::bind_texture(GL_TEXTURE_2D, diffuse_map.id());
             gl::active_texture(GL_TEXTURE1);
-            gl::bind_texture(GL_TEXTURE_2D, normal_map);
-            render_quad(&mut quadvao, &mut quadvbo);
+            gl::bind_texture(GL_TEXTURE_2D, normal_map.id());
+            render_quad(&mut quad);
 
             // render light source (simply re-renders a smaller plane at the light's position for debugging/visualization)
             model = Matrix4::from_translation(light_pos);
             model = model * Matrix4::from_scale(0.1);
             shader.set_mat4("model", &model);
-            render_quad(&mut quadvao, &mut quadvbo);
+            render_quad(&mut quad);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
             swapchain.present(&surface);
@@ -163,8 +164,8 @@ pub fn main() {
 }
 
 // renders a 1x1 quad in NDC with manually calculated tangent vectors
-fn render_quad(quadvao: &mut u32, quadvbo: &mut u32) {
-    if *quadvao == 0 {
+fn render_quad(quad: &mut Option<(GlVertexArray, GlBuffer)>) {
+    if quad.is_none() {
         // positions
         let pos1: Vector3<f32> = vec3(-1.0, 1.0, 0.0);
         let pos2: Vector3<f32> = vec3(-1.0, -1.0, 0.0);
@@ -308,10 +309,10 @@ fn render_quad(quadvao: &mut u32, quadvbo: &mut u32) {
         ];
 
         // configure plane vao
-        *quadvao = gl::gen_vertex_array();
-        *quadvbo = gl::gen_buffer();
-        gl::bind_vertex_array(*quadvao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, *quadvbo);
+        let quadvao = GlVertexArray::new();
+        let quadvbo = GlBuffer::new();
+        gl::bind_vertex_array(quadvao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &quad_vertices, GL_STATIC_DRAW);
         let stride = 14 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
@@ -324,9 +325,10 @@ fn render_quad(quadvao: &mut u32, quadvbo: &mut u32) {
         gl::vertex_attrib_pointer_offset(3, 3, GL_FLOAT, false, stride, 8 * mem::size_of::<f32>() as u32);
         gl::enable_vertex_attrib_array(4);
         gl::vertex_attrib_pointer_offset(4, 3, GL_FLOAT, false, stride, 11 * mem::size_of::<f32>() as u32);
+        *quad = Some((quadvao, quadvbo));
     }
 
-    gl::bind_vertex_array(*quadvao);
+    gl::bind_vertex_array(quad.as_ref().unwrap().0.id());
     gl::draw_arrays(GL_TRIANGLES, 0, 6);
     gl::bind_vertex_array(0);
 }
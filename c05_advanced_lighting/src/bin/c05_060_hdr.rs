@@ -1,4 +1,7 @@
-use c05_advanced_lighting::{process_events, Camera, Camera_Movement::*, Shader};
+use c05_advanced_lighting::{
+    process_events, run_event_loop, Attachment, Camera, Camera_Movement::*, GlBuffer, GlVertexArray, RenderTarget,
+    Shader, Texture2D,
+};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector3};
 use dx::{
@@ -7,8 +10,6 @@ use dx::{
     utils::*,
     Instance,
 };
-use image::{DynamicImage::*, GenericImageView};
-use std::path::PathBuf;
 use std::{mem, time::SystemTime};
 use winit::{
     event::*,
@@ -58,9 +59,10 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let mut cubevao = 0;
-    let mut cubevbo = 0;
-    let (shader, hdr_shader, wood_texture, hdr_fbo, color_buffer, light_positions, light_colors) = {
+    let (mut scr_width, mut scr_height) = (SCR_WIDTH, SCR_HEIGHT);
+
+    let mut cube = None;
+    let (shader, hdr_shader, wood_texture, mut hdr_fbo, light_positions, light_colors) = {
         // configure global opengl state
 
         gl::enable(GL_DEPTH_TEST);
@@ -71,39 +73,20 @@ pub fn main() {
 
         // load textures
 
-        let wood_texture = load_texture(assets!("textures/wood.png"), true); // note that we're loading the texture as an SRGB texture
+        // note that we're loading the texture as an SRGB texture
+        let wood_texture = Texture2D::builder()
+            .srgb(true)
+            .load(assets!("textures/wood.png"))
+            .expect("Texture failed to load");
 
         // configure floating point framebuffer
 
-        let hdr_fbo = gl::gen_framebuffer();
-        // create floating point color buffer
-        let color_buffer = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, color_buffer);
-        gl::empty_tex_image_2d(
-            GL_TEXTURE_2D,
-            0,
-            GL_RGBA16F as i32,
-            SCR_WIDTH as i32,
-            SCR_HEIGHT as i32,
-            0,
-            GL_RGBA,
-            GL_FLOAT,
-        );
-        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-
-        // create depth buffer (renderbuffer)
-        let rbo_depth = gl::gen_renderbuffer();
-        gl::bind_renderbuffer(GL_RENDERBUFFER, rbo_depth);
-        gl::renderbuffer_storage(GL_RENDERBUFFER, GL_DEPTH_COMPONENT, SCR_WIDTH as i32, SCR_HEIGHT as i32);
-        // attach buffers
-        gl::bind_framebuffer(GL_FRAMEBUFFER, hdr_fbo);
-        gl::framebuffer_texture_2d(GL_FRAMEBUFFER, GL_COLOR_ATTACHMENT0, GL_TEXTURE_2D, color_buffer, 0);
-        gl::framebuffer_renderbuffer(GL_FRAMEBUFFER, GL_DEPTH_ATTACHMENT, GL_RENDERBUFFER, rbo_depth);
-        if gl::check_framebuffer_status(GL_FRAMEBUFFER) != GL_FRAMEBUFFER_COMPLETE {
-            println!("Framebuffer not complete!");
-        }
-        gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+        // a floating point color buffer and a depth buffer (renderbuffer)
+        let hdr_fbo = RenderTarget::builder()
+            .color(Attachment::texture(GL_RGBA16F))
+            .depth(Attachment::renderbuffer(GL_DEPTH_COMPONENT24))
+            .build(scr_width, scr_height)
+            .expect("Framebuffer is not complete");
 
         // lighting info
 
@@ -127,15 +110,14 @@ pub fn main() {
         hdr_shader.use_program();
         hdr_shader.set_int("hdrBuffer", 0);
 
-        (shader, hdr_shader, wood_texture, hdr_fbo, color_buffer, light_positions, light_colors)
+        (shader, hdr_shader, wood_texture, hdr_fbo, light_positions, light_colors)
     };
 
-    let mut quadvao = 0;
-    let mut quadvbo = 0;
+    let mut quad = None;
 
     // render loop
 
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -163,6 +145,12 @@ pub fn main() {
                 // make sure the viewport matches the new window dimensions; note that width and
                 // height will be significantly larger than specified on retina displays.
                 gl::viewport(0, 0, physical_size.width as i32, physical_size.height as i32);
+                scr_width = physical_size.width;
+                scr_height = physical_size.height;
+                // the floating point framebuffer follows the window
+                hdr_fbo
+                    .resize(scr_width, scr_height)
+                    .expect("Framebuffer is not complete");
             }
             _ => {
                 // events
@@ -184,16 +172,16 @@ pub fn main() {
 
             // 1. render scene into floating point framebuffer
 
-            gl::bind_framebuffer(GL_FRAMEBUFFER, hdr_fbo);
+            hdr_fbo.bind();
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
             let projection: Matrix4<f32> =
-                perspective(Deg(camera.zoom), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
+                perspective(Deg(camera.zoom), scr_width as f32 / scr_height as f32, 0.1, 100.0);
             let view = camera.get_view_matrix();
             shader.use_program();
             shader.set_mat4("projection", &projection);
             shader.set_mat4("view", &view);
             gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, wood_texture);
+            gl::bind_texture(GL_TEXTURE_2D, wood_texture.id());
             // set lighting uniforms
             for (i, light_pos) in light_positions.iter().enumerate() {
                 let name = format!("lights[{}].Position", i);
@@ -207,17 +195,16 @@ pub fn main() {
             model = model * Matrix4::from_nonuniform_scale(2.5, 2.5, 27.5);
             shader.set_mat4("model", &model);
             shader.set_bool("inverse_normals", true);
-            render_cube(&mut cubevao, &mut cubevbo);
-            gl::bind_framebuffer(GL_FRAMEBUFFER, 0);
+            render_cube(&mut cube);
+            RenderTarget::bind_default(scr_width, scr_height);
 
             // 2. now render floating point color buffer to 2D quad and tonemap HDR colors to default framebuffer's (clamped) color range
             gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
             hdr_shader.use_program();
-            gl::active_texture(GL_TEXTURE0);
-            gl::bind_texture(GL_TEXTURE_2D, color_buffer);
+            hdr_fbo.bind_color_texture(0, 0);
             hdr_shader.set_bool("hdr", hdr);
             hdr_shader.set_float("exposure", exposure);
-            render_quad(&mut quadvao, &mut quadvbo);
+            render_quad(&mut quad);
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
             swapchain.present(&surface);
@@ -233,8 +220,8 @@ pub fn main() {
 }
 
 // renderCube() renders a 1x1 3D cube in NDC.
-fn render_cube(cubevao: &mut u32, cubevbo: &mut u32) {
-    if *cubevao == 0 {
+fn render_cube(cube: &mut Option<(GlVertexArray, GlBuffer)>) {
+    if cube.is_none() {
         let vertices: [f32; 288] = [
             // back face
             -1.0, -1.0, -1.0, 0.0, 0.0, -1.0, 0.0, 0.0, // bottom-left
@@ -280,15 +267,15 @@ fn render_cube(cubevao: &mut u32, cubevbo: &mut u32) {
             -1.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, // bottom-left
         ];
 
-        *cubevao = gl::gen_vertex_array();
-        *cubevbo = gl::gen_buffer();
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
         // fill buffer
-        gl::bind_buffer(GL_ARRAY_BUFFER, *cubevbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         // let size = (vertices.len() * mem::size_of::<f32>()) as isize;
         // let data = &vertices[0];
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
         // link vertex attributes
-        gl::bind_vertex_array(*cubevao);
+        gl::bind_vertex_array(cubevao.id());
         let stride = 8 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
@@ -298,34 +285,36 @@ fn render_cube(cubevao: &mut u32, cubevbo: &mut u32) {
         gl::vertex_attrib_pointer_offset(2, 2, GL_FLOAT, false, stride, 6 * mem::size_of::<f32>() as u32);
         gl::bind_buffer(GL_ARRAY_BUFFER, 0);
         gl::bind_vertex_array(0);
+        *cube = Some((cubevao, cubevbo));
     }
     // render Cube
-    gl::bind_vertex_array(*cubevao);
+    gl::bind_vertex_array(cube.as_ref().unwrap().0.id());
     gl::draw_arrays(GL_TRIANGLES, 0, 36);
     gl::bind_vertex_array(0);
 }
 
 // renders a 1x1 quad in NDC with manually calculated tangent vectors
-fn render_quad(quadvao: &mut u32, quadvbo: &mut u32) {
-    if *quadvao == 0 {
+fn render_quad(quad: &mut Option<(GlVertexArray, GlBuffer)>) {
+    if quad.is_none() {
         let quad_vertices: [f32; 20] = [
             // positions     // texture Coords
             -1.0, 1.0, 0.0, 0.0, 1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, -1.0, 0.0, 1.0, 0.0,
         ];
 
         // setup plane vao
-        *quadvao = gl::gen_vertex_array();
-        *quadvbo = gl::gen_buffer();
-        gl::bind_vertex_array(*quadvao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, *quadvbo);
+        let quadvao = GlVertexArray::new();
+        let quadvbo = GlBuffer::new();
+        gl::bind_vertex_array(quadvao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, quadvbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &quad_vertices, GL_STATIC_DRAW);
         let stride = 5 * mem::size_of::<f32>() as i32;
         gl::enable_vertex_attrib_array(0);
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
         gl::enable_vertex_attrib_array(1);
         gl::vertex_attrib_pointer_offset(1, 2, GL_FLOAT, false, stride, 3 * mem::size_of::<f32>() as u32);
+        *quad = Some((quadvao, quadvbo));
     }
-    gl::bind_vertex_array(*quadvao);
+    gl::bind_vertex_array(quad.as_ref().unwrap().0.id());
     gl::draw_arrays(GL_TRIANGLE_STRIP, 0, 4);
     gl::bind_vertex_array(0);
 }
@@ -401,42 +390,3 @@ pub fn process_input(
         _ => {}
     }
 }
-
-// NOTE: not the same version as in common.rs
-pub fn load_texture(path: PathBuf, gamma_correction: bool) -> u32 {
-    let texture_id = gl::gen_texture();
-    let path = path.as_path();
-
-    let img = image::open(&path).expect("Texture failed to load");
-    // need two different formats for gamma correction
-    let (internal_format, data_format) = match img {
-        ImageLuma8(_) => (GL_RED, GL_RED),
-        ImageLumaA8(_) => (GL_RG, GL_RG),
-        ImageRgb8(_) => (if gamma_correction { GL_SRGB } else { GL_RGB }, GL_RGB),
-        // ImageRgba8(_) => (if gammaCorrection { GL_SRGB_ALPHA } else { GL_RGB }, GL_RGBA),
-        _ => panic!("unhandled image format"),
-    };
-
-    let data = img.as_bytes();
-
-    gl::bind_texture(GL_TEXTURE_2D, texture_id);
-    gl::tex_image_2d(
-        GL_TEXTURE_2D,
-        0,
-        internal_format as i32,
-        img.width() as i32,
-        img.height() as i32,
-        0,
-        data_format,
-        GL_UNSIGNED_BYTE,
-        &data,
-    );
-    gl::generate_mipmap(GL_TEXTURE_2D);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR_MIPMAP_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-
-    texture_id
-}
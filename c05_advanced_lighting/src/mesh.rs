@@ -1,14 +1,27 @@
 use cgmath::prelude::*;
-use cgmath::{Vector2, Vector3};
+use cgmath::{Point3, Vector2, Vector3, Vector4};
 use dx::gles::{core30::gl, enums::*};
-use std::mem::size_of;
+use std::{cell::Cell, mem::size_of, rc::Rc};
+use vertex_format::VertexFormat;
 
+use super::bounds::{Aabb, BoundingSphere};
+use super::capabilities::Capabilities;
+use super::gl_object::{GlBuffer, GlVertexArray};
+use super::instancing::InstanceBuffer;
+use super::lod::{build_lods, Lod, LodOptions};
+use super::material::Material;
+use super::morph::{MorphPath, MorphTarget, MorphTargets};
+use super::optimize::MAX_U16_VERTICES;
 use super::shader::Shader;
+use super::texture_cache::CachedTexture;
 
 // NOTE: without repr(C) the compiler may reorder the fields or use different padding/alignment than C.
-// Depending on how you pass the data to OpenGL, this may be bad. In this case it's not strictly
-// necessary though because of the `offset!` macro used below in setupMesh()
+// `VertexFormat` computes the attribute offsets from the actual layout, but requires repr(C) so that
+// the layout doesn't change between builds.
+// Attribute locations follow the field order: position 0, normal 1, ... bitangent 4. Vertex
+// colors are a separate stream, see `Mesh::set_colors`; skinned meshes use `SkinnedVertex`.
 #[repr(C)]
+#[derive(Clone, Copy, VertexFormat)]
 pub struct Vertex {
     // position
     pub position: Vector3<f32>,
@@ -34,34 +47,107 @@ impl Default for Vertex {
     }
 }
 
-#[derive(Clone)]
+// Attribute locations of every stream a mesh can be drawn with, allocated one after the other
+// so that they never overlap:
+//   0-4    `Vertex`: position, normal, texCoords, tangent, bitangent
+//   5      vertex colors
+//   6-7    joints and weights of `SkinnedVertex`
+//   8-12   per-instance data
+//   13-20  morph target deltas on the vertex attribute path
+// OpenGL ES 3.0 only promises 16 locations. Everything but the morph deltas fits in them, and
+// `MorphPath::select` only picks the attribute path where the context has the extra ones.
+
+/// Location of the vertex colors of meshes that have them, right after the `Vertex`
+/// attributes; see `Mesh::set_colors`.
+pub const COLOR_ATTRIBUTE_LOCATION: u32 = Vertex::ATTRIBUTES.len() as u32;
+
+/// First location of the joints and weights of `SkinnedVertex`.
+pub const SKINNING_ATTRIBUTE_LOCATION: u32 = COLOR_ATTRIBUTE_LOCATION + 1;
+
+/// First location of per-instance data, see `InstanceBuffer`.
+pub const INSTANCE_ATTRIBUTE_LOCATION: u32 = SKINNING_ATTRIBUTE_LOCATION + 2;
+
+/// Locations per-instance data may use: enough for a model matrix and a color.
+pub const MAX_INSTANCE_ATTRIBUTES: u32 = 5;
+
+/// First location of the morph target deltas on the vertex attribute path.
+pub const MORPH_ATTRIBUTE_LOCATION: u32 = INSTANCE_ATTRIBUTE_LOCATION + MAX_INSTANCE_ATTRIBUTES;
+
+#[derive(Clone, Debug)]
 pub struct Texture {
     pub id: u32,
-    pub type_: String,
     pub path: String,
+    /// keeps the texture alive in the `TextureCache`; `None` for textures the caller manages
+    pub cached: Option<Rc<CachedTexture>>,
 }
 
-pub struct Mesh {
+/// An indexed triangle mesh with its material. The vertex layout comes from the vertex
+/// type's `VertexFormat`; morph targets need the full `Vertex`.
+pub struct Mesh<V: VertexFormat = Vertex> {
     /*  Mesh Data  */
-    pub vertices: Vec<Vertex>,
+    pub vertices: Vec<V>,
     pub indices: Vec<u32>,
-    pub textures: Vec<Texture>,
-    pub vao: u32,
+    pub material: Rc<Material>,
+    pub vao: GlVertexArray,
+
+    /*  Bounds in model space  */
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+
+    /*  Blend shapes  */
+    pub morph_targets: Option<MorphTargets>,
+
+    /*  Per-vertex colors (RGBA), for models whose files have them; see `set_colors`  */
+    pub colors: Option<Vec<Vector4<f32>>>,
+
+    /*  Simplified versions, see `set_lods`  */
+    pub lods: Vec<Lod>,
+    // error of every level, kept in step with `lods` by `set_lods`
+    lod_errors: Vec<f32>,
 
     /*  Render data  */
-    vbo: u32,
-    ebo: u32,
+    vbo: GlBuffer,
+    ebo: GlBuffer,
+    // the colors, in their own buffer so meshes without them don't pay for them
+    color_vbo: Option<GlBuffer>,
+    // the element buffer holds u16 indices, see `upload_indices`
+    u16_indices: bool,
+    // `InstanceBuffer::uid` of the buffer the VAO's instance attributes point at
+    instance_binding: Cell<Option<u64>>,
 }
 
-impl Mesh {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, textures: Vec<Texture>) -> Mesh {
+impl<V: VertexFormat> Mesh<V> {
+    pub fn new(vertices: Vec<V>, indices: Vec<u32>, material: Rc<Material>) -> Mesh<V> {
+        let positions = vertices.iter().map(|vertex| Point3::from_vec(vertex.position()));
+        let aabb = Aabb::from_points(positions.clone());
+        let bounding_sphere = BoundingSphere::from_points(positions);
+        Mesh::with_bounds(vertices, indices, material, aabb, bounding_sphere)
+    }
+
+    /// `new` with bounds computed beforehand, e.g. read from a model cache
+    pub fn with_bounds(
+        vertices: Vec<V>,
+        indices: Vec<u32>,
+        material: Rc<Material>,
+        aabb: Aabb,
+        bounding_sphere: BoundingSphere,
+    ) -> Mesh<V> {
         let mut mesh = Mesh {
             vertices,
             indices,
-            textures,
-            vao: 0,
-            vbo: 0,
-            ebo: 0,
+            material,
+            vao: GlVertexArray::new(),
+            aabb,
+            bounding_sphere,
+            morph_targets: None,
+            colors: None,
+            lods: Vec::new(),
+            lod_errors: vec![0.0],
+            vbo: GlBuffer::new(),
+            ebo: GlBuffer::new(),
+            color_vbo: None,
+            u16_indices: false,
+            instance_binding: Cell::new(None),
         };
 
         // now that we have all the required data, set the vertex buffers and its attribute pointers.
@@ -69,92 +155,215 @@ impl Mesh {
         mesh
     }
 
-    /// render the mesh
+    /// simplifies the mesh into a chain of LODs, see `build_lods`
+    pub fn generate_lods(&mut self, options: &LodOptions) {
+        let lods = build_lods(&self.vertices, &self.indices, options);
+        self.set_lods(lods);
+    }
+
+    /// replaces the LODs (level 1 and up; level 0 is the mesh itself) and uploads their
+    /// indices after the mesh's own in the element buffer.
+    pub fn set_lods(&mut self, mut lods: Vec<Lod>) {
+        let mut indices = self.indices.clone();
+        for lod in &mut lods {
+            lod.offset = indices.len();
+            indices.extend_from_slice(&lod.indices);
+        }
+        self.lod_errors = std::iter::once(0.0).chain(lods.iter().map(|lod| lod.error)).collect();
+        self.lods = lods;
+
+        gl::bind_vertex_array(self.vao.id());
+        self.upload_indices(&indices);
+        gl::bind_vertex_array(0);
+    }
+
+    /// number of levels including the full resolution one
+    pub fn lod_count(&self) -> usize {
+        self.lods.len() + 1
+    }
+
+    /// simplification error of every level in model units, starting with 0 for the mesh itself
+    pub fn lod_errors(&self) -> &[f32] {
+        &self.lod_errors
+    }
+
+    /// render the mesh; morph targets keep the weights of the last `draw_with_weights`
     pub fn draw(&self, shader: &Shader) {
-        // bind appropriate textures
-        let mut diffuse_nr = 0;
-        let mut specular_nr = 0;
-        let mut normal_nr = 0;
-        let mut height_nr = 0;
-        for (i, texture) in self.textures.iter().enumerate() {
-            gl::active_texture(GL_TEXTURE0 + i as u32); // active proper texture unit before binding
-                                                        // retrieve texture number (the N in diffuse_textureN)
-            let name = &texture.type_;
-            let number = match name.as_str() {
-                "texture_diffuse" => {
-                    diffuse_nr += 1;
-                    diffuse_nr
-                }
-                "texture_specular" => {
-                    specular_nr += 1;
-                    specular_nr
-                }
-                "texture_normal" => {
-                    normal_nr += 1;
-                    normal_nr
-                }
-                "texture_height" => {
-                    height_nr += 1;
-                    height_nr
-                }
-                _ => panic!("unknown texture type"),
-            };
-            // now set the sampler to the correct texture unit
-            let sampler = format!("{}{}", name, number);
-            gl::uniform1i(gl::get_uniform_location(shader.id, sampler.as_str()), i as i32);
-            // and finally bind the texture
-            gl::bind_texture(GL_TEXTURE_2D, texture.id);
+        self.draw_lod(shader, 0);
+    }
+
+    /// render a LOD of the mesh, the coarsest one if `level` is past the end of the chain
+    pub fn draw_lod(&self, shader: &Shader, level: usize) {
+        self.draw_elements(shader, level, None);
+    }
+
+    /// render one copy of the mesh per instance in `instances`, see `InstanceBuffer`
+    pub fn draw_instanced<I: VertexFormat>(&self, shader: &Shader, instances: &InstanceBuffer<I>) {
+        if instances.is_empty() {
+            return;
         }
+        // the VAO keeps pointing at the last instance buffer drawn with
+        let binding = Some(instances.uid());
+        if self.instance_binding.get() != binding {
+            gl::bind_vertex_array(self.vao.id());
+            instances.bind_attributes();
+            gl::bind_vertex_array(0);
+            self.instance_binding.set(binding);
+        }
+        self.draw_elements(shader, 0, Some(instances.len()));
+    }
+
+    fn draw_elements(&self, shader: &Shader, level: usize, instances: Option<usize>) {
+        // bind the material's textures and parameters
+        self.material.bind(shader);
+        let state = self.material.apply_render_state();
 
         // draw mesh
-        gl::bind_vertex_array(self.vao);
-        gl::draw_elements_offset(GL_TRIANGLES, self.indices.len() as i32, GL_UNSIGNED_INT, 0);
+        gl::bind_vertex_array(self.vao.id());
+        let (offset, count) = match level.min(self.lods.len()) {
+            0 => (0, self.indices.len()),
+            level => (self.lods[level - 1].offset, self.lods[level - 1].indices.len()),
+        };
+        let (index_type, index_size) = if self.u16_indices {
+            (GL_UNSIGNED_SHORT, size_of::<u16>())
+        } else {
+            (GL_UNSIGNED_INT, size_of::<u32>())
+        };
+        let offset = (offset * index_size) as u32;
+        match instances {
+            Some(instances) => {
+                gl::draw_elements_instanced_offset(GL_TRIANGLES, count as i32, index_type, offset, instances as i32)
+            }
+            None => gl::draw_elements_offset(GL_TRIANGLES, count as i32, index_type, offset),
+        }
         gl::bind_vertex_array(0);
 
         // always good practice to set everything back to defaults once configured.
-        gl::active_texture(GL_TEXTURE0);
+        state.restore();
     }
 
     fn setup_mesh(&mut self) {
-        // create buffers/arrays
-        self.vao = gl::gen_vertex_array();
-        self.vbo = gl::gen_buffer();
-        self.ebo = gl::gen_buffer();
-
-        gl::bind_vertex_array(self.vao);
+        gl::bind_vertex_array(self.vao.id());
         // load data into vertex buffers
-        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
         // A great thing about structs with repr(C) is that their memory layout is sequential for all its items.
         // The effect is that we can simply pass a pointer to the struct and it translates perfectly to a glm::vec3/2 array which
         // again translates to 3/2 floats which translates to a byte array.
         gl::buffer_data(GL_ARRAY_BUFFER, self.vertices.as_slice(), GL_STATIC_DRAW);
 
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, self.ebo);
-        gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, self.indices.as_slice(), GL_STATIC_DRAW);
+        let indices = std::mem::take(&mut self.indices);
+        self.upload_indices(&indices);
+        self.indices = indices;
 
         // set the vertex attribute pointers
-        let size = size_of::<Vertex>() as i32;
-        // vertex Positions
-        gl::enable_vertex_attrib_array(0);
-        // gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, size, offset_of!(Vertex, Position));
-        gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, size, 0);
-        // vertex normals
-        gl::enable_vertex_attrib_array(1);
-        // gl::vertex_attrib_pointer_offset(1, 3, GL_FLOAT, false, size, offset_of!(Vertex, Normal));
-        gl::vertex_attrib_pointer_offset(1, 3, GL_FLOAT, false, size, 12); // 3 * 4
-                                                                           // vertex texture coords
-        gl::enable_vertex_attrib_array(2);
-        // gl::vertex_attrib_pointer_offset(2, 2, GL_FLOAT, false, size, offset_of!(Vertex, TexCoords));
-        gl::vertex_attrib_pointer_offset(2, 2, GL_FLOAT, false, size, 24); // 12 + 3 * 4
-                                                                           // vertex tangent
-        gl::enable_vertex_attrib_array(3);
-        // gl::vertex_attrib_pointer_offset(3, 3, GL_FLOAT, false, size, offset_of!(Vertex, Tangent));
-        gl::vertex_attrib_pointer_offset(3, 3, GL_FLOAT, false, size, 32); // 24 + 2 * 4
-                                                                           // vertex bitangent
-        gl::enable_vertex_attrib_array(4);
-        // gl::vertex_attrib_pointer_offset(4, 3, GL_FLOAT, false, size, offset_of!(Vertex, Bitangent));
-        gl::vertex_attrib_pointer_offset(4, 3, GL_FLOAT, false, size, 44); // 32 + 3 * 4
+        set_attribute_pointers::<V>();
 
         gl::bind_vertex_array(0);
     }
+
+    // fills the element buffer of the bound VAO, with u16 indices whenever the vertex count
+    // allows it: half the memory and bandwidth, and all OpenGL ES 2.0 can draw without
+    // `OES_element_index_uint`
+    fn upload_indices(&mut self, indices: &[u32]) {
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, self.ebo.id());
+        self.u16_indices = self.vertices.len() <= MAX_U16_VERTICES;
+        if self.u16_indices {
+            let indices: Vec<u16> = indices.iter().map(|&idx| idx as u16).collect();
+            gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, indices.as_slice(), GL_STATIC_DRAW);
+        } else {
+            if !Capabilities::current().u32_indices() {
+                println!(
+                    "WARNING::MESH: {} vertices need 32-bit indices, which this context can't draw",
+                    self.vertices.len()
+                );
+            }
+            gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, indices, GL_STATIC_DRAW);
+        }
+    }
+}
+
+/// enables and points the attributes of `V` at the bound `GL_ARRAY_BUFFER`, in the bound VAO
+pub(crate) fn set_attribute_pointers<V: VertexFormat>() {
+    let stride = size_of::<V>() as i32;
+    for attribute in V::ATTRIBUTES {
+        // matrices take one location per column
+        for column in 0..attribute.locations {
+            let location = attribute.location + column;
+            let offset = (attribute.offset + column as usize * attribute.column_size()) as u32;
+            let component_type = attribute.component_type.gl_enum();
+            gl::enable_vertex_attrib_array(location);
+            if attribute.integer {
+                gl::vertex_attrib_i_pointer_offset(location, attribute.components, component_type, stride, offset);
+            } else {
+                gl::vertex_attrib_pointer_offset(
+                    location,
+                    attribute.components,
+                    component_type,
+                    attribute.normalized,
+                    stride,
+                    offset,
+                );
+            }
+            if attribute.divisor != 0 {
+                gl::vertex_attrib_divisor(location, attribute.divisor);
+            }
+        }
+    }
+}
+
+impl Mesh {
+    /// attaches a color to every vertex, read by shaders at `COLOR_ATTRIBUTE_LOCATION`. Without
+    /// colors the attribute is disabled and reads as GL's default (0, 0, 0, 1).
+    pub fn set_colors(&mut self, colors: Vec<Vector4<f32>>) {
+        assert_eq!(colors.len(), self.vertices.len(), "one color per vertex");
+        let vbo = self.color_vbo.get_or_insert_with(GlBuffer::new);
+        gl::bind_vertex_array(self.vao.id());
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
+        gl::buffer_data(GL_ARRAY_BUFFER, colors.as_slice(), GL_STATIC_DRAW);
+        gl::enable_vertex_attrib_array(COLOR_ATTRIBUTE_LOCATION);
+        gl::vertex_attrib_pointer_offset(
+            COLOR_ATTRIBUTE_LOCATION,
+            4,
+            GL_FLOAT,
+            false,
+            size_of::<Vector4<f32>>() as i32,
+            0,
+        );
+        gl::bind_vertex_array(0);
+        gl::bind_buffer(GL_ARRAY_BUFFER, 0);
+        self.colors = Some(colors);
+    }
+
+    /// attaches morph targets, applied with the best path the current context supports
+    pub fn set_morph_targets(&mut self, targets: Vec<MorphTarget>) {
+        let path = MorphPath::select(&Capabilities::current(), targets.len());
+        self.set_morph_targets_with_path(targets, path);
+    }
+
+    /// attaches morph targets applied with the given path
+    pub fn set_morph_targets_with_path(&mut self, targets: Vec<MorphTarget>, path: MorphPath) {
+        if path == MorphPath::Cpu {
+            // the blended vertices are uploaded every draw
+            gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
+            gl::buffer_data(GL_ARRAY_BUFFER, self.vertices.as_slice(), GL_DYNAMIC_DRAW);
+            gl::bind_buffer(GL_ARRAY_BUFFER, 0);
+        }
+        self.morph_targets = Some(MorphTargets::new(targets, path, self.vao.id(), self.vertices.len()));
+    }
+
+    /// render the mesh with the given morph target weights, one per target; missing weights
+    /// count as 0. The weights belong to the instance being drawn, so a mesh can be drawn
+    /// several times per frame with different weights.
+    pub fn draw_with_weights(&self, shader: &Shader, weights: &[f32]) {
+        if let Some(morph) = &self.morph_targets {
+            if morph.path == MorphPath::Cpu {
+                let vertices = morph.blend_reused(&self.vertices, weights);
+                gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
+                gl::buffer_sub_data(GL_ARRAY_BUFFER, 0, vertices.as_slice());
+                gl::bind_buffer(GL_ARRAY_BUFFER, 0);
+            }
+            morph.set_uniforms(shader, weights, self.vertices.len());
+        }
+        self.draw(shader);
+    }
 }
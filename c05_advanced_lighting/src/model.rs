@@ -1,158 +1,677 @@
-use cgmath::{vec2, vec3};
-use dx::gles::{core30::gl, enums::*};
-use image::{DynamicImage::*, GenericImageView};
-use std::path::{Path, PathBuf};
+use cgmath::prelude::*;
+use cgmath::{vec2, vec3, vec4, Matrix4, Point3, Vector4};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 use tobj::LoadOptions;
+use vertex_format::{VertexAttribute, VertexFormat};
 
+use super::bounds::{Aabb, BoundingSphere};
+use super::capabilities::Capabilities;
+use super::gltf::{load_gltf, GltfError};
+use super::instancing::InstanceBuffer;
+use super::lod::{build_lods, Lod, LodCamera, LodOptions, LodSelector};
+use super::material::{AlphaMode, Material, TextureSlot};
 use super::mesh::{Mesh, Texture, Vertex};
+use super::model_cache::{is_cache_fresh, model_cache_path, read_model_cache};
+use super::normals::{generate_normals, generate_tangents, Normals};
+use super::optimize::{optimize_mesh, optimize_vertex_cache, split_for_u16, OptimizeOptions, OptimizeReport};
+use super::ply::{load_ply, PlyError};
 use super::shader::Shader;
+use super::stl::{load_stl, StlError};
+use super::texture_cache::{canonical, CachedTexture, TextureCache, TextureOptions};
+
+/// Errors that prevent a model from being loaded.
+#[derive(Debug)]
+pub enum ModelError {
+    /// the OBJ file could not be read or parsed
+    Obj(tobj::LoadError),
+    /// a texture referenced by a material could not be loaded
+    Texture { path: PathBuf, reason: String },
+    /// the PLY file could not be read or parsed
+    Ply(PlyError),
+    /// the STL file could not be read or parsed
+    Stl(StlError),
+    /// the glTF file could not be read or parsed
+    Gltf(GltfError),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Obj(err) => write!(f, "failed to load OBJ file: {}", err),
+            ModelError::Texture { path, reason } => write!(f, "failed to load texture {:?}: {}", path, reason),
+            ModelError::Ply(err) => write!(f, "failed to load PLY file: {}", err),
+            ModelError::Stl(err) => write!(f, "failed to load STL file: {}", err),
+            ModelError::Gltf(err) => write!(f, "failed to load glTF file: {}", err),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+/// Options controlling how a model file is turned into meshes.
+#[derive(Clone, Copy, Debug)]
+pub struct ModelOptions {
+    /// how normals are generated for meshes that don't provide them
+    pub normals: Normals,
+    /// how material textures are loaded
+    pub textures: TextureOptions,
+    /// builds a LOD chain for every mesh while loading, see `Mesh::generate_lods`
+    pub lods: Option<LodOptions>,
+    /// reorders every mesh for the GPU while loading, see `optimize_mesh`; on by default
+    pub optimize: Option<OptimizeOptions>,
+}
+
+impl Default for ModelOptions {
+    fn default() -> Self {
+        ModelOptions {
+            normals: Normals::default(),
+            textures: TextureOptions::default(),
+            lods: None,
+            optimize: Some(OptimizeOptions::default()),
+        }
+    }
+}
+
+impl ModelOptions {
+    /// turns on `OptimizeOptions::split_for_u16` if the current context can't draw 32-bit
+    /// indices; the loading itself may happen on a thread without a context.
+    pub fn for_current_context(mut self) -> Self {
+        if let Some(optimize) = &mut self.optimize {
+            optimize.split_for_u16 |= !Capabilities::current().u32_indices();
+        }
+        self
+    }
+}
+
+/// Vertices and indices of one mesh, before upload.
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// one color per vertex, if the file has vertex colors; see `Mesh::set_colors`
+    pub colors: Option<Vec<Vector4<f32>>>,
+    /// index into `ModelData::materials`
+    pub material: Option<usize>,
+    /// simplified versions, if `ModelOptions::lods` asked for them
+    pub lods: Vec<Lod>,
+    /// bounds of the vertices
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+}
+
+/// A model file parsed into memory. Parsing doesn't touch GL, so it can happen on any
+/// thread; `Model::from_data` creates the GL objects.
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    /// how well every mesh of the file uses the vertex cache, if `ModelOptions::optimize` was set;
+    /// a mesh split for 16-bit indices has one report for all its parts
+    pub optimize_reports: Vec<OptimizeReport>,
+    pub materials: Vec<tobj::Material>,
+    /// directory of the model file, texture paths are relative to it
+    pub directory: PathBuf,
+    /// other files the model was read from (OBJ material libraries, glTF buffers); a compiled
+    /// model older than any of them is out of date
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl ModelData {
+    /// loads the compiled model cache next to the file (see `model_cache_path`) if it is newer
+    /// than the file and its `dependencies` and was compiled with the same options, parses
+    /// the file otherwise.
+    pub fn load<T>(path: T, options: &ModelOptions) -> Result<ModelData, ModelError>
+    where
+        T: Into<PathBuf>,
+    {
+        let path: PathBuf = path.into();
+        let cache_path = model_cache_path(&path);
+        if is_cache_fresh(&path, &cache_path) {
+            match read_model_cache(&cache_path, options) {
+                Ok(data) => match data.dependencies.iter().find(|file| !is_cache_fresh(file, &cache_path)) {
+                    None => return Ok(data),
+                    Some(file) => println!("ignoring model cache {:?}: {:?} changed", cache_path, file),
+                },
+                Err(err) => println!("ignoring model cache {:?}: {}", cache_path, err),
+            }
+        }
+        ModelData::parse(path, options)
+    }
+
+    /// parses the model file, ignoring any cache
+    pub fn parse<T>(path: T, options: &ModelOptions) -> Result<ModelData, ModelError>
+    where
+        T: Into<PathBuf>,
+    {
+        let path: PathBuf = path.into();
+
+        // retrieve the directory path of the filepath
+        let directory = path.parent().unwrap_or_else(|| Path::new("")).into();
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        let (raw_meshes, materials, dependencies) = match extension.to_ascii_lowercase().as_str() {
+            "ply" => (vec![load_ply(&path).map_err(ModelError::Ply)?], Vec::new(), Vec::new()),
+            "stl" => (vec![load_stl(&path).map_err(ModelError::Stl)?], Vec::new(), Vec::new()),
+            "gltf" | "glb" => {
+                let scene = load_gltf(&path).map_err(ModelError::Gltf)?;
+                (scene.meshes, scene.materials, scene.buffers)
+            }
+            _ => {
+                let dependencies = material_libraries(&path);
+                let (meshes, materials) = load_obj(path)?;
+                (meshes, materials, dependencies)
+            }
+        };
+
+        let mut meshes = Vec::new();
+        let mut optimize_reports = Vec::new();
+        for raw in raw_meshes {
+            let (parts, report) = process_mesh(raw, options);
+            meshes.extend(parts);
+            optimize_reports.extend(report);
+        }
+        Ok(ModelData {
+            meshes,
+            optimize_reports,
+            materials,
+            directory,
+            dependencies,
+        })
+    }
+
+    /// paths of every texture the materials reference, without duplicates
+    pub fn texture_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for material in &self.materials {
+            for (_, path) in material_textures(material) {
+                let path = self.directory.join(path);
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        paths
+    }
+}
+
+/// A mesh as read from a model file, before normals, optimization and LODs.
+pub struct RawMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// the file provides normals; they are generated otherwise, see `ModelOptions::normals`
+    pub has_normals: bool,
+    /// the file provides texture coordinates, which the tangents are generated from
+    pub has_tex_coords: bool,
+    /// one color per vertex, for files with vertex colors
+    pub colors: Option<Vec<Vector4<f32>>>,
+    /// index into the materials of the file
+    pub material: Option<usize>,
+}
+
+fn load_obj(path: PathBuf) -> Result<(Vec<RawMesh>, Vec<tobj::Material>), ModelError> {
+    let (models, materials) = tobj::load_obj(
+        path,
+        &LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+    )
+    .map_err(ModelError::Obj)?;
+    // a missing or broken material library still leaves the geometry usable, drawn with the
+    // default material
+    let materials = materials.unwrap_or_else(|err| {
+        println!(
+            "WARNING::MODEL: failed to load materials, using the default material: {}",
+            err
+        );
+        Vec::new()
+    });
+    Ok((models.iter().map(|model| obj_mesh(&model.mesh)).collect(), materials))
+}
+
+/// the material libraries an OBJ file refers to, resolved like tobj does
+fn material_libraries(path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let text = fs::read(path).unwrap_or_default();
+    String::from_utf8_lossy(&text)
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("mtllib"), Some(library)) => Some(directory.join(library)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn obj_mesh(mesh: &tobj::Mesh) -> RawMesh {
+    let num_vertices = mesh.positions.len() / 3;
+    let has_normals = mesh.normals.len() == mesh.positions.len();
+    let has_tex_coords = mesh.texcoords.len() / 2 == num_vertices;
+    // `v x y z r g b`
+    let has_colors = mesh.vertex_color.len() == mesh.positions.len();
+
+    // data to fill
+    let mut vertices: Vec<Vertex> = Vec::with_capacity(num_vertices);
+    let mut colors = Vec::with_capacity(if has_colors { num_vertices } else { 0 });
+
+    for idx in 0..num_vertices {
+        let mut vertex = Vertex {
+            position: vec3(
+                mesh.positions[idx * 3],
+                mesh.positions[idx * 3 + 1],
+                mesh.positions[idx * 3 + 2],
+            ),
+            ..Vertex::default()
+        };
+        if has_normals {
+            vertex.normal = vec3(
+                mesh.normals[idx * 3],
+                mesh.normals[idx * 3 + 1],
+                mesh.normals[idx * 3 + 2],
+            );
+        }
+        // meshes without texture coordinates keep (0, 0) for every vertex
+        if has_tex_coords {
+            vertex.tex_coords = vec2(mesh.texcoords[idx * 2], mesh.texcoords[idx * 2 + 1]);
+        }
+        if has_colors {
+            colors.push(vec4(
+                mesh.vertex_color[idx * 3],
+                mesh.vertex_color[idx * 3 + 1],
+                mesh.vertex_color[idx * 3 + 2],
+                1.0,
+            ));
+        }
+        vertices.push(vertex);
+    }
+
+    RawMesh {
+        vertices,
+        indices: mesh.indices.clone(),
+        has_normals,
+        has_tex_coords,
+        colors: if has_colors { Some(colors) } else { None },
+        material: mesh.material_id,
+    }
+}
+
+/// finishes a mesh of the file, which may turn into several meshes when split for 16-bit indices
+fn process_mesh(raw: RawMesh, options: &ModelOptions) -> (Vec<MeshData>, Option<OptimizeReport>) {
+    let RawMesh {
+        mut vertices,
+        mut indices,
+        has_normals,
+        has_tex_coords,
+        mut colors,
+        material,
+    } = raw;
+    if !has_normals {
+        let origins = generate_normals(&mut vertices, &mut indices, options.normals);
+        if let Some(colors) = &mut colors {
+            *colors = origins.iter().map(|&idx| colors[idx as usize]).collect();
+        }
+    }
+    // for normal maps; without texture coordinates there is no tangent space
+    if has_tex_coords {
+        generate_tangents(&mut vertices, &indices);
+    }
+
+    match colors {
+        None => finish_mesh(vertices, indices, material, options, |vertices| (vertices, None)),
+        // the colors go through the same reordering and splitting as their vertices
+        Some(colors) => {
+            let vertices = vertices
+                .into_iter()
+                .zip(colors)
+                .map(|(vertex, color)| ColoredVertex { vertex, color })
+                .collect();
+            finish_mesh(vertices, indices, material, options, |vertices| {
+                let (vertices, colors) = vertices
+                    .into_iter()
+                    .map(|colored| (colored.vertex, colored.color))
+                    .unzip();
+                (vertices, Some(colors))
+            })
+        }
+    }
+}
+
+// a vertex and its color, kept together while `finish_mesh` reorders and splits the vertices
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ColoredVertex {
+    vertex: Vertex,
+    color: Vector4<f32>,
+}
+
+// SAFETY: `vertex` is the first field of a repr(C) struct, so the `Vertex` attributes are at the
+// same offsets
+unsafe impl VertexFormat for ColoredVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] = Vertex::ATTRIBUTES;
+}
+
+// optimizes, splits and simplifies a mesh; `streams` takes the vertices apart into `Vertex` and
+// the colors
+fn finish_mesh<V: VertexFormat>(
+    mut vertices: Vec<V>,
+    mut indices: Vec<u32>,
+    material: Option<usize>,
+    options: &ModelOptions,
+    streams: impl Fn(Vec<V>) -> (Vec<Vertex>, Option<Vec<Vector4<f32>>>),
+) -> (Vec<MeshData>, Option<OptimizeReport>) {
+    let report = options
+        .optimize
+        .map(|optimize| optimize_mesh(&mut vertices, &mut indices, &optimize));
+    let parts = match options.optimize {
+        Some(optimize) if optimize.split_for_u16 => split_for_u16(&vertices, &indices),
+        _ => vec![(vertices, indices)],
+    };
+
+    let meshes = parts
+        .into_iter()
+        .map(|(vertices, indices)| {
+            // simplifying is slow enough to do it here, off the GL thread when loading asynchronously
+            let mut lods = match &options.lods {
+                Some(lod_options) => build_lods(&vertices, &indices, lod_options),
+                None => Vec::new(),
+            };
+            if options.optimize.is_some() {
+                for lod in &mut lods {
+                    optimize_vertex_cache(&mut lod.indices, vertices.len());
+                }
+            }
+            let (vertices, colors) = streams(vertices);
+            let positions = vertices.iter().map(|vertex| Point3::from_vec(vertex.position));
+            MeshData {
+                aabb: Aabb::from_points(positions.clone()),
+                bounding_sphere: BoundingSphere::from_points(positions),
+                vertices,
+                indices,
+                colors,
+                material,
+                lods,
+            }
+        })
+        .collect();
+    (meshes, report)
+}
+
+/// texture files referenced by an OBJ material and the slots they go to
+fn material_textures(material: &tobj::Material) -> Vec<(TextureSlot, String)> {
+    let emissive_texture = material.unknown_param.get("map_Ke").cloned().unwrap_or_default();
+    let textures = [
+        (TextureSlot::BaseColor, material.diffuse_texture.clone()),
+        (TextureSlot::Specular, material.specular_texture.clone()),
+        (TextureSlot::Normal, material.normal_texture.clone()),
+        (TextureSlot::Occlusion, material.ambient_texture.clone()),
+        (TextureSlot::Emissive, emissive_texture),
+    ];
+    textures.iter().filter(|(_, path)| !path.is_empty()).cloned().collect()
+}
+
+/// Textures of a model that an `AssetLoader` worker failed to decode, or that failed to
+/// upload, by canonical path, and the texture the materials get instead.
+pub(crate) struct FailedTextures {
+    pub(crate) errors: HashMap<PathBuf, ModelError>,
+    pub(crate) placeholder: Rc<CachedTexture>,
+}
+
+/// Creates the GL objects of a `ModelData` one mesh at a time, so the work can be spread
+/// over several frames (see `AssetLoader`).
+pub(crate) struct ModelUpload {
+    model: Model,
+    meshes: std::vec::IntoIter<MeshData>,
+    // for meshes without a material
+    default_material: Rc<Material>,
+}
+
+impl ModelUpload {
+    /// converts the materials, taking their textures from the `TextureCache`. Textures in
+    /// `failed` are reported and replaced by its placeholder instead of being loaded again.
+    pub(crate) fn new(data: ModelData, options: &ModelOptions, mut failed: Option<FailedTextures>) -> ModelUpload {
+        let mut model = Model {
+            directory: data.directory,
+            ..Model::default()
+        };
+        model.materials = data
+            .materials
+            .iter()
+            .map(|material| Rc::new(model.load_material(material, options, failed.as_mut())))
+            .collect();
+        ModelUpload {
+            model,
+            meshes: data.meshes.into_iter(),
+            default_material: Rc::new(Material::default()),
+        }
+    }
+
+    /// uploads the next mesh; false once all meshes are uploaded
+    pub(crate) fn upload_next_mesh(&mut self) -> bool {
+        let data = match self.meshes.next() {
+            Some(data) => data,
+            None => return false,
+        };
+        let material = match data.material.and_then(|id| self.model.materials.get(id)) {
+            Some(material) => material.clone(),
+            None => self.default_material.clone(),
+        };
+        let mut mesh = Mesh::with_bounds(data.vertices, data.indices, material, data.aabb, data.bounding_sphere);
+        if !data.lods.is_empty() {
+            mesh.set_lods(data.lods);
+        }
+        if let Some(colors) = data.colors {
+            mesh.set_colors(colors);
+        }
+        self.model.meshes.push(mesh);
+        true
+    }
+
+    pub(crate) fn finish(mut self) -> Model {
+        self.model.update_bounds();
+        self.model.update_lod_errors();
+        self.model
+    }
+}
 
 #[derive(Default)]
 pub struct Model {
     /*  Model Data */
     pub meshes: Vec<Mesh>,
-    pub textures_loaded: Vec<Texture>, // stores all the textures loaded so far, optimization to make sure textures aren't loaded more than once.
-    directory: String,
+    /// materials of the model file, shared by the meshes using them
+    pub materials: Vec<Rc<Material>>,
+    pub textures_loaded: Vec<Texture>, // stores all the textures of the model; the `TextureCache` shares them with other models.
+    /// textures referenced by the materials that could not be loaded; the meshes are drawn without them,
+    /// or with the placeholder texture if an `AssetLoader` loaded the model.
+    pub missing_textures: Vec<ModelError>,
+    /// bounds of all meshes in model space, see `Aabb::transform` for world-space bounds.
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    // error of every level over all meshes, see `update_lod_errors`
+    lod_errors: Vec<f32>,
+    directory: PathBuf,
 }
 
 impl Model {
     /// constructor, expects a filepath to a 3D model.
-    pub fn new<T>(path: T) -> Model
+    pub fn new<T>(path: T) -> Result<Model, ModelError>
     where
         T: Into<PathBuf>,
     {
-        let mut model = Model::default();
-        model.load_model(path);
-        model
+        Model::with_options(path, ModelOptions::default())
+    }
+
+    pub fn with_options<T>(path: T, options: ModelOptions) -> Result<Model, ModelError>
+    where
+        T: Into<PathBuf>,
+    {
+        let options = options.for_current_context();
+        let data = ModelData::load(path, &options)?;
+        Ok(Model::from_data(data, &options))
+    }
+
+    /// creates the GL objects of a parsed model; textures not in the `TextureCache` yet
+    /// are loaded from disk.
+    pub fn from_data(data: ModelData, options: &ModelOptions) -> Model {
+        let mut upload = ModelUpload::new(data, options, None);
+        while upload.upload_next_mesh() {}
+        upload.finish()
     }
 
     pub fn draw(&self, shader: &Shader) {
+        self.draw_lod(shader, 0);
+    }
+
+    /// draws every mesh at the given level, or at its coarsest one if it has fewer levels
+    pub fn draw_lod(&self, shader: &Shader, level: usize) {
         for mesh in &self.meshes {
-            mesh.draw(shader);
+            mesh.draw_lod(shader, level);
         }
     }
 
-    // loads a model from file and stores the resulting meshes in the meshes vector.
-    fn load_model<T>(&mut self, path: T)
-    where
-        T: Into<PathBuf>,
-    {
-        let path: PathBuf = path.into();
+    /// draws every mesh once per instance in `instances`, see `InstanceBuffer`
+    pub fn draw_instanced<I: VertexFormat>(&self, shader: &Shader, instances: &InstanceBuffer<I>) {
+        for mesh in &self.meshes {
+            mesh.draw_instanced(shader, instances);
+        }
+    }
 
-        // retrieve the directory path of the filepath
-        self.directory = path.parent().unwrap_or_else(|| Path::new("")).to_str().unwrap().into();
-        let obj = tobj::load_obj(
-            path,
-            &LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        );
+    /// builds the LOD chains of all meshes
+    pub fn generate_lods(&mut self, options: &LodOptions) {
+        for mesh in &mut self.meshes {
+            mesh.generate_lods(options);
+        }
+        self.update_lod_errors();
+    }
 
-        let (models, materials) = obj.unwrap();
-        for model in models {
-            let mesh = &model.mesh;
-            let num_vertices = mesh.positions.len() / 3;
-
-            // data to fill
-            let mut vertices: Vec<Vertex> = Vec::with_capacity(num_vertices);
-            let indices: Vec<u32> = mesh.indices.clone();
-
-            for idx in 0..num_vertices {
-                vertices.push(Vertex {
-                    position: vec3(mesh.positions[idx * 3], mesh.positions[idx * 3 + 1], mesh.positions[idx * 3 + 2]),
-                    normal: vec3(mesh.normals[idx * 3], mesh.normals[idx * 3 + 1], mesh.normals[idx * 3 + 2]),
-                    tex_coords: vec2(mesh.texcoords[idx * 2], mesh.texcoords[idx * 2 + 1]),
-                    ..Vertex::default()
-                })
-            }
+    /// number of levels of the mesh with the longest LOD chain
+    pub fn lod_count(&self) -> usize {
+        self.meshes.iter().map(Mesh::lod_count).max().unwrap_or(1)
+    }
 
-            // process material
-            match &materials {
-                Ok(materials) => {
-                    let mut textures = Vec::new();
-                    if let Some(material_id) = mesh.material_id {
-                        let material = &materials[material_id];
-
-                        // 1. diffuse map
-                        if !material.diffuse_texture.is_empty() {
-                            let texture = self.load_material_texture(&material.diffuse_texture, "texture_diffuse");
-                            textures.push(texture);
-                        }
-                        // 2. specular map
-                        if !material.specular_texture.is_empty() {
-                            let texture = self.load_material_texture(&material.specular_texture, "texture_specular");
-                            textures.push(texture);
-                        }
-                        // 3. normal map
-                        if !material.normal_texture.is_empty() {
-                            let texture = self.load_material_texture(&material.normal_texture, "texture_normal");
-                            textures.push(texture);
-                        }
-                        // NOTE: no height maps
-                    }
-
-                    self.meshes.push(Mesh::new(vertices, indices, textures));
-                }
-                Err(err) => {
-                    println!("{}", err)
-                }
+    /// error of every level in model units: the largest error of the meshes at that level
+    pub fn lod_errors(&self) -> &[f32] {
+        &self.lod_errors
+    }
+
+    /// recomputes the LOD error table from the meshes, needed after changing their LODs directly
+    pub fn update_lod_errors(&mut self) {
+        let mut errors = vec![0.0f32; self.lod_count()];
+        for mesh in &self.meshes {
+            let mesh_errors = mesh.lod_errors();
+            for (level, error) in errors.iter_mut().enumerate() {
+                // meshes with shorter chains stay at their coarsest level
+                *error = error.max(mesh_errors[level.min(mesh_errors.len() - 1)]);
             }
         }
+        self.lod_errors = errors;
+    }
+
+    /// the level to draw the model at when placed by `world`, see `LodSelector::select_for`
+    pub fn select_lod(
+        &self,
+        selector: &LodSelector,
+        world: &Matrix4<f32>,
+        camera: &LodCamera,
+        previous: Option<usize>,
+    ) -> usize {
+        selector.select_for(&self.lod_errors, &self.bounding_sphere, world, camera, previous)
+    }
+
+    /// recomputes the model bounds from the bounds of its meshes
+    pub fn update_bounds(&mut self) {
+        self.aabb = self
+            .meshes
+            .iter()
+            .fold(Aabb::empty(), |aabb, mesh| aabb.union(&mesh.aabb));
+        self.bounding_sphere = self.meshes.iter().fold(BoundingSphere::empty(), |sphere, mesh| {
+            sphere.union(&mesh.bounding_sphere)
+        });
+    }
+
+    /// converts an OBJ material; the Phong parameters map to the base color, specular and
+    /// emissive factors, a dissolve below 1 makes the material alpha blended.
+    fn load_material(
+        &mut self,
+        material: &tobj::Material,
+        options: &ModelOptions,
+        mut failed: Option<&mut FailedTextures>,
+    ) -> Material {
+        let [r, g, b] = material.diffuse;
+        let [sr, sg, sb] = material.specular;
+        let mut result = Material {
+            name: material.name.clone(),
+            base_color_factor: vec4(r, g, b, material.dissolve),
+            specular_factor: vec3(sr, sg, sb),
+            shininess: material.shininess,
+            alpha_mode: if material.dissolve < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            ..Material::default()
+        };
+
+        for (slot, path) in material_textures(material) {
+            let texture = self.load_material_texture(&path, options.textures, failed.as_deref_mut());
+            result.set_texture(slot, texture);
+        }
+        // NOTE: no height maps
+        result
     }
 
-    fn load_material_texture(&mut self, path: &str, type_name: &str) -> Texture {
+    /// loads a material texture through the `TextureCache`; a texture that fails to load is reported and
+    /// recorded in `missing_textures` instead of aborting the whole model.
+    fn load_material_texture(
+        &mut self,
+        path: &str,
+        options: TextureOptions,
+        failed: Option<&mut FailedTextures>,
+    ) -> Option<Texture> {
         {
             let texture = self.textures_loaded.iter().find(|t| t.path == path);
             if let Some(texture) = texture {
-                return texture.clone();
+                return Some(texture.clone());
             }
         }
 
-        let texture = Texture {
-            id: texture_from_file(path, &self.directory),
-            type_: type_name.into(),
-            path: path.into(),
-        };
-        self.textures_loaded.push(texture.clone());
-        texture
-    }
-}
-
-fn texture_from_file(path: &str, directory: &str) -> u32 {
-    let filename = format!("{}/{}", directory, path);
-
-    let texture_id = gl::gen_texture();
-
-    let img = image::open(&Path::new(&filename)).expect("Texture failed to load");
-    let img = img.flipv();
-    let format = match img {
-        ImageLuma8(_) => GL_RED,
-        ImageLumaA8(_) => GL_RG,
-        ImageRgb8(_) => GL_RGB,
-        ImageRgba8(_) => GL_RGBA,
-        _ => panic!("unhandled image format"),
-    };
+        // decoding it again would only fail again, stalling the GL thread
+        let error = failed.and_then(|failed| {
+            let err = failed.errors.remove(&canonical(&self.directory.join(path)))?;
+            Some((err, failed.placeholder.clone()))
+        });
+        if let Some((err, placeholder)) = error {
+            println!("{}", err);
+            self.missing_textures.push(err);
+            let texture = Texture {
+                id: placeholder.texture.id(),
+                path: path.into(),
+                cached: Some(placeholder),
+            };
+            // other materials using it get the placeholder too
+            self.textures_loaded.push(texture.clone());
+            return Some(texture);
+        }
 
-    gl::bind_texture(GL_TEXTURE_2D, texture_id);
-    gl::tex_image_2d(
-        GL_TEXTURE_2D,
-        0,
-        format as i32,
-        img.width() as i32,
-        img.height() as i32,
-        0,
-        format,
-        GL_UNSIGNED_BYTE,
-        img.as_bytes(),
-    );
-    gl::generate_mipmap(GL_TEXTURE_2D);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR_MIPMAP_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-
-    texture_id
+        match TextureCache::load(&self.directory.join(path), options) {
+            Ok(cached) => {
+                let texture = Texture {
+                    id: cached.texture.id(),
+                    path: path.into(),
+                    cached: Some(cached),
+                };
+                self.textures_loaded.push(texture.clone());
+                Some(texture)
+            }
+            Err(err) => {
+                println!("{}", err);
+                self.missing_textures.push(err);
+                None
+            }
+        }
+    }
 }
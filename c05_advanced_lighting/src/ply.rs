@@ -0,0 +1,567 @@
+use cgmath::{vec2, vec3, Vector4};
+use std::{collections::HashMap, convert::TryInto, error::Error, fmt, fs, io, path::Path, str};
+
+use super::mesh::Vertex;
+use super::model::RawMesh;
+
+/// Errors of `load_ply`.
+#[derive(Debug)]
+pub enum PlyError {
+    Io(io::Error),
+    /// the header is malformed or uses a format that isn't supported
+    Header(String),
+    /// the element data doesn't match the header
+    Data(String),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlyError::Io(err) => write!(f, "{}", err),
+            PlyError::Header(reason) => write!(f, "invalid header: {}", reason),
+            PlyError::Data(reason) => write!(f, "invalid data: {}", reason),
+        }
+    }
+}
+
+impl Error for PlyError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// value of full intensity for a color stored in this type
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum PropertyType {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+impl Property {
+    /// the fewest bytes a value can take: lists may be empty, and ASCII values need at least a
+    /// digit and a separator
+    fn min_size(&self, format: Format) -> usize {
+        match (format, &self.ty) {
+            (Format::Ascii, _) => 2,
+            (_, PropertyType::Scalar(scalar)) | (_, PropertyType::List { count: scalar, .. }) => scalar.size(),
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Reads a PLY file, ASCII or binary: vertex positions, normals, texture coordinates and
+/// colors, and polygon faces, which are triangulated as fans. Texture coordinates can be
+/// per vertex (`u`/`v`, `s`/`t`) or per face corner (a `texcoord` list, as MeshLab
+/// writes them); vertices are split where corners sharing them have different ones.
+/// Other elements and properties are skipped.
+pub fn load_ply(path: &Path) -> Result<RawMesh, PlyError> {
+    let bytes = fs::read(path).map_err(PlyError::Io)?;
+    let (format, elements, body_start) = parse_header(&bytes)?;
+    let body = &bytes[body_start..];
+    let mut values = match format {
+        Format::Ascii => Values::Ascii(
+            str::from_utf8(body)
+                .map_err(|_| PlyError::Data("ASCII data is not valid UTF-8".into()))?
+                .split_ascii_whitespace(),
+        ),
+        _ => Values::Binary {
+            bytes: body,
+            pos: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = RawMesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        has_normals: false,
+        has_tex_coords: false,
+        colors: None,
+        material: None,
+    };
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut values, &mut mesh)?,
+            "face" => read_faces(element, &mut values, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        values.skip(&property.ty)?;
+                    }
+                }
+            }
+        }
+    }
+
+    let vertex_count = mesh.vertices.len();
+    if let Some(&idx) = mesh.indices.iter().find(|&&idx| idx as usize >= vertex_count) {
+        return Err(PlyError::Data(format!("face uses vertex {} of {}", idx, vertex_count)));
+    }
+    Ok(mesh)
+}
+
+// returns the format, the elements and where the data starts
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), PlyError> {
+    let header_error = |reason: &str| PlyError::Header(reason.into());
+    if !bytes.starts_with(b"ply") {
+        return Err(header_error("not a PLY file"));
+    }
+    let end = bytes
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| header_error("no end_header"))?;
+    // the data starts after the line break ending the header
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |newline| end + newline + 1);
+    let header = String::from_utf8_lossy(&bytes[..end]);
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in header.lines().skip(1) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(PlyError::Header(format!("unknown format {:?}", name))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| PlyError::Header(format!("invalid element count {:?}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let ty = PropertyType::List {
+                    count: parse_scalar(count)?,
+                    item: parse_scalar(item)?,
+                };
+                push_property(&mut elements, name, ty)?;
+            }
+            ["property", ty, name] => {
+                let ty = PropertyType::Scalar(parse_scalar(ty)?);
+                push_property(&mut elements, name, ty)?;
+            }
+            _ => return Err(PlyError::Header(format!("unexpected line {:?}", line))),
+        }
+    }
+    let format = format.ok_or_else(|| header_error("no format line"))?;
+
+    // the counts size allocations, so the data has to be able to hold them
+    let mut min_size = 0usize;
+    for element in &elements {
+        let element_size: usize = element
+            .properties
+            .iter()
+            .map(|property| property.min_size(format))
+            .sum();
+        if element.count > 0 && element_size == 0 {
+            return Err(PlyError::Header(format!(
+                "element {:?} has no properties",
+                element.name
+            )));
+        }
+        min_size = min_size.saturating_add(element.count.saturating_mul(element_size));
+    }
+    // the last ASCII value needs no separator
+    let data_size = bytes.len() - body_start + (format == Format::Ascii) as usize;
+    if min_size > data_size {
+        return Err(PlyError::Header(format!(
+            "the element counts need at least {} bytes of data, there are {}",
+            min_size,
+            bytes.len() - body_start
+        )));
+    }
+    Ok((format, elements, body_start))
+}
+
+fn parse_scalar(name: &str) -> Result<Scalar, PlyError> {
+    Scalar::parse(name).ok_or_else(|| PlyError::Header(format!("unknown property type {:?}", name)))
+}
+
+fn push_property(elements: &mut [Element], name: &str, ty: PropertyType) -> Result<(), PlyError> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| PlyError::Header("property before any element".into()))?;
+    element.properties.push(Property { name: name.into(), ty });
+    Ok(())
+}
+
+// what a vertex property is for
+#[derive(Clone, Copy)]
+enum Role {
+    Position(usize),
+    Normal(usize),
+    TexCoord(usize),
+    Color(usize),
+    Ignored,
+}
+
+fn vertex_role(name: &str) -> Role {
+    match name {
+        "x" => Role::Position(0),
+        "y" => Role::Position(1),
+        "z" => Role::Position(2),
+        "nx" => Role::Normal(0),
+        "ny" => Role::Normal(1),
+        "nz" => Role::Normal(2),
+        "u" | "s" | "texture_u" | "texture_s" => Role::TexCoord(0),
+        "v" | "t" | "texture_v" | "texture_t" => Role::TexCoord(1),
+        "red" | "r" | "diffuse_red" => Role::Color(0),
+        "green" | "g" | "diffuse_green" => Role::Color(1),
+        "blue" | "b" | "diffuse_blue" => Role::Color(2),
+        "alpha" | "a" | "diffuse_alpha" => Role::Color(3),
+        _ => Role::Ignored,
+    }
+}
+
+fn read_vertices(element: &Element, values: &mut Values, mesh: &mut RawMesh) -> Result<(), PlyError> {
+    let roles: Vec<Role> = element
+        .properties
+        .iter()
+        .map(|property| vertex_role(&property.name))
+        .collect();
+    let normal_count = roles.iter().filter(|role| matches!(role, Role::Normal(_))).count();
+    mesh.has_normals = normal_count == 3;
+    let tex_coord_count = roles.iter().filter(|role| matches!(role, Role::TexCoord(_))).count();
+    mesh.has_tex_coords = tex_coord_count == 2;
+    let mut colors = Vec::new();
+    let has_colors = roles.iter().any(|role| matches!(role, Role::Color(_)));
+
+    // bounded by the size of the data, see `parse_header`
+    mesh.vertices.reserve(element.count);
+    for _ in 0..element.count {
+        let mut position = [0.0f32; 3];
+        let mut normal = [0.0f32; 3];
+        let mut tex_coords = [0.0f32; 2];
+        let mut color = [1.0f32; 4];
+        for (property, role) in element.properties.iter().zip(&roles) {
+            let scalar = match property.ty {
+                PropertyType::Scalar(scalar) => scalar,
+                PropertyType::List { .. } => {
+                    values.skip(&property.ty)?;
+                    continue;
+                }
+            };
+            let value = values.next(scalar)?;
+            match *role {
+                Role::Position(axis) => position[axis] = value as f32,
+                Role::Normal(axis) => normal[axis] = value as f32,
+                Role::TexCoord(axis) => tex_coords[axis] = value as f32,
+                Role::Color(channel) => color[channel] = (value / scalar.color_scale()) as f32,
+                Role::Ignored => {}
+            }
+        }
+        mesh.vertices.push(Vertex {
+            position: vec3(position[0], position[1], position[2]),
+            normal: vec3(normal[0], normal[1], normal[2]),
+            tex_coords: vec2(tex_coords[0], tex_coords[1]),
+            ..Vertex::default()
+        });
+        if has_colors {
+            colors.push(Vector4::from(color));
+        }
+    }
+    if has_colors {
+        mesh.colors = Some(colors);
+    }
+    Ok(())
+}
+
+fn read_faces(element: &Element, values: &mut Values, mesh: &mut RawMesh) -> Result<(), PlyError> {
+    // vertices split off for corner texture coordinates, by original vertex and coordinates
+    let mut corner_vertices: HashMap<(u32, [u32; 2]), u32> = HashMap::new();
+    let mut polygon: Vec<u32> = Vec::new();
+    let mut corner_tex_coords: Vec<f32> = Vec::new();
+
+    for _ in 0..element.count {
+        polygon.clear();
+        corner_tex_coords.clear();
+        for property in &element.properties {
+            match (property.name.as_str(), &property.ty) {
+                ("vertex_indices" | "vertex_index", PropertyType::List { count, item }) => {
+                    for _ in 0..values.next(*count)? as usize {
+                        let idx = values.next(*item)?;
+                        if idx < 0.0 {
+                            return Err(PlyError::Data(format!("negative vertex index {}", idx)));
+                        }
+                        polygon.push(idx as u32);
+                    }
+                }
+                ("texcoord", PropertyType::List { count, item }) => {
+                    for _ in 0..values.next(*count)? as usize {
+                        corner_tex_coords.push(values.next(*item)? as f32);
+                    }
+                }
+                (_, ty) => values.skip(ty)?,
+            }
+        }
+
+        if corner_tex_coords.len() == polygon.len() * 2 {
+            mesh.has_tex_coords = true;
+            for (corner, idx) in polygon.iter_mut().enumerate() {
+                let tex_coords = vec2(corner_tex_coords[corner * 2], corner_tex_coords[corner * 2 + 1]);
+                let key = (*idx, [tex_coords.x.to_bits(), tex_coords.y.to_bits()]);
+                let vertex = mesh
+                    .vertices
+                    .get(*idx as usize)
+                    .copied()
+                    .ok_or_else(|| PlyError::Data(format!("face uses missing vertex {}", idx)))?;
+                *idx = *corner_vertices.entry(key).or_insert_with(|| {
+                    mesh.vertices.push(Vertex { tex_coords, ..vertex });
+                    if let Some(colors) = &mut mesh.colors {
+                        let color = colors[key.0 as usize];
+                        colors.push(color);
+                    }
+                    (mesh.vertices.len() - 1) as u32
+                });
+            }
+        }
+
+        // fan triangulation, fine for the convex polygons scanners and CAD tools write
+        for corner in 2..polygon.len() {
+            mesh.indices
+                .extend_from_slice(&[polygon[0], polygon[corner - 1], polygon[corner]]);
+        }
+    }
+    Ok(())
+}
+
+// values of the element data in file order
+enum Values<'a> {
+    Ascii(str::SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Values<'a> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64, PlyError> {
+        let end_of_data = || PlyError::Data("unexpected end of data".into());
+        match self {
+            Values::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(end_of_data)?;
+                token
+                    .parse()
+                    .map_err(|_| PlyError::Data(format!("invalid number {:?}", token)))
+            }
+            Values::Binary { bytes, pos, big_endian } => {
+                let size = scalar.size();
+                let raw = bytes.get(*pos..*pos + size).ok_or_else(end_of_data)?;
+                *pos += size;
+                let mut le = [0u8; 8];
+                le[..size].copy_from_slice(raw);
+                if *big_endian {
+                    le[..size].reverse();
+                }
+                Ok(match scalar {
+                    Scalar::I8 => le[0] as i8 as f64,
+                    Scalar::U8 => le[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([le[0], le[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([le[0], le[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(le[..4].try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(le[..4].try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(le[..4].try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(le),
+                })
+            }
+        }
+    }
+
+    fn skip(&mut self, ty: &PropertyType) -> Result<(), PlyError> {
+        match *ty {
+            PropertyType::Scalar(scalar) => {
+                self.next(scalar)?;
+            }
+            PropertyType::List { count, item } => {
+                for _ in 0..self.next(count)? as usize {
+                    self.next(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{load_ply, ModelData, ModelOptions, Normals, PlyError};
+    use cgmath::{prelude::*, Vector3};
+    use std::fs;
+
+    fn parse(name: &str, text: &str, options: &ModelOptions) -> ModelData {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        let data = ModelData::parse(&path, options);
+        fs::remove_file(&path).unwrap();
+        data.unwrap()
+    }
+
+    const HEADER: &str =
+        "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n";
+
+    #[test]
+    fn colors_follow_their_vertices() {
+        // two triangles folded along x = 0, so flat normals split the shared vertices
+        let text = format!(
+            "{}property uchar red\nproperty uchar green\nproperty uchar blue\nelement face 2\nproperty list uchar int vertex_indices\nend_header\n\
+             0 0 0 255 0 0\n0 1 0 0 255 0\n1 0 0 0 0 255\n-1 0 1 255 255 255\n3 0 2 1\n3 0 1 3\n",
+            HEADER
+        );
+        let options = ModelOptions {
+            normals: Normals::Flat,
+            ..ModelOptions::default()
+        };
+        let data = parse("colored.ply", &text, &options);
+        let mesh = &data.meshes[0];
+        let colors = mesh.colors.as_ref().expect("colors");
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(colors.len(), mesh.vertices.len());
+        for (vertex, color) in mesh.vertices.iter().zip(colors) {
+            let expected = match (vertex.position.x, vertex.position.y) {
+                (x, _) if x > 0.5 => [0.0, 0.0, 1.0],
+                (x, _) if x < -0.5 => [1.0, 1.0, 1.0],
+                (_, y) if y > 0.5 => [0.0, 1.0, 0.0],
+                _ => [1.0, 0.0, 0.0],
+            };
+            assert_eq!(
+                [color.x, color.y, color.z, color.w],
+                [expected[0], expected[1], expected[2], 1.0]
+            );
+        }
+    }
+
+    #[test]
+    fn no_colors_without_color_properties() {
+        let text = format!(
+            "{}element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n0 1 0\n1 0 0\n2 2 2\n3 0 2 1\n",
+            HEADER
+        );
+        let data = parse("plain.ply", &text, &ModelOptions::default());
+        assert!(data.meshes[0].colors.is_none());
+    }
+
+    #[test]
+    fn tangents_follow_the_texture_coordinates() {
+        // a quad facing +Z with u along +X
+        let text = format!(
+            "{}property float u\nproperty float v\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n\
+             0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n4 0 1 2 3\n",
+            HEADER
+        );
+        let data = parse("textured.ply", &text, &ModelOptions::default());
+        for vertex in &data.meshes[0].vertices {
+            assert!((vertex.tangent - Vector3::unit_x()).magnitude() < 1e-6);
+            assert!((vertex.bitangent - Vector3::unit_y()).magnitude() < 1e-6);
+        }
+
+        // no texture coordinates, no tangent space
+        let text = format!(
+            "{}element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n",
+            HEADER
+        );
+        let data = parse("untextured.ply", &text, &ModelOptions::default());
+        assert!(data.meshes[0]
+            .vertices
+            .iter()
+            .all(|vertex| vertex.tangent == Vector3::zero()));
+    }
+
+    #[test]
+    fn counts_beyond_the_data() {
+        let path = std::env::temp_dir().join(format!("{}_counts.ply", std::process::id()));
+        let load = |text: &[u8]| {
+            fs::write(&path, text).unwrap();
+            let result = load_ply(&path);
+            fs::remove_file(&path).unwrap();
+            result
+        };
+        let binary = |count: u64| {
+            let mut bytes = format!(
+                "ply\nformat binary_little_endian 1.0\nelement vertex {}\nproperty float x\nproperty float y\nproperty float z\nend_header\n",
+                count
+            )
+            .into_bytes();
+            bytes.extend_from_slice(&[0; 12]);
+            bytes
+        };
+        assert!(load(&binary(1)).is_ok());
+        assert!(matches!(load(&binary(2)), Err(PlyError::Header(_))));
+        assert!(matches!(load(&binary(1 << 60)), Err(PlyError::Header(_))));
+
+        let ascii = format!("{}end_header\n0 0 0\n", HEADER);
+        assert!(matches!(load(ascii.as_bytes()), Err(PlyError::Header(_))));
+        let empty = b"ply\nformat ascii 1.0\nelement nothing 1000000000000\nend_header\n";
+        assert!(matches!(load(empty), Err(PlyError::Header(_))));
+    }
+}
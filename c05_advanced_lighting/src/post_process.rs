@@ -0,0 +1,385 @@
+use cgmath::Vector3;
+use dx::gles::{core30::gl, enums::*};
+
+use super::capabilities::{Capabilities, EnableState};
+use super::gl_object::GlVertexArray;
+use super::render_target::{Attachment, RenderTarget, RenderTargetError};
+use super::shader::Shader;
+use super::texture::Filter;
+
+/// A uniform of a `PostProcessPass`, set every time the pass is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PassParam {
+    Int(i32),
+    Float(f32),
+    Vec2(f32, f32),
+    Vec3(Vector3<f32>),
+    FloatArray(Vec<f32>),
+}
+
+impl PassParam {
+    fn apply(&self, shader: &Shader, name: &str) {
+        match self {
+            PassParam::Int(value) => shader.set_int(name, *value),
+            PassParam::Float(value) => shader.set_float(name, *value),
+            PassParam::Vec2(x, y) => shader.set_vec2(name, *x, *y),
+            PassParam::Vec3(value) => shader.set_vector3(name, value),
+            PassParam::FloatArray(values) => shader.set_float_array(name, values),
+        }
+    }
+}
+
+/// One fullscreen pass of a `PostProcessStack`.
+///
+/// The fragment shader gets the previous pass's output as `uniform sampler2D screenTexture`,
+/// its coordinates as `in vec2 TexCoords` and the size of one texel as `uniform vec2 texelSize`.
+pub struct PostProcessPass {
+    pub name: String,
+    pub enabled: bool,
+    shader: Shader,
+    params: Vec<(String, PassParam)>,
+}
+
+impl PostProcessPass {
+    /// compiles `fragment_code` with the fullscreen vertex shader
+    pub fn new(name: &str, fragment_code: &str) -> Self {
+        PostProcessPass {
+            name: name.into(),
+            enabled: true,
+            shader: Shader::from_source(FULLSCREEN_VERTEX_SHADER, fragment_code),
+            params: Vec::new(),
+        }
+    }
+
+    /// sets uniform `name`, replacing an earlier value
+    pub fn set(&mut self, name: &str, value: PassParam) -> &mut Self {
+        match self.params.iter_mut().find(|(param, _)| param == name) {
+            Some((_, param)) => *param = value,
+            None => self.params.push((name.into(), value)),
+        }
+        self
+    }
+
+    pub fn with(mut self, name: &str, value: PassParam) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&PassParam> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    /// inverts the colors
+    pub fn inversion() -> Self {
+        PostProcessPass::new("inversion", INVERSION_FRAGMENT_SHADER)
+    }
+
+    /// luminance with the weights of the human eye
+    pub fn grayscale() -> Self {
+        PostProcessPass::new("grayscale", GRAYSCALE_FRAGMENT_SHADER)
+    }
+
+    /// a 3x3 convolution; `kernel` is in rows from top left, and the `offset` parameter
+    /// is the distance of the samples in texture coordinates
+    pub fn kernel(name: &str, kernel: [f32; 9]) -> Self {
+        PostProcessPass::new(name, KERNEL_FRAGMENT_SHADER)
+            .with("kernel", PassParam::FloatArray(kernel.to_vec()))
+            .with("offset", PassParam::Float(1.0 / 300.0))
+    }
+
+    pub fn sharpen() -> Self {
+        PostProcessPass::kernel("sharpen", [-1.0, -1.0, -1.0, -1.0, 9.0, -1.0, -1.0, -1.0, -1.0])
+    }
+
+    pub fn blur() -> Self {
+        let kernel = [1.0, 2.0, 1.0, 2.0, 4.0, 2.0, 1.0, 2.0, 1.0].map(|weight| weight / 16.0);
+        PostProcessPass::kernel("blur", kernel)
+    }
+
+    pub fn edge_detect() -> Self {
+        PostProcessPass::kernel("edge detect", [1.0, 1.0, 1.0, 1.0, -8.0, 1.0, 1.0, 1.0, 1.0])
+    }
+
+    /// exposure tone mapping of HDR colors, `1 - exp(-color * exposure)`
+    pub fn tone_mapping(exposure: f32) -> Self {
+        PostProcessPass::new("tone mapping", TONE_MAPPING_FRAGMENT_SHADER).with("exposure", PassParam::Float(exposure))
+    }
+
+    /// gamma correction, `pow(color, 1 / gamma)`; not needed when rendering into an sRGB
+    /// framebuffer
+    pub fn gamma(gamma: f32) -> Self {
+        PostProcessPass::new("gamma", GAMMA_FRAGMENT_SHADER).with("gamma", PassParam::Float(gamma))
+    }
+
+    fn draw(&self, source: &RenderTarget) {
+        self.shader.use_program();
+        source.bind_color_texture(0, 0);
+        self.shader.set_int("screenTexture", 0);
+        self.shader
+            .set_vec2("texelSize", 1.0 / source.width as f32, 1.0 / source.height as f32);
+        for (name, value) in &self.params {
+            value.apply(&self.shader, name);
+        }
+        gl::draw_arrays(GL_TRIANGLES, 0, 3);
+    }
+}
+
+/// Renders the scene offscreen and runs it through a list of fullscreen passes, ping-ponging
+/// between two render targets; the last enabled pass draws into the window.
+///
+/// ```ignore
+/// let mut post = PostProcessStack::new(width, height)?;
+/// post.push(PostProcessPass::tone_mapping(1.0));
+/// post.push(PostProcessPass::sharpen().enabled(false));
+/// // every frame
+/// post.begin();
+/// draw_scene();
+/// post.render(width, height);
+/// ```
+pub struct PostProcessStack {
+    passes: Vec<PostProcessPass>,
+    scene: RenderTarget,
+    ping_pong: [RenderTarget; 2],
+    // attribute-less draws still need a vertex array
+    vao: GlVertexArray,
+}
+
+impl PostProcessStack {
+    /// the scene is rendered into `GL_RGBA16F` where the context can render into half
+    /// floats, so tone mapping has HDR colors to work with, and `GL_RGBA8` otherwise
+    pub fn new(width: u32, height: u32) -> Result<Self, RenderTargetError> {
+        let color_format = if Capabilities::current().color_buffer_half_float() {
+            GL_RGBA16F
+        } else {
+            GL_RGBA8
+        };
+        PostProcessStack::with_format(color_format, width, height)
+    }
+
+    pub fn with_format(color_format: u32, width: u32, height: u32) -> Result<Self, RenderTargetError> {
+        let scene = RenderTarget::builder()
+            .color(Attachment::texture(color_format))
+            .depth(Attachment::renderbuffer(GL_DEPTH24_STENCIL8))
+            .build(width, height)?;
+        let pass_target = RenderTarget::builder().color(Attachment::texture(color_format));
+        let ping_pong = [pass_target.build(width, height)?, pass_target.build(width, height)?];
+        Ok(PostProcessStack {
+            passes: Vec::new(),
+            scene,
+            ping_pong,
+            vao: GlVertexArray::new(),
+        })
+    }
+
+    /// the offscreen target the scene is drawn into
+    pub fn scene(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    /// resizes the offscreen targets, e.g. on `WindowEvent::Resized`
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), RenderTargetError> {
+        self.scene.resize(width, height)?;
+        for target in &mut self.ping_pong {
+            target.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// binds the scene target; draw the scene after this
+    pub fn begin(&self) {
+        self.scene.bind();
+    }
+
+    pub fn passes(&self) -> &[PostProcessPass] {
+        &self.passes
+    }
+
+    pub fn push(&mut self, pass: PostProcessPass) {
+        self.passes.push(pass);
+    }
+
+    pub fn insert(&mut self, index: usize, pass: PostProcessPass) {
+        self.passes.insert(index, pass);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PostProcessPass> {
+        let index = self.position(name)?;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PostProcessPass> {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostProcessPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /// turns pass `name` on or off; false if there is no such pass
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.pass_mut(name) {
+            Some(pass) => {
+                pass.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// moves pass `name` to `index` of the passes; false if there is no such pass
+    pub fn move_pass(&mut self, name: &str, index: usize) -> bool {
+        match self.position(name) {
+            Some(from) => {
+                let pass = self.passes.remove(from);
+                self.passes.insert(index.min(self.passes.len()), pass);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name == name)
+    }
+
+    /// runs the enabled passes over the scene and draws the result into the window, with
+    /// the viewport covering `width` x `height`. Without enabled passes the scene is copied.
+    pub fn render(&self, width: u32, height: u32) {
+        let enabled: Vec<&PostProcessPass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        if enabled.is_empty() {
+            self.scene.blit_to_default(width, height, Filter::Linear);
+            RenderTarget::bind_default(width, height);
+            return;
+        }
+
+        let disabled: Vec<u32> = [GL_DEPTH_TEST, GL_BLEND, GL_CULL_FACE]
+            .iter()
+            .copied()
+            .filter(|&capability| EnableState::set(capability, false))
+            .collect();
+        gl::bind_vertex_array(self.vao.id());
+
+        let mut source = &self.scene;
+        for (index, pass) in enabled.iter().enumerate() {
+            if index + 1 == enabled.len() {
+                RenderTarget::bind_default(width, height);
+                pass.draw(source);
+            } else {
+                let target = &self.ping_pong[index % 2];
+                target.bind();
+                pass.draw(source);
+                source = target;
+            }
+        }
+
+        gl::bind_vertex_array(0);
+        for &capability in &disabled {
+            EnableState::set(capability, true);
+        }
+    }
+}
+
+// a triangle covering the viewport
+const FULLSCREEN_VERTEX_SHADER: &str = r#"#version 330 core
+out vec2 TexCoords;
+
+void main()
+{
+    vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    TexCoords = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const INVERSION_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+
+void main()
+{
+    vec4 color = texture(screenTexture, TexCoords);
+    FragColor = vec4(1.0 - color.rgb, color.a);
+}
+"#;
+
+const GRAYSCALE_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+
+void main()
+{
+    vec4 color = texture(screenTexture, TexCoords);
+    float average = 0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b;
+    FragColor = vec4(vec3(average), color.a);
+}
+"#;
+
+const KERNEL_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform float kernel[9];
+uniform float offset;
+
+void main()
+{
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < 9; i++)
+    {
+        // rows from top left to bottom right
+        vec2 sampleOffset = vec2(float(i % 3 - 1), float(1 - i / 3)) * offset;
+        color += texture(screenTexture, TexCoords + sampleOffset).rgb * kernel[i];
+    }
+    FragColor = vec4(color, texture(screenTexture, TexCoords).a);
+}
+"#;
+
+const TONE_MAPPING_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform float exposure;
+
+void main()
+{
+    vec4 color = texture(screenTexture, TexCoords);
+    FragColor = vec4(vec3(1.0) - exp(-color.rgb * exposure), color.a);
+}
+"#;
+
+const GAMMA_FRAGMENT_SHADER: &str = r#"#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D screenTexture;
+uniform float gamma;
+
+void main()
+{
+    vec4 color = texture(screenTexture, TexCoords);
+    FragColor = vec4(pow(color.rgb, vec3(1.0 / gamma)), color.a);
+}
+"#;
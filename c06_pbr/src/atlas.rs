@@ -0,0 +1,749 @@
+use cgmath::{vec2, Vector2};
+use dx::gles::{core30::gl, enums::*};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::texture::{Texture2D, Texture2DBuilder, TextureError, Wrap};
+
+/// Where an image ended up in a `TextureAtlas`: its texels, without padding and gutter,
+/// and the same rectangle in texture coordinates. Row 0 of the atlas is at v = 0, so
+/// `uv_min` is the top left corner of the image as it was stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: Vector2<f32>,
+    pub uv_max: Vector2<f32>,
+}
+
+impl AtlasRegion {
+    fn new(x: u32, y: u32, width: u32, height: u32, atlas_width: u32, atlas_height: u32) -> Self {
+        let (w, h) = (atlas_width as f32, atlas_height as f32);
+        AtlasRegion {
+            x,
+            y,
+            width,
+            height,
+            uv_min: vec2(x as f32 / w, y as f32 / h),
+            uv_max: vec2((x + width) as f32 / w, (y + height) as f32 / h),
+        }
+    }
+
+    pub fn uv_size(&self) -> Vector2<f32> {
+        self.uv_max - self.uv_min
+    }
+
+    /// splits a sprite sheet into `columns` x `rows` equally sized frames, row by row
+    pub fn grid(&self, columns: u32, rows: u32) -> Vec<AtlasRegion> {
+        assert!(columns > 0 && rows > 0, "sprite sheet grid must have at least one cell");
+        let uv_size = self.uv_size();
+        let mut frames = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x0 = self.width * column / columns;
+                let x1 = self.width * (column + 1) / columns;
+                let y0 = self.height * row / rows;
+                let y1 = self.height * (row + 1) / rows;
+                frames.push(AtlasRegion {
+                    x: self.x + x0,
+                    y: self.y + y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                    uv_min: self.uv_min
+                        + vec2(
+                            uv_size.x * column as f32 / columns as f32,
+                            uv_size.y * row as f32 / rows as f32,
+                        ),
+                    uv_max: self.uv_min
+                        + vec2(
+                            uv_size.x * (column + 1) as f32 / columns as f32,
+                            uv_size.y * (row + 1) as f32 / rows as f32,
+                        ),
+                });
+            }
+        }
+        frames
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Skyline bin packing: the top edge of everything placed so far is kept as a list of
+/// horizontal segments, and each rectangle goes where its top ends up lowest, leftmost
+/// on ties. The result only depends on the order of the `pack` calls.
+#[derive(Clone, Debug)]
+pub struct SkylinePacker {
+    width: u32,
+    height: u32,
+    skyline: Vec<SkylineNode>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: u32, height: u32) -> Self {
+        SkylinePacker {
+            width,
+            height,
+            skyline: vec![SkylineNode { x: 0, y: 0, width }],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// reserves a `width` x `height` rectangle and returns its top left corner, or `None`
+    /// if it doesn't fit anymore
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width == 0 || height == 0 {
+            return Some((0, 0));
+        }
+        let mut best: Option<(usize, u32, u32)> = None;
+        for index in 0..self.skyline.len() {
+            if let Some(y) = self.fit(index, width, height) {
+                let x = self.skyline[index].x;
+                let better = match best {
+                    Some((_, best_x, best_y)) => (y + height, x) < (best_y + height, best_x),
+                    None => true,
+                };
+                if better {
+                    best = Some((index, x, y));
+                }
+            }
+        }
+        let (index, x, y) = best?;
+        self.place(index, x, y, width, height);
+        Some((x, y))
+    }
+
+    /// the y a rectangle starting at skyline segment `index` rests on
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered = 0;
+        for node in &self.skyline[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(node.y);
+            if y + height > self.height {
+                return None;
+            }
+            covered += node.width;
+        }
+        Some(y)
+    }
+
+    fn place(&mut self, index: usize, x: u32, y: u32, width: u32, height: u32) {
+        self.skyline.insert(
+            index,
+            SkylineNode {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+
+        // cut away the segments the new one covers
+        let end = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() && self.skyline[next].x < end {
+            let node = &mut self.skyline[next];
+            let overlap = end - node.x;
+            if node.width <= overlap {
+                self.skyline.remove(next);
+            } else {
+                node.x += overlap;
+                node.width -= overlap;
+                break;
+            }
+        }
+
+        // merge neighbours of equal height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// The packed atlas on the CPU, see `AtlasBuilder::pack`.
+#[derive(Clone, Debug)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    /// tightly packed RGBA8 rows
+    pub pixels: Vec<u8>,
+    /// mipmap levels that don't mix texels of neighbouring images, see `AtlasBuilder::gutter`
+    pub levels: u32,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+/// Packs many small images into one texture.
+///
+/// ```ignore
+/// let mut builder = AtlasBuilder::new().padding(1).gutter(2);
+/// builder.add_directory("resources/textures/particles")?;
+/// let atlas = builder.build()?;
+/// let spark = atlas.region("spark").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct AtlasBuilder {
+    /// transparent texels between neighbouring images
+    pub padding: u32,
+    /// edge texels repeated around each image, so bilinear filtering doesn't pull in its
+    /// neighbours. Mipmap level k keeps gutter / 2^k texels of it, so mipmapped atlases
+    /// stop at level log2(gutter) and align every image to that level's blocks.
+    pub gutter: u32,
+    /// largest atlas width and height
+    pub max_size: u32,
+    /// sampling, mipmaps and sRGB of the atlas texture; `flip` doesn't apply
+    pub texture: Texture2DBuilder,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        AtlasBuilder {
+            padding: 0,
+            gutter: 1,
+            max_size: 4096,
+            texture: Texture2D::builder().wrap(Wrap::ClampToEdge),
+            images: Vec::new(),
+        }
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn gutter(mut self, gutter: u32) -> Self {
+        self.gutter = gutter;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn texture(mut self, texture: Texture2DBuilder) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    /// adds an image under `name`, replacing an earlier image of the same name
+    pub fn add(&mut self, name: impl Into<String>, image: &DynamicImage) -> &mut Self {
+        let name = name.into();
+        self.images.retain(|(existing, _)| *existing != name);
+        self.images.push((name, image.to_rgba8()));
+        self
+    }
+
+    /// adds an image file, named after its file stem
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, TextureError> {
+        let path = path.as_ref();
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let image = image::open(path).map_err(TextureError::Image)?;
+        Ok(self.add(name, &image))
+    }
+
+    /// adds every image file of a directory, ignoring files of other types and
+    /// subdirectories
+    pub fn add_directory<P: AsRef<Path>>(&mut self, dir: P) -> Result<&mut Self, TextureError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir).map_err(TextureError::Io)? {
+            let path = entry.map_err(TextureError::Io)?.path();
+            if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+                paths.push(path);
+            }
+        }
+        // directory order differs between file systems
+        paths.sort();
+        for path in paths {
+            self.add_file(path)?;
+        }
+        Ok(self)
+    }
+
+    /// mipmap levels the gutter keeps apart
+    fn levels(&self) -> u32 {
+        if self.texture.mipmaps && self.gutter > 0 {
+            32 - self.gutter.leading_zeros()
+        } else {
+            1
+        }
+    }
+
+    /// packs the images into the smallest power of two atlas that fits, growing the width
+    /// first. The same images and settings always give the same layout.
+    pub fn pack(&self) -> Result<AtlasLayout, TextureError> {
+        let levels = self.levels();
+        let align = 1 << (levels - 1);
+        let cell = |width: u32, height: u32| {
+            let border = 2 * self.gutter + self.padding;
+            (
+                (width + border).next_multiple_of(align),
+                (height + border).next_multiple_of(align),
+            )
+        };
+
+        // tall images first, then wide ones, then by name
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (name_a, image_a) = &self.images[a];
+            let (name_b, image_b) = &self.images[b];
+            (image_b.height(), image_b.width())
+                .cmp(&(image_a.height(), image_a.width()))
+                .then_with(|| name_a.cmp(name_b))
+        });
+
+        let area: u64 = order
+            .iter()
+            .map(|&i| {
+                let (w, h) = cell(self.images[i].1.width(), self.images[i].1.height());
+                u64::from(w) * u64::from(h)
+            })
+            .sum();
+        let widest = order
+            .iter()
+            .map(|&i| cell(self.images[i].1.width(), 0).0)
+            .max()
+            .unwrap_or(1);
+        let tallest = order
+            .iter()
+            .map(|&i| cell(0, self.images[i].1.height()).1)
+            .max()
+            .unwrap_or(1);
+        let mut width = widest.max(1).next_power_of_two();
+        let mut height = tallest.max(1).next_power_of_two();
+        while u64::from(width) * u64::from(height) < area {
+            if width <= height {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+        }
+
+        let positions = loop {
+            if width > self.max_size || height > self.max_size {
+                return Err(TextureError::Format(format!(
+                    "{} images don't fit into a {}x{} atlas",
+                    self.images.len(),
+                    self.max_size,
+                    self.max_size
+                )));
+            }
+            let mut packer = SkylinePacker::new(width, height);
+            let positions: Option<Vec<(u32, u32)>> = order
+                .iter()
+                .map(|&i| {
+                    let (w, h) = cell(self.images[i].1.width(), self.images[i].1.height());
+                    packer.pack(w, h)
+                })
+                .collect();
+            match positions {
+                Some(positions) => break positions,
+                None if width <= height => width *= 2,
+                None => height *= 2,
+            }
+        };
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let mut regions = BTreeMap::new();
+        for (&i, &(cell_x, cell_y)) in order.iter().zip(&positions) {
+            let (name, image) = &self.images[i];
+            let (x, y) = (cell_x + self.gutter, cell_y + self.gutter);
+            self.blit(&mut pixels, width, image, x, y);
+            regions.insert(
+                name.clone(),
+                AtlasRegion::new(x, y, image.width(), image.height(), width, height),
+            );
+        }
+
+        Ok(AtlasLayout {
+            width,
+            height,
+            pixels,
+            levels,
+            regions,
+        })
+    }
+
+    /// copies `image` to (`x`, `y`) and extrudes its edges into the gutter
+    fn blit(&self, pixels: &mut [u8], atlas_width: u32, image: &RgbaImage, x: u32, y: u32) {
+        if image.width() == 0 || image.height() == 0 {
+            return;
+        }
+        let gutter = self.gutter as i64;
+        let (width, height) = (image.width() as i64, image.height() as i64);
+        for dy in -gutter..height + gutter {
+            let src_y = dy.clamp(0, height - 1) as u32;
+            for dx in -gutter..width + gutter {
+                let src_x = dx.clamp(0, width - 1) as u32;
+                let dst_x = (x as i64 + dx) as usize;
+                let dst_y = (y as i64 + dy) as usize;
+                let dst = (dst_y * atlas_width as usize + dst_x) * 4;
+                pixels[dst..dst + 4].copy_from_slice(&image.get_pixel(src_x, src_y).0);
+            }
+        }
+    }
+
+    /// packs the images and uploads the atlas
+    pub fn build(&self) -> Result<TextureAtlas, TextureError> {
+        let layout = self.pack()?;
+        Ok(TextureAtlas::from_layout(self.texture, layout))
+    }
+}
+
+/// Many images in one texture, looked up by name. See `AtlasBuilder`.
+#[derive(Debug)]
+pub struct TextureAtlas {
+    pub texture: Texture2D,
+    regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    /// uploads a packed layout, limiting the mipmap chain to `layout.levels`
+    pub fn from_layout(builder: Texture2DBuilder, layout: AtlasLayout) -> Self {
+        let mut texture = builder.from_pixels(layout.width, layout.height, GL_RGBA, &layout.pixels);
+        if texture.levels > layout.levels {
+            gl::bind_texture(GL_TEXTURE_2D, texture.id());
+            gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, layout.levels as i32 - 1);
+            gl::bind_texture(GL_TEXTURE_2D, 0);
+            texture.levels = layout.levels;
+        }
+        TextureAtlas {
+            texture,
+            regions: layout.regions,
+        }
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    /// all regions, ordered by name
+    pub fn regions(&self) -> impl Iterator<Item = (&str, &AtlasRegion)> {
+        self.regions.iter().map(|(name, region)| (name.as_str(), region))
+    }
+
+    /// the regions named `prefix` followed by a frame number, e.g. `run_0` to `run_11`,
+    /// in numeric order
+    pub fn frames(&self, prefix: &str) -> Vec<AtlasRegion> {
+        let mut frames: Vec<(u32, AtlasRegion)> = self
+            .regions
+            .iter()
+            .filter_map(|(name, region)| {
+                let number = name.strip_prefix(prefix)?.parse().ok()?;
+                Some((number, *region))
+            })
+            .collect();
+        frames.sort_by_key(|&(number, _)| number);
+        frames.into_iter().map(|(_, region)| region).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+/// Steps through sprite sheet frames at a fixed rate.
+///
+/// ```ignore
+/// let mut run = SpriteAnimation::new(atlas.frames("run_"), 12.0);
+/// // every frame
+/// run.update(delta_time);
+/// let frame = run.frame();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpriteAnimation {
+    frames: Vec<AtlasRegion>,
+    /// seconds each frame is shown
+    pub frame_time: f32,
+    /// starts over after the last frame, otherwise stays on it
+    pub looping: bool,
+    elapsed: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(frames: Vec<AtlasRegion>, frames_per_second: f32) -> Self {
+        assert!(!frames.is_empty(), "sprite animation without frames");
+        SpriteAnimation {
+            frames,
+            frame_time: 1.0 / frames_per_second,
+            looping: true,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// advances the animation by `delta_time` seconds
+    pub fn update(&mut self, delta_time: f32) {
+        self.elapsed += delta_time;
+        let duration = self.duration();
+        if self.looping && duration > 0.0 {
+            // keep the time small so it doesn't lose precision in long sessions
+            self.elapsed %= duration;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    /// length of one pass through all frames in seconds
+    pub fn duration(&self) -> f32 {
+        self.frame_time * self.frames.len() as f32
+    }
+
+    pub fn frame_index(&self) -> usize {
+        if self.frame_time <= 0.0 {
+            return 0;
+        }
+        let index = (self.elapsed / self.frame_time) as usize;
+        if self.looping {
+            index % self.frames.len()
+        } else {
+            index.min(self.frames.len() - 1)
+        }
+    }
+
+    pub fn frame(&self) -> &AtlasRegion {
+        &self.frames[self.frame_index()]
+    }
+
+    pub fn frames(&self) -> &[AtlasRegion] {
+        &self.frames
+    }
+
+    /// a non-looping animation reached its last frame's end
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.elapsed >= self.duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// texels encode the image and their position, so misplaced or smeared texels show
+    fn image(tag: u8, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([tag, x as u8, y as u8, 255])
+        }))
+    }
+
+    fn builder(sizes: &[(u32, u32)]) -> AtlasBuilder {
+        let mut builder = AtlasBuilder::new();
+        for (i, &(width, height)) in sizes.iter().enumerate() {
+            builder.add(format!("image_{}", i), &image(i as u8 + 1, width, height));
+        }
+        builder
+    }
+
+    const SIZES: [(u32, u32); 6] = [(10, 7), (3, 3), (16, 5), (1, 12), (6, 6), (9, 2)];
+
+    #[test]
+    fn skyline_packer() {
+        let mut packer = SkylinePacker::new(8, 8);
+        assert_eq!(packer.pack(4, 4), Some((0, 0)));
+        // lowest top wins over the leftmost position
+        assert_eq!(packer.pack(4, 2), Some((4, 0)));
+        assert_eq!(packer.pack(4, 2), Some((4, 2)));
+        // the two segments at height 4 merged, so a full width rectangle fits
+        assert_eq!(packer.pack(8, 4), Some((0, 4)));
+        assert_eq!(packer.pack(1, 1), None);
+        assert_eq!(packer.pack(0, 3), Some((0, 0)));
+
+        // a rectangle rests on the highest segment it spans
+        let mut packer = SkylinePacker::new(8, 8);
+        assert_eq!(packer.pack(2, 3), Some((0, 0)));
+        assert_eq!(packer.pack(2, 1), Some((2, 0)));
+        assert_eq!(packer.pack(3, 2), Some((4, 0)));
+        assert_eq!(packer.pack(7, 2), Some((0, 3)));
+        assert_eq!(packer.pack(9, 1), None);
+    }
+
+    #[test]
+    fn regions_padding_and_gutter() {
+        let (padding, gutter) = (1, 2);
+        let builder = builder(&SIZES).padding(padding).gutter(gutter);
+        let layout = builder.pack().unwrap();
+        assert!(layout.width.is_power_of_two() && layout.height.is_power_of_two());
+        assert_eq!(layout.pixels.len(), (layout.width * layout.height * 4) as usize);
+        // two texels of gutter keep levels 0 and 1 apart, aligned to 2x2 blocks
+        assert_eq!(layout.levels, 2);
+        assert_eq!(layout.regions.len(), SIZES.len());
+
+        let texel = |x: u32, y: u32| {
+            let start = ((y * layout.width + x) * 4) as usize;
+            &layout.pixels[start..start + 4]
+        };
+        // each image with its gutter and the padding after it
+        let cells: Vec<(u32, u32, u32, u32)> = layout
+            .regions
+            .values()
+            .map(|region| {
+                (
+                    region.x - gutter,
+                    region.y - gutter,
+                    region.width + 2 * gutter + padding,
+                    region.height + 2 * gutter + padding,
+                )
+            })
+            .collect();
+
+        for (i, &(width, height)) in SIZES.iter().enumerate() {
+            let region = &layout.regions[&format!("image_{}", i)];
+            assert_eq!((region.width, region.height), (width, height));
+            assert_eq!((region.x - gutter) % 2, 0);
+            assert_eq!((region.y - gutter) % 2, 0);
+            assert!(region.x + width + gutter + padding <= layout.width);
+            assert!(region.y + height + gutter + padding <= layout.height);
+            assert_eq!(
+                region.uv_min,
+                vec2(
+                    region.x as f32 / layout.width as f32,
+                    region.y as f32 / layout.height as f32
+                )
+            );
+            assert_eq!(region.uv_max.y, (region.y + height) as f32 / layout.height as f32);
+
+            // the image, then its edges repeated across the gutter
+            let g = gutter as i64;
+            for dy in -g..height as i64 + g {
+                for dx in -g..width as i64 + g {
+                    let x = dx.clamp(0, width as i64 - 1) as u8;
+                    let y = dy.clamp(0, height as i64 - 1) as u8;
+                    let texel = texel((region.x as i64 + dx) as u32, (region.y as i64 + dy) as u32);
+                    assert_eq!(texel, [i as u8 + 1, x, y, 255], "image_{} at ({}, {})", i, dx, dy);
+                }
+            }
+        }
+
+        for (i, a) in cells.iter().enumerate() {
+            for b in &cells[i + 1..] {
+                let apart = a.0 + a.2 <= b.0 || b.0 + b.2 <= a.0 || a.1 + a.3 <= b.1 || b.1 + b.3 <= a.1;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        // padding and unused space stay transparent
+        for y in 0..layout.height {
+            for x in 0..layout.width {
+                let covered = cells
+                    .iter()
+                    .any(|&(cx, cy, cw, ch)| x >= cx && x < cx + cw - padding && y >= cy && y < cy + ch - padding);
+                if !covered {
+                    assert_eq!(texel(x, y), [0, 0, 0, 0], "texel ({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn alignment_follows_the_gutter() {
+        let mipmapped = builder(&SIZES).gutter(4).pack().unwrap();
+        assert_eq!(mipmapped.levels, 3);
+        for region in mipmapped.regions.values() {
+            assert_eq!((region.x - 4) % 4, 0);
+            assert_eq!((region.y - 4) % 4, 0);
+        }
+
+        let mut texture = Texture2D::builder();
+        texture.mipmaps = false;
+        let single = builder(&SIZES).gutter(4).texture(texture).pack().unwrap();
+        assert_eq!(single.levels, 1);
+        let without_gutter = builder(&SIZES).gutter(0).pack().unwrap();
+        assert_eq!(without_gutter.levels, 1);
+        // without alignment the images pack tighter
+        assert!(without_gutter.width * without_gutter.height <= mipmapped.width * mipmapped.height);
+    }
+
+    #[test]
+    fn packing_is_deterministic() {
+        let first = builder(&SIZES).padding(1).pack().unwrap();
+        let again = builder(&SIZES).padding(1).pack().unwrap();
+        assert_eq!(first.regions, again.regions);
+        assert_eq!(first.pixels, again.pixels);
+
+        // the layout doesn't depend on the order the images were added in
+        let mut reversed = AtlasBuilder::new().padding(1);
+        for (i, &(width, height)) in SIZES.iter().enumerate().rev() {
+            reversed.add(format!("image_{}", i), &image(i as u8 + 1, width, height));
+        }
+        let reversed = reversed.pack().unwrap();
+        assert_eq!(first.regions, reversed.regions);
+        assert_eq!(first.pixels, reversed.pixels);
+    }
+
+    #[test]
+    fn oversize_input() {
+        assert!(builder(&[(20, 4)]).max_size(16).pack().is_err());
+        // each image fits on its own, but not all of them together
+        assert!(builder(&[(12, 12); 5]).gutter(0).max_size(32).pack().is_err());
+        let fits = builder(&[(12, 12); 4]).gutter(0).max_size(32).pack().unwrap();
+        assert_eq!((fits.width, fits.height), (32, 32));
+    }
+
+    #[test]
+    fn sprite_animation_frames() {
+        let frames: Vec<AtlasRegion> = (0..4).map(|i| AtlasRegion::new(i * 8, 0, 8, 8, 32, 8)).collect();
+        let mut animation = SpriteAnimation::new(frames.clone(), 2.0);
+        assert_eq!(animation.duration(), 2.0);
+        assert_eq!(*animation.frame(), frames[0]);
+        animation.update(1.25);
+        assert_eq!(animation.frame_index(), 2);
+        assert_eq!(animation.frame().x, 16);
+        // wraps around to the first frame
+        animation.update(1.0);
+        assert_eq!(animation.frame_index(), 0);
+        assert!(!animation.is_finished());
+
+        let mut once = SpriteAnimation::new(frames, 2.0).looping(false);
+        once.update(1.75);
+        assert_eq!(once.frame_index(), 3);
+        assert!(!once.is_finished());
+        once.update(5.0);
+        assert_eq!(once.frame_index(), 3);
+        assert!(once.is_finished());
+        once.reset();
+        assert_eq!(once.frame_index(), 0);
+    }
+}
@@ -1,13 +1,13 @@
-use c06_pbr::{process_events, process_input, Camera, Shader};
+use c06_pbr::{process_events, process_input, run_event_loop, Camera, Geometry, Shader};
 use cgmath::prelude::*;
-use cgmath::{perspective, vec2, vec3, Deg, Matrix4, Point3, Vector3};
+use cgmath::{perspective, vec3, Deg, Matrix4, Point3, Vector3};
 use dx::{
     assets,
     gles::{core30::gl, enums::*},
     utils::*,
     Instance,
 };
-use std::{f32::consts::PI, mem::size_of, time::SystemTime};
+use std::time::SystemTime;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -90,12 +90,11 @@ pub fn main() {
     let nr_columns = 7;
     let spacing = 2.5;
 
-    let mut spherevao = 0;
-    let mut index_count = 0;
+    let sphere = Geometry::uv_sphere(1.0, 64, 64).mesh();
 
     // render loop
 
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -154,7 +153,7 @@ pub fn main() {
                         0.0,
                     ));
                     shader.set_mat4("model", &model);
-                    render_sphere(&mut spherevao, &mut index_count);
+                    sphere.draw(&shader);
                 }
             }
 
@@ -173,7 +172,7 @@ pub fn main() {
                 model = Matrix4::from_translation(new_pos);
                 model = model * Matrix4::from_scale(0.5);
                 shader.set_mat4("model", &model);
-                render_sphere(&mut spherevao, &mut index_count);
+                sphere.draw(&shader);
             }
 
             // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
@@ -187,82 +186,3 @@ pub fn main() {
         _ => {}
     });
 }
-
-pub fn render_sphere(spherevao: &mut u32, index_count: &mut u32) {
-    if *spherevao == 0 {
-        *spherevao = gl::gen_vertex_array();
-
-        let vbo = gl::gen_buffer();
-        let ebo = gl::gen_buffer();
-
-        let mut positions = vec![];
-        let mut uv = vec![];
-        let mut normals = vec![];
-        let mut indices = vec![];
-
-        const X_SEGMENTS: u32 = 64;
-        const Y_SEGMENTS: u32 = 64;
-        for y in 0..Y_SEGMENTS + 1 {
-            for x in 0..X_SEGMENTS + 1 {
-                let x_segment = x as f32 / X_SEGMENTS as f32;
-                let y_segment = y as f32 / Y_SEGMENTS as f32;
-                let x_pos = (x_segment * 2.0 * PI).cos() * (y_segment * PI).sin();
-                let y_pos = (y_segment * PI).cos();
-                let z_pos = (x_segment * 2.0 * PI).sin() * (y_segment * PI).sin();
-
-                positions.push(vec3(x_pos, y_pos, z_pos));
-                uv.push(vec2(x_segment, y_segment));
-                normals.push(vec3(x_pos, y_pos, z_pos));
-            }
-        }
-
-        let mut odd_row = false;
-        for y in 0..Y_SEGMENTS {
-            if odd_row {
-                // even rows: y == 0, y == 2; and so on
-                for x in 0..X_SEGMENTS + 1 {
-                    indices.push(y * (X_SEGMENTS + 1) + x);
-                    indices.push((y + 1) * (X_SEGMENTS + 1) + x);
-                }
-            } else {
-                for x in (0..X_SEGMENTS + 1).rev() {
-                    indices.push((y + 1) * (X_SEGMENTS + 1) + x);
-                    indices.push(y * (X_SEGMENTS + 1) + x);
-                }
-            }
-            odd_row = !odd_row;
-        }
-        *index_count = indices.len() as u32;
-
-        let mut data: Vec<f32> = Vec::new();
-        for (i, position) in positions.iter().enumerate() {
-            data.push(position.x);
-            data.push(position.y);
-            data.push(position.z);
-            if !uv.is_empty() {
-                data.push(uv[i].x);
-                data.push(uv[i].y);
-            }
-            if !normals.is_empty() {
-                data.push(normals[i].x);
-                data.push(normals[i].y);
-                data.push(normals[i].z);
-            }
-        }
-        gl::bind_vertex_array(*spherevao);
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
-        gl::buffer_data(GL_ARRAY_BUFFER, &data, GL_STATIC_DRAW);
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
-        gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, &indices, GL_STATIC_DRAW);
-        let stride = (3 + 2 + 3) * size_of::<f32>() as i32;
-        gl::enable_vertex_attrib_array(0);
-        gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
-        gl::enable_vertex_attrib_array(1);
-        gl::vertex_attrib_pointer_offset(1, 2, GL_FLOAT, false, stride, 3 * size_of::<f32>() as u32);
-        gl::enable_vertex_attrib_array(2);
-        gl::vertex_attrib_pointer_offset(2, 3, GL_FLOAT, false, stride, 5 * size_of::<f32>() as u32);
-    }
-
-    gl::bind_vertex_array(*spherevao);
-    gl::draw_elements_offset(GL_TRIANGLE_STRIP, *index_count as i32, GL_UNSIGNED_INT, 0);
-}
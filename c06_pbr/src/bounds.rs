@@ -0,0 +1,219 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3};
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+impl Aabb {
+    /// a box that contains nothing; growing it by any point yields a box around that point
+    pub fn empty() -> Self {
+        Aabb {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+    {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.grow(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, point: Point3<f32>) {
+        self.min = Point3::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    /// smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn extents(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+            && point.z >= self.min.z
+            && point.z <= self.max.z
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// the axis-aligned box around this box after transforming it, e.g. by a model matrix
+    /// to get world-space bounds.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        // Arvo's method: start at the translation and add the smaller/larger product of
+        // every matrix element with the min/max extent along that axis.
+        let mut min = [matrix.w.x, matrix.w.y, matrix.w.z];
+        let mut max = min;
+        let from_min: [f32; 3] = self.min.into();
+        let from_max: [f32; 3] = self.max.into();
+        for (col, (&lo, &hi)) in from_min.iter().zip(from_max.iter()).enumerate() {
+            let column: [f32; 4] = matrix[col].into();
+            for row in 0..3 {
+                let a = column[row] * lo;
+                let b = column[row] * hi;
+                min[row] += a.min(b);
+                max[row] += a.max(b);
+            }
+        }
+        Aabb {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+}
+
+/// Bounding sphere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl Default for BoundingSphere {
+    fn default() -> Self {
+        BoundingSphere::empty()
+    }
+}
+
+impl BoundingSphere {
+    /// a sphere that contains nothing, see `Aabb::empty`
+    pub fn empty() -> Self {
+        BoundingSphere {
+            center: Point3::origin(),
+            radius: -1.0,
+        }
+    }
+
+    /// sphere centered on the bounding box of the points; not the minimal sphere, but
+    /// close enough for culling and camera framing.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Point3<f32>> + Clone,
+    {
+        let aabb = Aabb::from_points(points.clone());
+        if aabb.is_empty() {
+            return BoundingSphere::empty();
+        }
+        let center = aabb.center();
+        let radius = points
+            .into_iter()
+            .map(|point| point.distance2(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        BoundingSphere { center, radius }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    /// smallest sphere containing both spheres
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        BoundingSphere {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        point.distance2(self.center) <= self.radius * self.radius
+    }
+
+    /// the sphere after transforming it; non-uniform scaling grows the radius by the
+    /// largest axis scale so the result still contains the transformed geometry.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        if self.is_empty() {
+            return *self;
+        }
+        let scale = matrix
+            .x
+            .truncate()
+            .magnitude2()
+            .max(matrix.y.truncate().magnitude2())
+            .max(matrix.z.truncate().magnitude2())
+            .sqrt();
+        BoundingSphere {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use dx::gles::{core30::gl, enums::*};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// What the current GL context supports, queried once per thread.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// true for OpenGL ES contexts, false for desktop OpenGL
+    pub es: bool,
+    pub major_version: u32,
+    pub minor_version: u32,
+    pub extensions: HashSet<String>,
+    pub max_texture_size: i32,
+    pub max_vertex_attribs: i32,
+    /// largest `GL_TEXTURE_MAX_ANISOTROPY_EXT`; 1.0 without anisotropic filtering
+    pub max_anisotropy: f32,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<Capabilities>>> = const { RefCell::new(None) };
+    static ENABLED: RefCell<HashMap<u32, bool>> = RefCell::new(HashMap::new());
+}
+
+impl Capabilities {
+    /// capabilities of the context current on this thread; queried on first use.
+    pub fn current() -> Rc<Capabilities> {
+        CURRENT.with(|current| {
+            current
+                .borrow_mut()
+                .get_or_insert_with(|| Rc::new(Capabilities::query()))
+                .clone()
+        })
+    }
+
+    /// queries the context; prefer `current` which caches the result.
+    pub fn query() -> Capabilities {
+        let version = gl::get_string(GL_VERSION).unwrap_or_default();
+        let (es, major_version, minor_version) = parse_version(&version);
+        // core profiles dropped the space separated list, GL 3 and ES 3 list them one by one
+        let extensions: HashSet<String> = if major_version >= 3 {
+            (0..gl::get_integerv(GL_NUM_EXTENSIONS).max(0) as u32)
+                .filter_map(|index| gl::get_stringi(GL_EXTENSIONS, index))
+                .collect()
+        } else {
+            gl::get_string(GL_EXTENSIONS)
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect()
+        };
+        let anisotropic = ["GL_EXT_texture_filter_anisotropic", "GL_ARB_texture_filter_anisotropic"]
+            .iter()
+            .any(|&name| extensions.contains(name));
+        let max_anisotropy = if anisotropic {
+            gl::get_floatv(GL_MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+        } else {
+            1.0
+        };
+
+        Capabilities {
+            es,
+            major_version,
+            minor_version,
+            extensions,
+            max_texture_size: gl::get_integerv(GL_MAX_TEXTURE_SIZE),
+            max_vertex_attribs: gl::get_integerv(GL_MAX_VERTEX_ATTRIBS),
+            max_anisotropy,
+        }
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major_version, self.minor_version) >= (major, minor)
+    }
+
+    /// OpenGL ES 2.0 context (or anything older than ES 3.0 / GL 3.3)
+    pub fn is_gles2(&self) -> bool {
+        !self.gles3_features()
+    }
+
+    /// the feature set of OpenGL ES 3.0: integer attributes, texelFetch, float textures,
+    /// NPOT textures, instancing and so on. Desktop GL 3.3 provides all of it.
+    pub fn gles3_features(&self) -> bool {
+        if self.es {
+            self.version_at_least(3, 0)
+        } else {
+            self.version_at_least(3, 3)
+        }
+    }
+
+    /// `GL_UNSIGNED_INT` indices can be drawn; OpenGL ES 2.0 needs `OES_element_index_uint`
+    pub fn u32_indices(&self) -> bool {
+        self.gles3_features() || self.has_extension("GL_OES_element_index_uint")
+    }
+
+    /// non-power-of-two textures with mipmaps and `GL_REPEAT`: ES 3.0, desktop GL, or
+    /// `OES_texture_npot` on ES 2.0
+    pub fn npot_textures(&self) -> bool {
+        !self.es || self.version_at_least(3, 0) || self.has_extension("GL_OES_texture_npot")
+    }
+
+    /// immutable texture storage (`glTexStorage2D`): ES 3.0, GL 4.2 or `ARB_texture_storage`
+    pub fn texture_storage(&self) -> bool {
+        if self.es {
+            self.version_at_least(3, 0) || self.has_extension("GL_EXT_texture_storage")
+        } else {
+            self.version_at_least(4, 2) || self.has_extension("GL_ARB_texture_storage")
+        }
+    }
+
+    /// filterable half float textures (`GL_RGB16F`, `GL_RGBA16F`, `GL_R11F_G11F_B10F`):
+    /// ES 3.0 or GL 3.0
+    pub fn half_float_textures(&self) -> bool {
+        self.version_at_least(3, 0)
+    }
+
+    /// 16-bit normalized textures (`GL_R16`, `GL_RG16`, `GL_RGBA16`): GL 3.0, or
+    /// `EXT_texture_norm16` on ES
+    pub fn norm16_textures(&self) -> bool {
+        if self.es {
+            self.has_extension("GL_EXT_texture_norm16")
+        } else {
+            self.version_at_least(3, 0)
+        }
+    }
+
+    /// `GL_TEXTURE_SWIZZLE_R`..`A`: ES 3.0, GL 3.3 or `ARB_texture_swizzle`
+    pub fn texture_swizzle(&self) -> bool {
+        self.gles3_features() || self.has_extension("GL_ARB_texture_swizzle")
+    }
+
+    /// rendering into `GL_RGBA16F`: GL 3.0, ES 3.2, or `EXT_color_buffer_half_float` /
+    /// `EXT_color_buffer_float` on ES 3.0
+    pub fn color_buffer_half_float(&self) -> bool {
+        if self.es {
+            self.version_at_least(3, 2)
+                || self.has_extension("GL_EXT_color_buffer_half_float")
+                || self.has_extension("GL_EXT_color_buffer_float")
+        } else {
+            self.version_at_least(3, 0)
+        }
+    }
+
+    /// filtering across cubemap faces: always in ES 3.0, `GL_TEXTURE_CUBE_MAP_SEAMLESS` in
+    /// GL 3.2 or with `ARB_seamless_cube_map`
+    pub fn seamless_cube_maps(&self) -> bool {
+        if self.es {
+            self.version_at_least(3, 0)
+        } else {
+            self.version_at_least(3, 2) || self.has_extension("GL_ARB_seamless_cube_map")
+        }
+    }
+
+    /// ETC2 and EAC compressed textures: ES 3.0, GL 4.3 or `ARB_ES3_compatibility`
+    pub fn etc2_textures(&self) -> bool {
+        if self.es {
+            self.version_at_least(3, 0)
+        } else {
+            self.version_at_least(4, 3) || self.has_extension("GL_ARB_ES3_compatibility")
+        }
+    }
+
+    /// ASTC LDR compressed textures: ES 3.2 or `KHR_texture_compression_astc_ldr`
+    pub fn astc_textures(&self) -> bool {
+        (self.es && self.version_at_least(3, 2)) || self.has_extension("GL_KHR_texture_compression_astc_ldr")
+    }
+}
+
+/// `glEnable` state of the capabilities the library toggles around every draw (`GL_BLEND`,
+/// `GL_CULL_FACE` for materials, `GL_DEPTH_TEST` for post processing), shadowed on the CPU
+/// so drawing doesn't stall on `glIsEnabled`. Each capability is queried once per thread on
+/// first use; code changing it afterwards, between draws of library meshes, has to go
+/// through `set`.
+pub struct EnableState;
+
+impl EnableState {
+    pub fn is_enabled(capability: u32) -> bool {
+        ENABLED.with(|enabled| {
+            *enabled
+                .borrow_mut()
+                .entry(capability)
+                .or_insert_with(|| gl::is_enabled(capability))
+        })
+    }
+
+    /// enables or disables `capability`, calling GL only if that changes it; returns
+    /// whether it changed
+    pub fn set(capability: u32, enable: bool) -> bool {
+        if EnableState::is_enabled(capability) == enable {
+            return false;
+        }
+        if enable {
+            gl::enable(capability);
+        } else {
+            gl::disable(capability);
+        }
+        ENABLED.with(|enabled| enabled.borrow_mut().insert(capability, enable));
+        true
+    }
+}
+
+/// parses "OpenGL ES 3.2 ..." (ES) or "4.6.0 NVIDIA ..." (desktop) version strings
+fn parse_version(version: &str) -> (bool, u32, u32) {
+    let es = version.starts_with("OpenGL ES");
+    let number = version
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or("2.0");
+    let mut parts = number
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok());
+    let major = parts.next().unwrap_or(2);
+    let minor = parts.next().unwrap_or(0);
+    (es, major, minor)
+}
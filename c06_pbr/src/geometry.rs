@@ -0,0 +1,509 @@
+use cgmath::prelude::*;
+use cgmath::{vec2, vec3, Vector2, Vector3};
+use std::{collections::HashMap, f32::consts::PI, rc::Rc};
+
+use super::material::Material;
+use super::mesh::{Mesh, Vertex};
+use super::normals::generate_tangents;
+
+/// Vertices and indices of a procedural shape, with normals, texture coordinates and
+/// tangents. Shapes are centered on the origin with Y up and counter-clockwise front
+/// faces; `mesh` uploads them.
+#[derive(Clone, Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+// a point of the profile turned around the Y axis by `Geometry::lathe`
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    // outward normal in the (radius, y) plane
+    normal: Vector2<f32>,
+    v: f32,
+}
+
+impl Geometry {
+    /// axis-aligned cube with edges of length `size`; every face has its own vertices and
+    /// the full texture
+    pub fn cube(size: f32) -> Geometry {
+        let mut geometry = Geometry::default();
+        let half = size / 2.0;
+        // normal, then the directions of increasing u and v, with u x v = normal
+        let faces = [
+            (Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()),
+            (-Vector3::unit_x(), Vector3::unit_z(), Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_x(), -Vector3::unit_z()),
+            (-Vector3::unit_y(), Vector3::unit_x(), Vector3::unit_z()),
+            (Vector3::unit_z(), Vector3::unit_x(), Vector3::unit_y()),
+            (-Vector3::unit_z(), -Vector3::unit_x(), Vector3::unit_y()),
+        ];
+        for (normal, u, v) in faces {
+            let first = geometry.vertices.len() as u32;
+            for uv in [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)] {
+                let position = (normal + u * (uv.x * 2.0 - 1.0) + v * (uv.y * 2.0 - 1.0)) * half;
+                geometry.push_vertex(position, normal, uv);
+            }
+            geometry.quad(first, first + 1, first + 2, first + 3);
+        }
+        geometry.finish()
+    }
+
+    /// sphere made of `segments` slices around the Y axis and `rings` stacks from pole to
+    /// pole; u goes around the equator and v from the south to the north pole
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Geometry {
+        let rings = rings.max(2);
+        let profile: Vec<ProfilePoint> = (0..=rings)
+            .map(|ring| {
+                let theta = ring as f32 / rings as f32 * PI;
+                let normal = meridian(theta);
+                ProfilePoint {
+                    radius: radius * normal.x,
+                    y: radius * normal.y,
+                    normal,
+                    v: 1.0 - ring as f32 / rings as f32,
+                }
+            })
+            .collect();
+        let mut geometry = Geometry::default();
+        geometry.lathe(&profile, segments);
+        geometry.finish()
+    }
+
+    /// sphere made of evenly sized triangles: an icosahedron with every triangle split in
+    /// four `subdivisions` times. Texture coordinates are an equirectangular mapping, with
+    /// the vertices of the triangles crossing the seam duplicated.
+    pub fn ico_sphere(radius: f32, subdivisions: u32) -> Geometry {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut directions: Vec<Vector3<f32>> = [
+            vec3(-1.0, t, 0.0),
+            vec3(1.0, t, 0.0),
+            vec3(-1.0, -t, 0.0),
+            vec3(1.0, -t, 0.0),
+            vec3(0.0, -1.0, t),
+            vec3(0.0, 1.0, t),
+            vec3(0.0, -1.0, -t),
+            vec3(0.0, 1.0, -t),
+            vec3(t, 0.0, -1.0),
+            vec3(t, 0.0, 1.0),
+            vec3(-t, 0.0, -1.0),
+            vec3(-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|direction| direction.normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            // edges are shared by two triangles, which must share the midpoint too
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let direction = (directions[a as usize] + directions[b as usize]).normalize();
+                    directions.push(direction);
+                    (directions.len() - 1) as u32
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut geometry = Geometry::default();
+        for &direction in &directions {
+            geometry.push_vertex(direction * radius, direction, sphere_uv(direction));
+        }
+        // triangles spanning the seam at u = 0 get copies of their vertices on the u > 1 side
+        let mut wrapped: HashMap<u32, u32> = HashMap::new();
+        for mut triangle in triangles {
+            let u = triangle.map(|idx| geometry.vertices[idx as usize].tex_coords.x);
+            if u[0].max(u[1]).max(u[2]) - u[0].min(u[1]).min(u[2]) > 0.5 {
+                for idx in triangle.iter_mut() {
+                    if geometry.vertices[*idx as usize].tex_coords.x < 0.5 {
+                        *idx = *wrapped.entry(*idx).or_insert_with(|| {
+                            let mut vertex = geometry.vertices[*idx as usize];
+                            vertex.tex_coords.x += 1.0;
+                            geometry.vertices.push(vertex);
+                            (geometry.vertices.len() - 1) as u32
+                        });
+                    }
+                }
+            }
+            geometry.indices.extend_from_slice(&triangle);
+        }
+        geometry.finish()
+    }
+
+    /// flat rectangle in the XZ plane facing +Y
+    pub fn plane(width: f32, depth: f32) -> Geometry {
+        Geometry::grid(width, depth, 1, 1)
+    }
+
+    /// rectangle in the XZ plane facing +Y, split in `columns` by `rows` quads. Vertices are
+    /// stored row by row; u goes along +X and v along -Z.
+    pub fn grid(width: f32, depth: f32, columns: u32, rows: u32) -> Geometry {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let mut geometry = Geometry::default();
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let uv = vec2(column as f32 / columns as f32, row as f32 / rows as f32);
+                let position = vec3((uv.x - 0.5) * width, 0.0, (0.5 - uv.y) * depth);
+                geometry.push_vertex(position, Vector3::unit_y(), uv);
+            }
+        }
+        for row in 0..rows {
+            for column in 0..columns {
+                let first = row * (columns + 1) + column;
+                let above = first + columns + 1;
+                geometry.quad(first, first + 1, above + 1, above);
+            }
+        }
+        geometry.finish()
+    }
+
+    /// cylinder along the Y axis, with caps
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Geometry {
+        let half = height / 2.0;
+        let side = |y: f32, v: f32| ProfilePoint {
+            radius,
+            y,
+            normal: vec2(1.0, 0.0),
+            v,
+        };
+        let mut geometry = Geometry::default();
+        geometry.lathe(&[side(half, 1.0), side(-half, 0.0)], segments);
+        geometry.cap(radius, half, true, segments);
+        geometry.cap(radius, -half, false, segments);
+        geometry.finish()
+    }
+
+    /// cone along the Y axis with the tip at the top, with a cap at the bottom
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Geometry {
+        let half = height / 2.0;
+        let normal = vec2(height, radius).normalize();
+        let mut geometry = Geometry::default();
+        geometry.lathe(
+            &[
+                ProfilePoint {
+                    radius: 0.0,
+                    y: half,
+                    normal,
+                    v: 1.0,
+                },
+                ProfilePoint {
+                    radius,
+                    y: -half,
+                    normal,
+                    v: 0.0,
+                },
+            ],
+            segments,
+        );
+        geometry.cap(radius, -half, false, segments);
+        geometry.finish()
+    }
+
+    /// torus around the Y axis: a tube of radius `minor_radius` whose center runs along a
+    /// circle of radius `major_radius`, with `segments` slices around the ring and `sides`
+    /// around the tube
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Geometry {
+        let sides = sides.max(3);
+        // the profile starts on the outer equator and goes down, around the tube
+        let profile: Vec<ProfilePoint> = (0..=sides)
+            .map(|side| {
+                // the last side repeats the first exactly, closing the tube
+                let angle = (side % sides) as f32 / sides as f32 * 2.0 * PI;
+                let normal = vec2(angle.cos(), -angle.sin());
+                ProfilePoint {
+                    radius: major_radius + minor_radius * normal.x,
+                    y: minor_radius * normal.y,
+                    normal,
+                    v: 1.0 - side as f32 / sides as f32,
+                }
+            })
+            .collect();
+        let mut geometry = Geometry::default();
+        geometry.lathe(&profile, segments);
+        geometry.finish()
+    }
+
+    /// cylinder of length `height` along the Y axis with hemispheres of `rings` stacks at
+    /// both ends; v is proportional to the distance along the surface from the bottom
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Geometry {
+        let (rings, half) = (rings.max(1), height / 2.0);
+        let length = PI * radius + height;
+        let mut profile = Vec::with_capacity(2 * rings as usize + 2);
+        for (center, first, last) in [(half, 0, rings), (-half, rings, 2 * rings)] {
+            for ring in first..=last {
+                let theta = ring as f32 / (2 * rings) as f32 * PI;
+                let normal = meridian(theta);
+                // distance from the top along the surface
+                let arc = radius * theta + if center < 0.0 { height } else { 0.0 };
+                profile.push(ProfilePoint {
+                    radius: radius * normal.x,
+                    y: center + radius * normal.y,
+                    normal,
+                    v: 1.0 - arc / length,
+                });
+            }
+        }
+        let mut geometry = Geometry::default();
+        geometry.lathe(&profile, segments);
+        geometry.finish()
+    }
+
+    /// quad covering the viewport in normalized device coordinates, facing +Z, for post
+    /// processing passes
+    pub fn fullscreen_quad() -> Geometry {
+        let mut geometry = Geometry::default();
+        for uv in [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)] {
+            geometry.push_vertex(vec3(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0), Vector3::unit_z(), uv);
+        }
+        geometry.quad(0, 1, 2, 3);
+        geometry.finish()
+    }
+
+    /// uploads the shape into a mesh with the default material
+    pub fn mesh(self) -> Mesh {
+        self.mesh_with_material(Rc::default())
+    }
+
+    pub fn mesh_with_material(self, material: Rc<Material>) -> Mesh {
+        Mesh::new(self.vertices, self.indices, material)
+    }
+
+    fn push_vertex(&mut self, position: Vector3<f32>, normal: Vector3<f32>, tex_coords: Vector2<f32>) {
+        self.vertices.push(Vertex {
+            position,
+            normal,
+            tex_coords,
+            ..Vertex::default()
+        });
+    }
+
+    // two triangles, corners counter-clockwise
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+
+    // surface of revolution around the Y axis. The profile goes from the top to the bottom
+    // of the outside of the surface; rings of radius 0 (poles, tips) get no degenerate
+    // triangles. There is a seam at u = 0 where the first and last slices meet.
+    fn lathe(&mut self, profile: &[ProfilePoint], segments: u32) {
+        let segments = segments.max(3);
+        let first = self.vertices.len() as u32;
+        for point in profile {
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                // the last slice repeats the first exactly, so the seam has no crack
+                let (sin, cos) = ((segment % segments) as f32 / segments as f32 * 2.0 * PI).sin_cos();
+                // u increases to the right seen from outside
+                let around = vec3(cos, 0.0, -sin);
+                self.push_vertex(
+                    around * point.radius + vec3(0.0, point.y, 0.0),
+                    around * point.normal.x + vec3(0.0, point.normal.y, 0.0),
+                    vec2(u, point.v),
+                );
+            }
+        }
+        for (ring, pair) in profile.windows(2).enumerate() {
+            for segment in 0..segments {
+                let top = first + ring as u32 * (segments + 1) + segment;
+                let bottom = top + segments + 1;
+                if pair[0].radius > 0.0 {
+                    self.indices.extend_from_slice(&[top, bottom, top + 1]);
+                }
+                if pair[1].radius > 0.0 {
+                    self.indices.extend_from_slice(&[top + 1, bottom, bottom + 1]);
+                }
+            }
+        }
+    }
+
+    // disc closing a lathed shape, facing up or down
+    fn cap(&mut self, radius: f32, y: f32, up: bool, segments: u32) {
+        let segments = segments.max(3);
+        let normal = if up { Vector3::unit_y() } else { -Vector3::unit_y() };
+        let center = self.vertices.len() as u32;
+        self.push_vertex(vec3(0.0, y, 0.0), normal, vec2(0.5, 0.5));
+        for segment in 0..=segments {
+            let (sin, cos) = ((segment % segments) as f32 / segments as f32 * 2.0 * PI).sin_cos();
+            let tex_coords = vec2(0.5 + 0.5 * cos, if up { 0.5 + 0.5 * sin } else { 0.5 - 0.5 * sin });
+            self.push_vertex(vec3(cos * radius, y, -sin * radius), normal, tex_coords);
+        }
+        for segment in 0..segments {
+            let (a, b) = (center + 1 + segment, center + 2 + segment);
+            if up {
+                self.indices.extend_from_slice(&[center, a, b]);
+            } else {
+                self.indices.extend_from_slice(&[center, b, a]);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Geometry {
+        generate_tangents(&mut self.vertices, &self.indices);
+        self
+    }
+}
+
+// outward normal in the (radius, y) plane at `theta` from the north pole; exactly on the
+// axis at the south pole, so its vertices meet
+fn meridian(theta: f32) -> Vector2<f32> {
+    if theta >= PI {
+        vec2(0.0, -1.0)
+    } else {
+        vec2(theta.sin(), theta.cos())
+    }
+}
+
+// equirectangular texture coordinates of a direction, matching `Geometry::uv_sphere`
+fn sphere_uv(direction: Vector3<f32>) -> Vector2<f32> {
+    let u = (-direction.z).atan2(direction.x) / (2.0 * PI);
+    vec2(
+        if u < 0.0 { u + 1.0 } else { u },
+        0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<(&'static str, Geometry, bool)> {
+        vec![
+            ("cube", Geometry::cube(2.0), true),
+            ("uv_sphere", Geometry::uv_sphere(1.0, 16, 8), true),
+            ("ico_sphere", Geometry::ico_sphere(1.0, 2), true),
+            ("plane", Geometry::plane(2.0, 3.0), false),
+            ("grid", Geometry::grid(2.0, 3.0, 4, 5), false),
+            ("cylinder", Geometry::cylinder(1.0, 2.0, 12), true),
+            ("cone", Geometry::cone(1.0, 2.0, 12), true),
+            ("torus", Geometry::torus(1.0, 0.25, 16, 8), true),
+            ("capsule", Geometry::capsule(0.5, 1.0, 12, 4), true),
+            ("fullscreen_quad", Geometry::fullscreen_quad(), false),
+        ]
+    }
+
+    fn corners(geometry: &Geometry, tri: &[u32]) -> [Vertex; 3] {
+        [tri[0], tri[1], tri[2]].map(|idx| geometry.vertices[idx as usize])
+    }
+
+    #[test]
+    fn indices_are_in_range() {
+        for (name, geometry, _) in shapes() {
+            assert!(!geometry.indices.is_empty(), "{} has no triangles", name);
+            assert_eq!(geometry.indices.len() % 3, 0, "{}", name);
+            let count = geometry.vertices.len() as u32;
+            assert!(
+                geometry.indices.iter().all(|&idx| idx < count),
+                "{} index out of range",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn winding_agrees_with_normals() {
+        for (name, geometry, _) in shapes() {
+            for tri in geometry.indices.chunks_exact(3) {
+                let [a, b, c] = corners(&geometry, tri);
+                let face = (b.position - a.position).cross(c.position - a.position);
+                for vertex in &[a, b, c] {
+                    assert!(
+                        face.dot(vertex.normal) > 0.0,
+                        "{} triangle {:?} is clockwise",
+                        name,
+                        tri
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn normals_and_tangents_are_orthonormal() {
+        for (name, geometry, _) in shapes() {
+            for (idx, vertex) in geometry.vertices.iter().enumerate() {
+                assert!(
+                    (vertex.normal.magnitude() - 1.0).abs() < 1e-4,
+                    "{} normal {}",
+                    name,
+                    idx
+                );
+                assert!(
+                    (vertex.tangent.magnitude() - 1.0).abs() < 1e-4,
+                    "{} tangent {}",
+                    name,
+                    idx
+                );
+                assert!(
+                    vertex.normal.dot(vertex.tangent).abs() < 1e-4,
+                    "{} tangent {} not orthogonal",
+                    name,
+                    idx
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closed_shapes_have_no_borders() {
+        for (name, geometry, closed) in shapes() {
+            if !closed {
+                continue;
+            }
+            // vertices at the same position are welded, so UV seams and split normals don't count
+            let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+            let ids: Vec<u32> = geometry
+                .vertices
+                .iter()
+                .map(|vertex| {
+                    let p = vertex.position.map(|x| if x == 0.0 { 0.0 } else { x });
+                    let next = welded.len() as u32;
+                    *welded
+                        .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                        .or_insert(next)
+                })
+                .collect();
+            // every edge of a closed surface is used once in each direction
+            let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+            for tri in geometry.indices.chunks_exact(3) {
+                for corner in 0..3 {
+                    let (a, b) = (ids[tri[corner] as usize], ids[tri[(corner + 1) % 3] as usize]);
+                    *edges.entry((a, b)).or_insert(0) += 1;
+                }
+            }
+            for (&(a, b), &uses) in &edges {
+                assert_eq!(uses, 1, "{} edge {}-{} used {} times", name, a, b, uses);
+                assert_eq!(edges.get(&(b, a)), Some(&1), "{} border edge {}-{}", name, a, b);
+            }
+        }
+    }
+}
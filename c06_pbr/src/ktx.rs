@@ -0,0 +1,678 @@
+use dx::gles::{core30::gl, enums::*};
+use std::{
+    borrow::Cow,
+    convert::{TryFrom, TryInto},
+    fs,
+    path::Path,
+};
+
+use super::astc::decode_astc;
+use super::capabilities::Capabilities;
+use super::etc2::{decode_etc2, Etc2Format};
+use super::gl_object::GlTexture;
+use super::texture::{mip_levels, sized_format, Sampling, TextureError};
+
+const KTX1_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+
+// the ASTC block footprints, in the order of their GL and Vulkan format numbers
+const ASTC_BLOCKS: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+// GL_COMPRESSED_RGBA_ASTC_4x4_KHR and GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR, the other
+// footprints follow in the order of `ASTC_BLOCKS`
+const GL_ASTC_RGBA: u32 = 0x93B0;
+const GL_ASTC_SRGB8_ALPHA8: u32 = 0x93D0;
+
+/// Texel format of a KTX file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KtxFormat {
+    /// ETC2 or EAC blocks, core in OpenGL ES 3.0 (ETC1 files load as ETC2 RGB)
+    Etc2 { format: Etc2Format, srgb: bool },
+    /// ASTC LDR blocks of `block_width` x `block_height` texels
+    Astc {
+        block_width: u32,
+        block_height: u32,
+        srgb: bool,
+    },
+    /// 8-bit texels with 1 to 4 channels
+    Uncompressed { channels: u32, srgb: bool },
+}
+
+impl KtxFormat {
+    /// the format of a KTX 1 file's `glInternalFormat`, for compressed files
+    pub fn from_gl_internal_format(value: u32) -> Option<KtxFormat> {
+        let etc2 = |format, srgb| Some(KtxFormat::Etc2 { format, srgb });
+        let astc = |index: u32, srgb| {
+            let (block_width, block_height) = ASTC_BLOCKS[index as usize];
+            Some(KtxFormat::Astc {
+                block_width,
+                block_height,
+                srgb,
+            })
+        };
+        match value {
+            0x9270 => etc2(Etc2Format::R11, false),
+            0x9271 => etc2(Etc2Format::SignedR11, false),
+            0x9272 => etc2(Etc2Format::Rg11, false),
+            0x9273 => etc2(Etc2Format::SignedRg11, false),
+            0x9274 => etc2(Etc2Format::Rgb, false),
+            0x9275 => etc2(Etc2Format::Rgb, true),
+            0x9276 => etc2(Etc2Format::RgbA1, false),
+            0x9277 => etc2(Etc2Format::RgbA1, true),
+            0x9278 => etc2(Etc2Format::Rgba, false),
+            0x9279 => etc2(Etc2Format::Rgba, true),
+            // GL_ETC1_RGB8_OES, a subset of ETC2
+            0x8D64 => etc2(Etc2Format::Rgb, false),
+            0x93B0..=0x93BD => astc(value - GL_ASTC_RGBA, false),
+            0x93D0..=0x93DD => astc(value - GL_ASTC_SRGB8_ALPHA8, true),
+            _ => None,
+        }
+    }
+
+    /// the format of a KTX 2 file's `vkFormat`
+    pub fn from_vk_format(value: u32) -> Option<KtxFormat> {
+        let uncompressed = |channels, srgb| Some(KtxFormat::Uncompressed { channels, srgb });
+        match value {
+            // VK_FORMAT_R8_UNORM, R8G8_UNORM, R8G8B8_UNORM/SRGB, R8G8B8A8_UNORM/SRGB
+            9 => uncompressed(1, false),
+            16 => uncompressed(2, false),
+            23 => uncompressed(3, false),
+            29 => uncompressed(3, true),
+            37 => uncompressed(4, false),
+            43 => uncompressed(4, true),
+            // VK_FORMAT_ETC2_*_BLOCK in UNORM/SRGB pairs
+            147..=152 => Some(KtxFormat::Etc2 {
+                format: [Etc2Format::Rgb, Etc2Format::RgbA1, Etc2Format::Rgba][(value - 147) as usize / 2],
+                srgb: (value - 147) % 2 == 1,
+            }),
+            // VK_FORMAT_EAC_*_BLOCK
+            153..=156 => Some(KtxFormat::Etc2 {
+                format: [
+                    Etc2Format::R11,
+                    Etc2Format::SignedR11,
+                    Etc2Format::Rg11,
+                    Etc2Format::SignedRg11,
+                ][(value - 153) as usize],
+                srgb: false,
+            }),
+            // VK_FORMAT_ASTC_*_BLOCK in UNORM/SRGB pairs
+            157..=184 => {
+                let (block_width, block_height) = ASTC_BLOCKS[(value - 157) as usize / 2];
+                Some(KtxFormat::Astc {
+                    block_width,
+                    block_height,
+                    srgb: (value - 157) % 2 == 1,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, KtxFormat::Uncompressed { .. })
+    }
+
+    /// internal format for `glCompressedTexImage2D`, or the sized format of uncompressed texels
+    pub fn gl_internal_format(&self) -> u32 {
+        match *self {
+            KtxFormat::Etc2 { format, srgb } => match (format, srgb) {
+                (Etc2Format::Rgb, false) => GL_COMPRESSED_RGB8_ETC2,
+                (Etc2Format::Rgb, true) => GL_COMPRESSED_SRGB8_ETC2,
+                (Etc2Format::RgbA1, false) => GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+                (Etc2Format::RgbA1, true) => GL_COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+                (Etc2Format::Rgba, false) => GL_COMPRESSED_RGBA8_ETC2_EAC,
+                (Etc2Format::Rgba, true) => GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+                (Etc2Format::R11, _) => GL_COMPRESSED_R11_EAC,
+                (Etc2Format::SignedR11, _) => GL_COMPRESSED_SIGNED_R11_EAC,
+                (Etc2Format::Rg11, _) => GL_COMPRESSED_RG11_EAC,
+                (Etc2Format::SignedRg11, _) => GL_COMPRESSED_SIGNED_RG11_EAC,
+            },
+            KtxFormat::Astc {
+                block_width,
+                block_height,
+                srgb,
+            } => {
+                let index = ASTC_BLOCKS
+                    .iter()
+                    .position(|&block| block == (block_width, block_height))
+                    .expect("ASTC block footprint") as u32;
+                if srgb {
+                    GL_ASTC_SRGB8_ALPHA8 + index
+                } else {
+                    GL_ASTC_RGBA + index
+                }
+            }
+            KtxFormat::Uncompressed { channels, srgb } => sized_format(channel_format(channels), srgb),
+        }
+    }
+
+    /// bytes of one image of `width` x `height` texels, with tightly packed rows
+    pub fn image_size(&self, width: u32, height: u32) -> usize {
+        let blocks = |block_width: u32, block_height: u32| {
+            width.div_ceil(block_width) as usize * height.div_ceil(block_height) as usize
+        };
+        match *self {
+            KtxFormat::Etc2 { format, .. } => blocks(4, 4) * format.block_size(),
+            KtxFormat::Astc {
+                block_width,
+                block_height,
+                ..
+            } => blocks(block_width, block_height) * 16,
+            KtxFormat::Uncompressed { channels, .. } => width as usize * height as usize * channels as usize,
+        }
+    }
+
+    // sized internal format, pixel format and type of the texels uploaded without
+    // compression; ETC2, EAC and ASTC blocks are decoded to 8-bit channels
+    fn uncompressed_format(&self) -> (u32, u32, u32) {
+        match *self {
+            KtxFormat::Etc2 { format, .. } if format.signed() => {
+                let internal_format = if format.channels() == 1 {
+                    GL_R8_SNORM
+                } else {
+                    GL_RG8_SNORM
+                };
+                (internal_format, channel_format(format.channels() as u32), GL_BYTE)
+            }
+            KtxFormat::Etc2 { format, srgb } => {
+                let pixel_format = channel_format(format.channels() as u32);
+                (sized_format(pixel_format, srgb), pixel_format, GL_UNSIGNED_BYTE)
+            }
+            KtxFormat::Uncompressed { channels, srgb } => {
+                let pixel_format = channel_format(channels);
+                (sized_format(pixel_format, srgb), pixel_format, GL_UNSIGNED_BYTE)
+            }
+            KtxFormat::Astc { srgb, .. } => (sized_format(GL_RGBA, srgb), GL_RGBA, GL_UNSIGNED_BYTE),
+        }
+    }
+}
+
+fn channel_format(channels: u32) -> u32 {
+    match channels {
+        1 => GL_RED,
+        2 => GL_RG,
+        3 => GL_RGB,
+        _ => GL_RGBA,
+    }
+}
+
+/// One mipmap level of a `KtxFile`.
+#[derive(Clone, Debug)]
+pub struct KtxLevel {
+    pub width: u32,
+    pub height: u32,
+    /// one image per face, +X, -X, +Y, -Y, +Z, -Z for cubemaps; rows are tightly packed
+    pub faces: Vec<Vec<u8>>,
+}
+
+/// A 2D texture or cubemap read from a KTX 1 or KTX 2 file, with its mipmap levels.
+///
+/// The images are kept in the file's order, top row first unless the tool that wrote
+/// the file was told otherwise; flip the texture coordinates rather than the data, blocks
+/// of compressed formats can't be flipped. Array, 3D and supercompressed (Basis
+/// Universal, zstd) files are rejected.
+///
+/// ```ignore
+/// let texture = KtxFile::open("resources/textures/container.ktx2")?.upload(&Sampling::default())?;
+/// texture.bind(0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KtxFile {
+    pub format: KtxFormat,
+    pub width: u32,
+    pub height: u32,
+    /// the base level first
+    pub levels: Vec<KtxLevel>,
+    /// the file has no mipmaps and asks for them to be generated when loading
+    pub generate_mipmaps: bool,
+}
+
+impl KtxFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<KtxFile, TextureError> {
+        let bytes = fs::read(path).map_err(TextureError::Io)?;
+        KtxFile::parse(&bytes)
+    }
+
+    /// parses a KTX 1 or KTX 2 file, told apart by their identifier
+    pub fn parse(bytes: &[u8]) -> Result<KtxFile, TextureError> {
+        if bytes.starts_with(&KTX1_IDENTIFIER) {
+            parse_ktx1(bytes)
+        } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)
+        } else {
+            Err(invalid("not a KTX file"))
+        }
+    }
+
+    /// 6 for cubemaps, otherwise 1
+    pub fn faces(&self) -> usize {
+        self.levels[0].faces.len()
+    }
+
+    pub fn is_cube_map(&self) -> bool {
+        self.faces() == 6
+    }
+
+    /// creates the texture, uploading compressed blocks where the context supports the
+    /// format. ETC2/EAC and ASTC are decoded on the CPU otherwise, ASTC to RGBA8.
+    ///
+    /// `sampling` is applied to the texture; cubemaps usually want `Wrap::ClampToEdge`.
+    pub fn upload(&self, sampling: &Sampling) -> Result<KtxTexture, TextureError> {
+        let capabilities = Capabilities::current();
+        let compressed = match self.format {
+            KtxFormat::Etc2 { .. } => capabilities.etc2_textures(),
+            KtxFormat::Astc { .. } => capabilities.astc_textures(),
+            KtxFormat::Uncompressed { .. } => false,
+        };
+        let (internal_format, pixel_format, pixel_type) = if compressed {
+            (self.format.gl_internal_format(), 0, 0)
+        } else {
+            self.format.uncompressed_format()
+        };
+        let target = if self.is_cube_map() {
+            GL_TEXTURE_CUBE_MAP
+        } else {
+            GL_TEXTURE_2D
+        };
+
+        let texture = GlTexture::new();
+        let mut size_bytes = 0;
+        gl::bind_texture(target, texture.id());
+        // uncompressed and decoded rows are tightly packed
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 1);
+        for (level, data) in self.levels.iter().enumerate() {
+            let (width, height) = (data.width as i32, data.height as i32);
+            for (face, image) in data.faces.iter().enumerate() {
+                let face_target = if self.is_cube_map() {
+                    GL_TEXTURE_CUBE_MAP_POSITIVE_X + face as u32
+                } else {
+                    GL_TEXTURE_2D
+                };
+                if compressed {
+                    gl::compressed_tex_image_2d(face_target, level as i32, internal_format, width, height, 0, image);
+                    size_bytes += image.len();
+                    continue;
+                }
+                let texels = match self.format {
+                    KtxFormat::Etc2 { format, .. } => Cow::Owned(decode_etc2(format, data.width, data.height, image)),
+                    KtxFormat::Astc {
+                        block_width,
+                        block_height,
+                        srgb,
+                    } => Cow::Owned(decode_astc(
+                        block_width,
+                        block_height,
+                        srgb,
+                        data.width,
+                        data.height,
+                        image,
+                    )),
+                    _ => Cow::Borrowed(image),
+                };
+                gl::tex_image_2d(
+                    face_target,
+                    level as i32,
+                    internal_format as i32,
+                    width,
+                    height,
+                    0,
+                    pixel_format,
+                    pixel_type,
+                    &texels,
+                );
+                size_bytes += texels.len();
+            }
+        }
+        gl::pixel_storei(GL_UNPACK_ALIGNMENT, 4);
+
+        let mut levels = self.levels.len() as u32;
+        if self.generate_mipmaps && !compressed {
+            gl::generate_mipmap(target);
+            levels = mip_levels(self.width, self.height);
+            size_bytes = size_bytes * 4 / 3;
+        } else if levels > 1 && capabilities.gles3_features() {
+            // the chain may stop before 1x1
+            gl::tex_parameteri(target, GL_TEXTURE_MAX_LEVEL, levels as i32 - 1);
+        }
+        sampling.apply(target, levels > 1);
+        gl::bind_texture(target, 0);
+
+        Ok(KtxTexture {
+            texture,
+            target,
+            width: self.width,
+            height: self.height,
+            internal_format,
+            levels,
+            compressed,
+            size_bytes,
+        })
+    }
+}
+
+/// reads a KTX 1 or KTX 2 file and creates its texture, see `KtxFile::upload`
+pub fn load_ktx<P: AsRef<Path>>(path: P, sampling: &Sampling) -> Result<KtxTexture, TextureError> {
+    KtxFile::open(path)?.upload(sampling)
+}
+
+/// A texture created from a `KtxFile`.
+#[derive(Debug)]
+pub struct KtxTexture {
+    texture: GlTexture,
+    /// `GL_TEXTURE_2D` or `GL_TEXTURE_CUBE_MAP`
+    pub target: u32,
+    pub width: u32,
+    pub height: u32,
+    /// compressed format, or the sized format the texels were decoded to
+    pub internal_format: u32,
+    pub levels: u32,
+    /// uploaded as compressed blocks; false if decoded on the CPU or not compressed at all
+    pub compressed: bool,
+    size_bytes: usize,
+}
+
+impl KtxTexture {
+    /// name of the GL texture
+    pub fn id(&self) -> u32 {
+        self.texture.id()
+    }
+
+    /// binds the texture to texture unit `unit`
+    pub fn bind(&self, unit: u32) {
+        gl::active_texture(GL_TEXTURE0 + unit);
+        gl::bind_texture(self.target, self.texture.id());
+    }
+
+    /// GPU memory of all levels and faces
+    pub fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+}
+
+fn invalid(reason: &str) -> TextureError {
+    TextureError::Ktx(reason.into())
+}
+
+// bounds checked reads of the header fields
+struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], TextureError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| invalid("truncated file"))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, TextureError> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Result<usize, TextureError> {
+        let bytes = self.bytes(offset, 8)?.try_into().unwrap();
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| invalid("offset out of range"))
+    }
+}
+
+fn padded4(size: usize) -> usize {
+    (size + 3) & !3
+}
+
+fn check_layout(depth: u32, layers: u32, faces: u32) -> Result<(), TextureError> {
+    if depth > 1 || layers > 1 {
+        return Err(TextureError::Format("3D and array textures are not supported".into()));
+    }
+    if faces != 1 && faces != 6 {
+        return Err(invalid("a texture has 1 or 6 faces"));
+    }
+    Ok(())
+}
+
+// the levels are halved in turn, so a file can't have more than the full mipmap chain
+fn check_level_count(width: u32, height: u32, level_count: u32) -> Result<(), TextureError> {
+    if level_count > mip_levels(width, height) {
+        return Err(invalid("more levels than the mipmap chain has"));
+    }
+    Ok(())
+}
+
+// KTX 1: a header of GL parameters, key/value data, then every level prefixed with its
+// size, each face and level padded to 4 bytes
+fn parse_ktx1(bytes: &[u8]) -> Result<KtxFile, TextureError> {
+    let big_endian = match (Reader {
+        bytes,
+        big_endian: false,
+    })
+    .u32(12)?
+    {
+        0x0403_0201 => false,
+        0x0102_0304 => true,
+        _ => return Err(invalid("bad endianness")),
+    };
+    let reader = Reader { bytes, big_endian };
+    let field = |index: usize| reader.u32(16 + index * 4);
+    let (gl_type, gl_format, gl_internal_format) = (field(0)?, field(2)?, field(3)?);
+    let (width, height) = (field(5)?, field(6)?.max(1));
+    let (depth, layers, faces, level_count) = (field(7)?, field(8)?, field(9)?, field(10)?);
+    let key_value_bytes = field(11)? as usize;
+    check_layout(depth, layers, faces)?;
+    check_level_count(width, height, level_count)?;
+
+    let format = if gl_type == 0 {
+        KtxFormat::from_gl_internal_format(gl_internal_format)
+    } else {
+        // GL_UNSIGNED_BYTE texels of GL_RED, GL_RG, GL_RGB or GL_RGBA, sRGB for
+        // GL_SRGB8 and GL_SRGB8_ALPHA8
+        let channels = match gl_format {
+            0x1903 => Some(1),
+            0x8227 => Some(2),
+            0x1907 => Some(3),
+            0x1908 => Some(4),
+            _ => None,
+        };
+        channels
+            .filter(|_| gl_type == 0x1401)
+            .map(|channels| KtxFormat::Uncompressed {
+                channels,
+                srgb: gl_internal_format == 0x8C41 || gl_internal_format == 0x8C43,
+            })
+    }
+    .ok_or_else(|| TextureError::Format(format!("unsupported KTX format 0x{:x}", gl_internal_format)))?;
+
+    let mut offset = 64 + key_value_bytes;
+    let mut levels = Vec::new();
+    for level in 0..level_count.max(1) {
+        let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+        // the size of one face for cubemaps, of the whole level otherwise
+        let image_size = reader.u32(offset)? as usize;
+        offset += 4;
+
+        // uncompressed rows are padded to 4 bytes, as GL_UNPACK_ALIGNMENT expects by default
+        let row_size = format.image_size(level_width, 1);
+        let padded_row_size = if format.is_compressed() {
+            row_size
+        } else {
+            padded4(row_size)
+        };
+        let rows = if format.is_compressed() {
+            1
+        } else {
+            level_height as usize
+        };
+        if image_size != format.image_size(level_width, level_height) + (padded_row_size - row_size) * rows {
+            return Err(invalid("image size doesn't match the format"));
+        }
+
+        let mut images = Vec::new();
+        for _ in 0..faces {
+            let data = reader.bytes(offset, image_size)?;
+            images.push(if padded_row_size == row_size {
+                data.to_vec()
+            } else {
+                data.chunks(padded_row_size)
+                    .flat_map(|row| &row[..row_size])
+                    .copied()
+                    .collect()
+            });
+            offset += padded4(image_size);
+        }
+        levels.push(KtxLevel {
+            width: level_width,
+            height: level_height,
+            faces: images,
+        });
+    }
+
+    Ok(KtxFile {
+        format,
+        width,
+        height,
+        levels,
+        generate_mipmaps: level_count == 0,
+    })
+}
+
+// KTX 2: a header with a Vulkan format, an index with the offset of every level, and
+// the faces of a level packed together
+fn parse_ktx2(bytes: &[u8]) -> Result<KtxFile, TextureError> {
+    let reader = Reader {
+        bytes,
+        big_endian: false,
+    };
+    let field = |index: usize| reader.u32(12 + index * 4);
+    let vk_format = field(0)?;
+    let (width, height) = (field(2)?, field(3)?.max(1));
+    let (depth, layers, faces, level_count) = (field(4)?, field(5)?, field(6)?, field(7)?);
+    if field(8)? != 0 {
+        return Err(TextureError::Format(
+            "supercompressed KTX 2 files are not supported".into(),
+        ));
+    }
+    check_layout(depth, layers, faces)?;
+    check_level_count(width, height, level_count)?;
+    let format = KtxFormat::from_vk_format(vk_format)
+        .ok_or_else(|| TextureError::Format(format!("unsupported KTX 2 format {}", vk_format)))?;
+
+    let mut levels = Vec::new();
+    for level in 0..level_count.max(1) {
+        let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+        // the level index follows the 80 byte header
+        let index = 80 + level as usize * 24;
+        let (offset, length) = (reader.u64(index)?, reader.u64(index + 8)?);
+        let image_size = format.image_size(level_width, level_height);
+        if length != image_size * faces as usize {
+            return Err(invalid("level size doesn't match the format"));
+        }
+        let data = reader.bytes(offset, length)?;
+        levels.push(KtxLevel {
+            width: level_width,
+            height: level_height,
+            faces: data.chunks(image_size).map(<[u8]>::to_vec).collect(),
+        });
+    }
+
+    Ok(KtxFile {
+        format,
+        width,
+        height,
+        levels,
+        generate_mipmaps: level_count == 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KtxFile, TextureError};
+
+    const KTX1_IDENTIFIER: [u8; 12] = [
+        0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+    ];
+    const KTX2_IDENTIFIER: [u8; 12] = [
+        0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+    ];
+
+    // an RGBA8 KTX 1 file whose levels are all filled with their index
+    fn ktx1(size: u32, level_count: u32, levels_present: u32) -> Vec<u8> {
+        let mut bytes = KTX1_IDENTIFIER.to_vec();
+        // endianness, GL_UNSIGNED_BYTE, type size, GL_RGBA, GL_RGBA8, GL_RGBA, size, depth,
+        // layers, faces, levels, key/value bytes
+        let header = [
+            0x0403_0201,
+            0x1401,
+            1,
+            0x1908,
+            0x8058,
+            0x1908,
+            size,
+            size,
+            0,
+            0,
+            1,
+            level_count,
+            0,
+        ];
+        bytes.extend(header.iter().flat_map(|field: &u32| field.to_le_bytes()));
+        for level in 0..levels_present {
+            let level_size = (size >> level).max(1);
+            let image_size = level_size * level_size * 4;
+            bytes.extend(image_size.to_le_bytes());
+            bytes.extend(std::iter::repeat_n(level as u8, image_size as usize));
+        }
+        bytes
+    }
+
+    fn is_invalid<T>(result: Result<T, TextureError>) -> bool {
+        matches!(result, Err(TextureError::Ktx(_)))
+    }
+
+    #[test]
+    fn full_mipmap_chain() {
+        let file = KtxFile::parse(&ktx1(4, 3, 3)).unwrap();
+        let sizes: Vec<_> = file.levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, [(4, 4), (2, 2), (1, 1)]);
+        assert_eq!(file.levels[2].faces, [vec![2; 4]]);
+        assert!(!file.generate_mipmaps);
+    }
+
+    #[test]
+    fn more_levels_than_the_mipmap_chain() {
+        assert!(is_invalid(KtxFile::parse(&ktx1(4, 4, 4))));
+        // used to overflow the shift computing the level size
+        assert!(is_invalid(KtxFile::parse(&ktx1(4, 40, 3))));
+
+        let mut ktx2 = KTX2_IDENTIFIER.to_vec();
+        // VK_FORMAT_R8G8B8A8_UNORM, type size, width, height, depth, layers, faces, levels,
+        // supercompression, then the rest of the 80 byte header
+        let header = [37u32, 1, 4, 4, 0, 0, 1, 33, 0];
+        ktx2.extend(header.iter().flat_map(|field| field.to_le_bytes()));
+        ktx2.resize(80, 0);
+        assert!(is_invalid(KtxFile::parse(&ktx2)));
+    }
+}
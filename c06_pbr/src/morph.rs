@@ -0,0 +1,270 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use dx::gles::{core30::gl, enums::*};
+use std::cell::{RefCell, RefMut};
+use std::mem::size_of;
+
+use super::capabilities::Capabilities;
+use super::gl_object::{GlBuffer, GlTexture};
+use super::mesh::{Vertex, MORPH_ATTRIBUTE_LOCATION};
+use super::shader::Shader;
+
+/// Maximum number of morph targets the GPU paths are written for (`uniform float morphWeights[MAX_MORPH_TARGETS]`).
+pub const MAX_MORPH_TARGETS: usize = 16;
+
+/// Maximum number of morph targets the vertex attribute path can hold: four position and
+/// four normal delta attributes from `MORPH_ATTRIBUTE_LOCATION` on, 13-20.
+pub const MAX_ATTRIBUTE_MORPH_TARGETS: usize = 4;
+
+/// Texture unit the morph target texture is bound to on the texture path.
+pub const MORPH_TEXTURE_UNIT: u32 = 15;
+
+/// Width of the morph target texture; deltas wrap into further rows.
+const MORPH_TEXTURE_WIDTH: usize = 2048;
+
+/// Per-vertex offsets of a blend shape, added to the base mesh scaled by the target's weight.
+/// Normal and tangent deltas may be left empty.
+#[derive(Clone, Debug, Default)]
+pub struct MorphTarget {
+    pub name: String,
+    pub position_deltas: Vec<Vector3<f32>>,
+    pub normal_deltas: Vec<Vector3<f32>>,
+    pub tangent_deltas: Vec<Vector3<f32>>,
+}
+
+/// How morph targets are applied when drawing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MorphPath {
+    /// deltas of up to `MAX_ATTRIBUTE_MORPH_TARGETS` targets as extra vertex attributes
+    /// (positions and normals only). They come after all other attributes, past the 16 every
+    /// GLES 3.0 context has, so the shader declaring them only links where this path is supported.
+    Attributes,
+    /// deltas of all targets in a float texture, fetched with `gl_VertexID` (GLES 3.0)
+    Texture,
+    /// blended on the CPU and re-uploaded to the vertex buffer; works on GLES 2.0
+    Cpu,
+}
+
+impl MorphPath {
+    /// picks the best path the current context supports for `target_count` targets
+    pub fn select(caps: &Capabilities, target_count: usize) -> MorphPath {
+        [MorphPath::Attributes, MorphPath::Texture]
+            .iter()
+            .copied()
+            .find(|path| path.is_supported(caps, target_count))
+            .unwrap_or(MorphPath::Cpu)
+    }
+
+    /// whether the current context can apply `target_count` targets with this path
+    pub fn is_supported(self, caps: &Capabilities, target_count: usize) -> bool {
+        let attributes_needed = MORPH_ATTRIBUTE_LOCATION as usize + 2 * MAX_ATTRIBUTE_MORPH_TARGETS;
+        match self {
+            MorphPath::Attributes => {
+                caps.gles3_features()
+                    && target_count <= MAX_ATTRIBUTE_MORPH_TARGETS
+                    && caps.max_vertex_attribs >= attributes_needed as i32
+            }
+            MorphPath::Texture => caps.gles3_features() && target_count <= MAX_MORPH_TARGETS,
+            MorphPath::Cpu => true,
+        }
+    }
+
+    /// value of the `morphPath` uniform: 0 when the shader has nothing to do (CPU path)
+    fn shader_mode(self) -> i32 {
+        match self {
+            MorphPath::Cpu => 0,
+            MorphPath::Attributes => 1,
+            MorphPath::Texture => 2,
+        }
+    }
+}
+
+/// Morph targets of a mesh together with the GL objects of the selected path.
+pub struct MorphTargets {
+    pub targets: Vec<MorphTarget>,
+    pub path: MorphPath,
+    buffer: Option<GlBuffer>,
+    texture: Option<GlTexture>,
+    // the vertices last blended on the CPU path, kept so draws don't allocate
+    blended: RefCell<Vec<Vertex>>,
+}
+
+impl MorphTargets {
+    /// uploads the deltas for `path`; `vao` is the mesh's vertex array, which gets the
+    /// extra attributes on the attribute path.
+    pub(crate) fn new(targets: Vec<MorphTarget>, path: MorphPath, vao: u32, vertex_count: usize) -> Self {
+        for target in &targets {
+            assert_eq!(
+                target.position_deltas.len(),
+                vertex_count,
+                "morph target {:?} vertex count",
+                target.name
+            );
+        }
+        let mut morph = MorphTargets {
+            targets,
+            path,
+            buffer: None,
+            texture: None,
+            blended: RefCell::new(Vec::new()),
+        };
+        match path {
+            MorphPath::Attributes => morph.setup_attributes(vao, vertex_count),
+            MorphPath::Texture => morph.setup_texture(vertex_count),
+            MorphPath::Cpu => {}
+        }
+        morph
+    }
+
+    /// number of targets, which is also the expected length of the weight slices
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    fn setup_attributes(&mut self, vao: u32, vertex_count: usize) {
+        assert!(
+            self.targets.len() <= MAX_ATTRIBUTE_MORPH_TARGETS,
+            "too many morph targets for the attribute path"
+        );
+
+        // per vertex: the position deltas of every target slot, then the normal deltas
+        let mut data: Vec<Vector3<f32>> = Vec::with_capacity(vertex_count * 2 * MAX_ATTRIBUTE_MORPH_TARGETS);
+        for idx in 0..vertex_count {
+            for slot in 0..MAX_ATTRIBUTE_MORPH_TARGETS {
+                data.push(
+                    self.targets
+                        .get(slot)
+                        .map_or(Vector3::zero(), |target| target.position_deltas[idx]),
+                );
+            }
+            for slot in 0..MAX_ATTRIBUTE_MORPH_TARGETS {
+                data.push(
+                    self.targets
+                        .get(slot)
+                        .map_or(Vector3::zero(), |target| delta(&target.normal_deltas, idx)),
+                );
+            }
+        }
+
+        let buffer = GlBuffer::new();
+        gl::bind_vertex_array(vao);
+        gl::bind_buffer(GL_ARRAY_BUFFER, buffer.id());
+        gl::buffer_data(GL_ARRAY_BUFFER, data.as_slice(), GL_STATIC_DRAW);
+
+        let vec3_size = size_of::<Vector3<f32>>();
+        let stride = (vec3_size * 2 * MAX_ATTRIBUTE_MORPH_TARGETS) as i32;
+        for slot in 0..2 * MAX_ATTRIBUTE_MORPH_TARGETS {
+            let location = MORPH_ATTRIBUTE_LOCATION + slot as u32;
+            gl::enable_vertex_attrib_array(location);
+            gl::vertex_attrib_pointer_offset(location, 3, GL_FLOAT, false, stride, (slot * vec3_size) as u32);
+        }
+        gl::bind_vertex_array(0);
+        self.buffer = Some(buffer);
+    }
+
+    fn setup_texture(&mut self, vertex_count: usize) {
+        // texel (target * 3 + attribute) * vertex_count + vertex, attribute 0/1/2 being
+        // the position/normal/tangent delta; see `morphDelta` in the morph target shader.
+        let texels = self.targets.len() * 3 * vertex_count;
+        let height = texels.div_ceil(MORPH_TEXTURE_WIDTH).max(1);
+        let mut data = vec![[0.0f32; 4]; MORPH_TEXTURE_WIDTH * height];
+        for (target_idx, target) in self.targets.iter().enumerate() {
+            let deltas = [&target.position_deltas, &target.normal_deltas, &target.tangent_deltas];
+            for (attribute, deltas) in deltas.iter().enumerate() {
+                let base = (target_idx * 3 + attribute) * vertex_count;
+                for idx in 0..vertex_count {
+                    let d = delta(deltas, idx);
+                    data[base + idx] = [d.x, d.y, d.z, 0.0];
+                }
+            }
+        }
+
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture.id());
+        gl::tex_image_2d(
+            GL_TEXTURE_2D,
+            0,
+            GL_RGBA32F as i32,
+            MORPH_TEXTURE_WIDTH as i32,
+            height as i32,
+            0,
+            GL_RGBA,
+            GL_FLOAT,
+            data.as_slice(),
+        );
+        // fetched with texelFetch, float textures are not filterable everywhere
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_NEAREST as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_NEAREST as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
+        gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
+        gl::bind_texture(GL_TEXTURE_2D, 0);
+        self.texture = Some(texture);
+    }
+
+    /// sets the uniforms of the GPU paths (`morphPath`, `morphWeights`, `morphTargetCount`,
+    /// and the `morphTargets` sampler and `morphVertexCount` on the texture path).
+    pub(crate) fn set_uniforms(&self, shader: &Shader, weights: &[f32], vertex_count: usize) {
+        let count = self.targets.len().min(MAX_MORPH_TARGETS);
+        let mut padded = [0.0f32; MAX_MORPH_TARGETS];
+        for (padded, weight) in padded.iter_mut().zip(weights.iter().take(count)) {
+            *padded = *weight;
+        }
+        shader.set_int("morphPath", self.path.shader_mode());
+        if self.path == MorphPath::Cpu {
+            return;
+        }
+        shader.set_float_array("morphWeights", &padded);
+        shader.set_int("morphTargetCount", count as i32);
+        if let Some(texture) = &self.texture {
+            gl::active_texture(GL_TEXTURE0 + MORPH_TEXTURE_UNIT);
+            gl::bind_texture(GL_TEXTURE_2D, texture.id());
+            shader.set_int("morphTargets", MORPH_TEXTURE_UNIT as i32);
+            shader.set_int("morphVertexCount", vertex_count as i32);
+            gl::active_texture(GL_TEXTURE0);
+        }
+    }
+
+    /// the base vertices with the weighted deltas of every target applied
+    pub fn blend(&self, base: &[Vertex], weights: &[f32]) -> Vec<Vertex> {
+        let mut vertices = Vec::with_capacity(base.len());
+        self.blend_into(base, weights, &mut vertices);
+        vertices
+    }
+
+    /// like `blend`, replacing the contents of `vertices`, which keeps its allocation
+    pub fn blend_into(&self, base: &[Vertex], weights: &[f32], vertices: &mut Vec<Vertex>) {
+        vertices.clear();
+        vertices.extend_from_slice(base);
+        for (target, &weight) in self.targets.iter().zip(weights) {
+            if weight == 0.0 {
+                continue;
+            }
+            for (idx, vertex) in vertices.iter_mut().enumerate() {
+                vertex.position += target.position_deltas[idx] * weight;
+                vertex.normal += delta(&target.normal_deltas, idx) * weight;
+                vertex.tangent += delta(&target.tangent_deltas, idx) * weight;
+            }
+        }
+        for vertex in vertices.iter_mut() {
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+
+    /// `blend_into` a buffer owned by the morph targets, for the CPU path's upload on every draw
+    pub(crate) fn blend_reused(&self, base: &[Vertex], weights: &[f32]) -> RefMut<'_, Vec<Vertex>> {
+        let mut vertices = self.blended.borrow_mut();
+        self.blend_into(base, weights, &mut vertices);
+        vertices
+    }
+}
+
+/// delta of vertex `idx`, zero if the target has no deltas for that attribute
+fn delta(deltas: &[Vector3<f32>], idx: usize) -> Vector3<f32> {
+    deltas.get(idx).cloned().unwrap_or_else(Vector3::zero)
+}
@@ -0,0 +1,314 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+use std::collections::HashMap;
+
+use super::mesh::Vertex;
+
+/// How vertex normals are generated for meshes that come without them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normals {
+    /// angle-weighted average of the adjacent face normals; faces meeting at an angle
+    /// larger than `crease_angle` (in degrees) keep a hard edge between them.
+    Smooth { crease_angle: f32 },
+    /// every triangle gets its own face normal
+    Flat,
+}
+
+impl Default for Normals {
+    fn default() -> Self {
+        Normals::Smooth { crease_angle: 60.0 }
+    }
+}
+
+/// Generates normals for an indexed triangle list.
+///
+/// Corners that share a position but end up with different normals (hard edges, flat
+/// shading) are split into separate vertices, so both `vertices` and `indices` may grow.
+/// Returns the old vertex every new one was copied from, to remap data kept outside the
+/// vertices (vertex colors, for instance).
+pub fn generate_normals(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, mode: Normals) -> Vec<u32> {
+    let num_triangles = indices.len() / 3;
+    indices.truncate(num_triangles * 3);
+
+    // face normals and the angle of every triangle corner
+    let mut face_normals = Vec::with_capacity(num_triangles);
+    let mut corner_angles = Vec::with_capacity(num_triangles * 3);
+    for triangle in indices.chunks(3) {
+        let p = [
+            vertices[triangle[0] as usize].position,
+            vertices[triangle[1] as usize].position,
+            vertices[triangle[2] as usize].position,
+        ];
+        let normal = (p[1] - p[0]).cross(p[2] - p[0]);
+        face_normals.push(if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::zero()
+        });
+        for k in 0..3 {
+            let a = p[(k + 1) % 3] - p[k];
+            let b = p[(k + 2) % 3] - p[k];
+            let angle = if a.magnitude2() > 0.0 && b.magnitude2() > 0.0 {
+                a.angle(b).0
+            } else {
+                0.0
+            };
+            corner_angles.push(angle);
+        }
+    }
+
+    // normal of every corner (triangle * 3 + k)
+    let corner_normals: Vec<Vector3<f32>> = match mode {
+        Normals::Flat => (0..num_triangles * 3).map(|corner| face_normals[corner / 3]).collect(),
+        Normals::Smooth { crease_angle } => {
+            let cos_crease = crease_angle.to_radians().cos();
+
+            // group corners by position rather than by vertex index: OBJ files split vertices
+            // along UV seams, which must not show up as hard edges in the shading.
+            let mut corners_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+            for (corner, &idx) in indices.iter().enumerate() {
+                corners_at
+                    .entry(position_key(&vertices[idx as usize]))
+                    .or_default()
+                    .push(corner);
+            }
+
+            indices
+                .iter()
+                .enumerate()
+                .map(|(corner, &idx)| {
+                    let face_normal = face_normals[corner / 3];
+                    let mut normal = Vector3::zero();
+                    for &other in &corners_at[&position_key(&vertices[idx as usize])] {
+                        let other_normal = face_normals[other / 3];
+                        if other / 3 == corner / 3 || face_normal.dot(other_normal) >= cos_crease {
+                            normal += other_normal * corner_angles[other];
+                        }
+                    }
+                    if normal.magnitude2() > 0.0 {
+                        normal.normalize()
+                    } else {
+                        face_normal
+                    }
+                })
+                .collect()
+        }
+    };
+
+    // write the normals back, splitting vertices that need more than one normal
+    let mut remap: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    let mut new_vertices = Vec::with_capacity(vertices.len());
+    let mut origins = Vec::with_capacity(vertices.len());
+    for (corner, idx) in indices.iter_mut().enumerate() {
+        let normal = corner_normals[corner];
+        let key = (*idx, vector_key(normal));
+        *idx = *remap.entry(key).or_insert_with(|| {
+            new_vertices.push(Vertex {
+                normal,
+                ..vertices[key.0 as usize]
+            });
+            origins.push(key.0);
+            (new_vertices.len() - 1) as u32
+        });
+    }
+    *vertices = new_vertices;
+    origins
+}
+
+/// Generates tangents and bitangents from the texture coordinates of an indexed triangle
+/// list, orthogonalized against the normals, which must already be set.
+pub fn generate_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![Vector3::zero(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [v0, v1, v2] = [triangle[0], triangle[1], triangle[2]].map(|idx| &vertices[idx as usize]);
+        let (edge1, edge2) = (v1.position - v0.position, v2.position - v0.position);
+        let (duv1, duv2) = (v1.tex_coords - v0.tex_coords, v2.tex_coords - v0.tex_coords);
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+        for &idx in triangle {
+            tangents[idx as usize] += tangent;
+            bitangents[idx as usize] += bitangent;
+        }
+    }
+
+    for ((vertex, tangent), bitangent) in vertices.iter_mut().zip(tangents).zip(bitangents) {
+        let normal = vertex.normal;
+        let mut tangent = tangent - normal * normal.dot(tangent);
+        if tangent.magnitude2() < 1e-12 {
+            // no usable texture coordinates: any direction perpendicular to the normal
+            let axis = if normal.x.abs() < 0.9 {
+                Vector3::unit_x()
+            } else {
+                Vector3::unit_y()
+            };
+            tangent = axis - normal * normal.dot(axis);
+        }
+        vertex.tangent = tangent.normalize();
+        // keep the handedness of the texture mapping (mirrored UVs)
+        vertex.bitangent = if normal.cross(vertex.tangent).dot(bitangent) < 0.0 {
+            vertex.tangent.cross(normal)
+        } else {
+            normal.cross(vertex.tangent)
+        };
+    }
+}
+
+fn position_key(vertex: &Vertex) -> [u32; 3] {
+    vector_key(vertex.position)
+}
+
+// bits of the components, with -0.0 turned into 0.0 so they compare equal
+fn vector_key(v: Vector3<f32>) -> [u32; 3] {
+    [(v.x + 0.0).to_bits(), (v.y + 0.0).to_bits(), (v.z + 0.0).to_bits()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::vec3;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: vec3(x, y, z),
+            ..Vertex::default()
+        }
+    }
+
+    /// unit cube with its 8 corners shared by all faces, counter-clockwise from outside
+    fn cube() -> (Vec<Vertex>, Vec<u32>) {
+        let position = |corner: u32| vec3((corner & 1) as f32, (corner >> 1 & 1) as f32, (corner >> 2 & 1) as f32);
+        let vertices: Vec<Vertex> = (0..8)
+            .map(|corner| {
+                let p = position(corner) - vec3(0.5, 0.5, 0.5);
+                vertex(p.x, p.y, p.z)
+            })
+            .collect();
+        let mut indices = Vec::new();
+        for axis in 0..3 {
+            for side in 0..2 {
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                let corner = |a: u32, b: u32| side << axis | a << u | b << v;
+                let [a, b, c, d] = [corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)];
+                let mut outward = Vector3::zero();
+                outward[axis as usize] = if side == 1 { 1.0 } else { -1.0 };
+                let normal = (position(b) - position(a)).cross(position(c) - position(a));
+                if normal.dot(outward) > 0.0 {
+                    indices.extend_from_slice(&[a, b, c, a, c, d]);
+                } else {
+                    indices.extend_from_slice(&[a, c, b, a, d, c]);
+                }
+            }
+        }
+        (vertices, indices)
+    }
+
+    /// the positions of every triangle corner, which generating normals must not change
+    fn corner_positions(vertices: &[Vertex], indices: &[u32]) -> Vec<Vector3<f32>> {
+        indices.iter().map(|&idx| vertices[idx as usize].position).collect()
+    }
+
+    fn assert_origins(old: &[Vertex], vertices: &[Vertex], origins: &[u32]) {
+        assert_eq!(origins.len(), vertices.len());
+        for (vertex, &origin) in vertices.iter().zip(origins) {
+            assert_eq!(vertex.position, old[origin as usize].position);
+        }
+    }
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn cube_with_crease() {
+        let (old, old_indices) = cube();
+        let (mut vertices, mut indices) = (old.clone(), old_indices.clone());
+        let origins = generate_normals(&mut vertices, &mut indices, Normals::Smooth { crease_angle: 30.0 });
+
+        // the faces meet at 90 degrees, so every corner is split into one vertex per face
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(
+            corner_positions(&vertices, &indices),
+            corner_positions(&old, &old_indices)
+        );
+        assert_origins(&old, &vertices, &origins);
+        for triangle in indices.chunks_exact(3) {
+            let p = corner_positions(&vertices, triangle);
+            let face_normal = (p[1] - p[0]).cross(p[2] - p[0]).normalize();
+            for &idx in triangle {
+                assert_near(vertices[idx as usize].normal, face_normal);
+            }
+        }
+        for corner in 0..8 {
+            assert_eq!(origins.iter().filter(|&&origin| origin == corner).count(), 3);
+        }
+    }
+
+    #[test]
+    fn cube_smooth() {
+        let (old, old_indices) = cube();
+        let (mut vertices, mut indices) = (old.clone(), old_indices.clone());
+        let origins = generate_normals(&mut vertices, &mut indices, Normals::Smooth { crease_angle: 100.0 });
+
+        // every face adds 90 degrees around each of its corners, so the normals point along
+        // the diagonals
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(
+            corner_positions(&vertices, &indices),
+            corner_positions(&old, &old_indices)
+        );
+        assert_origins(&old, &vertices, &origins);
+        for vertex in &vertices {
+            assert_near(vertex.normal, vertex.position.normalize());
+        }
+    }
+
+    #[test]
+    fn cube_flat() {
+        let (old, old_indices) = cube();
+        let (mut vertices, mut indices) = (old.clone(), old_indices.clone());
+        let origins = generate_normals(&mut vertices, &mut indices, Normals::Flat);
+
+        // both triangles of a face share its normal, so a face keeps 4 vertices
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(
+            corner_positions(&vertices, &indices),
+            corner_positions(&old, &old_indices)
+        );
+        assert_origins(&old, &vertices, &origins);
+        for vertex in &vertices {
+            let p = vertex.position;
+            let axis = (0..3).filter(|&axis| vertex.normal[axis] != 0.0).collect::<Vec<_>>();
+            assert_eq!(axis.len(), 1);
+            assert_eq!(vertex.normal[axis[0]], p[axis[0]] * 2.0);
+        }
+    }
+
+    #[test]
+    fn flat_quad() {
+        // in the XZ plane facing +Y, with a UV seam: vertex 4 is vertex 0 again
+        let old = vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(1.0, 0.0, -1.0),
+            vertex(0.0, 0.0, -1.0),
+            vertex(0.0, 0.0, 0.0),
+        ];
+        let old_indices = vec![0, 1, 2, 4, 2, 3];
+        for &mode in &[Normals::Flat, Normals::Smooth { crease_angle: 30.0 }] {
+            let (mut vertices, mut indices) = (old.clone(), old_indices.clone());
+            let origins = generate_normals(&mut vertices, &mut indices, mode);
+            // nothing to split, not even on normals of -0.0 and 0.0; the vertices keep their order
+            assert_eq!(origins, [0, 1, 2, 4, 3]);
+            assert_eq!(indices, [0, 1, 2, 3, 2, 4]);
+            assert_origins(&old, &vertices, &origins);
+            for vertex in &vertices {
+                assert_near(vertex.normal, Vector3::unit_y());
+            }
+        }
+    }
+}
@@ -13,13 +13,13 @@ void main()
 {
    // compute vertex normal from height map
    float hxl = textureOffset( s_texture,
-                  a_position.xy, ivec2(-1,  0) ).w;
+                  a_position.xy, ivec2(-1,  0) ).r;
    float hxr = textureOffset( s_texture,
-                  a_position.xy, ivec2( 1,  0) ).w;
+                  a_position.xy, ivec2( 1,  0) ).r;
    float hyl = textureOffset( s_texture,
-                  a_position.xy, ivec2( 0, -1) ).w;
+                  a_position.xy, ivec2( 0, -1) ).r;
    float hyr = textureOffset( s_texture,
-                  a_position.xy, ivec2( 0,  1) ).w;
+                  a_position.xy, ivec2( 0,  1) ).r;
    vec3 u = normalize( vec3(0.05, 0.0, hxr-hxl) );
    vec3 v = normalize( vec3(0.0, 0.05, hyr-hyl) );
    vec3 normal = cross( u, v );
@@ -29,7 +29,7 @@ void main()
    v_color = vec4( vec3(diffuse), 1.0 );
 
    // get vertex position from height map
-   float h = texture ( s_texture, a_position.xy ).w;
+   float h = texture ( s_texture, a_position.xy ).r;
    vec4 v_position = vec4 ( a_position.xy,
                             h/2.5,          
                             a_position.w );
@@ -0,0 +1,31 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 6) in uvec4 aJoints;
+layout (location = 7) in vec4 aWeights;
+
+const int MAX_JOINTS = 64;
+
+out vec3 FragPos;
+out vec3 Normal;
+out vec2 TexCoords;
+
+uniform mat4 joints[MAX_JOINTS];
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+    mat4 skin = aWeights.x * joints[aJoints.x]
+              + aWeights.y * joints[aJoints.y]
+              + aWeights.z * joints[aJoints.z]
+              + aWeights.w * joints[aJoints.w];
+    mat4 skinnedModel = model * skin;
+
+    FragPos = vec3(skinnedModel * vec4(aPos, 1.0));
+    Normal = mat3(transpose(inverse(skinnedModel))) * aNormal;
+    TexCoords = aTexCoords;
+    gl_Position = projection * view * vec4(FragPos, 1.0);
+}
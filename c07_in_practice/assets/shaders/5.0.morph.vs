@@ -0,0 +1,47 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+
+const int MAX_MORPH_TARGETS = 16;
+const int MORPH_TEXTURE_WIDTH = 2048;
+
+out vec3 FragPos;
+out vec3 Normal;
+
+// 0: nothing to do (blended on the CPU), 2: texture; the attribute path (1) has its own
+// shader, 5.0.morph_attributes.vs
+uniform int morphPath;
+uniform int morphTargetCount;
+uniform float morphWeights[MAX_MORPH_TARGETS];
+uniform sampler2D morphTargets;
+uniform int morphVertexCount;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+// attribute 0: position delta, 1: normal delta, 2: tangent delta
+vec3 morphDelta(int target, int attribute)
+{
+    int texel = (target * 3 + attribute) * morphVertexCount + gl_VertexID;
+    return texelFetch(morphTargets, ivec2(texel % MORPH_TEXTURE_WIDTH, texel / MORPH_TEXTURE_WIDTH), 0).xyz;
+}
+
+void main()
+{
+    vec3 pos = aPos;
+    vec3 normal = aNormal;
+    if (morphPath == 2)
+    {
+        for (int i = 0; i < morphTargetCount; ++i)
+        {
+            pos += morphWeights[i] * morphDelta(i, 0);
+            normal += morphWeights[i] * morphDelta(i, 1);
+        }
+    }
+
+    FragPos = vec3(model * vec4(pos, 1.0));
+    Normal = mat3(transpose(inverse(model))) * normal;
+    gl_Position = projection * view * vec4(FragPos, 1.0);
+}
@@ -0,0 +1,37 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+// position deltas of four targets, then their normal deltas; these come after the instance
+// attributes (8-12), so the shader only links where the context has 21 attributes
+layout (location = 13) in vec3 aMorphPos0;
+layout (location = 14) in vec3 aMorphPos1;
+layout (location = 15) in vec3 aMorphPos2;
+layout (location = 16) in vec3 aMorphPos3;
+layout (location = 17) in vec3 aMorphNormal0;
+layout (location = 18) in vec3 aMorphNormal1;
+layout (location = 19) in vec3 aMorphNormal2;
+layout (location = 20) in vec3 aMorphNormal3;
+
+const int MAX_MORPH_TARGETS = 16;
+
+out vec3 FragPos;
+out vec3 Normal;
+
+uniform float morphWeights[MAX_MORPH_TARGETS];
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+    vec3 pos = aPos + morphWeights[0] * aMorphPos0 + morphWeights[1] * aMorphPos1
+             + morphWeights[2] * aMorphPos2 + morphWeights[3] * aMorphPos3;
+    vec3 normal = aNormal + morphWeights[0] * aMorphNormal0 + morphWeights[1] * aMorphNormal1
+                + morphWeights[2] * aMorphNormal2 + morphWeights[3] * aMorphNormal3;
+
+    FragPos = vec3(model * vec4(pos, 1.0));
+    Normal = mat3(transpose(inverse(model))) * normal;
+    gl_Position = projection * view * vec4(FragPos, 1.0);
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
-use c07_in_practice::Shader;
+use c07_in_practice::{run_event_loop, GlBuffer, GlTexture, GlVertexArray, Shader};
 use cgmath::prelude::*;
 use cgmath::{perspective, vec3, Deg, Matrix4, Rad};
 use dx::{
@@ -121,7 +121,7 @@ pub fn main() {
     // tell to capture our mouse
     // window.set_cursor_mode(CursorMode::Disabled);
 
-    let (shader, cubevao, texture) = {
+    let (shader, cubevao, texture, cubevbo) = {
         // enable OpenGL debug context if context allows for debug context
         let flags = gl::get_integerv(GL_CONTEXT_FLAGS);
         if flags as u32 & GL_CONTEXT_FLAG_DEBUG_BIT != 0 {
@@ -191,13 +191,13 @@ pub fn main() {
             -0.5, 0.5, 0.5, 0.0, 0.0, // bottom-left
         ];
 
-        let cubevao = gl::gen_vertex_array();
-        let cubevbo = gl::gen_buffer();
+        let cubevao = GlVertexArray::new();
+        let cubevbo = GlBuffer::new();
         // fill buffer
-        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, cubevbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, &vertices, GL_STATIC_DRAW);
         // link vertex attributes
-        gl::bind_vertex_array(cubevao);
+        gl::bind_vertex_array(cubevao.id());
         gl::enable_vertex_attrib_array(0);
         let stride = 5 * mem::size_of::<f32>() as i32;
         gl::vertex_attrib_pointer_offset(0, 3, GL_FLOAT, false, stride, 0);
@@ -207,8 +207,8 @@ pub fn main() {
         gl::bind_vertex_array(0);
 
         // load cube texture
-        let texture = gl::gen_texture();
-        gl::bind_texture(GL_TEXTURE_2D, texture);
+        let texture = GlTexture::new();
+        gl::bind_texture(GL_TEXTURE_2D, texture.id());
         let img = image::open(assets!("textures/wood.png")).expect("Failed to load texture");
         let data = img.as_bytes();
         gl::tex_image_2d(
@@ -236,11 +236,11 @@ pub fn main() {
 
         glCheckError!();
 
-        (shader, cubevao, texture)
+        (shader, cubevao, texture, cubevbo)
     };
 
     // render loop
-    event_loop.run(move |event, _, control_flow| match event {
+    run_event_loop(event_loop, cubevbo, move |event, _, control_flow| match event {
         Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
             WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::KeyboardInput { input, .. } => match input {
@@ -274,8 +274,8 @@ pub fn main() {
             model = model * Matrix4::from_axis_angle(vec3(1.0, 1.0, 1.0).normalize(), Rad(angle));
             shader.set_mat4("model", &model);
 
-            gl::bind_texture(GL_TEXTURE_2D, texture);
-            gl::bind_vertex_array(cubevao);
+            gl::bind_texture(GL_TEXTURE_2D, texture.id());
+            gl::bind_vertex_array(cubevao.id());
             gl::draw_arrays(GL_TRIANGLES, 0, 36);
             gl::bind_vertex_array(0);
 
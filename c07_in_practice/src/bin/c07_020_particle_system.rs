@@ -4,6 +4,7 @@
 
 #![allow(dead_code)]
 #![allow(unused_variables)]
+use c07_in_practice::{GlBuffer, GlContext, GlProgram, TeardownGuard, Texture2D, Wrap};
 use cgmath::Point3;
 use dx::{
     app::{runner::Runner, Application, Key, Store, Time},
@@ -11,7 +12,6 @@ use dx::{
     gles::{core20::gl, enums::*, utils},
     utils::get_time,
 };
-use image::GenericImageView;
 use rand::random;
 use spin_sleep::LoopHelper;
 use std::{mem, time::SystemTime};
@@ -19,28 +19,6 @@ use std::{mem, time::SystemTime};
 const NUM_PARTICLES: usize = 1000;
 const PARTICLE_SIZE: i32 = 7;
 
-// Load texture from disk
-fn load_texture(filename: &str) -> u32 {
-    let img = image::open(assets!(filename)).expect("Failed to load texture");
-    let width = img.width() as i32;
-    let height = img.height() as i32;
-
-    let data = img.as_bytes();
-
-    let tex_id = gl::gen_texture();
-    gl::bind_texture(GL_TEXTURE_2D, tex_id);
-
-    println!("tex {} {} {}", tex_id, width, height);
-    gl::tex_image_2d(GL_TEXTURE_2D, 0, GL_RGB as i32, width, height, 0, GL_RGB, GL_UNSIGNED_BYTE, &data);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
-
-    return tex_id;
-}
-
 #[repr(C)]
 struct Particle {
     lifetime: f32,
@@ -49,12 +27,12 @@ struct Particle {
 }
 
 struct App {
-    vbo: u32,
+    vbo: GlBuffer,
 
     loop_helper: LoopHelper,
 
     // Handle to a program object
-    program_id: u32,
+    program: GlProgram,
 
     // Attribute locations
     lifetime_loc: i32,
@@ -68,7 +46,7 @@ struct App {
     sampler_loc: i32,
 
     // Texture handle
-    texture_id: u32,
+    texture: Texture2D,
 
     // Particle vertex data
     particles: Vec<Particle>,
@@ -77,6 +55,9 @@ struct App {
     time: f32,
 
     start_time: SystemTime,
+
+    // dropped after the GL objects above, reports the ones that leaked
+    teardown: TeardownGuard,
 }
 
 impl App {
@@ -135,7 +116,8 @@ impl Application<Runner> for App {
             utils::shader_from_file(assets!("shaders/2.0.particle.vs").to_str().unwrap(), GL_VERTEX_SHADER).unwrap();
         let fragment_shader =
             utils::shader_from_file(assets!("shaders/2.0.particle.fs").to_str().unwrap(), GL_FRAGMENT_SHADER).unwrap();
-        let program_id = utils::program_from_shaders(vertex_shader, fragment_shader).unwrap();
+        let program = GlProgram::from_raw(utils::program_from_shaders(vertex_shader, fragment_shader).unwrap());
+        let program_id = program.id();
 
         // Get the attribute locations
         let lifetime_loc = gl::get_attrib_location(program_id, "a_lifetime");
@@ -188,13 +170,17 @@ impl Application<Runner> for App {
         // Initialize time to cause reset on first update
         let time = 1.0;
 
-        let texture_id = load_texture("textures/smoke.tga");
+        let texture = Texture2D::builder()
+            .wrap(Wrap::ClampToEdge)
+            .mipmaps(false)
+            .load(assets!("textures/smoke.tga"))
+            .expect("Failed to load texture");
 
         // int VBO
-        let vbo = gl::gen_buffer();
+        let vbo = GlBuffer::new();
         glchk!("gen_buffer");
 
-        gl::bind_buffer(GL_ARRAY_BUFFER, vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, vbo.id());
         glchk!("bind_buffer");
 
         gl::buffer_data(GL_ARRAY_BUFFER, particles.as_slice(), GL_DYNAMIC_DRAW);
@@ -205,7 +191,7 @@ impl Application<Runner> for App {
         Ok(App {
             vbo,
 
-            program_id,
+            program,
 
             loop_helper,
 
@@ -216,10 +202,11 @@ impl Application<Runner> for App {
             center_position_loc,
             color_loc,
             sampler_loc,
-            texture_id,
+            texture,
             time,
             particles,
             start_time,
+            teardown: GlContext::current().teardown_guard(),
         })
     }
 
@@ -234,14 +221,14 @@ impl Application<Runner> for App {
         glchk!("clear");
 
         // use the program object
-        gl::use_program(self.program_id);
+        gl::use_program(self.program.id());
         glchk!("use_program");
 
         let current_frame = get_time(&self.start_time) as f32;
         self.update(0.01);
 
         // bind VBO
-        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, self.vbo.id());
         glchk!("bind_buffer");
 
         let fsize = mem::size_of::<f32>() as i32;
@@ -290,7 +277,7 @@ impl Application<Runner> for App {
         gl::active_texture(GL_TEXTURE0);
         glchk!("active_texture");
 
-        gl::bind_texture(GL_TEXTURE_2D, self.texture_id);
+        gl::bind_texture(GL_TEXTURE_2D, self.texture.id());
         glchk!("bind_texture");
 
         // Set the sampler texture unit to 0
@@ -312,17 +299,6 @@ impl Application<Runner> for App {
     }
 }
 
-impl Drop for App {
-    // cleanup
-    fn drop(&mut self) {
-        // Delete texture object
-        gl::delete_textures(&[self.texture_id]);
-
-        // Delete program object
-        gl::delete_program(self.program_id);
-    }
-}
-
 fn main() {
     env_logger::init();
     Runner::run::<App>("ParticleSystem", 640, 480, ());
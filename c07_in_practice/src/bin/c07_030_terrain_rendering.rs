@@ -4,6 +4,7 @@
 
 #![allow(dead_code)]
 #![allow(unused_variables)]
+use c07_in_practice::{Geometry, GlBuffer, GlContext, GlProgram, TeardownGuard, Texture2D, Wrap};
 use cgmath::prelude::*;
 use cgmath::{Deg, Matrix4, Point3, Vector3};
 use dx::{
@@ -11,50 +12,11 @@ use dx::{
     assets, color, glchk,
     gles::{core20::gl, enums::*, utils},
 };
-use image::GenericImageView;
 use spin_sleep::LoopHelper;
 use std::{mem, time::SystemTime};
 
 const POSITION_LOC: usize = 0;
 
-/// \brief Generates a square grid consisting of triangles.  
-///        Allocates memory for the vertex data and stores
-///        the results in the arrays.  Generate index list as TRIANGLES.
-/// @size create a grid of size by size (number of triangles = (size-1)*(size-1)*2)
-///
-/// Return value: (vertices, indices) vertices, will contain array of float3 positions,
-/// indices, will contain the array of indices for the triangle strip
-fn gen_square_grid(size: u32) -> (Vec<Point3<f32>>, Vec<u32>) {
-    let num_indices = (size - 1) * (size - 1) * 2 * 3;
-
-    let num_vertices = size * size;
-    let step_size = size as f32 - 1_f32;
-
-    let mut vertices = Vec::with_capacity(3 * num_vertices as usize);
-
-    for row in 0..size {
-        for column in 0..size {
-            vertices.push(Point3::new(row as f32 / step_size, column as f32 / step_size, 0_f32));
-        }
-    }
-
-    // Generate the indices
-    let mut indices: Vec<u32> = Vec::with_capacity(num_indices as usize);
-    for row in 0..size - 1 {
-        for column in 0..size - 1 {
-            // two triangles per quad
-            indices.push(column + row * size);
-            indices.push(column + row * size + 1);
-            indices.push(column + (row + 1) * size + 1);
-            indices.push(column + row * size);
-            indices.push(column + (row + 1) * size + 1);
-            indices.push(column + (row + 1) * size);
-        }
-    }
-
-    return (vertices, indices);
-}
-
 // Initialize the MVP matrix
 fn init_mvp() -> Matrix4<f32> {
     let width = 640;
@@ -90,27 +52,6 @@ fn init_mvp() -> Matrix4<f32> {
 const NUM_PARTICLES: usize = 1000;
 const PARTICLE_SIZE: i32 = 7;
 
-// Load texture from disk
-fn load_texture(filename: &str) -> u32 {
-    let img = image::open(assets!(filename)).expect("Failed to load texture");
-    let width = img.width() as i32;
-    let height = img.height() as i32;
-
-    let data = img.as_bytes();
-
-    let tex_id = gl::gen_texture();
-    gl::bind_texture(GL_TEXTURE_2D, tex_id);
-
-    gl::tex_image_2d(GL_TEXTURE_2D, 0, GL_ALPHA as i32, width, height, 0, GL_ALPHA, GL_UNSIGNED_BYTE, &data);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
-
-    return tex_id;
-}
-
 #[repr(C)]
 struct Particle {
     lifetime: f32,
@@ -120,13 +61,13 @@ struct Particle {
 
 struct App {
     // VBOs
-    indices_ibo: u32,
-    position_vbo: u32,
+    indices_ibo: GlBuffer,
+    position_vbo: GlBuffer,
 
     loop_helper: LoopHelper,
 
     // Handle to a program object
-    program_id: u32,
+    program: GlProgram,
 
     // Attribute locations
     // we do not store position location coz we use layout scheme in GLSL
@@ -139,7 +80,7 @@ struct App {
     sampler_loc: i32,
 
     // Texture handle
-    texture_id: u32,
+    texture: Texture2D,
 
     // MVP matrix
     mvp_matrix: Matrix4<f32>,
@@ -148,6 +89,9 @@ struct App {
     num_indices: u32,
 
     start_time: SystemTime,
+
+    // dropped after the GL objects above, reports the ones that leaked
+    teardown: TeardownGuard,
 }
 
 impl App {}
@@ -174,7 +118,8 @@ impl Application<Runner> for App {
             utils::shader_from_file(assets!("shaders/3.0.terrain.vs").to_str().unwrap(), GL_VERTEX_SHADER).unwrap();
         let fragment_shader =
             utils::shader_from_file(assets!("shaders/3.0.terrain.fs").to_str().unwrap(), GL_FRAGMENT_SHADER).unwrap();
-        let program_id = utils::program_from_shaders(vertex_shader, fragment_shader).unwrap();
+        let program = GlProgram::from_raw(utils::program_from_shaders(vertex_shader, fragment_shader).unwrap());
+        let program_id = program.id();
 
         // Get the uniform locations
         let mvp_loc = gl::get_uniform_location(program_id, "u_mvpMatrix");
@@ -183,23 +128,34 @@ impl Application<Runner> for App {
         // Get the sampler location
         let sampler_loc = gl::get_uniform_location(program_id, "s_texture");
 
-        // Generate the position and indices of a square grid for the base terrain
+        // Generate the position and indices of a square grid for the base terrain; the shader
+        // samples the height map at the xy of the positions, so they go from 0 to 1
         let grid_size = 200;
-        let (positions, indices) = gen_square_grid(grid_size);
+        let grid = Geometry::grid(1.0, 1.0, grid_size - 1, grid_size - 1);
+        let positions: Vec<Point3<f32>> = grid
+            .vertices
+            .iter()
+            .map(|vertex| Point3::new(vertex.tex_coords.y, vertex.tex_coords.x, 0.0))
+            .collect();
+        let indices = grid.indices;
 
         // Index buffer for base terrain
-        let indices_ibo = gl::gen_buffer();
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, indices_ibo);
+        let indices_ibo = GlBuffer::new();
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, indices_ibo.id());
         gl::buffer_data(GL_ELEMENT_ARRAY_BUFFER, indices.as_slice(), GL_STATIC_DRAW);
         gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, 0);
 
         // Position VBO for base terrain
-        let position_vbo = gl::gen_buffer();
-        gl::bind_buffer(GL_ARRAY_BUFFER, position_vbo);
+        let position_vbo = GlBuffer::new();
+        gl::bind_buffer(GL_ARRAY_BUFFER, position_vbo.id());
         gl::buffer_data(GL_ARRAY_BUFFER, positions.as_slice(), GL_STATIC_DRAW);
 
         // Load the heightmap
-        let texture_id = load_texture("textures/heightmap.tga");
+        let texture = Texture2D::builder()
+            .wrap(Wrap::ClampToEdge)
+            .mipmaps(false)
+            .load(assets!("textures/heightmap.tga"))
+            .expect("Failed to load texture");
 
         let loop_helper = spin_sleep::LoopHelper::builder().build_with_target_rate(250.0);
 
@@ -210,11 +166,12 @@ impl Application<Runner> for App {
             light_direction_loc,
             sampler_loc,
             num_indices: indices.len() as u32,
-            program_id,
+            program,
             loop_helper,
-            texture_id,
+            texture,
             mvp_matrix: Matrix4::identity(),
             start_time,
+            teardown: GlContext::current().teardown_guard(),
         })
     }
 
@@ -229,22 +186,22 @@ impl Application<Runner> for App {
         glchk!("clear");
 
         // use the program object
-        gl::use_program(self.program_id);
+        gl::use_program(self.program.id());
         glchk!("use_program");
 
         self.mvp_matrix = init_mvp();
 
         // Load the vertex position
-        gl::bind_buffer(GL_ARRAY_BUFFER, self.position_vbo);
+        gl::bind_buffer(GL_ARRAY_BUFFER, self.position_vbo.id());
         gl::vertex_attrib_pointer_offset(POSITION_LOC as u32, 3, GL_FLOAT, false, 3 * mem::size_of::<f32>() as i32, 0);
         gl::enable_vertex_attrib_array(POSITION_LOC as u32);
 
         // Bind the index buffer
-        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, self.indices_ibo);
+        gl::bind_buffer(GL_ELEMENT_ARRAY_BUFFER, self.indices_ibo.id());
 
         // Bind the height map
         gl::active_texture(GL_TEXTURE0);
-        gl::bind_texture(GL_TEXTURE_2D, self.texture_id);
+        gl::bind_texture(GL_TEXTURE_2D, self.texture.id());
 
         // Load the MVP matrix
         let proj_view: &[f32; 16] = self.mvp_matrix.as_ref();
@@ -274,20 +231,6 @@ impl Application<Runner> for App {
     }
 }
 
-impl Drop for App {
-    // cleanup
-    fn drop(&mut self) {
-        // Delete VBO & IBO
-        gl::delete_buffers(&[self.indices_ibo, self.position_vbo]);
-
-        // Delete texture object
-        gl::delete_textures(&[self.texture_id]);
-
-        // Delete program object
-        gl::delete_program(self.program_id);
-    }
-}
-
 fn main() {
     env_logger::init();
     Runner::run::<App>("TerrainRendering", 640, 480, ());
@@ -0,0 +1,329 @@
+// Skeletal animation with GPU skinning: the nanosuit model is bound to a humanoid rig and
+// plays looping clips. Press SPACE to cross-fade to the next clip.
+
+use c07_in_practice::{
+    process_events, process_input, run_event_loop, AnimationClip, Animator, Camera, Channel, Interpolation, Joint, Mesh,
+    Model, Shader, Skeleton, SkinnedVertex, Track, Transform, MAX_JOINT_INFLUENCES,
+};
+use cgmath::prelude::*;
+use cgmath::{perspective, vec3, vec4, Deg, Matrix4, Point3, Quaternion, Vector3};
+use dx::{
+    assets,
+    gles::{core30::gl, enums::*},
+    utils::get_time,
+    Instance,
+};
+use std::{f32::consts::PI, time::SystemTime};
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// settings
+const SCR_WIDTH: u32 = 800;
+const SCR_HEIGHT: u32 = 600;
+
+// name, parent, and the start and end of the bone in the bind pose (model space)
+type Bone = (&'static str, Option<usize>, [f32; 3], [f32; 3]);
+
+// joints of the rig fitted to the nanosuit; the model faces +Z, its left side is +X
+#[rustfmt::skip]
+const BONES: [Bone; 17] = [
+    ("hips",        None,     [0.0, 8.8, 0.0],     [0.0, 10.6, 0.0]),
+    ("spine",       Some(0),  [0.0, 10.6, 0.0],    [0.0, 12.0, -0.2]),
+    ("chest",       Some(1),  [0.0, 12.0, -0.2],   [0.0, 12.9, -0.2]),
+    ("neck",        Some(2),  [0.0, 12.9, -0.2],   [0.0, 13.5, -0.2]),
+    ("head",        Some(3),  [0.0, 13.5, -0.2],   [0.0, 15.4, -0.2]),
+    ("upper_arm.L", Some(2),  [1.8, 12.2, -0.3],   [2.9, 10.1, -0.4]),
+    ("forearm.L",   Some(5),  [2.9, 10.1, -0.4],   [3.6, 9.0, 1.1]),
+    ("hand.L",      Some(6),  [3.6, 9.0, 1.1],     [3.6, 7.3, 1.2]),
+    ("upper_arm.R", Some(2),  [-1.8, 12.2, -0.3],  [-2.9, 10.1, -0.4]),
+    ("forearm.R",   Some(8),  [-2.9, 10.1, -0.4],  [-3.6, 9.0, 1.1]),
+    ("hand.R",      Some(9),  [-3.6, 9.0, 1.1],    [-3.6, 7.3, 1.2]),
+    ("thigh.L",     Some(0),  [0.85, 8.6, 0.0],    [1.35, 4.5, -0.1]),
+    ("shin.L",      Some(11), [1.35, 4.5, -0.1],   [1.6, 1.2, -0.5]),
+    ("foot.L",      Some(12), [1.6, 1.2, -0.5],    [1.7, 0.2, 1.0]),
+    ("thigh.R",     Some(0),  [-0.85, 8.6, 0.0],   [-1.35, 4.5, -0.1]),
+    ("shin.R",      Some(14), [-1.35, 4.5, -0.1],  [-1.6, 1.2, -0.5]),
+    ("foot.R",      Some(15), [-1.6, 1.2, -0.5],   [-1.7, 0.2, 1.0]),
+];
+
+fn joint_index(name: &str) -> usize {
+    BONES.iter().position(|bone| bone.0 == name).unwrap()
+}
+
+/// the rig: every joint sits at the start of its bone, unrotated in the bind pose
+fn build_skeleton() -> Skeleton {
+    let joints = BONES
+        .iter()
+        .map(|&(name, parent, head, _)| {
+            let head = Vector3::from(head);
+            let parent_head = parent.map_or(Vector3::zero(), |parent| Vector3::from(BONES[parent].2));
+            Joint {
+                name: name.into(),
+                parent,
+                inverse_bind: Matrix4::from_translation(-head),
+                rest: Transform::from_translation(head - parent_head),
+            }
+        })
+        .collect();
+    Skeleton::new(joints)
+}
+
+/// distance from `point` to the segment from `a` to `b`
+fn distance_to_bone(point: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>) -> f32 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.magnitude2()).clamp(0.0, 1.0);
+    (a + ab * t).distance(point)
+}
+
+/// binds every vertex to the two nearest bones, weighted by inverse distance, so the surface
+/// bends smoothly where two bones meet
+fn skin_mesh(mesh: &Mesh) -> Mesh<SkinnedVertex> {
+    let vertices = mesh
+        .vertices
+        .iter()
+        .map(|vertex| {
+            let mut nearest: Vec<(f32, usize)> = BONES
+                .iter()
+                .enumerate()
+                .map(|(idx, &(_, _, head, tail))| (distance_to_bone(vertex.position, head.into(), tail.into()), idx))
+                .collect();
+            nearest.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            let mut joints = [0; MAX_JOINT_INFLUENCES];
+            let mut weights = [0.0; MAX_JOINT_INFLUENCES];
+            for (slot, &(distance, joint)) in nearest.iter().take(2).enumerate() {
+                joints[slot] = joint as u32;
+                weights[slot] = 1.0 / distance.max(1e-3).powi(4);
+            }
+            let total: f32 = weights.iter().sum();
+            let weights = vec4(weights[0], weights[1], weights[2], weights[3]) / total;
+            SkinnedVertex::new(vertex, joints, weights)
+        })
+        .collect();
+    Mesh::new(vertices, mesh.indices.clone(), mesh.material.clone())
+}
+
+/// rotation track around `axis`: `offset` plus a sine wave of `amplitude` degrees, sampled at
+/// `keys` keyframes over `duration` seconds
+fn wave_track(
+    axis: [f32; 3],
+    offset: f32,
+    amplitude: f32,
+    phase: f32,
+    duration: f32,
+    keys: usize,
+    interpolation: Interpolation,
+) -> Track<Quaternion<f32>> {
+    let axis = Vector3::from(axis).normalize();
+    let times: Vec<f32> = (0..=keys).map(|key| key as f32 / keys as f32 * duration).collect();
+    let angle = |time: f32| offset + (time / duration * 2.0 * PI + phase).sin() * amplitude;
+    let values = times
+        .iter()
+        .flat_map(|&time| {
+            let value = Quaternion::from_axis_angle(axis, Deg(angle(time)));
+            if interpolation == Interpolation::CubicSpline {
+                // zero tangents: ease in and out of every keyframe
+                let zero = Quaternion::new(0.0, 0.0, 0.0, 0.0);
+                vec![zero, value, zero]
+            } else {
+                vec![value]
+            }
+        })
+        .collect();
+    Track::new(times, values, interpolation)
+}
+
+/// rotation track around `axis` holding each angle (in degrees) for `hold` seconds
+fn step_track(axis: [f32; 3], angles: &[f32], hold: f32) -> Track<Quaternion<f32>> {
+    let axis = Vector3::from(axis).normalize();
+    let times = (0..angles.len()).map(|key| key as f32 * hold).collect();
+    let values = angles
+        .iter()
+        .map(|&angle| Quaternion::from_axis_angle(axis, Deg(angle)))
+        .collect();
+    Track::new(times, values, Interpolation::Step)
+}
+
+fn rotation(joint: &str, track: Track<Quaternion<f32>>) -> Channel {
+    Channel {
+        joint: joint_index(joint),
+        rotation: Some(track),
+        ..Channel::default()
+    }
+}
+
+fn build_clips() -> Vec<AnimationClip> {
+    const X: [f32; 3] = [1.0, 0.0, 0.0];
+    const Y: [f32; 3] = [0.0, 1.0, 0.0];
+    const Z: [f32; 3] = [0.0, 0.0, 1.0];
+    use Interpolation::{CubicSpline, Linear};
+
+    // breathing and swaying arms with eased keyframes, the head glancing around in steps
+    let idle = AnimationClip::new(
+        "idle",
+        vec![
+            rotation("chest", wave_track(X, 0.0, 3.0, 0.0, 4.0, 4, CubicSpline)),
+            rotation("upper_arm.L", wave_track(Z, 0.0, 4.0, 0.0, 4.0, 4, CubicSpline)),
+            rotation("upper_arm.R", wave_track(Z, 0.0, -4.0, 0.0, 4.0, 4, CubicSpline)),
+            rotation("head", step_track(Y, &[0.0, 25.0, 0.0, -25.0, 0.0], 0.8)),
+        ],
+    );
+
+    // legs and arms swinging in opposite phase, knees bending on the way forward
+    let period = 1.2;
+    let walk = AnimationClip::new(
+        "walk",
+        vec![
+            rotation("thigh.L", wave_track(X, -5.0, 25.0, 0.0, period, 12, Linear)),
+            rotation("thigh.R", wave_track(X, -5.0, 25.0, PI, period, 12, Linear)),
+            rotation("shin.L", wave_track(X, 20.0, 20.0, PI / 2.0, period, 12, Linear)),
+            rotation("shin.R", wave_track(X, 20.0, 20.0, PI * 1.5, period, 12, Linear)),
+            rotation("upper_arm.L", wave_track(X, 0.0, 20.0, PI, period, 12, Linear)),
+            rotation("upper_arm.R", wave_track(X, 0.0, 20.0, 0.0, period, 12, Linear)),
+            rotation("spine", wave_track(Y, 0.0, 5.0, 0.0, period, 12, Linear)),
+        ],
+    );
+
+    // right arm raised, the forearm waving
+    let wave = AnimationClip::new(
+        "wave",
+        vec![
+            rotation("upper_arm.R", step_track(Z, &[-120.0], 1.0)),
+            rotation("forearm.R", wave_track(Z, -20.0, 25.0, 0.0, 1.0, 8, Linear)),
+            rotation("head", step_track(Z, &[-5.0], 1.0)),
+        ],
+    );
+
+    vec![idle, walk, wave]
+}
+
+pub fn main() {
+    let start_time = SystemTime::now();
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().build(&event_loop).unwrap();
+
+    let mut camera = Camera {
+        position: Point3::new(0.0, 0.0, 4.0),
+        ..Camera::default()
+    };
+
+    let mut first_mouse = true;
+    let mut last_x: f32 = SCR_WIDTH as f32 / 2.0;
+    let mut last_y: f32 = SCR_HEIGHT as f32 / 2.0;
+
+    // timing
+    let mut last_frame: f32 = 0.0;
+    let mut last_update: f32 = 0.0;
+
+    // initialize and configure
+    let instance = Instance::new();
+    let surface = instance.create_surface(&window);
+    let adapter = instance.request_adapter();
+    let (device, _) = adapter.request_device();
+    let desc = dx::SwapChainDescriptor {
+        usage: dx::TextureUsage::RENDER_ATTACHMENT,
+        format: dx::TextureFormat::Bgra8UnormSrgb,
+        width: 800,
+        height: 600,
+        present_mode: dx::PresentMode::Fifo,
+    };
+    let swapchain = device.create_swap_chain(&surface, &desc);
+
+    let (shader, meshes, skeleton, mut animator) = {
+        // configure global opengl state
+
+        gl::enable(GL_DEPTH_TEST);
+
+        // build and compile shaders
+        let shader = Shader::new(assets!("shaders/4.0.skinning.vs"), assets!("shaders/4.0.skinning.fs"));
+
+        // load the character, bind its meshes to the rig and build its animations; the
+        // materials (and their textures) are shared with the static meshes, dropped here
+        let mut model = Model::new(assets!("objects/nanosuit/nanosuit.obj")).expect("Failed to load model");
+        let skeleton = build_skeleton();
+        let meshes: Vec<Mesh<SkinnedVertex>> = std::mem::take(&mut model.meshes).iter().map(skin_mesh).collect();
+        let animator = Animator::new(&skeleton, build_clips());
+
+        (shader, meshes, skeleton, animator)
+    };
+
+    // render loop
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
+        Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::KeyboardInput { input, .. } => match input {
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Space),
+                    ..
+                } => {
+                    let next = (animator.current_clip() + 1) % animator.clips.len();
+                    println!("playing {}", animator.clips[next].name);
+                    animator.play(next, 0.5);
+                }
+                _ => {
+                    let current_frame = get_time(&start_time);
+
+                    // input
+                    process_input(&input, current_frame - last_frame, &mut camera);
+                    last_frame = current_frame;
+                }
+            },
+            WindowEvent::Resized(physical_size) => {
+                // make sure the viewport matches the new window dimensions; note that width and
+                // height will be significantly larger than specified on retina displays.
+                gl::viewport(0, 0, physical_size.width as i32, physical_size.height as i32);
+            }
+            _ => {
+                // events
+                process_events(&event, &mut first_mouse, &mut last_x, &mut last_y, &mut camera);
+            }
+        },
+        Event::MainEventsCleared => {
+            // per-frame time logic
+            let current_frame = get_time(&start_time);
+            animator.update(&skeleton, current_frame - last_update);
+            last_update = current_frame;
+
+            gl::clear_color(0.1, 0.1, 0.1, 1.0);
+            gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
+
+            shader.use_program();
+            shader.set_vec3("lightPos", 2.0, 3.0, 4.0);
+            shader.set_vector3("viewPos", &camera.position.to_vec());
+
+            // view/projection transformations
+            let projection: Matrix4<f32> =
+                perspective(Deg(camera.zoom), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
+            let view = camera.get_view_matrix();
+            shader.set_mat4("projection", &projection);
+            shader.set_mat4("view", &view);
+            let mut model = Matrix4::<f32>::from_translation(vec3(0.0, -1.75, 0.0)); // translate it down so it's at the center of the scene
+            model = model * Matrix4::from_scale(0.2); // it's a bit too big for our scene, so scale it down
+            shader.set_mat4("model", &model);
+
+            // upload the joint matrices of the current pose and draw the skinned meshes
+            animator.set_uniforms(&shader);
+            for mesh in &meshes {
+                mesh.draw(&shader);
+            }
+
+            // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
+            swapchain.present(&surface);
+            // request redraw again
+            window.request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            // redraw here when something changed
+        }
+        _ => {}
+    });
+}
@@ -0,0 +1,317 @@
+// Morph target (blend shape) animation: a sphere with three blend shapes whose weights
+// are driven by looping clips. The left instance plays the clips, the right one uses
+// weights toggled with the 1-3 keys. Press SPACE to cross-fade to the next clip and P to
+// switch between the vertex attribute, texture and CPU paths. The attribute path needs more
+// vertex attributes than OpenGL ES 3.0 promises and a shader of its own, so it is skipped
+// where the context doesn't have them.
+
+use c07_in_practice::{
+    process_events, process_input, run_event_loop, AnimationClip, Animator, Camera, Capabilities, Interpolation, Mesh,
+    MorphChannel, MorphPath, MorphTarget, Shader, Skeleton, Track, Vertex,
+};
+use cgmath::prelude::*;
+use cgmath::{perspective, vec2, vec3, Deg, Matrix4, Point3, Vector3};
+use dx::{
+    assets,
+    gles::{core30::gl, enums::*},
+    utils::get_time,
+    Instance,
+};
+use std::{f32::consts::PI, rc::Rc, time::SystemTime};
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+// settings
+const SCR_WIDTH: u32 = 800;
+const SCR_HEIGHT: u32 = 600;
+
+const RINGS: usize = 32;
+const SECTORS: usize = 48;
+
+/// unit sphere positions; the normals of a unit sphere are its positions
+fn sphere_points() -> Vec<Vector3<f32>> {
+    let mut points = Vec::new();
+    for ring in 0..=RINGS {
+        let theta = ring as f32 / RINGS as f32 * PI;
+        for sector in 0..=SECTORS {
+            let phi = sector as f32 / SECTORS as f32 * 2.0 * PI;
+            points.push(vec3(phi.cos() * theta.sin(), theta.cos(), phi.sin() * theta.sin()));
+        }
+    }
+    points
+}
+
+fn build_sphere() -> Mesh {
+    let vertices = sphere_points()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, point)| Vertex {
+            position: point,
+            normal: point,
+            tex_coords: vec2(
+                (idx % (SECTORS + 1)) as f32 / SECTORS as f32,
+                (idx / (SECTORS + 1)) as f32 / RINGS as f32,
+            ),
+            ..Vertex::default()
+        })
+        .collect();
+
+    let mut indices = Vec::new();
+    let stride = SECTORS as u32 + 1;
+    for ring in 0..RINGS as u32 {
+        for sector in 0..SECTORS as u32 {
+            let current = ring * stride + sector;
+            let next = current + stride;
+            indices.extend_from_slice(&[current, current + 1, next, current + 1, next + 1, next]);
+        }
+    }
+    Mesh::new(vertices, indices, Rc::default())
+}
+
+/// a blend shape moving every point of the unit sphere to `shape(point)`; the normal
+/// deltas are approximated by the direction change of the displaced point.
+fn build_target<F>(name: &str, shape: F) -> MorphTarget
+where
+    F: Fn(Vector3<f32>) -> Vector3<f32>,
+{
+    let points = sphere_points();
+    let moved: Vec<Vector3<f32>> = points.iter().map(|&point| shape(point)).collect();
+    MorphTarget {
+        name: name.into(),
+        position_deltas: points
+            .iter()
+            .zip(&moved)
+            .map(|(&point, &moved)| moved - point)
+            .collect(),
+        normal_deltas: points
+            .iter()
+            .zip(&moved)
+            .map(|(&point, &moved)| {
+                let normal = if moved.magnitude2() > 0.0 {
+                    moved.normalize()
+                } else {
+                    point
+                };
+                normal - point
+            })
+            .collect(),
+        tangent_deltas: Vec::new(),
+    }
+}
+
+fn build_targets() -> Vec<MorphTarget> {
+    vec![
+        build_target("stretch", |p| vec3(p.x * 0.6, p.y * 1.6, p.z * 0.6)),
+        build_target("bulge", |p| p * (1.0 + 0.25 * (p.x * 6.0).sin() * (p.z * 6.0).cos())),
+        build_target("squash", |p| vec3(p.x * 1.4, p.y * 0.5, p.z * 1.4)),
+    ]
+}
+
+/// a looping weight track rising from 0 to 1 and back, starting at `phase` seconds
+fn pulse_track(phase: f32, interpolation: Interpolation) -> Track<f32> {
+    let duration = 3.0;
+    let keys = 12;
+    let times: Vec<f32> = (0..=keys).map(|key| key as f32 / keys as f32 * duration).collect();
+    let values = times
+        .iter()
+        .map(|&time| 0.5 - 0.5 * ((time + phase) / duration * 2.0 * PI).cos())
+        .collect();
+    Track::new(times, values, interpolation)
+}
+
+fn build_clips() -> Vec<AnimationClip> {
+    let clip = |name: &str, targets: &[usize], interpolation: Interpolation| {
+        let channels = targets
+            .iter()
+            .enumerate()
+            .map(|(idx, &target)| MorphChannel {
+                target,
+                track: pulse_track(idx as f32 * 1.5, interpolation),
+            })
+            .collect();
+        AnimationClip::new(name, Vec::new()).with_morph_channels(channels)
+    };
+    vec![
+        clip("breathe", &[0, 2], Interpolation::Linear),
+        clip("wobble", &[1], Interpolation::Linear),
+        clip("pop", &[0, 1, 2], Interpolation::Step),
+    ]
+}
+
+pub fn main() {
+    let start_time = SystemTime::now();
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().build(&event_loop).unwrap();
+
+    let mut camera = Camera {
+        position: Point3::new(0.0, 0.0, 6.0),
+        ..Camera::default()
+    };
+
+    let mut first_mouse = true;
+    let mut last_x: f32 = SCR_WIDTH as f32 / 2.0;
+    let mut last_y: f32 = SCR_HEIGHT as f32 / 2.0;
+
+    // timing
+    let mut last_frame: f32 = 0.0;
+    let mut last_update: f32 = 0.0;
+
+    // weights of the right instance
+    let mut manual_weights = vec![0.0; 3];
+
+    // initialize and configure
+    let instance = Instance::new();
+    let surface = instance.create_surface(&window);
+    let adapter = instance.request_adapter();
+    let (device, _) = adapter.request_device();
+    let desc = dx::SwapChainDescriptor {
+        usage: dx::TextureUsage::RENDER_ATTACHMENT,
+        format: dx::TextureFormat::Bgra8UnormSrgb,
+        width: 800,
+        height: 600,
+        present_mode: dx::PresentMode::Fifo,
+    };
+    let swapchain = device.create_swap_chain(&surface, &desc);
+
+    let (shader, attribute_shader, mut sphere, skeleton, mut animator) = {
+        // configure global opengl state
+
+        gl::enable(GL_DEPTH_TEST);
+
+        // build and compile shaders
+        let shader = Shader::new(assets!("shaders/5.0.morph.vs"), assets!("shaders/5.0.morph.fs"));
+        let attribute_shader = if MorphPath::Attributes.is_supported(&Capabilities::current(), build_targets().len()) {
+            Some(Shader::new(
+                assets!("shaders/5.0.morph_attributes.vs"),
+                assets!("shaders/5.0.morph.fs"),
+            ))
+        } else {
+            None
+        };
+
+        // build the mesh with its blend shapes; the path depends on what the context supports
+        let mut sphere = build_sphere();
+        sphere.set_morph_targets(build_targets());
+        println!("morph target path: {:?}", sphere.morph_targets.as_ref().unwrap().path);
+
+        // morph-only animation: the skeleton has no joints
+        let skeleton = Skeleton::new(Vec::new());
+        let animator = Animator::new(&skeleton, build_clips());
+
+        (shader, attribute_shader, sphere, skeleton, animator)
+    };
+
+    // render loop
+    run_event_loop(event_loop, (), move |event, _, control_flow| match event {
+        Event::WindowEvent { ref event, window_id } if window_id == window.id() => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::KeyboardInput { input, .. } => match input {
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Space),
+                    ..
+                } => {
+                    let next = (animator.current_clip() + 1) % animator.clips.len();
+                    println!("playing {}", animator.clips[next].name);
+                    animator.play(next, 0.5);
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::P),
+                    ..
+                } => {
+                    let mut path = sphere.morph_targets.as_ref().unwrap().path;
+                    // skip the paths the context doesn't support; the CPU path always works
+                    loop {
+                        path = match path {
+                            MorphPath::Attributes => MorphPath::Texture,
+                            MorphPath::Texture => MorphPath::Cpu,
+                            MorphPath::Cpu => MorphPath::Attributes,
+                        };
+                        if path.is_supported(&Capabilities::current(), build_targets().len()) {
+                            break;
+                        }
+                    }
+                    println!("morph target path: {:?}", path);
+                    sphere.set_morph_targets_with_path(build_targets(), path);
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key @ (VirtualKeyCode::Key1 | VirtualKeyCode::Key2 | VirtualKeyCode::Key3)),
+                    ..
+                } => {
+                    let target = match key {
+                        VirtualKeyCode::Key1 => 0,
+                        VirtualKeyCode::Key2 => 1,
+                        _ => 2,
+                    };
+                    manual_weights[target] = 1.0 - manual_weights[target];
+                }
+                _ => {
+                    let current_frame = get_time(&start_time);
+
+                    // input
+                    process_input(&input, current_frame - last_frame, &mut camera);
+                    last_frame = current_frame;
+                }
+            },
+            WindowEvent::Resized(physical_size) => {
+                // make sure the viewport matches the new window dimensions; note that width and
+                // height will be significantly larger than specified on retina displays.
+                gl::viewport(0, 0, physical_size.width as i32, physical_size.height as i32);
+            }
+            _ => {
+                // events
+                process_events(&event, &mut first_mouse, &mut last_x, &mut last_y, &mut camera);
+            }
+        },
+        Event::MainEventsCleared => {
+            // per-frame time logic
+            let current_frame = get_time(&start_time);
+            animator.update(&skeleton, current_frame - last_update);
+            last_update = current_frame;
+
+            gl::clear_color(0.1, 0.1, 0.1, 1.0);
+            gl::clear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
+
+            let shader = match (sphere.morph_targets.as_ref().unwrap().path, &attribute_shader) {
+                (MorphPath::Attributes, Some(attribute_shader)) => attribute_shader,
+                _ => &shader,
+            };
+            shader.use_program();
+            shader.set_vec3("objectColor", 0.4, 0.7, 0.9);
+            shader.set_vec3("lightPos", 2.0, 4.0, 4.0);
+            shader.set_vector3("viewPos", &camera.position.to_vec());
+
+            // view/projection transformations
+            let projection: Matrix4<f32> =
+                perspective(Deg(camera.zoom), SCR_WIDTH as f32 / SCR_HEIGHT as f32, 0.1, 100.0);
+            let view = camera.get_view_matrix();
+            shader.set_mat4("projection", &projection);
+            shader.set_mat4("view", &view);
+
+            // the same mesh drawn twice with different weights
+            shader.set_mat4("model", &Matrix4::from_translation(vec3(-1.5, 0.0, 0.0)));
+            sphere.draw_with_weights(shader, animator.morph_weights());
+            shader.set_mat4("model", &Matrix4::from_translation(vec3(1.5, 0.0, 0.0)));
+            sphere.draw_with_weights(shader, &manual_weights);
+
+            // swap buffers and poll IO events (keys pressed/released, mouse moved etc.)
+            swapchain.present(&surface);
+            // request redraw again
+            window.request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            // redraw here when something changed
+        }
+        _ => {}
+    });
+}
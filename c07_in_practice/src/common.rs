@@ -1,12 +1,14 @@
 use std::path::PathBuf;
 
 /// Common code that the original tutorials repeat over and over and over and over
-use dx::gles::{core30::gl, enums::*};
-use image::{DynamicImage::*, GenericImageView};
+use dx::gles::core30::gl;
 use winit::event::*;
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
 
 use super::camera::Camera;
 use super::camera::Camera_Movement::*;
+use super::gl_object::GlContext;
+use super::texture::Texture2D;
 
 /// Event processing function as introduced in 1.7.4 (Camera Class) and used in
 /// most later tutorials
@@ -85,43 +87,34 @@ pub fn process_input(input: &KeyboardInput, delta_time: f32, camera: &mut Camera
     }
 }
 
-/// utility function for loading a 2D texture from file
-
-#[allow(dead_code)]
-pub fn load_texture<T>(path: T) -> u32
+/// utility function for loading a 2D texture from file, with the tutorials' usual settings:
+/// repeating, trilinear filtering and mipmaps. Non-power-of-two images are resized where the
+/// context can't mipmap them, see `NpotFallback`. See `Texture2D::builder` for anything else.
+pub fn load_texture<T>(path: T) -> Texture2D
 where
     T: Into<PathBuf>,
 {
-    let texture_id = gl::gen_texture();
-    let path = path.into();
-    let img = image::open(&path).expect("Texture failed to load");
-
-    let format = match img {
-        ImageLuma8(_) => GL_RED,
-        ImageLumaA8(_) => GL_RG,
-        ImageRgb8(_) => GL_RGB,
-        ImageRgba8(_) => GL_RGBA,
-        _ => panic!("unhandled image format"),
-    };
-
-    gl::bind_texture(GL_TEXTURE_2D, texture_id);
-    gl::tex_image_2d(
-        GL_TEXTURE_2D,
-        0,
-        format as i32,
-        img.width() as i32,
-        img.height() as i32,
-        0,
-        format,
-        GL_UNSIGNED_BYTE,
-        img.as_bytes(),
-    );
-    gl::generate_mipmap(GL_TEXTURE_2D);
-
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR_MIPMAP_LINEAR as i32);
-    gl::tex_parameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
+    Texture2D::builder().load(path.into()).expect("Texture failed to load")
+}
 
-    texture_id
+/// render loop of the tutorials, `EventLoop::run` with the GL context torn down at the end:
+/// once the loop is destroyed, `handler` with everything it captured is dropped, then
+/// `keep_alive` (objects the loop doesn't use but has to keep, like the vertex buffers of
+/// its vertex arrays), then the context, reporting the GL objects still alive as leaks.
+pub fn run_event_loop<T, F>(event_loop: EventLoop<()>, keep_alive: T, handler: F) -> !
+where
+    T: 'static,
+    F: 'static + FnMut(Event<'_, ()>, &EventLoopWindowTarget<()>, &mut ControlFlow),
+{
+    // tuple fields drop in order
+    let mut state = Some((handler, keep_alive, GlContext::current().teardown_guard()));
+    event_loop.run(move |event, target, control_flow| {
+        let destroyed = matches!(event, Event::LoopDestroyed);
+        if let Some((handler, ..)) = &mut state {
+            handler(event, target, control_flow);
+        }
+        if destroyed {
+            state = None;
+        }
+    })
 }
@@ -0,0 +1,498 @@
+use cgmath::{Point3, Vector4};
+use memmap2::Mmap;
+use std::{
+    convert::TryInto,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io,
+    mem::size_of,
+    path::{Path, PathBuf},
+    ptr, slice,
+};
+use vertex_format::VertexFormat;
+
+use super::bounds::{Aabb, BoundingSphere};
+use super::lod::Lod;
+use super::mesh::Vertex;
+use super::model::{MeshData, ModelData, ModelOptions};
+use super::optimize::OptimizeReport;
+
+// A compiled model: the `ModelData` of a model file, after normals, optimization and LODs.
+//
+// header, 32 bytes:
+//   magic "LOGLMDL\0", format version u32, vertex layout hash u32, options hash u32,
+//   CRC-32 of the payload u32, payload length u64
+// payload:
+//   dependencies (paths relative to the model), materials, optimize reports, meshes
+//   (material, bounds, vertices, indices, colors if any, LODs)
+//
+// Numbers are little-endian. Arrays are a u32 length followed by the elements, starting
+// at a multiple of 16 bytes from the start of the file, so a mapped file can be used in
+// place; vertices are stored as the `Vertex` struct.
+
+const MAGIC: [u8; 8] = *b"LOGLMDL\0";
+const HEADER_SIZE: usize = 32;
+const ALIGNMENT: usize = 16;
+
+/// Version of the compiled model format; caches of other versions are ignored.
+pub const MODEL_CACHE_VERSION: u32 = 3;
+
+/// Reasons a compiled model can't be used.
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    /// not a compiled model, or a truncated one
+    Format(&'static str),
+    /// written by another version of the format or for another `Vertex` layout
+    Version,
+    /// compiled with different `ModelOptions`
+    Options,
+    /// the payload doesn't match its checksum
+    Checksum,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Io(err) => write!(f, "{}", err),
+            CacheError::Format(reason) => write!(f, "invalid compiled model: {}", reason),
+            CacheError::Version => write!(f, "compiled by another version"),
+            CacheError::Options => write!(f, "compiled with other options"),
+            CacheError::Checksum => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+impl Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(err: io::Error) -> Self {
+        CacheError::Io(err)
+    }
+}
+
+/// where the compiled version of a model file goes: next to it, `.cmodel` appended
+pub fn model_cache_path(source: &Path) -> PathBuf {
+    let mut name = source.file_name().unwrap_or_default().to_os_string();
+    name.push(".cmodel");
+    source.with_file_name(name)
+}
+
+/// true if the cache exists and is at least as new as the source; a cache without its
+/// source is used as is. `ModelData::load` checks the `dependencies` of the model the same way.
+pub fn is_cache_fresh(source: &Path, cache: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(source), modified(cache)) {
+        (Some(source), Some(cache)) => cache >= source,
+        (None, Some(_)) => true,
+        _ => false,
+    }
+}
+
+// the options changing what gets cached; textures are loaded from their own files
+fn options_hash(options: &ModelOptions) -> u32 {
+    let key = format!("{:?} {:?} {:?}", options.normals, options.lods, options.optimize);
+    crc32fast::hash(key.as_bytes())
+}
+
+fn layout_hash() -> u32 {
+    let key = format!("{} {:?}", size_of::<Vertex>(), Vertex::ATTRIBUTES);
+    crc32fast::hash(key.as_bytes())
+}
+
+/// Types stored as raw bytes.
+///
+/// # Safety
+///
+/// The type has no padding and any bit pattern is a valid value.
+unsafe trait Pod: Copy {}
+
+unsafe impl Pod for u32 {}
+// only f32 and u32 fields, all 4-byte aligned
+unsafe impl Pod for Vertex {}
+unsafe impl Pod for Vector4<f32> {}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for &value in values {
+            self.f32(value);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn array<T: Pod>(&mut self, values: &[T]) {
+        self.u32(values.len() as u32);
+        // the header is a multiple of the alignment, so aligning in the payload is enough
+        self.bytes.resize(self.bytes.len().next_multiple_of(ALIGNMENT), 0);
+        let bytes = unsafe { slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values)) };
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CacheError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or(CacheError::Format("unexpected end of data"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, CacheError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CacheError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, CacheError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], CacheError> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = self.f32()?;
+        }
+        Ok(values)
+    }
+
+    fn string(&mut self) -> Result<String, CacheError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| CacheError::Format("invalid string"))
+    }
+
+    fn array<T: Pod>(&mut self) -> Result<Vec<T>, CacheError> {
+        let len = self.u32()? as usize;
+        self.pos = self.pos.next_multiple_of(ALIGNMENT);
+        let bytes = self.take(
+            len.checked_mul(size_of::<T>())
+                .ok_or(CacheError::Format("array too large"))?,
+        )?;
+        let mut values: Vec<T> = Vec::with_capacity(len);
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), values.as_mut_ptr() as *mut u8, bytes.len());
+            values.set_len(len);
+        }
+        Ok(values)
+    }
+}
+
+/// Writes a compiled model. The file is written next to `path` first and then renamed, so
+/// a reader never sees half a file.
+pub fn write_model_cache(data: &ModelData, options: &ModelOptions, path: &Path) -> Result<(), CacheError> {
+    let mut payload = Writer::default();
+
+    payload.u32(data.dependencies.len() as u32);
+    for dependency in &data.dependencies {
+        let relative = dependency.strip_prefix(&data.directory).unwrap_or(dependency);
+        payload.string(&relative.to_string_lossy());
+    }
+
+    payload.u32(data.materials.len() as u32);
+    for material in &data.materials {
+        payload.string(&material.name);
+        payload.f32s(&material.ambient);
+        payload.f32s(&material.diffuse);
+        payload.f32s(&material.specular);
+        payload.f32s(&[material.shininess, material.dissolve, material.optical_density]);
+        for texture in [
+            &material.ambient_texture,
+            &material.diffuse_texture,
+            &material.specular_texture,
+            &material.normal_texture,
+            &material.shininess_texture,
+            &material.dissolve_texture,
+        ] {
+            payload.string(texture);
+        }
+        payload.u32(material.illumination_model.map_or(u32::MAX, u32::from));
+        // sorted, so compiling the same model twice gives the same file
+        let mut params: Vec<_> = material.unknown_param.iter().collect();
+        params.sort();
+        payload.u32(params.len() as u32);
+        for (key, value) in params {
+            payload.string(key);
+            payload.string(value);
+        }
+    }
+
+    payload.u32(data.optimize_reports.len() as u32);
+    for report in &data.optimize_reports {
+        payload.u64(report.vertices as u64);
+        payload.u64(report.triangles as u64);
+        payload.f32s(&[
+            report.acmr_before,
+            report.acmr_after,
+            report.atvr_before,
+            report.atvr_after,
+        ]);
+    }
+
+    payload.u32(data.meshes.len() as u32);
+    for mesh in &data.meshes {
+        payload.u32(mesh.material.map_or(u32::MAX, |id| id as u32));
+        let (aabb, sphere) = (&mesh.aabb, &mesh.bounding_sphere);
+        payload.f32s(&[aabb.min.x, aabb.min.y, aabb.min.z, aabb.max.x, aabb.max.y, aabb.max.z]);
+        payload.f32s(&[sphere.center.x, sphere.center.y, sphere.center.z, sphere.radius]);
+        payload.array(&mesh.vertices);
+        payload.array(&mesh.indices);
+        match &mesh.colors {
+            Some(colors) => {
+                payload.u32(1);
+                payload.array(colors);
+            }
+            None => payload.u32(0),
+        }
+        payload.u32(mesh.lods.len() as u32);
+        for lod in &mesh.lods {
+            payload.f32(lod.error);
+            payload.array(&lod.indices);
+        }
+    }
+
+    let mut file = Writer::default();
+    file.bytes.extend_from_slice(&MAGIC);
+    file.u32(MODEL_CACHE_VERSION);
+    file.u32(layout_hash());
+    file.u32(options_hash(options));
+    file.u32(crc32fast::hash(&payload.bytes));
+    file.u64(payload.bytes.len() as u64);
+    file.bytes.extend_from_slice(&payload.bytes);
+
+    let mut temporary = path.as_os_str().to_os_string();
+    temporary.push(".tmp");
+    fs::write(&temporary, &file.bytes)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Reads a compiled model written with the same options; texture paths are relative to the
+/// directory of the compiled file.
+pub fn read_model_cache(path: &Path, options: &ModelOptions) -> Result<ModelData, CacheError> {
+    let file = File::open(path)?;
+    // compiled files are replaced by renaming (see `write_model_cache`), never changed in place
+    let map = unsafe { Mmap::map(&file)? };
+
+    let mut header = Reader { bytes: &map, pos: 0 };
+    if header.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(CacheError::Format("not a compiled model"));
+    }
+    if header.u32()? != MODEL_CACHE_VERSION || header.u32()? != layout_hash() {
+        return Err(CacheError::Version);
+    }
+    if header.u32()? != options_hash(options) {
+        return Err(CacheError::Options);
+    }
+    let checksum = header.u32()?;
+    let len = header.u64()? as usize;
+    let payload = map
+        .get(HEADER_SIZE..)
+        .filter(|payload| payload.len() == len)
+        .ok_or(CacheError::Format("wrong payload length"))?;
+    if crc32fast::hash(payload) != checksum {
+        return Err(CacheError::Checksum);
+    }
+
+    let mut reader = Reader { bytes: payload, pos: 0 };
+    let directory: PathBuf = path.parent().unwrap_or_else(|| Path::new("")).into();
+    let dependency_count = reader.u32()?;
+    let mut dependencies = Vec::new();
+    for _ in 0..dependency_count {
+        dependencies.push(directory.join(reader.string()?));
+    }
+
+    let material_count = reader.u32()?;
+    let mut materials = Vec::new();
+    for _ in 0..material_count {
+        let name = reader.string()?;
+        let ambient = reader.f32s()?;
+        let diffuse = reader.f32s()?;
+        let specular = reader.f32s()?;
+        let [shininess, dissolve, optical_density] = reader.f32s()?;
+        let ambient_texture = reader.string()?;
+        let diffuse_texture = reader.string()?;
+        let specular_texture = reader.string()?;
+        let normal_texture = reader.string()?;
+        let shininess_texture = reader.string()?;
+        let dissolve_texture = reader.string()?;
+        let illumination_model = match reader.u32()? {
+            u32::MAX => None,
+            model => Some(model as u8),
+        };
+        let mut params = Vec::new();
+        for _ in 0..reader.u32()? {
+            params.push((reader.string()?, reader.string()?));
+        }
+        materials.push(tobj::Material {
+            name,
+            ambient,
+            diffuse,
+            specular,
+            shininess,
+            dissolve,
+            optical_density,
+            ambient_texture,
+            diffuse_texture,
+            specular_texture,
+            normal_texture,
+            shininess_texture,
+            dissolve_texture,
+            illumination_model,
+            unknown_param: params.into_iter().collect(),
+        });
+    }
+
+    let report_count = reader.u32()?;
+    let mut optimize_reports = Vec::new();
+    for _ in 0..report_count {
+        let vertices = reader.u64()? as usize;
+        let triangles = reader.u64()? as usize;
+        let [acmr_before, acmr_after, atvr_before, atvr_after] = reader.f32s()?;
+        optimize_reports.push(OptimizeReport {
+            vertices,
+            triangles,
+            acmr_before,
+            acmr_after,
+            atvr_before,
+            atvr_after,
+        });
+    }
+
+    let mesh_count = reader.u32()?;
+    let mut meshes = Vec::new();
+    for _ in 0..mesh_count {
+        let material = match reader.u32()? {
+            u32::MAX => None,
+            id => Some(id as usize),
+        };
+        let [min_x, min_y, min_z, max_x, max_y, max_z] = reader.f32s()?;
+        let [center_x, center_y, center_z, radius] = reader.f32s()?;
+        let vertices: Vec<Vertex> = reader.array()?;
+        let indices: Vec<u32> = reader.array()?;
+        let colors: Option<Vec<Vector4<f32>>> = match reader.u32()? {
+            0 => None,
+            _ => Some(reader.array()?),
+        };
+        if colors.as_ref().is_some_and(|colors| colors.len() != vertices.len()) {
+            return Err(CacheError::Format("one color per vertex expected"));
+        }
+        let lod_count = reader.u32()?;
+        let mut lods = Vec::new();
+        for _ in 0..lod_count {
+            let error = reader.f32()?;
+            lods.push(Lod::new(reader.array()?, error));
+        }
+        // a corrupt index would make GL read past the vertex buffer
+        let mut all_indices = indices.iter().chain(lods.iter().flat_map(|lod| &lod.indices));
+        if all_indices.any(|&idx| idx as usize >= vertices.len()) {
+            return Err(CacheError::Format("index out of range"));
+        }
+        meshes.push(MeshData {
+            vertices,
+            indices,
+            colors,
+            material,
+            lods,
+            aabb: Aabb {
+                min: Point3::new(min_x, min_y, min_z),
+                max: Point3::new(max_x, max_y, max_z),
+            },
+            bounding_sphere: BoundingSphere {
+                center: Point3::new(center_x, center_y, center_z),
+                radius,
+            },
+        });
+    }
+
+    Ok(ModelData {
+        meshes,
+        optimize_reports,
+        materials,
+        directory,
+        dependencies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn touch(path: &Path, time: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn material_library_changes_invalidate_the_cache() {
+        let directory = std::env::temp_dir().join(format!("model_cache_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let (model, library) = (directory.join("triangle.obj"), directory.join("triangle.mtl"));
+        fs::write(
+            &model,
+            "mtllib triangle.mtl\nusemtl paint\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+        fs::write(&library, "newmtl paint\nKd 1 0 0\n").unwrap();
+        let options = ModelOptions::default();
+
+        let data = ModelData::parse(&model, &options).unwrap();
+        assert_eq!(data.dependencies, std::slice::from_ref(&library));
+        let cache = model_cache_path(&model);
+        write_model_cache(&data, &options, &cache).unwrap();
+        let start = SystemTime::now();
+        touch(&model, start - Duration::from_secs(20));
+        touch(&library, start - Duration::from_secs(20));
+        touch(&cache, start - Duration::from_secs(10));
+
+        // the cache is used while it is newer than both files
+        let cached = read_model_cache(&cache, &options).unwrap();
+        assert_eq!(cached.dependencies, std::slice::from_ref(&library));
+        assert_eq!(
+            ModelData::load(&model, &options).unwrap().materials[0].diffuse,
+            [1.0, 0.0, 0.0]
+        );
+        // a cache older than the material library is ignored, even though the model didn't change
+        fs::write(&library, "newmtl paint\nKd 0 1 0\n").unwrap();
+        touch(&library, start);
+        assert_eq!(
+            ModelData::load(&model, &options).unwrap().materials[0].diffuse,
+            [0.0, 1.0, 0.0]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+//! Vertex layouts described at compile time.
+//!
+//! `#[derive(VertexFormat)]` on a `#[repr(C)]` struct lists every field as a vertex
+//! attribute, with the location, component type and offset OpenGL needs to read it from
+//! a vertex buffer:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Clone, Copy, VertexFormat)]
+//! struct CompactVertex {
+//!     position: Vector3<f32>,          // location 0
+//!     normal: PackedNormal,            // location 1, 4 bytes instead of 12
+//!     tex_coords: [Half; 2],           // location 2
+//!     #[vertex(normalized)]
+//!     color: [u8; 4],                  // location 3, read as floats from 0 to 1
+//! }
+//! ```
+//!
+//! Field attributes:
+//! - `#[vertex(location = N)]` sets the location of the field; the following fields
+//!   continue from there. By default locations follow the field order from 0, matrices
+//!   taking one location per column.
+//! - `#[vertex(normalized)]` maps integer components to 0..1 (unsigned) or -1..1 (signed)
+//!   floats.
+//! - `#[vertex(float)]` converts integer components to floats as they are, instead of
+//!   passing them to the shader as integers.
+//! - `#[vertex(skip)]` leaves the field out, e.g. for padding.
+//!
+//! On the struct, `#[vertex(instance)]` makes every attribute advance once per instance
+//! instead of once per vertex, and `#[vertex(divisor = N)]` once every N instances.
+
+use cgmath::{Matrix2, Matrix3, Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+use std::{mem::size_of, slice};
+
+pub use vertex_format_derive::VertexFormat;
+
+/// Type of the components of an attribute in the vertex buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ComponentType {
+    Float,
+    HalfFloat,
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    /// four signed components packed in 10, 10, 10 and 2 bits, see `PackedNormal`
+    Int2101010Rev,
+}
+
+impl ComponentType {
+    /// the GL enum passed to `glVertexAttribPointer`
+    pub const fn gl_enum(self) -> u32 {
+        match self {
+            ComponentType::Byte => 0x1400,
+            ComponentType::UnsignedByte => 0x1401,
+            ComponentType::Short => 0x1402,
+            ComponentType::UnsignedShort => 0x1403,
+            ComponentType::Int => 0x1404,
+            ComponentType::UnsignedInt => 0x1405,
+            ComponentType::Float => 0x1406,
+            ComponentType::HalfFloat => 0x140B,
+            ComponentType::Int2101010Rev => 0x8D9F,
+        }
+    }
+
+    /// size of one component in bytes; packed types count as a single component
+    pub const fn size(self) -> usize {
+        match self {
+            ComponentType::Byte | ComponentType::UnsignedByte => 1,
+            ComponentType::Short | ComponentType::UnsignedShort | ComponentType::HalfFloat => 2,
+            ComponentType::Float | ComponentType::Int | ComponentType::UnsignedInt => 4,
+            ComponentType::Int2101010Rev => 4,
+        }
+    }
+
+    /// true for the types that can be passed to shaders as integers
+    pub const fn is_integer(self) -> bool {
+        matches!(
+            self,
+            ComponentType::Byte
+                | ComponentType::UnsignedByte
+                | ComponentType::Short
+                | ComponentType::UnsignedShort
+                | ComponentType::Int
+                | ComponentType::UnsignedInt
+        )
+    }
+}
+
+/// One attribute of a vertex format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// name of the struct field
+    pub name: &'static str,
+    /// location of the attribute, or of its first column for matrices
+    pub location: u32,
+    /// components per location, 1 to 4
+    pub components: i32,
+    pub component_type: ComponentType,
+    /// integer components are mapped to 0..1 or -1..1
+    pub normalized: bool,
+    /// integer components are passed to the shader as integers (`glVertexAttribIPointer`)
+    pub integer: bool,
+    /// byte offset of the field in the vertex
+    pub offset: usize,
+    /// number of consecutive locations used, one per column for matrices
+    pub locations: u32,
+    /// 0 for per-vertex attributes, N for attributes advancing every N instances
+    pub divisor: u32,
+}
+
+impl VertexAttribute {
+    /// size of one location (matrix column) in bytes
+    pub const fn column_size(&self) -> usize {
+        match self.component_type {
+            ComponentType::Int2101010Rev => 4,
+            component_type => component_type.size() * self.components as usize,
+        }
+    }
+
+    /// decodes the attribute of a vertex to floats, as the shader sees it without the
+    /// integer path; only the first column of matrices, missing components are 0
+    pub fn read<V: VertexFormat>(&self, vertex: &V) -> [f32; 4] {
+        let size = self.column_size();
+        assert!(self.offset + size <= size_of::<V>());
+        // SAFETY: `VertexFormat` implementations guarantee the field lies within the vertex
+        let bytes = unsafe { slice::from_raw_parts((vertex as *const V as *const u8).add(self.offset), size) };
+
+        let mut result = [0.0; 4];
+        if self.component_type == ComponentType::Int2101010Rev {
+            let packed = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            result = PackedNormal(packed).to_array();
+            if !self.normalized {
+                let scale = [511.0, 511.0, 511.0, 1.0];
+                for (value, scale) in result.iter_mut().zip(scale.iter()) {
+                    *value *= scale;
+                }
+            }
+            return result;
+        }
+        let component_size = self.component_type.size();
+        for (value, bytes) in result.iter_mut().zip(bytes.chunks_exact(component_size)) {
+            let (raw, max) = match self.component_type {
+                ComponentType::Float => (f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]), 1.0),
+                ComponentType::HalfFloat => (Half(u16::from_ne_bytes([bytes[0], bytes[1]])).to_f32(), 1.0),
+                ComponentType::Byte => (bytes[0] as i8 as f32, i8::MAX as f32),
+                ComponentType::UnsignedByte => (bytes[0] as f32, u8::MAX as f32),
+                ComponentType::Short => (i16::from_ne_bytes([bytes[0], bytes[1]]) as f32, i16::MAX as f32),
+                ComponentType::UnsignedShort => (u16::from_ne_bytes([bytes[0], bytes[1]]) as f32, u16::MAX as f32),
+                ComponentType::Int => (
+                    i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+                    i32::MAX as f32,
+                ),
+                ComponentType::UnsignedInt => (
+                    u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+                    u32::MAX as f32,
+                ),
+                ComponentType::Int2101010Rev => unreachable!(),
+            };
+            *value = if self.normalized { (raw / max).max(-1.0) } else { raw };
+        }
+        result
+    }
+}
+
+/// A vertex struct whose layout is described by its attributes; usually derived.
+///
+/// # Safety
+///
+/// Every attribute must lie within the struct, with a component type matching the bytes
+/// of the field at its offset.
+pub unsafe trait VertexFormat: Copy + 'static {
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    /// the attribute at location 0, by convention the position in model space
+    fn position(&self) -> Vector3<f32> {
+        match Self::ATTRIBUTES.iter().find(|attribute| attribute.location == 0) {
+            Some(attribute) => {
+                let [x, y, z, _] = attribute.read(self);
+                Vector3::new(x, y, z)
+            }
+            None => Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Rust types usable as vertex attributes, see `VertexFormat`.
+pub trait AttributeType {
+    const COMPONENTS: i32;
+    const COMPONENT_TYPE: ComponentType;
+    /// locations used, one per column for matrices
+    const LOCATIONS: u32 = 1;
+    /// read as normalized even without `#[vertex(normalized)]`
+    const NORMALIZED: bool = false;
+}
+
+macro_rules! attribute_types {
+    ($component_type:ident: $($ty:ty => $components:expr),* $(,)?) => {
+        $(
+            impl AttributeType for $ty {
+                const COMPONENTS: i32 = $components;
+                const COMPONENT_TYPE: ComponentType = ComponentType::$component_type;
+            }
+        )*
+    };
+}
+
+attribute_types!(Float: f32 => 1, [f32; 2] => 2, [f32; 3] => 3, [f32; 4] => 4,
+    Vector2<f32> => 2, Vector3<f32> => 3, Vector4<f32> => 4, Point2<f32> => 2, Point3<f32> => 3);
+attribute_types!(HalfFloat: Half => 1, [Half; 2] => 2, [Half; 3] => 3, [Half; 4] => 4);
+attribute_types!(Byte: i8 => 1, [i8; 2] => 2, [i8; 3] => 3, [i8; 4] => 4);
+attribute_types!(UnsignedByte: u8 => 1, [u8; 2] => 2, [u8; 3] => 3, [u8; 4] => 4);
+attribute_types!(Short: i16 => 1, [i16; 2] => 2, [i16; 3] => 3, [i16; 4] => 4);
+attribute_types!(UnsignedShort: u16 => 1, [u16; 2] => 2, [u16; 3] => 3, [u16; 4] => 4);
+attribute_types!(Int: i32 => 1, [i32; 2] => 2, [i32; 3] => 3, [i32; 4] => 4);
+attribute_types!(UnsignedInt: u32 => 1, [u32; 2] => 2, [u32; 3] => 3, [u32; 4] => 4);
+
+impl AttributeType for PackedNormal {
+    const COMPONENTS: i32 = 4;
+    const COMPONENT_TYPE: ComponentType = ComponentType::Int2101010Rev;
+    const NORMALIZED: bool = true;
+}
+
+impl AttributeType for Matrix2<f32> {
+    const COMPONENTS: i32 = 2;
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const LOCATIONS: u32 = 2;
+}
+
+impl AttributeType for Matrix3<f32> {
+    const COMPONENTS: i32 = 3;
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const LOCATIONS: u32 = 3;
+}
+
+impl AttributeType for Matrix4<f32> {
+    const COMPONENTS: i32 = 4;
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const LOCATIONS: u32 = 4;
+}
+
+/// IEEE 754 half precision float, for compact texture coordinates and colors.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Half(pub u16);
+
+impl Half {
+    /// rounds to the nearest half, overflowing to infinity
+    pub fn from_f32(value: f32) -> Half {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x007f_ffff;
+
+        if exponent == 0xff {
+            // infinity stays infinity, NaN stays NaN
+            let nan = if mantissa != 0 { 0x0200 } else { 0 };
+            return Half(sign | 0x7c00 | nan);
+        }
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return Half(sign | 0x7c00);
+        }
+        if exponent <= 0 {
+            // subnormal or zero
+            if exponent < -10 {
+                return Half(sign);
+            }
+            let mantissa = mantissa | 0x0080_0000;
+            let shift = (14 - exponent) as u32;
+            let half = mantissa >> shift;
+            // round to nearest, ties to even, as below
+            let remainder = mantissa & ((1 << shift) - 1);
+            let halfway = 1 << (shift - 1);
+            let round = (remainder > halfway || (remainder == halfway && half & 1 == 1)) as u32;
+            return Half(sign | (half + round) as u16);
+        }
+        let half = ((exponent as u32) << 10) | (mantissa >> 13);
+        // round to nearest, ties to even; a carry into the exponent is still correct
+        let remainder = mantissa & 0x1fff;
+        let round = (remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1)) as u32;
+        Half(sign | (half + round) as u16)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        let sign = ((self.0 & 0x8000) as u32) << 16;
+        let exponent = ((self.0 >> 10) & 0x1f) as u32;
+        let mantissa = (self.0 & 0x03ff) as u32;
+        let bits = match exponent {
+            0 if mantissa == 0 => sign,
+            0 => {
+                // subnormal: normalize the mantissa
+                let shift = mantissa.leading_zeros() - 21;
+                sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x03ff) << 13
+            }
+            0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+impl From<f32> for Half {
+    fn from(value: f32) -> Half {
+        Half::from_f32(value)
+    }
+}
+
+/// A unit vector packed in 4 bytes: x, y and z as signed normalized 10 bit integers and w
+/// (e.g. the handedness of a tangent) in the top 2 bits. Read as a normalized
+/// `vec4` by the shader.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PackedNormal(pub u32);
+
+impl PackedNormal {
+    pub fn new(normal: Vector3<f32>, w: f32) -> PackedNormal {
+        let pack = |value: f32, max: f32, bits: u32| {
+            let value = (value.clamp(-1.0, 1.0) * max).round() as i32;
+            (value as u32) & ((1 << bits) - 1)
+        };
+        PackedNormal(
+            pack(normal.x, 511.0, 10)
+                | pack(normal.y, 511.0, 10) << 10
+                | pack(normal.z, 511.0, 10) << 20
+                | pack(w, 1.0, 2) << 30,
+        )
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        // sign extend every field, then normalize
+        let unpack = |shift: u32, bits: u32, max: f32| {
+            let value = ((self.0 << (32 - shift - bits)) as i32) >> (32 - bits);
+            (value as f32 / max).max(-1.0)
+        };
+        [
+            unpack(0, 10, 511.0),
+            unpack(10, 10, 511.0),
+            unpack(20, 10, 511.0),
+            unpack(30, 2, 1.0),
+        ]
+    }
+
+    pub fn to_vector(self) -> Vector3<f32> {
+        let [x, y, z, _] = self.to_array();
+        Vector3::new(x, y, z)
+    }
+}
+
+impl From<Vector3<f32>> for PackedNormal {
+    fn from(normal: Vector3<f32>) -> PackedNormal {
+        PackedNormal::new(normal, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn half_round_trips_every_value() {
+        for bits in 0..=u16::MAX {
+            let half = Half(bits);
+            let value = half.to_f32();
+            if value.is_nan() {
+                assert!(Half::from_f32(value).to_f32().is_nan(), "{:#06x}", bits);
+            } else {
+                assert_eq!(Half::from_f32(value), half, "{:#06x} read as {}", bits, value);
+            }
+        }
+    }
+
+    #[test]
+    fn half_special_values() {
+        assert_eq!(Half::from_f32(0.0), Half(0x0000));
+        assert_eq!(Half::from_f32(-0.0), Half(0x8000));
+        assert_eq!(Half::from_f32(1.0), Half(0x3c00));
+        assert_eq!(Half::from_f32(-1.0), Half(0xbc00));
+        assert_eq!(Half::from_f32(f32::INFINITY), Half(0x7c00));
+        assert_eq!(Half::from_f32(f32::NEG_INFINITY), Half(0xfc00));
+        assert!(Half::from_f32(f32::NAN).to_f32().is_nan());
+        // smallest and largest subnormals
+        assert_eq!(Half::from_f32(2f32.powi(-24)), Half(0x0001));
+        assert_eq!(Half(0x0001).to_f32(), 2f32.powi(-24));
+        assert_eq!(Half::from_f32(1023.0 * 2f32.powi(-24)), Half(0x03ff));
+        assert_eq!(Half(0x03ff).to_f32(), 1023.0 * 2f32.powi(-24));
+    }
+
+    #[test]
+    fn half_rounds_to_nearest_even() {
+        // halfway between 1 and the next half goes to the even one
+        assert_eq!(Half::from_f32(1.0 + 2f32.powi(-11)), Half(0x3c00));
+        assert_eq!(Half::from_f32(1.0 + 3.0 * 2f32.powi(-11)), Half(0x3c02));
+        // the same in the subnormal range
+        assert_eq!(Half::from_f32(2f32.powi(-25)), Half(0x0000));
+        assert_eq!(Half::from_f32(3.0 * 2f32.powi(-25)), Half(0x0002));
+        assert_eq!(Half::from_f32(2f32.powi(-26)), Half(0x0000));
+        // at the top of the range
+        assert_eq!(Half::from_f32(65504.0), Half(0x7bff));
+        assert_eq!(Half::from_f32(65519.0), Half(0x7bff));
+        assert_eq!(Half::from_f32(65520.0), Half(0x7c00));
+        assert_eq!(Half::from_f32(-65520.0), Half(0xfc00));
+        assert_eq!(Half::from_f32(1e10), Half(0x7c00));
+    }
+
+    #[test]
+    fn packed_normal_round_trips() {
+        for i in 0..1000 {
+            // points spread over the sphere
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / 1000.0;
+            let angle = i as f32 * 2.399_963;
+            let radius = (1.0 - y * y).sqrt();
+            let normal = Vector3::new(radius * angle.cos(), y, radius * angle.sin()).normalize();
+            for &w in &[-1.0, 0.0, 1.0] {
+                let [x, y, z, packed_w] = PackedNormal::new(normal, w).to_array();
+                for (packed, exact) in [x, y, z].iter().zip([normal.x, normal.y, normal.z].iter()) {
+                    assert!(
+                        (packed - exact).abs() <= 1.0 / 511.0,
+                        "{:?} packed as {:?}",
+                        normal,
+                        [x, y, z]
+                    );
+                }
+                assert_eq!(packed_w, w);
+            }
+        }
+        for &axis in &[Vector3::unit_x(), -Vector3::unit_y(), Vector3::unit_z()] {
+            assert_eq!(PackedNormal::from(axis).to_vector(), axis);
+        }
+    }
+}
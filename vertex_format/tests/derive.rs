@@ -0,0 +1,109 @@
+use cgmath::{Matrix4, Vector2, Vector3};
+use std::mem::{offset_of, size_of};
+use vertex_format::{ComponentType, Half, PackedNormal, VertexAttribute, VertexFormat};
+
+#[repr(C)]
+#[derive(Clone, Copy, VertexFormat)]
+struct CompactVertex {
+    position: Vector3<f32>,
+    normal: PackedNormal,
+    tex_coords: [Half; 2],
+    #[vertex(normalized)]
+    color: [u8; 4],
+    #[vertex(skip)]
+    _padding: [u8; 2],
+    #[vertex(location = 6)]
+    material: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, VertexFormat)]
+#[vertex(instance)]
+struct Instance {
+    model: Matrix4<f32>,
+    #[vertex(float)]
+    id: u32,
+    offset: Vector2<f32>,
+}
+
+#[test]
+fn offsets_match_the_struct() {
+    let attributes = CompactVertex::ATTRIBUTES;
+    let names: Vec<&str> = attributes.iter().map(|attribute| attribute.name).collect();
+    assert_eq!(names, ["position", "normal", "tex_coords", "color", "material"]);
+    let offsets: Vec<usize> = attributes.iter().map(|attribute| attribute.offset).collect();
+    assert_eq!(
+        offsets,
+        [
+            offset_of!(CompactVertex, position),
+            offset_of!(CompactVertex, normal),
+            offset_of!(CompactVertex, tex_coords),
+            offset_of!(CompactVertex, color),
+            offset_of!(CompactVertex, material),
+        ]
+    );
+    assert_eq!(offsets, [0, 12, 16, 20, 26]);
+    assert_eq!(size_of::<CompactVertex>(), 28);
+
+    let instance = Instance::ATTRIBUTES;
+    assert_eq!(instance[1].offset, offset_of!(Instance, id));
+    assert_eq!(instance[2].offset, offset_of!(Instance, offset));
+    assert_eq!(size_of::<Instance>(), 76);
+}
+
+// every attribute lies within the stride, without overlapping the next one
+fn assert_fits<V: VertexFormat>() {
+    let attributes = V::ATTRIBUTES;
+    let end = |attribute: &VertexAttribute| attribute.offset + attribute.column_size() * attribute.locations as usize;
+    for pair in attributes.windows(2) {
+        assert!(
+            end(&pair[0]) <= pair[1].offset,
+            "{} overlaps {}",
+            pair[0].name,
+            pair[1].name
+        );
+    }
+    assert_eq!(end(attributes.last().unwrap()), size_of::<V>());
+}
+
+#[test]
+fn attributes_fit_in_the_stride() {
+    assert_fits::<CompactVertex>();
+    assert_fits::<Instance>();
+}
+
+#[test]
+fn locations_and_types() {
+    let attributes = CompactVertex::ATTRIBUTES;
+    let locations: Vec<u32> = attributes.iter().map(|attribute| attribute.location).collect();
+    assert_eq!(locations, [0, 1, 2, 3, 6]);
+    assert_eq!(attributes[1].component_type, ComponentType::Int2101010Rev);
+    assert!(attributes[1].normalized);
+    assert_eq!(attributes[2].component_type, ComponentType::HalfFloat);
+    assert!(attributes[3].normalized && !attributes[3].integer);
+    assert!(attributes[4].integer);
+    assert!(attributes.iter().all(|attribute| attribute.divisor == 0));
+
+    let instance = Instance::ATTRIBUTES;
+    let locations: Vec<u32> = instance.iter().map(|attribute| attribute.location).collect();
+    assert_eq!(locations, [0, 4, 5]);
+    assert_eq!(instance[0].locations, 4);
+    assert!(!instance[1].integer && !instance[1].normalized);
+    assert!(instance.iter().all(|attribute| attribute.divisor == 1));
+}
+
+#[test]
+fn position_reads_location_zero() {
+    let vertex = CompactVertex {
+        position: Vector3::new(1.0, -2.0, 3.5),
+        normal: PackedNormal::from(Vector3::new(0.0, 1.0, 0.0)),
+        tex_coords: [Half::from_f32(0.25), Half::from_f32(0.75)],
+        color: [255, 0, 128, 255],
+        _padding: [0; 2],
+        material: 3,
+    };
+    assert_eq!(vertex.position(), Vector3::new(1.0, -2.0, 3.5));
+    assert_eq!(CompactVertex::ATTRIBUTES[2].read(&vertex), [0.25, 0.75, 0.0, 0.0]);
+    assert_eq!(CompactVertex::ATTRIBUTES[3].read(&vertex)[0], 1.0);
+    assert_eq!(CompactVertex::ATTRIBUTES[4].read(&vertex), [3.0, 0.0, 0.0, 0.0]);
+}
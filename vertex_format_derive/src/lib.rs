@@ -0,0 +1,160 @@
+//! `#[derive(VertexFormat)]`, see the `vertex_format` crate.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(VertexFormat, attributes(vertex))]
+pub fn derive_vertex_format(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+// options of a `#[vertex(...)]` attribute
+#[derive(Default)]
+struct Options {
+    location: Option<u32>,
+    normalized: bool,
+    float: bool,
+    skip: bool,
+    divisor: Option<u32>,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    if !is_repr_c(input)? {
+        return Err(Error::new(
+            input.ident.span(),
+            "VertexFormat needs #[repr(C)] for the field offsets to be stable",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "VertexFormat needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "VertexFormat can only be derived for structs",
+            ))
+        }
+    };
+
+    let struct_options = options(&input.attrs)?;
+    if struct_options.location.is_some() || struct_options.normalized || struct_options.float || struct_options.skip {
+        return Err(Error::new(
+            input.ident.span(),
+            "only `instance` and `divisor` apply to the whole struct",
+        ));
+    }
+    let divisor = struct_options.divisor.unwrap_or(0);
+
+    let mut attributes = Vec::new();
+    // location of the next field, as a constant expression
+    let mut next_location = quote!(0u32);
+    for field in fields {
+        let field_options = options(&field.attrs)?;
+        if field_options.divisor.is_some() {
+            return Err(Error::new(
+                field.span(),
+                "`instance` and `divisor` apply to the whole struct",
+            ));
+        }
+        if field_options.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = ident.to_string();
+        let location = match field_options.location {
+            Some(location) => quote!(#location),
+            None => next_location,
+        };
+        let normalized = field_options.normalized;
+        let normalized = quote!((#normalized || <#ty as ::vertex_format::AttributeType>::NORMALIZED));
+        let float = field_options.float;
+        attributes.push(quote! {
+            ::vertex_format::VertexAttribute {
+                name: #name,
+                location: #location,
+                components: <#ty as ::vertex_format::AttributeType>::COMPONENTS,
+                component_type: <#ty as ::vertex_format::AttributeType>::COMPONENT_TYPE,
+                normalized: #normalized,
+                integer: <#ty as ::vertex_format::AttributeType>::COMPONENT_TYPE.is_integer() && !#normalized && !#float,
+                offset: ::core::mem::offset_of!(Self, #ident),
+                locations: <#ty as ::vertex_format::AttributeType>::LOCATIONS,
+                divisor: #divisor,
+            }
+        });
+        next_location = quote!((#location) + <#ty as ::vertex_format::AttributeType>::LOCATIONS);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        unsafe impl #impl_generics ::vertex_format::VertexFormat for #ident #ty_generics #where_clause {
+            const ATTRIBUTES: &'static [::vertex_format::VertexAttribute] = &[#(#attributes),*];
+        }
+    })
+}
+
+fn is_repr_c(input: &DeriveInput) -> Result<bool, Error> {
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("repr")) {
+        if let Meta::List(list) = attr.parse_meta()? {
+            let is_c = |nested: &NestedMeta| matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("C"));
+            if list.nested.iter().any(is_c) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn options(attrs: &[syn::Attribute]) -> Result<Options, Error> {
+    let mut options = Options::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("vertex")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected #[vertex(...)]")),
+        };
+        for nested in &list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("normalized") => options.normalized = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("float") => options.float = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => options.skip = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("instance") => options.divisor = Some(1),
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("location") => {
+                    options.location = Some(int_literal(&pair.lit)?)
+                }
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("divisor") => {
+                    options.divisor = Some(int_literal(&pair.lit)?)
+                }
+                _ => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "expected `location = N`, `normalized`, `float`, `skip`, `instance` or `divisor = N`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn int_literal(lit: &Lit) -> Result<u32, Error> {
+    match lit {
+        Lit::Int(int) => int.base10_parse(),
+        _ => Err(Error::new(lit.span(), "expected an integer")),
+    }
+}